@@ -1,14 +1,33 @@
 // cb_core/src/api.rs
 
 use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc;
 
-use crate::clipboard::{make_ingest_plan, ClipboardSnapshot, IngestPlan, LocalIngestDeps};
+use crate::clipboard::{
+    expand_dir_entries, is_image_mime, make_ingest_plan, make_streamed_file_plan, make_streamed_image_plan, ClipboardSnapshot,
+    IngestPlan, LocalIngestDeps, StreamedBlob,
+};
 pub(crate) use crate::model::ItemMeta;
+use crate::model::{
+    add_reclaim, CacheReport, DeleteScope, GcRun, HistoryCursor, HistoryFilter, HistoryPage, ItemKind, ReclaimReason,
+    ReclaimStat, SearchPage, TreeView,
+};
 use crate::net::{NetCmd, NetManager};
+use crate::cas::{FileAttrs, TreeEntry};
+use crate::item_fetch::{FetchMsg, FetchRoutes};
 use crate::{cas::Cas, store::Store, logs::LogStore, stats::StatsStore, util::now_ms};
 pub use crate::policy::{AppConfig, GlobalPolicy};
 
+/// tmp/ 中的临时文件、没有 blob 记录的下载目录超过这个时间未修改即视为残留
+const STALE_CACHE_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// 未完成的续传数据超过这个时间没有进展即放弃
+const STALE_PARTIAL_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// 启动后第一轮 CAS 巡检前的等待时间
+const SCRUB_START_DELAY: Duration = Duration::from_secs(60);
+
 /**
  * Core 的配置项。
  *
@@ -56,6 +75,8 @@ pub struct PeerStatus {
     pub device_name: Option<String>,      // 设备名称（从 HELLO 或 discovery 获取）
     pub state: PeerConnectionState,       // 连接状态
     pub last_seen_ts_ms: i64,            // 最后见到时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_good_addr: Option<String>,   // 上次连接成功的地址
     pub share_to_peer: bool,             // Outbound allow（策略状态）
     pub accept_from_peer: bool,          // Inbound allow（策略状态）
 }
@@ -133,6 +154,21 @@ impl Core {
         };
         let store_arc = Arc::new(Mutex::new(store));
 
+        // 配置里的静态对端并入 core.db，与 add_static_peer 添加的一起由网络层拨号
+        for addr in &cfg.app_config.static_peers {
+            let res = crate::net::normalize_static_peer(addr).and_then(|addr| {
+                store_arc.lock().unwrap().add_static_peer(&cfg.account_uid, &addr, now_ms())
+            });
+            if let Err(e) = res {
+                let mut log_store = log_store_arc.lock().unwrap();
+                let _ = log_store.log_warn(
+                    "Init",
+                    &format!("Ignoring static peer from config: {}", e),
+                    Some(&format!("忽略配置中的静态对端: {}", e)),
+                );
+            }
+        }
+
         let stats_store = match StatsStore::open(&cfg.data_dir) {
             Ok(s) => {
                 let mut log_store = log_store_arc.lock().unwrap();
@@ -178,10 +214,13 @@ impl Core {
             }
         };
 
+        // 子传输的进度 / 结束需要交给 FileList 整体拉取，网络层与 Core 共用同一张路由表
+        let fetch_routes = Arc::new(Mutex::new(FetchRoutes::default()));
+
         // --- M1 集成：启动网络管理器 ---
         // 注意：这里假设 NetManager::spawn 是同步封装（内部 spawn 异步任务）
         // 如果是在 FFI 环境且有 Tokio Runtime，这将正常工作。
-        let net_tx = match NetManager::spawn(cfg.clone(), sink.clone(), store_arc.clone(),cas.clone(), log_store_arc.clone()) {
+        let net_tx = match NetManager::spawn(cfg.clone(), sink.clone(), store_arc.clone(),cas.clone(), log_store_arc.clone(), fetch_routes.clone()) {
             Ok(tx) => {
                 let mut log_store = log_store_arc.lock().unwrap();
                 let _ = log_store.log_info(
@@ -211,9 +250,13 @@ impl Core {
             stats_store: stats_store_arc,
             cas,
             net: net_tx,
+            gc_stats: Mutex::new(GcStats::default()),
+            fetch_routes,
         };
         let core = Self { inner: Arc::new(inner) };
+        core.backfill_search_bodies();
         let _ = core.run_gc("Startup");
+        core.spawn_scrubber();
         core
     }
 
@@ -289,14 +332,19 @@ impl Core {
             anyhow::bail!("core already shutdown");
         }
 
-        // 获取内容类型和大小
-        let (content_type, size_bytes) = match snapshot {
+        // 获取内容类型和大小（多格式快照按主格式计）
+        let (content_type, size_bytes) = match snapshot.primary() {
             ClipboardSnapshot::Text { text_utf8, .. } => ("Text", text_utf8.len() as i64),
             ClipboardSnapshot::Image { bytes, .. } => ("Image", bytes.len() as i64),
             ClipboardSnapshot::FileList { files, .. } => {
                 let total: i64 = files.iter().map(|f| f.size_bytes).sum();
                 ("FileList", total)
             }
+            ClipboardSnapshot::RichText { html, rtf, .. } => {
+                let size = html.as_ref().or(rtf.as_ref()).map(|s| s.len()).unwrap_or(0);
+                ("RichText", size as i64)
+            }
+            ClipboardSnapshot::Multi { .. } => unreachable!("primary() 已展开 Multi"),
         };
 
         // 记录规划日志
@@ -315,9 +363,17 @@ impl Core {
             account_uid: &self.inner.core_config.account_uid,
         };
 
-		let size_limits = &self.inner.core_config.app_config.size_limits;
+		let app_config = &self.inner.core_config.app_config;
 
-        make_ingest_plan(&deps, snapshot, size_limits, force)
+        // 复制的文件夹在规划前展开为完整的目录树
+        if let ClipboardSnapshot::FileList { files, ts_ms } = snapshot {
+            if files.iter().any(|f| f.is_dir || f.abs_path.as_deref().is_some_and(|p| std::path::Path::new(p).is_dir())) {
+                let expanded = ClipboardSnapshot::FileList { files: expand_dir_entries(files)?, ts_ms: *ts_ms };
+                return make_ingest_plan(&deps, &expanded, &app_config.size_limits, &app_config.expiry, force);
+            }
+        }
+
+        make_ingest_plan(&deps, snapshot, &app_config.size_limits, &app_config.expiry, force)
     }
 
     pub fn apply_ingest(&self, plan: IngestPlan) -> anyhow::Result<ItemMeta> {
@@ -338,8 +394,16 @@ impl Core {
 
         // Phase B：CAS 去重写入（这里不持 store 锁）
         if !cache.present || !self.inner.cas.blob_exists(&sha) {
-            let tmp_name = format!("{}.tmp", plan.meta.item_id);
-            let _wrote = self.inner.cas.put_if_absent(&sha, &plan.content_bytes, &tmp_name)?;
+            match &plan.streamed_blob {
+                // 流式摄入的图片：正文已在临时文件中，直接转正
+                Some(blob) if blob.sha256 == sha => {
+                    self.inner.cas.commit_tmp_file(&blob.tmp_path, &sha)?;
+                }
+                _ => {
+                    let tmp_name = format!("{}.tmp", plan.meta.item_id);
+                    let _wrote = self.inner.cas.put_if_absent(&sha, &plan.content_bytes, &tmp_name)?;
+                }
+            }
 
             if self.inner.cas.blob_exists(&sha) {
                 let mut store = self.inner.store.lock().unwrap();
@@ -375,6 +439,46 @@ impl Core {
             );
         }
 
+        // Phase C：RichText 的纯文本降级内容同样进 CAS
+        if let (Some(fallback), Some(bytes)) = (&plan.meta.text_fallback, &plan.fallback_bytes) {
+            if !self.inner.cas.blob_exists(&fallback.sha256) {
+                let tmp_name = format!("{}.fallback.tmp", plan.meta.item_id);
+                self.inner.cas.put_if_absent(&fallback.sha256, bytes, &tmp_name)?;
+            }
+            let mut store = self.inner.store.lock().unwrap();
+            store.mark_cache_present(&fallback.sha256, now)?;
+        }
+
+        // Phase D：多格式 item 的额外表示逐个写入 CAS
+        for (idx, (rep, bytes)) in plan.meta.representations.iter().zip(&plan.representation_bytes).enumerate() {
+            if !self.inner.cas.blob_exists(&rep.sha256) {
+                let tmp_name = format!("{}.rep{}.tmp", plan.meta.item_id, idx);
+                self.inner.cas.put_if_absent(&rep.sha256, bytes, &tmp_name)?;
+            }
+            let mut store = self.inner.store.lock().unwrap();
+            store.mark_cache_present(&rep.sha256, now)?;
+        }
+
+        // Phase E：流式摄入的临时文件转正（单文件 FileList 的文件内容；去重命中时只删除临时文件）。
+        // 条目行已在 Phase A 落库，之后 blob 才出现，孤儿清扫不会误删
+        if let Some(blob) = &plan.streamed_blob {
+            if blob.tmp_path.exists() {
+                self.inner.cas.commit_tmp_file(&blob.tmp_path, &blob.sha256)?;
+            }
+            let mut store = self.inner.store.lock().unwrap();
+            store.mark_cache_present(&blob.sha256, now)?;
+        }
+
+        // 全文索引正文：RichText 用纯文本降级，其它文本类用正文本身
+        let body = match &plan.fallback_bytes {
+            Some(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            None => crate::clipboard::searchable_text(&plan.meta.content.mime, &plan.content_bytes),
+        };
+        if let Some(body) = body {
+            let mut store = self.inner.store.lock().unwrap();
+            store.index_item_body(&item_id, &body)?;
+        }
+
         // 事件（不需要 store 锁）
         let meta_evt = serde_json::json!({
           "type": "ITEM_META_ADDED",
@@ -402,6 +506,11 @@ impl Core {
             let _ = net_tx.try_send(NetCmd::BroadcastMeta(plan.meta.clone()));
         }
 
+        // FileList 的文件哈希在后台补算，算完再广播一次更新后的元数据
+        if plan.meta.files.iter().any(|f| !f.is_dir && f.sha256.is_none() && f.local_path.is_some()) {
+            self.spawn_file_hasher(item_id.clone());
+        }
+
         // GC（现在一定不会死锁）
         let _ = self.run_gc("AfterIngest");
 
@@ -409,12 +518,38 @@ impl Core {
     }
 
 
-    pub fn run_gc(&self, _reason: &str) -> anyhow::Result<()> {
+    /// GC：过期清理 → History GC → 引用计数回收 → Cache GC（LRU）→ 孤儿文件清扫。
+    /// 孤儿文件清扫需要遍历缓存目录，AfterIngest 时跳过。结果可通过 cache_report 查询。
+    pub fn run_gc(&self, reason: &str) -> anyhow::Result<()> {
         if self.inner.is_shutdown.load(Ordering::Acquire) {
             anyhow::bail!("core already shutdown");
         }
 
         let now = now_ms();
+        let mut reclaimed = Vec::new();
+
+        // 0) 过期清理：过期条目变为墓碑，不再被引用的内容从 CAS 删除
+		let (expired, orphans) = {
+			let mut store = self.inner.store.lock().unwrap();
+			store.purge_expired(now)?
+		};
+		let (files, bytes) = self.remove_blobs(&orphans)?;
+		add_reclaim(&mut reclaimed, ReclaimReason::Expired, files, bytes);
+		if !expired.is_empty() {
+			{
+				let mut log_store = self.inner.log_store.lock().unwrap();
+				let _ = log_store.log_info(
+					"GC",
+					&format!("Purged {} expired items, blobs_removed={}", expired.len(), files),
+					Some(&format!("已清理 {} 个过期条目，移除缓存={}", expired.len(), files)),
+				);
+			}
+			self.inner.emit_json(serde_json::json!({
+				"type": "ITEMS_EXPIRED",
+				"ts_ms": now,
+				"payload": { "item_ids": expired }
+			}));
+		}
 
         // 1) History GC
 		let max_history = self.inner.core_config.app_config.gc_history_max_items;
@@ -426,7 +561,15 @@ impl Core {
 			}
 		}
 
-        // 2) Cache GC（LRU）
+        // 2) 引用计数回收：不再被任何存活条目引用的内容立即删除
+		let unreferenced = {
+			let mut store = self.inner.store.lock().unwrap();
+			store.reclaim_unreferenced()?
+		};
+		let (files, bytes) = self.remove_blobs(&unreferenced)?;
+		add_reclaim(&mut reclaimed, ReclaimReason::Unreferenced, files, bytes);
+
+        // 3) Cache GC（LRU）
 		let max_cas = self.inner.core_config.app_config.gc_cas_max_bytes;
 		if max_cas > 0 {
 			let mut cur = self.inner.cas.total_size_bytes()?;
@@ -437,11 +580,15 @@ impl Core {
 					if cands.is_empty() { break; }
 					cands[0].clone()
 				};
+				let existed = self.inner.cas.blob_exists(&sha);
 				let freed = self.inner.cas.remove_blob(&sha)?;
 				{
 					let mut store = self.inner.store.lock().unwrap();
 					store.mark_cache_missing(&sha, now)?;
 				}
+				if existed {
+					add_reclaim(&mut reclaimed, ReclaimReason::Lru, 1, freed);
+				}
 				if freed > 0 {
 					cur -= freed;
 				} else {
@@ -449,9 +596,346 @@ impl Core {
 				}
 			}
 		}
+
+        // 4) 孤儿文件清扫
+		if reason != "AfterIngest" {
+			// 先列出磁盘上的 blob 再查引用：入库总是先写记录后写 blob，
+			// 这样列表中的 blob 若仍被引用，其记录一定已经可见
+			let blobs = self.inner.cas.list_blobs()?;
+			let live = {
+				let store = self.inner.store.lock().unwrap();
+				store.live_blob_set()?
+			};
+			for (sha, size) in blobs {
+				if live.contains(&sha) {
+					let mut store = self.inner.store.lock().unwrap();
+					store.repair_cache_present(&sha)?;
+				} else {
+					self.inner.cas.remove_blob(&sha)?;
+					add_reclaim(&mut reclaimed, ReclaimReason::OrphanBlob, 1, size);
+				}
+			}
+
+			let (files, bytes) = self.inner.cas.sweep_stale_tmp(STALE_CACHE_FILE_AGE)?;
+			add_reclaim(&mut reclaimed, ReclaimReason::StaleTmp, files, bytes);
+			let (files, bytes) = self.inner.cas.sweep_stale_partials(STALE_PARTIAL_AGE)?;
+			add_reclaim(&mut reclaimed, ReclaimReason::StaleTmp, files, bytes);
+			let (files, bytes) = self.inner.cas.sweep_orphan_views(STALE_CACHE_FILE_AGE)?;
+			add_reclaim(&mut reclaimed, ReclaimReason::OrphanView, files, bytes);
+		}
+
+		if !reclaimed.is_empty() {
+			let summary = reclaimed
+				.iter()
+				.map(|r| format!("{:?}: {} files / {} bytes", r.reason, r.files, r.bytes))
+				.collect::<Vec<_>>()
+				.join(", ");
+			let mut log_store = self.inner.log_store.lock().unwrap();
+			let _ = log_store.log_info(
+				"GC",
+				&format!("GC ({}) reclaimed {}", reason, summary),
+				Some(&format!("GC（{}）已回收 {}", reason, summary)),
+			);
+		}
+
+		let mut stats = self.inner.gc_stats.lock().unwrap();
+		for r in &reclaimed {
+			add_reclaim(&mut stats.total, r.reason, r.files, r.bytes);
+		}
+		stats.last = Some(GcRun { ts_ms: now, trigger: reason.to_string(), reclaimed });
 		Ok(())
     }
 
+    /// 从 CAS 删除一组 blob，返回实际删除的 (文件数, 字节数)
+    fn remove_blobs(&self, shas: &[String]) -> anyhow::Result<(i64, i64)> {
+        let mut files = 0;
+        let mut bytes = 0;
+        for sha in shas {
+            if self.inner.cas.blob_exists(sha) {
+                bytes += self.inner.cas.remove_blob(sha)?;
+                files += 1;
+            }
+        }
+        Ok((files, bytes))
+    }
+
+    /// 缓存占用与回收情况：当前占用、置顶占用、最近一次 GC 以及启动以来各原因的回收量
+    pub fn cache_report(&self) -> anyhow::Result<CacheReport> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+
+        let blob_bytes = self.inner.cas.total_size_bytes()?;
+        let ((present_count, present_bytes), pinned_bytes) = {
+            let store = self.inner.store.lock().unwrap();
+            (store.present_stats()?, store.pinned_present_bytes()?)
+        };
+        let stats = self.inner.gc_stats.lock().unwrap();
+        Ok(CacheReport {
+            blob_bytes,
+            present_count,
+            present_bytes,
+            pinned_bytes,
+            cap_bytes: self.inner.core_config.app_config.gc_cas_max_bytes,
+            last_gc: stats.last.clone(),
+            reclaimed_total: stats.total.clone(),
+        })
+    }
+
+    /// CAS 完整性巡检一轮：重算全部 blob 的 sha256，损坏的隔离并尝试重新拉取。
+    /// 后台线程按 ScrubPolicy 周期调用，也可直接触发。返回损坏的 blob 列表。
+    pub fn scrub_cas(&self) -> anyhow::Result<Vec<String>> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+
+        let rate = self.inner.core_config.app_config.scrub.bytes_per_sec.max(0);
+        let corrupt = self
+            .inner
+            .cas
+            .scrub_pass(rate, || self.inner.is_shutdown.load(Ordering::Acquire))?;
+        for sha in &corrupt {
+            self.handle_corrupt_blob(sha)?;
+        }
+        Ok(corrupt)
+    }
+
+    /// 处理损坏的 blob：移入隔离区、标记为未缓存、发 CAS_CORRUPTION 事件，
+    /// 并向仍在线的来源设备重新拉取（本机产生的条目无处可拉）。
+    fn handle_corrupt_blob(&self, sha: &str) -> anyhow::Result<()> {
+        let now = now_ms();
+        let quarantined = self.inner.cas.quarantine_blob(sha)?;
+        let refs = {
+            let mut store = self.inner.store.lock().unwrap();
+            store.mark_cache_missing(sha, now)?;
+            store.blob_refs(sha)?
+        };
+
+        let mut item_ids: Vec<String> = refs.iter().map(|r| r.item_id.clone()).collect();
+        item_ids.sort();
+        item_ids.dedup();
+        let mut refetch_item_ids = Vec::new();
+        if let Some(net_tx) = &self.inner.net {
+            for r in refs.iter().filter(|r| r.owner_device_id != self.inner.core_config.device_id) {
+                // 结果通过 CONTENT_CACHED / TRANSFER_FAILED 事件通知，这里不等待 transfer_id
+                let (reply, _) = tokio::sync::oneshot::channel();
+                let sent = net_tx.try_send(NetCmd::EnsureContentCached {
+                    item_id: r.item_id.clone(),
+                    file_id: r.file_id.clone(),
+                    force: true,
+                    mime: r.mime.clone(),
+                    reply,
+                });
+                if sent.is_ok() && !refetch_item_ids.contains(&r.item_id) {
+                    refetch_item_ids.push(r.item_id.clone());
+                }
+            }
+        }
+
+        {
+            let mut log_store = self.inner.log_store.lock().unwrap();
+            let _ = log_store.log_warn(
+                "CAS",
+                &format!("Corrupt blob quarantined: sha256={}, items={:?}, refetching={}", sha, item_ids, refetch_item_ids.len()),
+                Some(&format!("发现损坏的缓存并已隔离: sha256={}，条目={:?}，重新拉取={}", sha, item_ids, refetch_item_ids.len())),
+            );
+        }
+        self.inner.emit_json(serde_json::json!({
+            "type": "CAS_CORRUPTION",
+            "ts_ms": now,
+            "payload": {
+                "sha256": sha,
+                "item_ids": item_ids,
+                "refetch_item_ids": refetch_item_ids,
+                "quarantine_path": quarantined.map(|p| p.to_string_lossy().to_string())
+            }
+        }));
+        Ok(())
+    }
+
+    /// 升级前已缓存的文本条目只索引了预览：从 CAS 读出正文补进全文索引（迁移排队，只做一次）
+    fn backfill_search_bodies(&self) {
+        let pending = self.inner.store.lock().unwrap().list_search_backfill();
+        let pending = match pending {
+            Ok(p) if !p.is_empty() => p,
+            Ok(_) => return,
+            Err(e) => {
+                let mut log_store = self.inner.log_store.lock().unwrap();
+                let _ = log_store.log_warn(
+                    "Init",
+                    &format!("Failed to read search backfill queue: {}", e),
+                    Some(&format!("读取全文索引补建队列失败: {}", e)),
+                );
+                return;
+            }
+        };
+
+        let mut indexed = 0;
+        for (item_id, content) in &pending {
+            if !self.inner.cas.blob_exists(&content.sha256) {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            let read = std::fs::File::open(self.inner.cas.blob_path(&content.sha256))
+                .and_then(|f| std::io::Read::read_to_end(&mut std::io::Read::take(f, crate::store::SEARCH_BODY_MAX_BYTES as u64), &mut bytes));
+            let Some(body) = read.ok().and_then(|_| crate::clipboard::searchable_text(&content.mime, &bytes)) else { continue };
+            if self.inner.store.lock().unwrap().index_item_body(item_id, &body).is_ok() {
+                indexed += 1;
+            }
+        }
+        let _ = self.inner.store.lock().unwrap().clear_search_backfill();
+
+        let mut log_store = self.inner.log_store.lock().unwrap();
+        let _ = log_store.log_info(
+            "Init",
+            &format!("Search index backfilled from cached text: indexed={}, queued={}", indexed, pending.len()),
+            Some(&format!("已从缓存的文本补建全文索引: 已索引={}，排队={}", indexed, pending.len())),
+        );
+    }
+
+    /// 启动后台 CAS 巡检线程（ScrubPolicy.bytes_per_sec <= 0 时不启动）。
+    /// 线程只持有 Weak 引用，Core 关闭或释放后自动退出。
+    fn spawn_scrubber(&self) {
+        let policy = self.inner.core_config.app_config.scrub.clone();
+        if policy.bytes_per_sec <= 0 {
+            return;
+        }
+        let weak = Arc::downgrade(&self.inner);
+        let interval = Duration::from_millis(policy.interval_ms.max(1000) as u64);
+
+        let _ = std::thread::Builder::new()
+            .name("cb-cas-scrub".to_string())
+            .spawn(move || {
+                // 首轮稍等片刻，避开启动期的 GC 与首轮同步
+                let mut wait = SCRUB_START_DELAY;
+                loop {
+                    let deadline = std::time::Instant::now() + wait;
+                    while std::time::Instant::now() < deadline {
+                        match weak.upgrade() {
+                            Some(inner) if !inner.is_shutdown.load(Ordering::Acquire) => {}
+                            _ => return,
+                        }
+                        std::thread::sleep(Duration::from_millis(500));
+                    }
+                    let Some(inner) = weak.upgrade() else { return };
+                    let core = Core { inner };
+                    if let Err(e) = core.scrub_cas() {
+                        if core.inner.is_shutdown.load(Ordering::Acquire) {
+                            return;
+                        }
+                        let mut log_store = core.inner.log_store.lock().unwrap();
+                        let _ = log_store.log_warn(
+                            "CAS",
+                            &format!("CAS scrub failed: {}", e),
+                            Some(&format!("缓存完整性巡检失败: {}", e)),
+                        );
+                    }
+                    wait = interval;
+                }
+            });
+    }
+
+    /// 为本机 FileList 条目中尚无 sha256 的文件计算哈希（读取 local_path 指向的源文件）。
+    /// 哈希写回 files_json 后发 ITEM_META_UPDATED 并重新广播元数据；复制之后已被改动
+    /// （大小不符、无法读取或哈希期间被修改）的文件不记录哈希，改发 SOURCE_FILE_CHANGED。
+    /// 返回更新后的元数据，没有文件被更新时返回 None。
+    pub fn hash_item_files(&self, item_id: &str) -> anyhow::Result<Option<ItemMeta>> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+        let account_uid = &self.inner.core_config.account_uid;
+        let meta = self.inner.store.lock().unwrap().get_item_meta(account_uid, item_id, true)?;
+        let Some(meta) = meta else { return Ok(None) };
+        if meta.source_device_id != self.inner.core_config.device_id {
+            return Ok(None);
+        }
+
+        let mut hashed = Vec::new();
+        for f in meta.files.iter().filter(|f| !f.is_dir && f.sha256.is_none()) {
+            let Some(path) = f.local_path.as_deref() else { continue };
+            if self.inner.is_shutdown.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+            match hash_source_file(std::path::Path::new(path), f.size_bytes, f.local_mtime_ms) {
+                Some((sha, mtime)) => hashed.push(crate::model::FileMeta {
+                    sha256: Some(sha),
+                    local_mtime_ms: mtime,
+                    ..f.clone()
+                }),
+                None => self.report_source_file_changed(item_id, f),
+            }
+        }
+        if hashed.is_empty() {
+            return Ok(None);
+        }
+
+        let updated = {
+            let mut store = self.inner.store.lock().unwrap();
+            if store.fill_file_hashes(item_id, &hashed, now_ms())? {
+                store.get_item_meta(account_uid, item_id, true)?
+            } else {
+                None
+            }
+        };
+        let Some(updated) = updated else { return Ok(None) };
+
+        {
+            let mut log_store = self.inner.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Ingest",
+                &format!("File hashes computed: item_id={}, files={}", item_id, hashed.len()),
+                Some(&format!("文件哈希已计算: 项目ID={}，文件数={}", item_id, hashed.len())),
+            );
+        }
+        self.inner.emit_json(serde_json::json!({
+            "type": "ITEM_META_UPDATED",
+            "ts_ms": now_ms(),
+            "payload": { "meta": updated }
+        }));
+        if let Some(net_tx) = &self.inner.net {
+            let _ = net_tx.try_send(NetCmd::BroadcastMeta(updated.clone()));
+        }
+        Ok(Some(updated))
+    }
+
+    fn report_source_file_changed(&self, item_id: &str, file: &crate::model::FileMeta) {
+        {
+            let mut log_store = self.inner.log_store.lock().unwrap();
+            let _ = log_store.log_warn(
+                "Ingest",
+                &format!("Source file changed after copy: item_id={}, file={}", item_id, file.rel_name),
+                Some(&format!("源文件在复制后被修改: 项目ID={}，文件={}", item_id, file.rel_name)),
+            );
+        }
+        self.inner.emit_json(serde_json::json!({
+            "type": "SOURCE_FILE_CHANGED",
+            "ts_ms": now_ms(),
+            "payload": { "item_id": item_id, "file_id": file.file_id, "rel_name": file.rel_name }
+        }));
+    }
+
+    /// 在后台线程中为刚摄入的 FileList 计算文件哈希（见 hash_item_files）
+    fn spawn_file_hasher(&self, item_id: String) {
+        let weak = Arc::downgrade(&self.inner);
+        let _ = std::thread::Builder::new()
+            .name("cb-file-hash".to_string())
+            .spawn(move || {
+                let Some(inner) = weak.upgrade() else { return };
+                let core = Core { inner };
+                if let Err(e) = core.hash_item_files(&item_id) {
+                    if core.inner.is_shutdown.load(Ordering::Acquire) {
+                        return;
+                    }
+                    let mut log_store = core.inner.log_store.lock().unwrap();
+                    let _ = log_store.log_warn(
+                        "Ingest",
+                        &format!("File hashing failed: item_id={}, error={}", item_id, e),
+                        Some(&format!("文件哈希计算失败: 项目ID={}，错误={}", item_id, e)),
+                    );
+                }
+            });
+    }
+
     pub fn plan_local_ingest_result(
         &self,
         snapshot: &crate::clipboard::ClipboardSnapshot,
@@ -474,6 +958,88 @@ impl Core {
         self.apply_ingest(plan)
     }
 
+    /// 流式摄入图片：边读边写入 CAS 临时文件并计算哈希，内存占用与图片大小无关。
+    /// 超过硬限制时中止（ITEM_TOO_LARGE），不留下临时文件。
+    pub fn ingest_from_reader(
+        &self,
+        reader: impl std::io::Read,
+        mime: &str,
+        ts_ms: i64,
+        force: bool,
+    ) -> anyhow::Result<ItemMeta> {
+        if !is_image_mime(mime) {
+            anyhow::bail!("UNSUPPORTED_MIME");
+        }
+        self.ingest_streamed(reader, ItemKind::Image, |deps, blob, app_config| {
+            make_streamed_image_plan(deps, mime, blob, ts_ms, &app_config.size_limits, &app_config.expiry, force)
+        })
+    }
+
+    /// 流式摄入本地文件：image/* 按图片入库，其它 MIME 按单文件 FileList 入库（sha256 已算好，
+    /// 本机直接可用）。先按文件大小快速拒绝超过硬限制的文件。
+    pub fn ingest_from_path(
+        &self,
+        path: &std::path::Path,
+        mime: &str,
+        ts_ms: i64,
+        force: bool,
+    ) -> anyhow::Result<ItemMeta> {
+        let kind = if is_image_mime(mime) { ItemKind::Image } else { ItemKind::FileList };
+        let md = std::fs::metadata(path)?;
+        if !md.is_file() {
+            anyhow::bail!("NOT_A_FILE");
+        }
+        if md.len() as i64 > self.inner.core_config.app_config.size_limits.hard_bytes(&kind) {
+            anyhow::bail!("ITEM_TOO_LARGE");
+        }
+        let file = std::fs::File::open(path)?;
+
+        match kind {
+            ItemKind::Image => self.ingest_from_reader(file, mime, ts_ms, force),
+            _ => self.ingest_streamed(file, ItemKind::FileList, |deps, blob, app_config| {
+                make_streamed_file_plan(deps, path, blob, ts_ms, &app_config.size_limits, &app_config.expiry, force)
+            }),
+        }
+    }
+
+    /// 流式摄入的公共流程：写临时文件 → 生成计划 → apply_ingest；失败时清理临时文件并记录日志
+    fn ingest_streamed(
+        &self,
+        reader: impl std::io::Read,
+        kind: ItemKind,
+        make_plan: impl FnOnce(&LocalIngestDeps<'_>, StreamedBlob, &AppConfig) -> anyhow::Result<IngestPlan>,
+    ) -> anyhow::Result<ItemMeta> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+        let app_config = &self.inner.core_config.app_config;
+        let tmp_name = format!("ingest_{}.tmp", uuid::Uuid::new_v4());
+
+        let res = (|| -> anyhow::Result<ItemMeta> {
+            let (tmp_path, sha256, total_bytes) =
+                self.inner.cas.write_tmp_from_reader(reader, &tmp_name, app_config.size_limits.hard_bytes(&kind))?;
+            let deps = LocalIngestDeps {
+                device_id: &self.inner.core_config.device_id,
+                device_name: &self.inner.core_config.device_name,
+                account_uid: &self.inner.core_config.account_uid,
+            };
+            let plan = make_plan(&deps, StreamedBlob { tmp_path, sha256, total_bytes }, app_config)?;
+            self.apply_ingest(plan)
+        })();
+
+        if let Err(e) = &res {
+            let _ = std::fs::remove_file(self.inner.cas.get_tmp_path(&tmp_name));
+            let mut log_store = self.inner.log_store.lock().unwrap();
+            let _ = log_store.log_error(
+                "Ingest",
+                &format!("Streamed ingest failed: {}", e),
+                Some(&format!("流式内容摄入失败: {}", e)),
+                Some(&e.to_string()),
+            );
+        }
+        res
+    }
+
     /**
      * 获取当前在线设备列表。
      * * 这是一个同步阻塞调用，适合 FFI 使用。
@@ -605,14 +1171,117 @@ impl Core {
         Ok(())
     }
 
+    /// 添加静态对端（"host:port"），用于 mDNS 发现不到对方的网络；对方的 device_id 握手后得知。
+    /// 返回 false 表示地址已存在
+    pub fn add_static_peer(&self, addr: &str) -> anyhow::Result<bool> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+
+        let addr = crate::net::normalize_static_peer(addr)?;
+        let added = self.inner.store.lock().unwrap()
+            .add_static_peer(&self.inner.core_config.account_uid, &addr, now_ms())?;
+        if added {
+            if let Some(net_tx) = &self.inner.net {
+                let _ = net_tx.try_send(NetCmd::AddStaticPeer(addr));
+            }
+        }
+        Ok(added)
+    }
+
+    /// 删除静态对端；已建立的会话不受影响，断开后不再重拨。返回 false 表示地址不存在
+    pub fn remove_static_peer(&self, addr: &str) -> anyhow::Result<bool> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+
+        let addr = addr.trim();
+        let removed = self.inner.store.lock().unwrap()
+            .remove_static_peer(&self.inner.core_config.account_uid, addr)?;
+        if removed {
+            if let Some(net_tx) = &self.inner.net {
+                let _ = net_tx.try_send(NetCmd::RemoveStaticPeer(addr.to_string()));
+            }
+        }
+        Ok(removed)
+    }
+
+    /// 本机实际监听的地址（监听端口配置为 0 时由系统分配），供其他设备配置为静态对端
+    pub fn listen_addrs(&self) -> anyhow::Result<Vec<std::net::SocketAddr>> {
+        if self.inner.is_shutdown.load(Ordering::Acquire) {
+            anyhow::bail!("core already shutdown");
+        }
+        let Some(net_tx) = &self.inner.net else {
+            return Ok(vec![]);
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        net_tx.blocking_send(NetCmd::GetListenAddrs(tx))
+            .map_err(|_| anyhow::anyhow!("NetManager channel closed"))?;
+        futures::executor::block_on(rx)
+            .map_err(|_| anyhow::anyhow!("Failed to receive response from NetManager"))
+    }
+
+    /// 列出静态对端（含配置文件中的），device_id 为握手后得知的对端身份
+    pub fn list_static_peers(&self) -> anyhow::Result<Vec<crate::store::StaticPeer>> {
+        self.inner.store.lock().unwrap().list_static_peers(&self.inner.core_config.account_uid)
+    }
+
 	pub fn ensure_content_cached(&self, item_id: &str, file_id: Option<&str>) -> anyhow::Result<String> {
+		self.ensure_content_cached_with_mimes(item_id, file_id, &[])
+	}
+
+	/// 一次拉取整个 FileList：返回父传输 id，进度以 ITEM_FETCH_PROGRESS 汇总，
+	/// 全部到齐后还原目录树并发出 ITEM_CACHED（带 root_path），任一文件失败则发出 ITEM_FETCH_FAILED
+	pub fn ensure_item_cached(&self, item_id: &str) -> anyhow::Result<String> {
+		if self.inner.is_shutdown.load(Ordering::Acquire) {
+			anyhow::bail!("core shutdown");
+		}
+		let Some(meta) = self.get_item_meta(item_id)? else {
+			anyhow::bail!("item not found: {}", item_id);
+		};
+		if meta.kind != ItemKind::FileList {
+			anyhow::bail!("item is not a file list: {}", item_id);
+		}
+		// 本机复制的文件就在原处，不需要拉取
+		if meta.source_device_id == self.inner.core_config.device_id {
+			anyhow::bail!("ITEM_IS_LOCAL: {}", item_id);
+		}
+		crate::item_fetch::start(self, meta)
+	}
+
+	/// 确保 item 的纯文本表示已缓存（RichText 取其降级内容，不支持富文本的平台使用）
+	pub fn ensure_text_fallback_cached(&self, item_id: &str) -> anyhow::Result<String> {
+		self.ensure_content_cached_with_mimes(item_id, None, &["text/plain".to_string()])
+	}
+
+	/// 按偏好 MIME 列表拉取：从主格式、额外格式、纯文本降级中选第一个匹配的表示，
+	/// 都不匹配时拉取主格式。file_id 非空（FileList 子文件）时忽略偏好。
+	pub fn ensure_content_cached_with_mimes(&self, item_id: &str, file_id: Option<&str>, preferred_mimes: &[String]) -> anyhow::Result<String> {
 		if self.inner.is_shutdown.load(std::sync::atomic::Ordering::Acquire) {
 			anyhow::bail!("core shutdown");
 		}
 
+		let meta_opt = self.get_item_meta(item_id)?;
+
+		// 目录条目没有内容（用 materialize_item_tree 还原）
+		if let (Some(meta), Some(fid)) = (&meta_opt, file_id) {
+			if meta.files.iter().any(|f| f.file_id == fid && f.is_dir) {
+				anyhow::bail!("IS_DIRECTORY: {}", fid);
+			}
+		}
+
+		// 选中的表示与主格式不同时才在 ContentGet 中指明 MIME（兼容旧版本对端）
+		let requested_mime = match (&meta_opt, file_id) {
+			(Some(meta), None) => {
+				let picked = meta.pick_representation(preferred_mimes);
+				(picked.mime != meta.content.mime).then(|| picked.mime.clone())
+			}
+			_ => None,
+		};
+
 		// ---------- Fast path: 本机内容不走网络 ----------
 		// 1) 查元数据（至少要拿到 source_device_id + content.sha256/bytes）
-		if let Some(meta) = self.get_item_meta(item_id)? {
+		if let Some(meta) = meta_opt {
 			// 你 init 里叫 device_id；这里按你真实字段改
 			let my_device_id = &self.inner.core_config.device_id;
 
@@ -620,14 +1289,23 @@ impl Core {
 			if meta.source_device_id == *my_device_id {
 				// v1：只处理 file_id == None 的简单类型（text/image）
 				if file_id.is_none() {
-					let content = meta.content;
+					let content = meta.pick_representation(preferred_mimes).clone();
+						// 大小对不上说明 blob 已被截断：隔离后按未缓存处理，不把坏数据交给壳
+						if self.inner.cas.blob_exists(&content.sha256)
+							&& !self.inner.cas.blob_size_matches(&content.sha256, content.total_bytes)
+						{
+							self.handle_corrupt_blob(&content.sha256)?;
+						}
+
 						// cas_has：判断 sha256 对应 blob 是否存在
 						// 新逻辑：命中本机缓存也返回一个可等待的 transfer_id，并同步发 CONTENT_CACHED
 						if self.inner.cas.blob_exists(&content.sha256) {
 							let transfer_id = uuid::Uuid::new_v4().to_string();
 
 							// kind：与接收侧一致的简单判定（你接收侧也是这么做的）
-							let kind = if content.mime.starts_with("text/") {
+							let kind = if content.mime == "text/html" || content.mime == "text/rtf" {
+								"rich_text"
+							} else if content.mime.starts_with("text/") {
 								"text"
 							} else if content.mime.starts_with("image/") {
 								"image"
@@ -636,13 +1314,17 @@ impl Core {
 							};
 
 							// local_path：text 直接给 CAS 路径；image 建议 materialize 带扩展名（可选）
-							let local_path = if kind == "image" {
+							let local_path = if kind == "image" || kind == "rich_text" {
 								let ext = if content.mime == "image/png" {
 									Some("png")
 								} else if content.mime == "image/jpeg" {
 									Some("jpg")
 								} else if content.mime == "image/gif" {
 									Some("gif")
+								} else if content.mime == "text/html" {
+									Some("html")
+								} else if content.mime == "text/rtf" {
+									Some("rtf")
 								} else {
 									None
 								};
@@ -683,6 +1365,7 @@ impl Core {
 						});
 
 							self.inner.emit_json(evt);
+							self.inner.fetch_routes.lock().unwrap().notify(FetchMsg::Cached { transfer_id: transfer_id.clone() });
 
 							return Ok(transfer_id);
 						}
@@ -702,6 +1385,7 @@ impl Core {
 				item_id: item_id.to_string(),
 				file_id: file_id.map(|s| s.to_string()),
 				force: false,
+				mime: requested_mime,
 				reply: tx,
 			})
 			.map_err(|_| anyhow::anyhow!("NetManager closed"))?;
@@ -713,8 +1397,13 @@ impl Core {
 	}
 
 
-    /// M3: 取消传输
+    /// M3: 取消传输（也可传入 ensure_item_cached 返回的父传输 id，整体取消）
     pub fn cancel_transfer(&self, transfer_id: &str) {
+        let parent = self.inner.fetch_routes.lock().unwrap().parent(transfer_id);
+        if let Some(tx) = parent {
+            let _ = tx.send(FetchMsg::Cancel);
+            return;
+        }
         if let Some(net_tx) = &self.inner.net {
             let _ = net_tx.try_send(crate::net::NetCmd::CancelTransfer {
                 transfer_id: transfer_id.to_string(),
@@ -722,30 +1411,180 @@ impl Core {
         }
     }
 
-	// [修改] 增强 list_history，虽然底层 store 可能只支持 limit，但 API 要预留 cursor 位置
-	pub fn list_history(&self, limit: usize, _cursor: Option<i64>) -> anyhow::Result<Vec<crate::model::ItemMeta>> {
+	/// 列出历史（从 cursor 之后开始，不过滤）
+	pub fn list_history(&self, limit: usize, cursor: Option<HistoryCursor>) -> anyhow::Result<Vec<crate::model::ItemMeta>> {
+		Ok(self.list_history_page(limit, cursor, &HistoryFilter::default())?.items)
+	}
+
+	/// 分页 + 过滤查询历史，返回下一页游标
+	pub fn list_history_page(
+		&self,
+		limit: usize,
+		cursor: Option<HistoryCursor>,
+		filter: &HistoryFilter,
+	) -> anyhow::Result<HistoryPage> {
 		if self.inner.is_shutdown.load(Ordering::Acquire) {
 			anyhow::bail!("core already shutdown");
 		}
 		let store = self.inner.store.lock().unwrap();
-		// 目前 store.rs 里的 list_history_metas 只接受 limit
-		// 后续你需要去 store.rs 实现基于 cursor 的分页
-		store.list_history_metas(&self.inner.core_config.account_uid, limit)
+		store.list_history_metas(&self.inner.core_config.account_uid, limit, cursor.as_ref(), filter)
 	}
 
-	// [新增] 获取单条 Meta，供 FFI 调用
-	pub fn get_item_meta(&self, item_id: &str) -> anyhow::Result<Option<crate::model::ItemMeta>> {
+	/// 全文检索历史（预览、已缓存的文本正文、文件名），结果按时间倒序分页
+	pub fn search_history(
+		&self,
+		query: &str,
+		filter: &HistoryFilter,
+		cursor: Option<HistoryCursor>,
+		limit: usize,
+	) -> anyhow::Result<SearchPage> {
 		if self.inner.is_shutdown.load(Ordering::Acquire) {
 			anyhow::bail!("core already shutdown");
 		}
 		let store = self.inner.store.lock().unwrap();
+		store.search_history(&self.inner.core_config.account_uid, query, limit, cursor.as_ref(), filter)
+	}
+
+	/// 删除单条历史：条目变为墓碑，不再被其它条目引用的 CAS 内容一并删除。
+	/// scope=All 时同时通知在线对端删除，本机之后也会拒绝对端对该条目的内容拉取。
+	pub fn delete_item(&self, item_id: &str, scope: DeleteScope) -> anyhow::Result<()> {
+		if self.inner.is_shutdown.load(Ordering::Acquire) {
+			anyhow::bail!("core already shutdown");
+		}
+
+		let now = now_ms();
+		let orphans = {
+			let mut store = self.inner.store.lock().unwrap();
+			store.delete_item(item_id, now)?
+		};
+		let Some(orphans) = orphans else {
+			anyhow::bail!("item not found: {}", item_id);
+		};
+		let (files, bytes) = self.remove_blobs(&orphans)?;
+		{
+			let mut stats = self.inner.gc_stats.lock().unwrap();
+			add_reclaim(&mut stats.total, ReclaimReason::Deleted, files, bytes);
+		}
+
+		{
+			let mut log_store = self.inner.log_store.lock().unwrap();
+			let _ = log_store.log_info(
+				"History",
+				&format!("Item deleted: item_id={}, scope={:?}, blobs_removed={}", item_id, scope, files),
+				Some(&format!("条目已删除: 项目ID={}，范围={:?}，移除缓存={}", item_id, scope, files)),
+			);
+		}
+		self.inner.emit_json(serde_json::json!({
+			"type": "ITEM_DELETED",
+			"ts_ms": now,
+			"payload": {
+				"item_id": item_id,
+				"scope": scope
+			}
+		}));
+
+		if scope == DeleteScope::All {
+			if let Some(net_tx) = &self.inner.net {
+				let _ = net_tx.try_send(NetCmd::BroadcastDelete {
+					item_id: item_id.to_string(),
+					deleted_ts_ms: now,
+				});
+			}
+		}
+		Ok(())
+	}
 
-		// 这里的 SQL 查询逻辑需要你确认 store.rs 里有没有。
-		// 如果 store.rs 还没有 get_item_meta，你需要去加一个简单的 select。
-		// 为了方便，这里暂时用 list_history 模拟（性能差，建议后续在 Store 实现专用查询）
-		let list = store.list_history_metas(&self.inner.core_config.account_uid, 100)?;
-		Ok(list.into_iter().find(|i| i.item_id == item_id))
+	/// 置顶（收藏）历史条目：置顶条目不会被 History GC 清理，其内容也不会被 Cache GC 淘汰，
+	/// 置顶期间也不会过期（见 `ExpiryPolicy`）
+	pub fn pin_item(&self, item_id: &str) -> anyhow::Result<()> {
+		self.set_item_pinned(item_id, true)
+	}
+
+	/// 取消置顶；已过期的条目会在下一次 GC 时清理
+	pub fn unpin_item(&self, item_id: &str) -> anyhow::Result<()> {
+		self.set_item_pinned(item_id, false)
+	}
+
+	fn set_item_pinned(&self, item_id: &str, pinned: bool) -> anyhow::Result<()> {
+		if self.inner.is_shutdown.load(Ordering::Acquire) {
+			anyhow::bail!("core already shutdown");
+		}
+		{
+			let mut store = self.inner.store.lock().unwrap();
+			if !store.set_item_pinned(&self.inner.core_config.account_uid, item_id, pinned)? {
+				anyhow::bail!("item not found in history: {}", item_id);
+			}
+		}
+		{
+			let mut log_store = self.inner.log_store.lock().unwrap();
+			let _ = log_store.log_info(
+				"History",
+				&format!("Item {}: item_id={}", if pinned { "pinned" } else { "unpinned" }, item_id),
+				Some(&format!("{}: 项目ID={}", if pinned { "已置顶" } else { "已取消置顶" }, item_id)),
+			);
+		}
+		self.inner.emit_json(serde_json::json!({
+			"type": "ITEM_PIN_CHANGED",
+			"ts_ms": now_ms(),
+			"payload": {
+				"item_id": item_id,
+				"pinned": pinned
+			}
+		}));
+		Ok(())
 	}
+
+	/// 在 downloads/<item_id>/ 下还原 FileList 的目录树：包括空目录、权限与修改时间。
+	/// 内容尚未缓存的文件跳过并在 missing_file_ids 中列出，拉取后再次调用即可补齐。
+	pub fn materialize_item_tree(&self, item_id: &str) -> anyhow::Result<TreeView> {
+		let Some(meta) = self.get_item_meta(item_id)? else {
+			anyhow::bail!("item not found: {}", item_id);
+		};
+		if meta.kind != ItemKind::FileList {
+			anyhow::bail!("item is not a file list: {}", item_id);
+		}
+
+		let entries: Vec<TreeEntry<'_>> = meta
+			.files
+			.iter()
+			.map(|f| TreeEntry {
+				rel_name: &f.rel_name,
+				is_dir: f.is_dir,
+				sha256: f.sha256.as_deref(),
+				attrs: FileAttrs { mode: f.mode, mtime_ms: f.mtime_ms },
+			})
+			.collect();
+		let (root, missing) = self.inner.cas.materialize_tree(&meta.item_id, &entries)?;
+		Ok(TreeView {
+			root_path: root.to_string_lossy().into_owned(),
+			missing_file_ids: missing.into_iter().map(|i| meta.files[i].file_id.clone()).collect(),
+		})
+	}
+
+	/// 获取单条 Meta（供 FFI 及内容拉取使用），不包含已被 History GC 软删除的条目
+	pub fn get_item_meta(&self, item_id: &str) -> anyhow::Result<Option<crate::model::ItemMeta>> {
+		if self.inner.is_shutdown.load(Ordering::Acquire) {
+			anyhow::bail!("core already shutdown");
+		}
+		let store = self.inner.store.lock().unwrap();
+		store.get_item_meta(&self.inner.core_config.account_uid, item_id, false)
+	}
+}
+
+/// 计算源文件的 sha256 并记下修改时间。大小或修改时间与复制时记录的不符、无法读取，
+/// 或哈希期间被修改时返回 None（视为源文件已变化）。
+fn hash_source_file(path: &std::path::Path, size_bytes: i64, mtime_ms: Option<i64>) -> Option<(String, Option<i64>)> {
+    let before = std::fs::metadata(path).ok()?;
+    if crate::util::source_file_changed(&before, size_bytes, mtime_ms) {
+        return None;
+    }
+    let mtime = crate::util::file_mtime_ms(&before);
+    let (sha, len) = crate::util::sha256_file(path).ok()?;
+    let after = std::fs::metadata(path).ok()?;
+    if len as i64 != size_bytes || crate::util::source_file_changed(&after, size_bytes, mtime) {
+        return None;
+    }
+    Some((sha, mtime))
 }
 
 #[cfg(test)]
@@ -769,6 +1608,15 @@ pub struct Inner {
     pub stats_store: Arc<Mutex<StatsStore>>,
     pub cas: Cas,
     pub net: Option<mpsc::Sender<NetCmd>>,
+    pub gc_stats: Mutex<GcStats>,
+    pub(crate) fetch_routes: Arc<Mutex<FetchRoutes>>,
+}
+
+/// GC 回收记录（供 cache_report 使用）
+#[derive(Default)]
+pub struct GcStats {
+    pub last: Option<GcRun>,
+    pub total: Vec<ReclaimStat>,
 }
 
 
@@ -21,9 +21,22 @@ impl CoreEventSink for PrintSink {
 
 /// 兼容旧测试调用：保留 mk_core(sub, gc_history_max_items, gc_cas_max_bytes)
 pub fn mk_core(sub: &str, gc_history_max_items: i64, gc_cas_max_bytes: i64) -> (Core, TestDirs) {
+	mk_core_with(sub, |app| {
+		app.gc_history_max_items = gc_history_max_items;
+		app.gc_cas_max_bytes = gc_cas_max_bytes;
+	})
+}
+
+/// 需要调整其它 AppConfig 字段时使用
+pub fn mk_core_with(sub: &str, modify: impl FnOnce(&mut AppConfig)) -> (Core, TestDirs) {
 	let dirs = unique_dirs(sub);
+	let core = mk_core_in(&dirs, modify);
+	(core, dirs)
+}
 
-	let cfg = CoreConfig {
+/// 在已准备好的目录上启动（例如先放入旧版数据库夹具）
+pub fn mk_core_in(dirs: &TestDirs, modify: impl FnOnce(&mut AppConfig)) -> Core {
+	let mut cfg = CoreConfig {
 		device_id: "dev-1".to_string(),
 		device_name: "dev1".to_string(),
 		account_uid: "acct-uid-1".to_string(),
@@ -33,15 +46,70 @@ pub fn mk_core(sub: &str, gc_history_max_items: i64, gc_cas_max_bytes: i64) -> (
 		data_dir: dirs.data_dir.clone(),
 		cache_dir: dirs.cache_dir.clone(),
 
-		app_config: AppConfig {
-			gc_history_max_items,
-			gc_cas_max_bytes,
-			..Default::default()
-		},
+		app_config: AppConfig::default(),
 	};
+	modify(&mut cfg.app_config);
 
 	let sink: Arc<dyn CoreEventSink> = Arc::new(PrintSink);
-	let core = Core::init(cfg, sink);
+	Core::init(cfg, sink)
+}
 
-	(core, dirs)
+pub fn ingest_text(core: &Core, text: &str, ts_ms: i64) -> crate::model::ItemMeta {
+	core.ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+		text_utf8: text.to_string(),
+		ts_ms,
+	})
+	.unwrap()
+}
+
+/// 40 字节的“图片”，fill 不同即内容不同
+pub fn ingest_image(core: &Core, fill: u8, ts_ms: i64) -> crate::model::ItemMeta {
+	core.ingest_local_copy(crate::clipboard::ClipboardSnapshot::Image {
+		bytes: vec![fill; 40],
+		mime: "image/png".to_string(),
+		ts_ms,
+	})
+	.unwrap()
+}
+
+/// 等待类型为 ty、内容包含 needle 的事件（超时返回 None；缓冲溢出时跳过丢失的事件继续等）
+pub async fn wait_event(rx: &mut tokio::sync::broadcast::Receiver<String>, ty: &str, needle: &str) -> Option<String> {
+	let start = std::time::Instant::now();
+	while start.elapsed() < std::time::Duration::from_secs(120) {
+		match rx.try_recv() {
+			Ok(evt) if evt.contains(&format!("\"type\":\"{ty}\"")) && evt.contains(needle) => return Some(evt),
+			Ok(_) => {}
+			Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {}
+			Err(_) => tokio::time::sleep(std::time::Duration::from_millis(20)).await,
+		}
+	}
+	None
+}
+
+/// 等待 core 与 peers 中的每一台设备都处于在线状态
+pub async fn online_with(core: &Arc<Core>, peers: &[&str]) -> bool {
+	super::m1_net::wait_for(std::time::Duration::from_secs(120), || async {
+		let list = super::m1_net::list_peers_async(core).await;
+		peers.iter().all(|d| list.iter().any(|p| p.device_id == *d && p.state == PeerConnectionState::Online))
+	}).await
+}
+
+/// 在阻塞线程上调用 ensure_content_cached，返回 transfer_id
+pub async fn fetch(core: &Arc<Core>, item_id: &str, file_id: Option<&str>) -> anyhow::Result<String> {
+	let c = core.clone();
+	let (iid, fid) = (item_id.to_string(), file_id.map(str::to_string));
+	tokio::task::spawn_blocking(move || c.ensure_content_cached(&iid, fid.as_deref())).await.unwrap()
+}
+
+/// 复制一个文件夹时剪贴板给出的条目（由 abs_path 识别为目录）
+pub fn folder_entry(root: &std::path::Path) -> crate::clipboard::ClipboardFileEntry {
+	crate::clipboard::ClipboardFileEntry {
+		rel_name: root.file_name().unwrap().to_string_lossy().into_owned(),
+		abs_path: Some(root.to_string_lossy().into_owned()),
+		size_bytes: 0,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	}
 }
@@ -0,0 +1,117 @@
+use super::common::*;
+use super::m1_net::create_test_core;
+use crate::api::Core;
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+use crate::session::compression::{choose, Compressor, Decompressor, ZSTD};
+
+#[test]
+fn compression_is_chosen_by_type_size_and_peer() {
+	let zstd_peer = vec!["text".to_string(), ZSTD.to_string()];
+	let old_peer = vec!["text".to_string()];
+
+	assert_eq!(choose(&zstd_peer, true, "text/plain", None, 64 * 1024), Some(ZSTD));
+	assert_eq!(choose(&zstd_peer, true, "application/octet-stream", Some("notes.TXT"), 64 * 1024), Some(ZSTD));
+	// 对端不支持 / 本端关闭 / 内容太小
+	assert_eq!(choose(&old_peer, true, "text/plain", None, 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, false, "text/plain", None, 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, true, "text/plain", None, 100), None);
+	// 已压缩的格式：按 MIME 或文件扩展名
+	assert_eq!(choose(&zstd_peer, true, "image/png", None, 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, true, "image/jpeg", None, 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, true, "application/octet-stream", Some("dir/photo.JPG"), 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, true, "application/octet-stream", Some("backup.zip"), 64 * 1024), None);
+	assert_eq!(choose(&zstd_peer, true, "video/mp4", None, 64 * 1024), None);
+
+	// 分块压缩、分块解压后与原文一致
+	let data: Vec<u8> = b"clipboard sync ".iter().copied().cycle().take(300_000).collect();
+	let mut enc = Compressor::new().unwrap();
+	let mut wire = Vec::new();
+	for chunk in data.chunks(64 * 1024) {
+		wire.extend(enc.push(chunk).unwrap());
+	}
+	wire.extend(enc.finish().unwrap());
+	assert!(wire.len() < data.len() / 10, "{} bytes on the wire", wire.len());
+
+	let mut dec = Decompressor::new(data.len() as u64).unwrap();
+	let mut out = Vec::new();
+	for chunk in wire.chunks(1000) {
+		out.extend(dec.push(chunk).unwrap());
+	}
+	assert_eq!(out, data);
+	assert!(Decompressor::new(1 << 20).unwrap().push(b"definitely not zstd").is_err());
+	// 解压结果超过声明的大小：立即失败，不会先把全部内容解出来
+	let mut dec = Decompressor::new(data.len() as u64 - 1).unwrap();
+	assert!(wire.chunks(1000).try_for_each(|chunk| dec.push(chunk).map(drop)).is_err());
+}
+
+async fn fetch_file(
+	core: &std::sync::Arc<Core>,
+	rx: &mut tokio::sync::broadcast::Receiver<String>,
+	item_id: &str,
+	file_id: &str,
+) -> serde_json::Value {
+	let c = core.clone();
+	let (iid, fid) = (item_id.to_string(), file_id.to_string());
+	let tid = tokio::task::spawn_blocking(move || c.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+	let evt = wait_event(rx, "CONTENT_CACHED", &tid).await.expect("content not cached");
+	serde_json::from_str::<serde_json::Value>(&evt).unwrap()["payload"].clone()
+}
+
+#[tokio::test]
+async fn compressible_files_travel_compressed() {
+	let shared_uid = format!("compression_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("cz_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("cz_b", &shared_uid, |_| {});
+	let (core_c, mut rx_c, _dir_c) = create_test_core("cz_c", &shared_uid, |cfg| {
+		cfg.app_config.transfer.compression = false;
+	});
+	for (core, peers) in [(&core_a, &["cz_b", "cz_c"][..]), (&core_b, &["cz_a"][..]), (&core_c, &["cz_a"][..])] {
+		assert!(online_with(core, peers).await, "Peers not connected");
+	}
+
+	let text: Vec<u8> = b"the quick brown fox jumps over the lazy dog\n".iter().copied().cycle().take(512 * 1024).collect();
+	// PNG 头开头的伪随机数据：按扩展名跳过压缩
+	let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+	png.extend((0..64 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8));
+	let mut entries = Vec::new();
+	for (name, data) in [("notes.txt", &text), ("photo.png", &png)] {
+		let src = dir_a.path().join(name);
+		std::fs::write(&src, data).unwrap();
+		entries.push(ClipboardFileEntry {
+			rel_name: name.to_string(),
+			abs_path: Some(src.to_string_lossy().into_owned()),
+			size_bytes: data.len() as i64,
+			sha256: None,
+			is_dir: false,
+			mode: None,
+			mtime_ms: None,
+		});
+	}
+	let c_a = core_a.clone();
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: entries, ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	// 等发送端算完哈希
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+	assert!(wait_event(&mut rx_c, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+	let file_id = |name: &str| meta.files.iter().find(|f| f.rel_name == name).unwrap().file_id.clone();
+
+	// 双方都支持：文本压缩传输，接收端解压后按原始内容校验落盘
+	let cached = fetch_file(&core_b, &mut rx_b, &meta.item_id, &file_id("notes.txt")).await;
+	assert_eq!(cached["compression"], serde_json::json!("zstd"));
+	assert_eq!(std::fs::read(cached["local_ref"]["local_path"].as_str().unwrap()).unwrap(), text);
+
+	// 已压缩的格式原样发送
+	let cached = fetch_file(&core_b, &mut rx_b, &meta.item_id, &file_id("photo.png")).await;
+	assert!(cached["compression"].is_null());
+	assert_eq!(std::fs::read(cached["local_ref"]["local_path"].as_str().unwrap()).unwrap(), png);
+
+	// 对端关闭了压缩：不声明能力，发送端不压缩
+	let cached = fetch_file(&core_c, &mut rx_c, &meta.item_id, &file_id("notes.txt")).await;
+	assert!(cached["compression"].is_null());
+	assert_eq!(std::fs::read(cached["local_ref"]["local_path"].as_str().unwrap()).unwrap(), text);
+
+	core_a.shutdown();
+	core_b.shutdown();
+	core_c.shutdown();
+}
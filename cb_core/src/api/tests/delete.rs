@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::model::DeleteScope;
+
+#[test]
+fn delete_item_removes_history_search_and_blob() {
+	let (core, _dirs) = mk_core("delete_local", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let secret = ingest_text(&core, "hunter2-password", ts);
+	let keep = ingest_text(&core, "keep me", ts + 1);
+	core.pin_item(&secret.item_id).unwrap();
+
+	core.delete_item(&secret.item_id, DeleteScope::Local).unwrap();
+
+	let ids: Vec<_> = core.list_history(10, None).unwrap().into_iter().map(|m| m.item_id).collect();
+	assert_eq!(ids, vec![keep.item_id.clone()]);
+	assert!(core.get_item_meta(&secret.item_id).unwrap().is_none());
+	assert!(core.search_history("hunter2", &Default::default(), None, 10).unwrap().hits.is_empty());
+	assert!(!core.inner.cas.blob_exists(&secret.content.sha256));
+	assert!(core.inner.cas.blob_exists(&keep.content.sha256));
+
+	// 墓碑保留：不能重复删除，重放的元数据也不会让它重新出现
+	assert!(core.delete_item(&secret.item_id, DeleteScope::Local).is_err());
+	let replayed = {
+		let mut store = core.inner.store.lock().unwrap();
+		store.insert_remote_item("acct-uid-1", &secret, ts).unwrap()
+	};
+	assert!(!replayed);
+	assert!(core.get_item_meta(&secret.item_id).unwrap().is_none());
+}
+
+#[test]
+fn delete_item_keeps_shared_blob() {
+	let (core, _dirs) = mk_core("delete_shared", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let a = ingest_text(&core, "same text", ts);
+	let b = ingest_text(&core, "same text", ts + 1);
+	assert_eq!(a.content.sha256, b.content.sha256);
+
+	core.delete_item(&a.item_id, DeleteScope::Local).unwrap();
+	assert!(core.inner.cas.blob_exists(&b.content.sha256));
+	assert!(core.ensure_content_cached(&b.item_id, None).is_ok());
+
+	core.delete_item(&b.item_id, DeleteScope::Local).unwrap();
+	assert!(!core.inner.cas.blob_exists(&b.content.sha256));
+}
+
+#[tokio::test]
+async fn delete_item_propagates_and_refuses_content() {
+	let shared_uid = format!("delete_sync_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("del_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("del_b", &shared_uid, |c| {
+		c.app_config.size_limits.text_auto_prefetch_bytes = 0;
+	});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "del_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let ts = crate::util::now_ms();
+	let shared = ingest_text(&core_a, "oops, a password", ts);
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &shared.item_id).await.is_some());
+	let local_only = ingest_text(&core_a, "deleted only on A", ts + 1);
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &local_only.item_id).await.is_some());
+
+	// 1. scope=all：B 收到通知后删除本地副本
+	core_a.delete_item(&shared.item_id, DeleteScope::All).unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_DELETED", &shared.item_id).await.is_some(), "B did not receive ITEM_DELETED");
+	let c_b = core_b.clone();
+	let iid = shared.item_id.clone();
+	let meta = tokio::task::spawn_blocking(move || c_b.get_item_meta(&iid)).await.unwrap().unwrap();
+	assert!(meta.is_none());
+
+	// 2. scope=local：B 仍有元数据，但 A 拒绝提供内容
+	core_a.delete_item(&local_only.item_id, DeleteScope::Local).unwrap();
+	let c_b = core_b.clone();
+	let iid = local_only.item_id.clone();
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, None))
+		.await
+		.unwrap()
+		.unwrap();
+	assert!(
+		wait_event(&mut rx_b, "TRANSFER_FAILED", &transfer_id).await.is_some(),
+		"B did not receive TRANSFER_FAILED(ITEM_DELETED)"
+	);
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
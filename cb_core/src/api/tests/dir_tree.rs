@@ -0,0 +1,177 @@
+use std::path::Path;
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::cas::{safe_rel_path, FileAttrs, TreeEntry};
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+
+const OLD_MTIME_MS: i64 = 1_600_000_000_000;
+
+/// root/
+///   b.bin
+///   sub/a.txt
+///   sub/empty/
+fn make_tree(base: &Path) -> std::path::PathBuf {
+	let root = base.join("root");
+	std::fs::create_dir_all(root.join("sub").join("empty")).unwrap();
+	std::fs::write(root.join("sub").join("a.txt"), b"nested file").unwrap();
+	std::fs::write(root.join("b.bin"), [9u8; 2048]).unwrap();
+	let old = std::time::UNIX_EPOCH + Duration::from_millis(OLD_MTIME_MS as u64);
+	std::fs::File::options().write(true).open(root.join("sub").join("a.txt")).unwrap().set_modified(old).unwrap();
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		std::fs::set_permissions(root.join("b.bin"), std::fs::Permissions::from_mode(0o755)).unwrap();
+	}
+	root
+}
+
+#[test]
+fn unsafe_relative_paths_are_rejected() {
+	for bad in ["", "/", "../evil", "a/../../b", "/etc/passwd", "\\\\server\\share", "C:\\Windows\\x", "a/./b", "x:stream", "a\0b"] {
+		assert!(safe_rel_path(bad).is_err(), "accepted {:?}", bad);
+	}
+	assert_eq!(safe_rel_path("dir/sub/a.txt").unwrap(), Path::new("dir").join("sub").join("a.txt"));
+	assert_eq!(safe_rel_path("dir\\b.txt").unwrap(), Path::new("dir").join("b.txt"));
+	assert_eq!(safe_rel_path("dir/").unwrap(), Path::new("dir"));
+
+	let (core, dirs) = mk_core("tree_traversal", 1_000_000, 1_i64 << 60);
+	let cas = &core.inner.cas;
+	let sha = cas.put_blob(b"payload").unwrap();
+	let downloads = Path::new(&dirs.cache_dir).join("downloads");
+
+	assert!(cas.materialize_file(&sha, "tid-1", "../../escaped.txt", &FileAttrs::default()).is_err());
+	assert!(cas.materialize_file(&sha, "../tid", "a.txt", &FileAttrs::default()).is_err());
+	let entries = [
+		TreeEntry { rel_name: "ok.txt", is_dir: false, sha256: Some(&sha), attrs: FileAttrs::default() },
+		TreeEntry { rel_name: "sub/../../escaped", is_dir: true, sha256: None, attrs: FileAttrs::default() },
+	];
+	assert!(cas.materialize_tree("item-1", &entries).is_err());
+	// 整体拒绝：合法的那一项也没有写入
+	assert!(!downloads.join("item-1").join("ok.txt").exists());
+	assert!(!Path::new(&dirs.cache_dir).join("escaped.txt").exists());
+
+	// 本地摄入同样拒绝不安全的路径
+	let err = core
+		.ingest_local_copy(ClipboardSnapshot::FileList {
+			files: vec![ClipboardFileEntry { rel_name: "../x".to_string(), ..folder_entry(Path::new("/nonexistent")) }],
+			ts_ms: 1,
+		})
+		.unwrap_err();
+	assert!(err.to_string().contains("INVALID_REL_PATH"));
+}
+
+#[test]
+fn copied_folder_becomes_a_tree() {
+	let (core, dirs) = mk_core("tree_local", 1_000_000, 1_i64 << 60);
+	let root = make_tree(Path::new(&dirs.cache_dir).join("src").as_path());
+
+	let meta = core
+		.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![folder_entry(&root)], ts_ms: crate::util::now_ms() })
+		.unwrap();
+	let names: Vec<(&str, bool)> = meta.files.iter().map(|f| (f.rel_name.as_str(), f.is_dir)).collect();
+	assert_eq!(names, vec![
+		("root", true),
+		("root/b.bin", false),
+		("root/sub", true),
+		("root/sub/a.txt", false),
+		("root/sub/empty", true),
+	]);
+	assert_eq!(meta.size_bytes, 2048 + 11);
+	assert_eq!(meta.preview.file_count, Some(2));
+	assert_eq!(meta.files[3].mtime_ms, Some(OLD_MTIME_MS));
+	#[cfg(unix)]
+	assert_eq!(meta.files[1].mode, Some(0o755));
+
+	// 本机条目的内容还不在 CAS 中：只还原目录
+	let view = core.materialize_item_tree(&meta.item_id).unwrap();
+	assert_eq!(view.missing_file_ids, vec![meta.files[1].file_id.clone(), meta.files[3].file_id.clone()]);
+	assert!(Path::new(&view.root_path).join("root/sub/empty").is_dir());
+
+	// 内容入库后再次还原即补齐，并带上修改时间与权限
+	core.inner.cas.put_blob(&[9u8; 2048]).unwrap();
+	core.inner.cas.put_blob(b"nested file").unwrap();
+	let hashed = {
+		let start = std::time::Instant::now();
+		loop {
+			let m = core.get_item_meta(&meta.item_id).unwrap().unwrap();
+			if m.files.iter().all(|f| f.is_dir || f.sha256.is_some()) || start.elapsed() > Duration::from_secs(10) {
+				break m;
+			}
+			std::thread::sleep(Duration::from_millis(50));
+		}
+	};
+	assert!(hashed.files.iter().filter(|f| f.is_dir).all(|f| f.sha256.is_none()));
+
+	let view = core.materialize_item_tree(&meta.item_id).unwrap();
+	assert!(view.missing_file_ids.is_empty());
+	let out = Path::new(&view.root_path);
+	assert_eq!(std::fs::read(out.join("root/sub/a.txt")).unwrap(), b"nested file");
+	let md = std::fs::metadata(out.join("root/sub/a.txt")).unwrap();
+	assert_eq!(crate::util::file_mtime_ms(&md), Some(OLD_MTIME_MS));
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		let md = std::fs::metadata(out.join("root/b.bin")).unwrap();
+		assert_eq!(md.permissions().mode() & 0o777, 0o755);
+	}
+
+	// 视图仍被引用：孤儿清扫不会删除
+	core.run_gc("Test").unwrap();
+	assert!(out.join("root/sub/empty").is_dir());
+}
+
+#[tokio::test]
+async fn receiver_recreates_tree() {
+	let shared_uid = format!("tree_sync_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("tree_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("tree_b", &shared_uid, |_| {});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "tree_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let root = make_tree(dir_a.path());
+	let c_a = core_a.clone();
+	let entry = folder_entry(&root);
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &meta.item_id).await.is_some());
+
+	// 目录条目不能单独拉取
+	let dir_id = meta.files[2].file_id.clone();
+	let c_b = core_b.clone();
+	let iid = meta.item_id.clone();
+	let err = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&dir_id))).await.unwrap().unwrap_err();
+	assert!(err.to_string().contains("IS_DIRECTORY"));
+
+	// 逐个拉取文件：单文件视图保留目录层级
+	for idx in [1, 3] {
+		let c_b = core_b.clone();
+		let (iid, fid) = (meta.item_id.clone(), meta.files[idx].file_id.clone());
+		let tid = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+		let evt = wait_event(&mut rx_b, "CONTENT_CACHED", &tid).await.expect("file not cached on B");
+		let evt: serde_json::Value = serde_json::from_str(&evt).unwrap();
+		let local_path = evt["payload"]["local_ref"]["local_path"].as_str().unwrap().replace('\\', "/");
+		assert!(local_path.ends_with(&meta.files[idx].rel_name), "{}", local_path);
+	}
+
+	let c_b = core_b.clone();
+	let iid = meta.item_id.clone();
+	let view = tokio::task::spawn_blocking(move || c_b.materialize_item_tree(&iid)).await.unwrap().unwrap();
+	assert!(view.missing_file_ids.is_empty());
+	let out = Path::new(&view.root_path);
+	assert!(out.join("root/sub/empty").is_dir());
+	assert_eq!(std::fs::read(out.join("root/sub/a.txt")).unwrap(), b"nested file");
+	assert_eq!(std::fs::read(out.join("root/b.bin")).unwrap(), vec![9u8; 2048]);
+	let md = std::fs::metadata(out.join("root/sub/a.txt")).unwrap();
+	assert_eq!(crate::util::file_mtime_ms(&md), Some(OLD_MTIME_MS));
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+
+#[test]
+fn ingest_applies_per_kind_ttl() {
+	let (core, _dirs) = mk_core_with("expiry_ttl", |app| {
+		app.expiry.text_ttl_ms = 60_000;
+		app.expiry.image_ttl_ms = 0;
+	});
+	let ts = crate::util::now_ms();
+
+	let text = ingest_text(&core, "short lived", ts);
+	assert_eq!(text.expires_ts_ms, Some(ts + 60_000));
+	let image = ingest_image(&core, 1, ts + 1);
+	assert_eq!(image.expires_ts_ms, None);
+
+	let stored = core.get_item_meta(&text.item_id).unwrap().unwrap();
+	assert_eq!(stored.expires_ts_ms, Some(ts + 60_000));
+}
+
+#[test]
+fn gc_purges_expired_items() {
+	let (core, _dirs) = mk_core_with("expiry_gc", |app| {
+		app.expiry.text_ttl_ms = 1_000;
+	});
+	let now = crate::util::now_ms();
+
+	// 创建时间在 10 秒前，入库即已过期
+	let old = ingest_text(&core, "expired secret", now - 10_000);
+	let fresh = ingest_text(&core, "fresh", now);
+
+	// GC 之前也不再列出 / 可查
+	let ids: Vec<_> = core.list_history(10, None).unwrap().into_iter().map(|m| m.item_id).collect();
+	assert_eq!(ids, vec![fresh.item_id.clone()]);
+	assert!(core.get_item_meta(&old.item_id).unwrap().is_none());
+
+	core.run_gc("Test").unwrap();
+	assert!(!core.inner.cas.blob_exists(&old.content.sha256));
+	assert!(core.inner.cas.blob_exists(&fresh.content.sha256));
+	assert!(core.search_history("secret", &Default::default(), None, 10).unwrap().hits.is_empty());
+}
+
+#[test]
+fn pinned_items_do_not_expire() {
+	let (core, _dirs) = mk_core_with("expiry_pinned", |app| {
+		app.expiry.text_ttl_ms = 1_000;
+	});
+	let now = crate::util::now_ms();
+
+	let keep = ingest_text(&core, "pinned note", now - 500);
+	let drop = ingest_text(&core, "temporary", now - 499);
+	core.pin_item(&keep.item_id).unwrap();
+
+	std::thread::sleep(Duration::from_millis(600));
+	core.run_gc("Test").unwrap();
+
+	let ids: Vec<_> = core.list_history(10, None).unwrap().into_iter().map(|m| m.item_id).collect();
+	assert_eq!(ids, vec![keep.item_id.clone()]);
+	assert!(core.inner.cas.blob_exists(&keep.content.sha256));
+	assert!(!core.inner.cas.blob_exists(&drop.content.sha256));
+	assert!(core.ensure_content_cached(&keep.item_id, None).is_ok());
+
+	// 豁免只在置顶期间有效：取消置顶后按原过期时间清理
+	core.unpin_item(&keep.item_id).unwrap();
+	core.run_gc("Test").unwrap();
+	assert!(core.list_history(10, None).unwrap().is_empty());
+	assert!(core.get_item_meta(&keep.item_id).unwrap().is_none());
+	assert!(!core.inner.cas.blob_exists(&keep.content.sha256));
+}
+
+#[tokio::test]
+async fn expired_items_are_not_served_or_accepted() {
+	let shared_uid = format!("expiry_sync_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("exp_a", &shared_uid, |c| {
+		c.app_config.expiry.text_ttl_ms = 3_000;
+	});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("exp_b", &shared_uid, |c| {
+		c.app_config.size_limits.text_auto_prefetch_bytes = 0;
+	});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "exp_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	// 1. 到达时已过期的元数据被 B 丢弃；用随后的正常条目确认消息已处理
+	let now = crate::util::now_ms();
+	let stale = ingest_text(&core_a, "already stale", now - 10_000);
+	let marker = ingest_text(&core_a, "expires soon", now);
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &marker.item_id).await.is_some());
+	let c_b = core_b.clone();
+	let iid = stale.item_id.clone();
+	let meta = tokio::task::spawn_blocking(move || c_b.get_item_meta(&iid)).await.unwrap().unwrap();
+	assert!(meta.is_none());
+
+	// 2. B 置顶后本地仍可见，但 A 过期后拒绝提供内容
+	let c_b = core_b.clone();
+	let iid = marker.item_id.clone();
+	tokio::task::spawn_blocking(move || c_b.pin_item(&iid)).await.unwrap().unwrap();
+	let wait_ms = (marker.expires_ts_ms.unwrap() - crate::util::now_ms()).max(0) as u64 + 200;
+	tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+
+	let c_b = core_b.clone();
+	let iid = marker.item_id.clone();
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, None))
+		.await
+		.unwrap()
+		.unwrap();
+	assert!(
+		wait_event(&mut rx_b, "TRANSFER_FAILED", &transfer_id).await.is_some(),
+		"B did not receive TRANSFER_FAILED(ITEM_EXPIRED)"
+	);
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
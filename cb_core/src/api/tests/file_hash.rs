@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+
+fn write_file(dir: &std::path::Path, name: &str, data: &[u8]) -> ClipboardFileEntry {
+	let path = dir.join(name);
+	std::fs::write(&path, data).unwrap();
+	ClipboardFileEntry {
+		rel_name: name.to_string(),
+		abs_path: Some(path.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	}
+}
+
+fn wait_hashed(core: &crate::api::Core, item_id: &str) -> crate::model::ItemMeta {
+	let start = std::time::Instant::now();
+	loop {
+		let meta = core.get_item_meta(item_id).unwrap().unwrap();
+		if meta.files.iter().all(|f| f.sha256.is_some()) || start.elapsed() > Duration::from_secs(10) {
+			return meta;
+		}
+		std::thread::sleep(Duration::from_millis(50));
+	}
+}
+
+#[test]
+fn file_list_hashes_are_computed_after_ingest() {
+	let (core, dirs) = mk_core("file_hash_local", 1_000_000, 1_i64 << 60);
+	let src = std::path::Path::new(&dirs.cache_dir);
+	let a = write_file(src, "a.txt", b"alpha contents");
+	let b = write_file(src, "b.bin", &[7u8; 70_000]);
+
+	let meta = core
+		.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![a, b], ts_ms: crate::util::now_ms() })
+		.unwrap();
+	assert!(meta.files.iter().all(|f| f.sha256.is_none()));
+
+	let hashed = wait_hashed(&core, &meta.item_id);
+	assert_eq!(hashed.files[0].sha256.as_deref(), Some(crate::util::sha256_hex(b"alpha contents").as_str()));
+	assert_eq!(hashed.files[1].sha256.as_deref(), Some(crate::util::sha256_hex(&[7u8; 70_000]).as_str()));
+	assert!(hashed.files.iter().all(|f| f.local_mtime_ms.is_some()));
+
+	// 广播时本地修改时间与路径一样被清除
+	let mut wire = hashed.clone();
+	wire.sanitize_for_broadcast();
+	assert!(wire.files.iter().all(|f| f.local_mtime_ms.is_none() && f.sha256.is_some()));
+
+	// 再次计算没有可更新的文件
+	assert!(core.hash_item_files(&meta.item_id).unwrap().is_none());
+}
+
+#[test]
+fn changed_source_file_is_not_hashed() {
+	let (core, dirs) = mk_core("file_hash_changed", 1_000_000, 1_i64 << 60);
+	let src = std::path::Path::new(&dirs.cache_dir);
+	let mut entry = write_file(src, "grown.txt", b"original");
+	// 复制时记录的大小与磁盘上的文件已不一致
+	entry.size_bytes = 3;
+
+	let meta = core
+		.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+		.unwrap();
+	assert!(core.hash_item_files(&meta.item_id).unwrap().is_none());
+	let stored = core.get_item_meta(&meta.item_id).unwrap().unwrap();
+	assert!(stored.files[0].sha256.is_none());
+}
+
+#[test]
+fn source_rewritten_after_copy_is_not_hashed() {
+	let (core, dirs) = mk_core("file_hash_rewritten", 1_000_000, 1_i64 << 60);
+	let src = std::path::Path::new(&dirs.cache_dir);
+	let entry = write_file(src, "same_size.txt", b"original");
+	let path = std::path::PathBuf::from(entry.abs_path.clone().unwrap());
+	let copied_at = std::time::SystemTime::now() - Duration::from_secs(3600);
+	std::fs::File::options().write(true).open(&path).unwrap().set_modified(copied_at).unwrap();
+
+	// 复制时刻的修改时间记在摄入计划里
+	let meta = core
+		.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+		.unwrap();
+	let recorded = meta.files[0].local_mtime_ms;
+	assert!(recorded.is_some());
+
+	// 大小不变、内容和修改时间都变了：不能把新内容的哈希记在复制时的条目上
+	std::fs::write(&path, b"replaced").unwrap();
+	assert!(core.hash_item_files(&meta.item_id).unwrap().is_none());
+	let stored = core.get_item_meta(&meta.item_id).unwrap().unwrap();
+	assert_ne!(stored.files[0].sha256.as_deref(), Some(crate::util::sha256_hex(b"replaced").as_str()));
+	assert_eq!(stored.files[0].local_mtime_ms, recorded);
+}
+
+#[tokio::test]
+async fn peers_receive_hashes_and_changed_files_are_refused() {
+	let shared_uid = format!("file_hash_sync_{}", uuid::Uuid::new_v4());
+	let (core_a, mut rx_a, dir_a) = create_test_core("fh_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("fh_b", &shared_uid, |_| {});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "fh_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let stable = write_file(dir_a.path(), "stable.txt", b"stable file");
+	let edited = write_file(dir_a.path(), "edited.txt", b"will be edited");
+	let edited_path = edited.abs_path.clone().unwrap();
+	let c_a = core_a.clone();
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList {
+			files: vec![stable, edited],
+			ts_ms: crate::util::now_ms(),
+		})
+	}).await.unwrap().unwrap();
+
+	// B 先收到没有哈希的元数据，随后收到 A 补算哈希后的更新
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &meta.item_id).await.is_some());
+	let evt = wait_event(&mut rx_b, "ITEM_META_UPDATED", &meta.item_id).await.expect("no ITEM_META_UPDATED on B");
+	let evt: serde_json::Value = serde_json::from_str(&evt).unwrap();
+	let stable_sha = crate::util::sha256_hex(b"stable file");
+	assert_eq!(evt["payload"]["meta"]["files"][0]["sha256"], serde_json::json!(stable_sha));
+	assert!(evt["payload"]["meta"]["files"][0].get("local_mtime_ms").is_none());
+
+	// 未改动的文件正常拉取，并按哈希进入 B 的 CAS
+	let c_b = core_b.clone();
+	let (iid, fid) = (meta.item_id.clone(), meta.files[0].file_id.clone());
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid)))
+		.await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &transfer_id).await.is_some());
+	assert!(core_b.inner.cas.blob_exists(&stable_sha));
+
+	// 复制之后被改动的文件：A 拒绝发送并报告 SOURCE_FILE_CHANGED
+	std::fs::write(&edited_path, b"edited after the copy").unwrap();
+	let c_b = core_b.clone();
+	let (iid, fid) = (meta.item_id.clone(), meta.files[1].file_id.clone());
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid)))
+		.await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_a, "SOURCE_FILE_CHANGED", &meta.files[1].file_id).await.is_some());
+	let failed = wait_event(&mut rx_b, "TRANSFER_FAILED", &transfer_id).await.expect("no TRANSFER_FAILED on B");
+	assert!(failed.contains("SOURCE_FILE_CHANGED"));
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
@@ -45,3 +45,101 @@ fn gc_evicts_lru_when_over_cap() {
     assert_eq!(p1, false);
     assert_eq!(p2, true);
 }
+
+fn reclaimed(stats: &[crate::model::ReclaimStat], reason: crate::model::ReclaimReason) -> (i64, i64) {
+    stats
+        .iter()
+        .find(|s| s.reason == reason)
+        .map(|s| (s.files, s.bytes))
+        .unwrap_or((0, 0))
+}
+
+#[test]
+fn gc_reclaims_blobs_of_trimmed_history() {
+    use crate::model::ReclaimReason;
+
+    // 只保留最新 2 条历史，缓存不设上限
+    let (core, _dirs) = mk_core("gc_refcount", 2, 1_i64 << 60);
+    let ts = crate::util::now_ms();
+
+    let first = ingest_image(&core, 1, ts);
+    let second = ingest_image(&core, 2, ts + 1);
+    assert!(core.inner.cas.blob_exists(&first.content.sha256));
+
+    // 与第二条内容相同：first 被裁剪，其 blob 立即回收
+    let dup = ingest_image(&core, 2, ts + 2);
+    assert!(!core.inner.cas.blob_exists(&first.content.sha256));
+
+    // second 被裁剪，但 blob 仍被 dup 引用
+    let third = ingest_image(&core, 3, ts + 3);
+    assert!(core.inner.cas.blob_exists(&second.content.sha256));
+    assert!(core.inner.cas.blob_exists(&dup.content.sha256));
+    assert!(core.inner.cas.blob_exists(&third.content.sha256));
+    {
+        let store = core.inner.store.lock().unwrap();
+        assert_eq!(store.cache_row_count_for_sha(&first.content.sha256).unwrap(), 0);
+        assert_eq!(store.cache_row_count_for_sha(&second.content.sha256).unwrap(), 1);
+    }
+
+    let report = core.cache_report().unwrap();
+    assert_eq!(reclaimed(&report.reclaimed_total, ReclaimReason::Unreferenced), (1, 40));
+    assert_eq!(report.present_count, 2);
+    assert_eq!(report.present_bytes, 80);
+    assert_eq!(report.blob_bytes, 80);
+    assert_eq!(report.last_gc.unwrap().trigger, "AfterIngest");
+}
+
+#[test]
+fn gc_sweeps_orphan_cache_files() {
+    use crate::model::ReclaimReason;
+    use std::time::{Duration, SystemTime};
+
+    let (core, dirs) = mk_core("gc_sweep", 1_000_000, 1_i64 << 60);
+    let ts = crate::util::now_ms();
+    let keep = ingest_image(&core, 1, ts);
+    core.pin_item(&keep.item_id).unwrap();
+
+    let cache = std::path::Path::new(&dirs.cache_dir);
+    let cas = &core.inner.cas;
+
+    // blobs/ 中没有条目引用的文件
+    let stray = crate::util::sha256_hex(b"stray");
+    let stray_path = cas.blob_path(&stray);
+    std::fs::create_dir_all(stray_path.parent().unwrap()).unwrap();
+    std::fs::write(&stray_path, b"stray").unwrap();
+
+    // tmp/ 中一个很久没更新的文件和一个刚写的文件
+    let old_tmp = cache.join("tmp").join("old.tmp");
+    std::fs::write(&old_tmp, vec![0u8; 7]).unwrap();
+    let f = std::fs::File::options().write(true).open(&old_tmp).unwrap();
+    f.set_modified(SystemTime::now() - Duration::from_secs(2 * 3600)).unwrap();
+    drop(f);
+    let fresh_tmp = cache.join("tmp").join("fresh.tmp");
+    std::fs::write(&fresh_tmp, b"in flight").unwrap();
+
+    // 视图：一个对应仍存在的 blob，一个对应已不存在的 blob
+    let live_view = cas.materialize_blob(&keep.content.sha256, "png").unwrap();
+    let dead_view = cache.join("files").join(format!("{}.png", crate::util::sha256_hex(b"gone")));
+    std::fs::write(&dead_view, b"gone").unwrap();
+
+    // 下载目录：对应的 blob 被删除后成为孤儿
+    let dl = cas.materialize_file(&stray, "tid-1", "a.txt", &Default::default()).unwrap();
+
+    core.run_gc("Test").unwrap();
+
+    assert!(!stray_path.exists());
+    assert!(!old_tmp.exists());
+    assert!(fresh_tmp.exists());
+    assert!(live_view.exists());
+    assert!(!dead_view.exists());
+    assert!(!dl.exists());
+    assert!(cas.blob_exists(&keep.content.sha256));
+
+    let report = core.cache_report().unwrap();
+    let last = report.last_gc.unwrap();
+    assert_eq!(last.trigger, "Test");
+    assert_eq!(reclaimed(&last.reclaimed, ReclaimReason::OrphanBlob), (1, 5));
+    assert_eq!(reclaimed(&last.reclaimed, ReclaimReason::StaleTmp), (1, 7));
+    assert_eq!(reclaimed(&last.reclaimed, ReclaimReason::OrphanView), (2, 9));
+    assert_eq!(report.pinned_bytes, 40);
+}
@@ -27,3 +27,144 @@ fn list_history_orders_newest_first() {
     assert_eq!(list[0].item_id, m2.item_id);
     assert_eq!(list[1].item_id, m1.item_id);
 }
+
+#[test]
+fn list_history_pages_with_cursor() {
+    let (core, _dirs) = mk_core("history_page", 1_000_000, 1_i64 << 60);
+
+    let ts = crate::util::now_ms();
+    let mut ids = Vec::new();
+    for i in 0..5 {
+        let m = core
+            .ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+                text_utf8: format!("item {}", i),
+                // 两两同一时间戳，验证 history_id 作为第二排序键
+                ts_ms: ts + (i / 2) as i64,
+            })
+            .unwrap();
+        ids.push(m.item_id);
+    }
+    ids.reverse();
+
+    let filter = crate::model::HistoryFilter::default();
+    let mut cursor = None;
+    let mut seen = Vec::new();
+    loop {
+        let page = core.list_history_page(2, cursor, &filter).unwrap();
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.into_iter().map(|m| m.item_id));
+        match page.next_cursor {
+            Some(c) => cursor = Some(c),
+            None => break,
+        }
+    }
+    assert_eq!(seen, ids);
+
+    // limit=0 的空页无法表达“后面还有”，直接拒绝
+    let err = core.list_history_page(0, None, &filter).unwrap_err();
+    assert!(err.to_string().contains("HISTORY_LIMIT_ZERO"), "{err}");
+}
+
+#[test]
+fn list_history_filters() {
+    let (core, _dirs) = mk_core("history_filter", 1_000_000, 1_i64 << 60);
+
+    let ts = crate::util::now_ms();
+    let text = core
+        .ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+            text_utf8: "text".to_string(),
+            ts_ms: ts,
+        })
+        .unwrap();
+    let image = core
+        .ingest_local_copy(crate::clipboard::ClipboardSnapshot::Image {
+            bytes: vec![1, 2, 3],
+            mime: "image/png".to_string(),
+            ts_ms: ts + 100,
+        })
+        .unwrap();
+
+    let by_kind = crate::model::HistoryFilter {
+        kinds: vec![crate::model::ItemKind::Image],
+        ..Default::default()
+    };
+    let page = core.list_history_page(10, None, &by_kind).unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].item_id, image.item_id);
+    assert!(page.next_cursor.is_none());
+
+    let by_time = crate::model::HistoryFilter {
+        since_ts_ms: Some(ts),
+        until_ts_ms: Some(ts + 100),
+        ..Default::default()
+    };
+    let page = core.list_history_page(10, None, &by_time).unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].item_id, text.item_id);
+
+    let by_device = crate::model::HistoryFilter {
+        source_device_id: Some("other-device".to_string()),
+        ..Default::default()
+    };
+    assert!(core.list_history_page(10, None, &by_device).unwrap().items.is_empty());
+
+    // 本机摄入的内容都已在 CAS 中
+    let not_cached = crate::model::HistoryFilter { cached: Some(false), ..Default::default() };
+    assert!(core.list_history_page(10, None, &not_cached).unwrap().items.is_empty());
+    let cached = crate::model::HistoryFilter { cached: Some(true), ..Default::default() };
+    assert_eq!(core.list_history_page(10, None, &cached).unwrap().items.len(), 2);
+}
+
+#[test]
+fn get_item_meta_finds_items_beyond_recent_window() {
+    let (core, _dirs) = mk_core("history_get_old", 1_000_000, 1_i64 << 60);
+
+    let ts = crate::util::now_ms();
+    let oldest = core
+        .ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+            text_utf8: "oldest".to_string(),
+            ts_ms: ts,
+        })
+        .unwrap();
+    for i in 1..=120 {
+        core.ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+            text_utf8: format!("filler {}", i),
+            ts_ms: ts + i,
+        })
+        .unwrap();
+    }
+
+    let got = core.get_item_meta(&oldest.item_id).unwrap().expect("oldest item should be found");
+    assert_eq!(got.item_id, oldest.item_id);
+    assert_eq!(got.content.sha256, oldest.content.sha256);
+    assert_eq!(got.preview.text.as_deref(), Some("oldest"));
+    assert!(core.get_item_meta("no-such-item").unwrap().is_none());
+}
+
+#[test]
+fn get_item_meta_hides_tombstoned_unless_asked() {
+    let (core, _dirs) = mk_core("history_get_deleted", 1, 1_i64 << 60);
+
+    let ts = crate::util::now_ms();
+    let old = core
+        .ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+            text_utf8: "gone".to_string(),
+            ts_ms: ts,
+        })
+        .unwrap();
+    // gc_history_max_items = 1：第二条摄入后第一条被软删除
+    core.ingest_local_copy(crate::clipboard::ClipboardSnapshot::Text {
+        text_utf8: "kept".to_string(),
+        ts_ms: ts + 1,
+    })
+    .unwrap();
+
+    assert!(core.get_item_meta(&old.item_id).unwrap().is_none());
+
+    let store = core.inner.store.lock().unwrap();
+    let tomb = store
+        .get_item_meta(&core.inner.core_config.account_uid, &old.item_id, true)
+        .unwrap()
+        .expect("tombstoned item should be returned when asked");
+    assert_eq!(tomb.item_id, old.item_id);
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::clipboard::ClipboardSnapshot;
+
+/// 文件数超过并发上限，并带一个空目录；seed 区分不同目录的文件内容
+fn make_folder(base: &Path, name: &str, seed: u8) -> std::path::PathBuf {
+	let root = base.join(name);
+	std::fs::create_dir_all(root.join("nested").join("empty")).unwrap();
+	for i in 0..6 {
+		std::fs::write(root.join(format!("f{i}.bin")), vec![seed + i as u8; 10_000 + i * 1000]).unwrap();
+	}
+	std::fs::write(root.join("nested").join("deep.txt"), b"deep file").unwrap();
+	root
+}
+
+#[test]
+fn only_remote_file_lists_can_be_fetched() {
+	let (core, dirs) = mk_core("item_fetch_local", 1_000_000, 1_i64 << 60);
+	let root = make_folder(Path::new(&dirs.cache_dir).join("src").as_path(), "local", 0);
+
+	let meta = core
+		.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![folder_entry(&root)], ts_ms: crate::util::now_ms() })
+		.unwrap();
+	let err = core.ensure_item_cached(&meta.item_id).unwrap_err();
+	assert!(err.to_string().contains("ITEM_IS_LOCAL"), "{err:#}");
+
+	let text = core
+		.ingest_local_copy(ClipboardSnapshot::Text { text_utf8: "plain".to_string(), ts_ms: crate::util::now_ms() })
+		.unwrap();
+	assert!(core.ensure_item_cached(&text.item_id).is_err());
+	assert!(core.ensure_item_cached("no-such-item").is_err());
+}
+
+#[tokio::test]
+async fn whole_file_list_is_fetched_as_one_transfer() {
+	let shared_uid = format!("item_fetch_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("if_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("if_b", &shared_uid, |_| {});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "if_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let root = make_folder(dir_a.path(), "bundle", 0);
+	let c_a = core_a.clone();
+	let entry = folder_entry(&root);
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	// 等发送端后台算完各文件哈希，否则 ContentBegin 里没有可校验的哈希
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+	let total_files = meta.files.iter().filter(|f| !f.is_dir).count();
+
+	let c_b = core_b.clone();
+	let iid = meta.item_id.clone();
+	let parent_id = tokio::task::spawn_blocking(move || c_b.ensure_item_cached(&iid)).await.unwrap().unwrap();
+
+	// 汇总进度与完成事件都使用父传输 id
+	let mut saw_progress = false;
+	let start = std::time::Instant::now();
+	let cached = loop {
+		assert!(start.elapsed() < Duration::from_secs(60), "no ITEM_CACHED on B");
+		let Ok(evt) = rx_b.try_recv() else {
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			continue;
+		};
+		let v: serde_json::Value = serde_json::from_str(&evt).unwrap();
+		if v["payload"]["transfer_id"] != serde_json::json!(parent_id) {
+			continue;
+		}
+		match v["type"].as_str().unwrap() {
+			"ITEM_FETCH_PROGRESS" => {
+				saw_progress = true;
+				assert_eq!(v["payload"]["total_files"], serde_json::json!(total_files));
+				assert!(v["payload"]["done_files"].as_u64().unwrap() <= total_files as u64);
+			}
+			"ITEM_CACHED" => break v,
+			other => panic!("unexpected {other}: {evt}"),
+		}
+	};
+	assert!(saw_progress);
+	assert_eq!(cached["payload"]["item_id"], serde_json::json!(meta.item_id));
+
+	let out = Path::new(cached["payload"]["root_path"].as_str().unwrap()).join("bundle");
+	assert!(out.join("nested").join("empty").is_dir());
+	assert_eq!(std::fs::read(out.join("nested").join("deep.txt")).unwrap(), b"deep file");
+	for i in 0..6u8 {
+		assert_eq!(std::fs::read(out.join(format!("f{i}.bin"))).unwrap(), vec![i; 10_000 + i as usize * 1000]);
+	}
+
+	// 源文件在复制后被改动：整体失败，指出出错的文件，不发 ITEM_CACHED
+	let root = make_folder(dir_a.path(), "changing", 100);
+	let c_a = core_a.clone();
+	let entry = folder_entry(&root);
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+	let changed = meta.files.iter().find(|f| f.rel_name == "changing/f3.bin").unwrap();
+	std::fs::write(root.join("f3.bin"), b"edited after the copy").unwrap();
+
+	let c_b = core_b.clone();
+	let iid = meta.item_id.clone();
+	let parent_id = tokio::task::spawn_blocking(move || c_b.ensure_item_cached(&iid)).await.unwrap().unwrap();
+	let failed = wait_event(&mut rx_b, "ITEM_FETCH_FAILED", &parent_id).await.expect("no ITEM_FETCH_FAILED on B");
+	let failed: serde_json::Value = serde_json::from_str(&failed).unwrap();
+	assert_eq!(failed["payload"]["file_id"], serde_json::json!(changed.file_id));
+	assert_eq!(failed["payload"]["code"], serde_json::json!("SOURCE_FILE_CHANGED"));
+	tokio::time::sleep(Duration::from_secs(1)).await;
+	while let Ok(evt) = rx_b.try_recv() {
+		assert!(!(evt.contains("\"type\":\"ITEM_CACHED\"") && evt.contains(&parent_id)), "{evt}");
+	}
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
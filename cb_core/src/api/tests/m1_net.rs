@@ -58,6 +58,11 @@ where F: FnOnce(&mut CoreConfig)
 	(Arc::new(core), rx, dir)
 }
 
+// 按原始字节计时 / 计量的传输测试用：接收端不声明 zstd，发送端就不压缩
+pub(crate) fn without_compression(cfg: &mut CoreConfig) {
+	cfg.app_config.transfer.compression = false;
+}
+
 // 异步包装：将阻塞调用移到 blocking thread
 pub(crate) async fn list_peers_async(core: &Arc<Core>) -> Vec<PeerStatus> {
 	let c = core.clone();
@@ -66,6 +71,13 @@ pub(crate) async fn list_peers_async(core: &Arc<Core>) -> Vec<PeerStatus> {
 	}).await.unwrap()
 }
 
+// 实际监听的端口：由系统分配（不要预先占用再释放端口，并行测试会抢到同一个）
+pub(crate) async fn listen_port_async(core: &Arc<Core>) -> u16 {
+	let c = core.clone();
+	let addrs = tokio::task::spawn_blocking(move || c.listen_addrs().unwrap()).await.unwrap();
+	addrs.first().expect("core is not listening").port()
+}
+
 pub(crate) async fn wait_for<F, Fut>(timeout: Duration, mut condition: F) -> bool
 where
 	F: FnMut() -> Fut,
@@ -103,6 +103,9 @@ fn test_m2_store_idempotency() {
 		content: ItemContent { mime: "text/plain".to_string(), sha256: "abc".to_string(), total_bytes: 100 },
 		files: vec![],
 		expires_ts_ms: None,
+		text_fallback: None,
+		representations: vec![],
+		pinned: false,
 	};
 
 	// 第一次插入
@@ -231,9 +231,16 @@ async fn test_m3_3_file_fetch_success() {
 				size_bytes: file_content.len() as i64,
 				sha256: Some(sha.clone()),
 				local_path: None, // 模拟只有 CAS 的情况
+				local_mtime_ms: None,
+				is_dir: false,
+				mode: None,
+				mtime_ms: None,
 			}
 		],
 		expires_ts_ms: None,
+		text_fallback: None,
+		representations: vec![],
+		pinned: false,
 	};
 
 	// B. 必须写入 A 的 DB
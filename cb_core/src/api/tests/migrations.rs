@@ -0,0 +1,109 @@
+use super::common::*;
+use crate::logs::LogStore;
+use crate::migrate::{schema_version, STORE_SCHEMA_TOO_NEW};
+use crate::stats::StatsStore;
+use crate::store::Store;
+use crate::testsupport::legacy_db::*;
+
+#[test]
+fn legacy_databases_upgrade_to_latest() {
+	let dirs = unique_dirs("migrate_legacy");
+	let data_dir = std::path::PathBuf::from(&dirs.data_dir);
+	create_legacy_core_db(&data_dir).unwrap();
+	create_legacy_logs_db(&data_dir).unwrap();
+	create_legacy_stats_db(&data_dir).unwrap();
+
+	let store = Store::open(&data_dir).unwrap();
+	let conn = rusqlite::Connection::open(data_dir.join("core.db")).unwrap();
+	assert_eq!(schema_version(&conn).unwrap(), 9);
+	assert!(crate::migrate::table_exists(&conn, "static_peers").unwrap());
+	assert!(crate::migrate::table_exists(&conn, "peers").unwrap());
+	assert!(crate::migrate::table_exists(&conn, "search_backfill").unwrap());
+	let mut stmt = conn.prepare("PRAGMA table_info(items)").unwrap();
+	let cols: Vec<String> = stmt.query_map([], |r| r.get(1)).unwrap().map(|c| c.unwrap()).collect();
+	assert!(cols.iter().any(|c| c == "text_fallback_json"));
+	assert!(cols.iter().any(|c| c == "representations_json"));
+
+	// 旧数据仍可列出、按预览和文件名检索
+	let page = store
+		.list_history_metas(LEGACY_ACCOUNT_UID, 10, None, &Default::default())
+		.unwrap();
+	let ids: Vec<&str> = page.items.iter().map(|m| m.item_id.as_str()).collect();
+	assert_eq!(ids, vec![LEGACY_FILES_ITEM_ID, LEGACY_TEXT_ITEM_ID]);
+	assert!(page.items[1].text_fallback.is_none());
+	assert!(page.items[1].representations.is_empty());
+
+	let hits = store
+		.search_history(LEGACY_ACCOUNT_UID, "legacy hello", 10, None, &Default::default())
+		.unwrap();
+	assert_eq!(hits.hits.len(), 1);
+	assert_eq!(hits.hits[0].meta.item_id, LEGACY_TEXT_ITEM_ID);
+	let hits = store
+		.search_history(LEGACY_ACCOUNT_UID, "旧版报告", 10, None, &Default::default())
+		.unwrap();
+	assert_eq!(hits.hits.len(), 1);
+	assert_eq!(hits.hits[0].meta.item_id, LEGACY_FILES_ITEM_ID);
+	drop(store);
+
+	// 再次打开不会重复执行迁移
+	let store = Store::open(&data_dir).unwrap();
+	let hits = store
+		.search_history(LEGACY_ACCOUNT_UID, "legacy hello", 10, None, &Default::default())
+		.unwrap();
+	assert_eq!(hits.hits.len(), 1);
+
+	let logs = LogStore::open(&data_dir).unwrap();
+	assert_eq!(logs.stats().unwrap().count, 1);
+	let stats = StatsStore::open(&data_dir).unwrap();
+	assert_eq!(stats.query_cache_stats(0, 10_000, 60).unwrap().len(), 1);
+	for db in ["logs.db", "stats.db"] {
+		let conn = rusqlite::Connection::open(data_dir.join(db)).unwrap();
+		assert_eq!(schema_version(&conn).unwrap(), 1, "{db}");
+	}
+}
+
+#[test]
+fn newer_schema_is_refused() {
+	let dirs = unique_dirs("migrate_too_new");
+	let data_dir = std::path::PathBuf::from(&dirs.data_dir);
+	drop(Store::open(&data_dir).unwrap());
+
+	let conn = rusqlite::Connection::open(data_dir.join("core.db")).unwrap();
+	conn.pragma_update(None, "user_version", 999).unwrap();
+	drop(conn);
+
+	let err = Store::open(&data_dir).err().expect("newer schema must be refused");
+	assert!(format!("{err:#}").contains(STORE_SCHEMA_TOO_NEW), "{err:#}");
+
+	// 数据库本身不能被改动
+	let conn = rusqlite::Connection::open(data_dir.join("core.db")).unwrap();
+	assert_eq!(schema_version(&conn).unwrap(), 999);
+}
+
+#[test]
+fn failed_migration_rolls_back() {
+	use crate::migrate::{run_migrations, Migration};
+
+	fn ok(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+		tx.execute_batch("CREATE TABLE t1 (x INTEGER);")?;
+		Ok(())
+	}
+	fn broken(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+		tx.execute_batch("CREATE TABLE t2 (x INTEGER);")?;
+		anyhow::bail!("boom")
+	}
+
+	let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+	let migrations = [
+		Migration { version: 1, name: "ok", up: ok },
+		Migration { version: 2, name: "broken", up: broken },
+	];
+	let err = run_migrations(&mut conn, "test.db", &migrations).unwrap_err();
+	assert!(format!("{err:#}").contains("v2 (broken)"), "{err:#}");
+	assert_eq!(schema_version(&conn).unwrap(), 1);
+	assert!(crate::migrate::table_exists(&conn, "t1").unwrap());
+	assert!(!crate::migrate::table_exists(&conn, "t2").unwrap());
+
+	let gap = [Migration { version: 2, name: "gap", up: ok }];
+	assert!(run_migrations(&mut conn, "test.db", &gap).is_err());
+}
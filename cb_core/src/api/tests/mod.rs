@@ -8,5 +8,25 @@ mod m1_net;
 mod m1_opaque_integration;
 mod m2_meta;
 mod m3_content;
+mod rich_text;
+mod multi_rep;
+mod search;
 
 
+mod migrations;
+mod pinned;
+mod delete;
+mod expiry;
+mod scrub;
+mod stream_ingest;
+mod file_hash;
+mod dir_tree;
+mod item_fetch;
+mod resume;
+mod multi_source;
+mod throttle;
+mod streams;
+mod compression;
+mod static_peers;
+mod network;
+mod peer_book;
@@ -0,0 +1,75 @@
+use crate::clipboard::{ClipboardRepresentation, ClipboardSnapshot};
+use crate::model::ItemKind;
+use super::common::*;
+
+fn multi_snapshot(png: Vec<u8>) -> ClipboardSnapshot {
+	ClipboardSnapshot::Multi {
+		primary: Box::new(ClipboardSnapshot::Text {
+			text_utf8: "hello".to_string(),
+			ts_ms: crate::util::now_ms(),
+		}),
+		extras: vec![
+			ClipboardRepresentation { mime: "text/html".to_string(), bytes: b"<b>hello</b>".to_vec() },
+			// 与主格式同 MIME 的重复项应被忽略
+			ClipboardRepresentation { mime: "text/plain".to_string(), bytes: b"dup".to_vec() },
+			ClipboardRepresentation { mime: "image/png".to_string(), bytes: png },
+		],
+	}
+}
+
+#[test]
+fn ingest_multi_stores_all_representations() {
+	let (core, _dirs) = mk_core("multi_rep", 1_000_000, 1_i64 << 60);
+
+	let meta = core.ingest_local_copy(multi_snapshot(vec![1, 2, 3, 4])).unwrap();
+
+	assert!(matches!(meta.kind, ItemKind::Text));
+	assert_eq!(meta.content.mime, "text/plain");
+	let mimes: Vec<&str> = meta.representations.iter().map(|r| r.mime.as_str()).collect();
+	assert_eq!(mimes, vec!["text/html", "image/png"]);
+	for r in &meta.representations {
+		assert!(core.inner.cas.blob_exists(&r.sha256), "blob missing for {}", r.mime);
+	}
+
+	// 历史列表能读回额外表示
+	let list = core.list_history(10, None).unwrap();
+	let got = list.iter().find(|m| m.item_id == meta.item_id).unwrap();
+	assert_eq!(got.representations.len(), 2);
+
+	// Session 侧按 MIME 查找
+	let store = core.inner.store.lock().unwrap();
+	let png = store.get_item_representation(&meta.item_id, "image/png").unwrap().unwrap();
+	assert_eq!(png.sha256, crate::util::sha256_hex(&[1, 2, 3, 4]));
+	assert!(store.get_item_representation(&meta.item_id, "text/rtf").unwrap().is_none());
+}
+
+#[test]
+fn pick_representation_follows_preference() {
+	let (core, _dirs) = mk_core("multi_pick", 1_000_000, 1_i64 << 60);
+	let meta = core.ingest_local_copy(multi_snapshot(vec![9; 8])).unwrap();
+
+	let prefs = vec!["image/jpeg".to_string(), "IMAGE/PNG".to_string()];
+	assert_eq!(meta.pick_representation(&prefs).mime, "image/png");
+	assert_eq!(meta.pick_representation(&[]).mime, "text/plain");
+	assert_eq!(meta.pick_representation(&["application/pdf".to_string()]).mime, "text/plain");
+
+	// 本机 item 命中 CAS 时直接返回 transfer_id
+	let tid = core.ensure_content_cached_with_mimes(&meta.item_id, None, &prefs).unwrap();
+	assert!(!tid.is_empty());
+}
+
+#[test]
+fn oversized_extra_representation_is_dropped() {
+	let deps = crate::clipboard::LocalIngestDeps {
+		device_id: "dev-1",
+		device_name: "dev1",
+		account_uid: "acct-uid-1",
+	};
+	let limits = crate::policy::SizeLimits { hard_image_bytes: 4, ..Default::default() };
+
+	let plan = crate::clipboard::make_ingest_plan(&deps, &multi_snapshot(vec![0; 5]), &limits, &Default::default(), false).unwrap();
+
+	let mimes: Vec<&str> = plan.meta.representations.iter().map(|r| r.mime.as_str()).collect();
+	assert_eq!(mimes, vec!["text/html"]);
+	assert_eq!(plan.representation_bytes.len(), 1);
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use super::common::*;
+use super::m1_net::{create_test_core, without_compression};
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+
+#[tokio::test]
+async fn content_is_fetched_from_any_peer_that_has_it() {
+	let shared_uid = format!("multi_src_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("ms_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("ms_b", &shared_uid, |_| {});
+	let (core_c, mut rx_c, _dir_c) = create_test_core("ms_c", &shared_uid, without_compression);
+	assert!(online_with(&core_a, &["ms_b", "ms_c"]).await, "A not connected");
+	assert!(online_with(&core_b, &["ms_a", "ms_c"]).await, "B not connected");
+
+	// A 复制一段文字和一个大文件，B 把两者都拉取到本地缓存
+	let c_a = core_a.clone();
+	let text = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::Text { text_utf8: "held by several devices".to_string(), ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	let data: Vec<u8> = (0..48 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+	let sha = hex::encode(Sha256::digest(&data));
+	let src = dir_a.path().join("big.bin");
+	std::fs::write(&src, &data).unwrap();
+	let entry = ClipboardFileEntry {
+		rel_name: "big.bin".to_string(),
+		abs_path: Some(src.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	};
+	let c_a = core_a.clone();
+	let files = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	let file_id = files.files[0].file_id.clone();
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &files.item_id).await.is_some());
+	assert!(wait_event(&mut rx_c, "ITEM_META_UPDATED", &files.item_id).await.is_some());
+
+	let tid = fetch(&core_b, &text.item_id, None).await.unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &tid).await.is_some(), "text not cached on B");
+	let tid = fetch(&core_b, &files.item_id, Some(&file_id)).await.unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &tid).await.is_some(), "file not cached on B");
+
+	// C 向 A 拉取大文件，中途 A 下线：换到 B 从已校验的偏移续传
+	let transfer_id = fetch(&core_c, &files.item_id, Some(&file_id)).await.unwrap();
+	let start = std::time::Instant::now();
+	loop {
+		assert!(start.elapsed() < Duration::from_secs(60), "no progress on C");
+		let evt = wait_event(&mut rx_c, "TRANSFER_PROGRESS", &transfer_id).await.expect("no TRANSFER_PROGRESS");
+		let v: serde_json::Value = serde_json::from_str(&evt).unwrap();
+		if v["payload"]["received"].as_u64().unwrap() >= 12 * 1024 * 1024 {
+			break;
+		}
+	}
+	core_a.shutdown();
+	drop(core_a);
+
+	let changed = wait_event(&mut rx_c, "TRANSFER_SOURCE_CHANGED", &transfer_id).await.expect("no failover on C");
+	let changed: serde_json::Value = serde_json::from_str(&changed).unwrap();
+	assert_eq!(changed["payload"]["from_device_id"], serde_json::json!("ms_a"));
+	assert_eq!(changed["payload"]["to_device_id"], serde_json::json!("ms_b"));
+	let resumed = wait_event(&mut rx_c, "TRANSFER_RESUMED", &transfer_id).await.expect("transfer not resumed");
+	let resumed: serde_json::Value = serde_json::from_str(&resumed).unwrap();
+	assert!(resumed["payload"]["offset"].as_u64().unwrap() > 0);
+	assert!(wait_event(&mut rx_c, "CONTENT_CACHED", &transfer_id).await.is_some(), "failover transfer did not finish");
+	assert_eq!(std::fs::read(core_c.inner.cas.blob_path(&sha)).unwrap().len(), data.len());
+
+	// 来源设备不在线：新的拉取直接由持有者按哈希提供
+	let tid = fetch(&core_c, &text.item_id, None).await.unwrap();
+	let cached = wait_event(&mut rx_c, "CONTENT_CACHED", &tid).await.expect("text not cached on C");
+	let cached: serde_json::Value = serde_json::from_str(&cached).unwrap();
+	let path = cached["payload"]["local_ref"]["local_path"].as_str().unwrap();
+	assert_eq!(std::fs::read_to_string(path).unwrap(), "held by several devices");
+
+	// 没有任何在线设备持有的内容仍然报告来源设备不在线
+	core_b.inner.cas.remove_blob(&text.content.sha256).unwrap();
+	core_c.inner.cas.remove_blob(&text.content.sha256).unwrap();
+	let err = fetch(&core_c, &text.item_id, None).await.unwrap_err();
+	assert!(err.to_string().contains("not online"), "{err:#}");
+
+	core_b.shutdown();
+	core_c.shutdown();
+}
@@ -0,0 +1,217 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use super::common::wait_event;
+use super::m1_net::{create_test_core, list_peers_async, listen_port_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::discovery::{DiscoveryEvent, PeerCandidate};
+use crate::net::{addrs_changed, dial_order, race_connect, session_addr_stale, NetCmd};
+use crate::policy::NetworkPolicy;
+use crate::transport::Transport;
+
+async fn wait_core_error(rx: &mut tokio::sync::broadcast::Receiver<String>) -> Option<serde_json::Value> {
+	let start = std::time::Instant::now();
+	while start.elapsed() < Duration::from_secs(30) {
+		match rx.try_recv() {
+			Ok(evt) if evt.contains("\"type\":\"CORE_ERROR\"") => return Some(serde_json::from_str(&evt).unwrap()),
+			Ok(_) => {}
+			Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {}
+			Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+		}
+	}
+	None
+}
+
+#[tokio::test]
+async fn listen_failures_surface_as_core_error() {
+	let uid = format!("bind_err_{}", uuid::Uuid::new_v4());
+
+	// 端口已被其他程序占用
+	let taken = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+	let port = taken.local_addr().unwrap().port();
+	let (core_a, mut rx_a, _dir_a) = create_test_core("be_a", &uid, |cfg| {
+		cfg.app_config.network.listen_port = port;
+	});
+	let evt = wait_core_error(&mut rx_a).await.expect("no CORE_ERROR for port conflict");
+	assert_eq!(evt["payload"]["code"], "PORT_IN_USE");
+	assert!(evt["payload"]["message"].as_str().unwrap().contains(&port.to_string()));
+	assert_eq!(evt["payload"]["context"]["listen_port"], port);
+
+	// 既不是 IP 也不是网卡名
+	let (core_b, mut rx_b, _dir_b) = create_test_core("be_b", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["no-such-nic0".to_string()];
+	});
+	let evt = wait_core_error(&mut rx_b).await.expect("no CORE_ERROR for bad bind address");
+	assert_eq!(evt["payload"]["code"], "INVALID_BIND_ADDR");
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
+
+#[tokio::test]
+async fn static_peer_is_identified_by_handshake() {
+	let uid = format!("fixed_port_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("fp_a", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["127.0.0.1".to_string()];
+	});
+	let port = listen_port_async(&core_a).await;
+	// 只知道地址，不知道对方是谁：身份在握手时得知
+	let (core_c, _rx_c, _dir_c) = create_test_core("fp_c", &uid, |cfg| {
+		cfg.app_config.static_peers = vec![format!("127.0.0.1:{port}")];
+	});
+
+	let identified = wait_for(Duration::from_secs(120), || async {
+		core_c.list_static_peers().unwrap().iter().any(|p| p.device_id.as_deref() == Some("fp_a"))
+	}).await;
+	assert!(identified, "static peer identity not learned");
+	let online = wait_for(Duration::from_secs(120), || async {
+		list_peers_async(&core_c).await.iter().any(|p| p.device_id == "fp_a" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(online, "static peer not online");
+
+	core_a.shutdown();
+	core_c.shutdown();
+}
+
+#[test]
+fn dial_order_prefers_last_good_then_alternates_families() {
+	let addrs: Vec<SocketAddr> = ["10.8.0.2:4433", "192.168.1.5:4433", "[fd00::5]:4433", "[fe80::5%2]:4433", "10.8.0.2:4433"]
+		.iter().map(|a| a.parse().unwrap()).collect();
+	let order = |preferred: Option<&str>| -> Vec<String> {
+		dial_order(addrs.clone(), preferred.map(|p| p.parse().unwrap())).iter().map(|a| a.to_string()).collect()
+	};
+
+	assert_eq!(order(None), vec!["[fd00::5]:4433", "10.8.0.2:4433", "[fe80::5%2]:4433", "192.168.1.5:4433"]);
+	assert_eq!(order(Some("192.168.1.5:4433")), vec!["192.168.1.5:4433", "[fd00::5]:4433", "10.8.0.2:4433", "[fe80::5%2]:4433"]);
+	// 上次成功的地址已经不在候选里
+	assert_eq!(order(Some("192.168.9.9:4433"))[0], "[fd00::5]:4433");
+}
+
+#[tokio::test]
+async fn dial_race_does_not_wait_for_dead_address() {
+	let dir = tempfile::tempdir().unwrap();
+	let server_net = NetworkPolicy { bind: vec!["127.0.0.1".to_string()], ..Default::default() };
+	let server = std::sync::Arc::new(Transport::new(&server_net, dir.path().join("srv"), "race_srv", "race_uid").unwrap());
+	let port = server.local_port().unwrap();
+	let srv = server.clone();
+	tokio::spawn(async move {
+		// 保持连接直到测试结束
+		let mut held = Vec::new();
+		while let Some(conn) = srv.accept().await {
+			held.push(conn);
+		}
+	});
+	let client = Transport::new(&NetworkPolicy::default(), dir.path().join("cli"), "race_cli", "race_uid").unwrap();
+
+	// 排在前面的地址永远不会应答（TEST-NET-1），顺序拨号要等满 QUIC 超时
+	let good: SocketAddr = format!("127.0.0.1:{port}").parse().unwrap();
+	let start = std::time::Instant::now();
+	let (_conn, addr) = race_connect(&client, vec!["192.0.2.1:4433".parse().unwrap(), good])
+		.await
+		.expect("no address connected");
+	assert_eq!(addr, good);
+	assert!(start.elapsed() < Duration::from_secs(8), "took {:?}", start.elapsed());
+
+	// 全部不通时返回 None
+	let dead = Transport::new(&NetworkPolicy::default(), dir.path().join("dead"), "race_dead", "race_uid").unwrap();
+	let dead_port = dead.local_port().unwrap();
+	dead.shutdown();
+	assert!(race_connect(&client, vec![format!("127.0.0.1:{dead_port}").parse().unwrap()]).await.is_none());
+	server.shutdown();
+}
+
+#[test]
+fn address_changes_are_detected_ignoring_order() {
+	let addrs = |list: &[&str]| -> Vec<String> { list.iter().map(|a| a.to_string()).collect() };
+	let old = addrs(&["192.168.1.5:4433", "[fd00::5]:4433"]);
+
+	assert!(!addrs_changed(&old, &addrs(&["[fd00::5]:4433", "192.168.1.5:4433", "192.168.1.5:4433"])));
+	assert!(addrs_changed(&old, &addrs(&["192.168.1.9:4433", "[fd00::5]:4433"])));
+	assert!(addrs_changed(&old, &addrs(&["192.168.1.5:4433"])));
+
+	// 双栈 socket 上的 IPv4 连接，对端地址是 v4 映射形式
+	let mapped: SocketAddr = "[::ffff:192.168.1.5]:4433".parse().unwrap();
+	assert!(!session_addr_stale(mapped, &old));
+	assert!(session_addr_stale(mapped, &addrs(&["192.168.1.9:4433", "[fd00::5]:4433"])));
+	// 端口变了同样失效
+	assert!(session_addr_stale("192.168.1.5:4433".parse().unwrap(), &addrs(&["192.168.1.5:5000"])));
+	// 无法解析的宣告无从判断
+	assert!(!session_addr_stale(mapped, &addrs(&["office-pc.local:4433"])));
+}
+
+#[tokio::test]
+async fn lost_peer_stays_listed_as_offline() {
+	let uid = format!("lost_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("lp_a", &uid, |_| {});
+	let (core_b, _rx_b, _dir_b) = create_test_core("lp_b", &uid, |_| {});
+	let online = wait_for(Duration::from_secs(120), || async {
+		list_peers_async(&core_a).await.iter().any(|p| p.device_id == "lp_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(online, "Peers not connected");
+
+	// 退出时注销 mDNS 宣告：对端仍列出它，但显示为离线，不再退避重拨
+	core_b.shutdown();
+	let gone_at = crate::util::now_ms();
+	let offline = wait_for(Duration::from_secs(60), || async {
+		list_peers_async(&core_a).await.iter().any(|p| p.device_id == "lp_b" && p.state == PeerConnectionState::Offline)
+	}).await;
+	assert!(offline, "lost peer not shown as offline");
+	tokio::time::sleep(Duration::from_secs(3)).await;
+	let peer = list_peers_async(&core_a).await.into_iter().find(|p| p.device_id == "lp_b").unwrap();
+	assert_eq!(peer.state, PeerConnectionState::Offline);
+	assert!(peer.last_seen_ts_ms <= gone_at, "last_seen advanced after the peer left");
+
+	core_a.shutdown();
+}
+
+#[tokio::test]
+async fn connected_peer_moving_is_redialed_on_the_new_address() {
+	let uid = format!("moved_{}", uuid::Uuid::new_v4());
+	let (core_a, mut rx_a, _dir_a) = create_test_core("mv_a", &uid, |_| {});
+	let (core_b, _rx_b, _dir_b) = create_test_core("mv_b", &uid, |_| {});
+	// device_id 较小的 A 拨号，会话在 A 这一侧是客户端
+	let online = wait_for(Duration::from_secs(120), || async {
+		list_peers_async(&core_a).await.iter().any(|p| p.device_id == "mv_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(online, "Peers not connected");
+
+	// B 仍监听在同一端口上的所有地址，但宣告里只剩一个 A 从没连过的地址（127.0.0.2 同样落在回环网卡上）
+	let new_addr = format!("127.0.0.2:{}", listen_port_async(&core_b).await);
+	let moved = PeerCandidate {
+		device_id: "mv_b".to_string(),
+		addrs: vec![new_addr.clone()],
+		capabilities: vec!["txt".to_string(), "img".to_string(), "file".to_string()],
+		device_name: None,
+	};
+	let count_logs = |needle: &str| {
+		core_a.inner.log_store.lock().unwrap().query_after_id(0, 0, Some(needle), 1000, None).unwrap().len()
+	};
+	let moved_away = "Peer moved away, closing session to redial: device_id=mv_b";
+	let connected = "Connected to peer: device_id=mv_b, addr=";
+	let backoff = "Peer in backoff period: device_id=mv_b";
+	let (moved_before, connected_before, backoff_before) = (count_logs(moved_away), count_logs(connected), count_logs(backoff));
+	core_a.inner.net.as_ref().unwrap().send(NetCmd::InjectDiscovery(DiscoveryEvent::CandidateFound(moved))).await.unwrap();
+
+	// mDNS 陆续解析出地址时也会发这个事件，按新地址找注入的那一条
+	let evt = wait_event(&mut rx_a, "PEER_ADDRESS_CHANGED", &new_addr).await.expect("no PEER_ADDRESS_CHANGED");
+	let evt: serde_json::Value = serde_json::from_str(&evt).unwrap();
+	assert_eq!(evt["payload"]["new_addrs"], serde_json::json!([new_addr]));
+	assert!(!evt["payload"]["old_addrs"].as_array().unwrap().is_empty());
+
+	// 连着旧地址的客户端会话被关掉，回收后立即按新地址重拨
+	let redialed = wait_for(Duration::from_secs(60), || async {
+		list_peers_async(&core_a).await.iter().any(|p| {
+			p.device_id == "mv_b"
+				&& p.state == PeerConnectionState::Online
+				&& p.last_good_addr.as_deref().is_some_and(|a| a.contains("127.0.0.2"))
+		})
+	}).await;
+	assert!(redialed, "session not re-established on the new address");
+	assert_eq!(count_logs(moved_away), moved_before + 1);
+	assert!(count_logs(connected) > connected_before);
+	// 重拨不经过断线退避
+	assert_eq!(count_logs(backoff), backoff_before);
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use super::m1_net::{create_test_core, list_peers_async, listen_port_async, wait_for};
+use crate::api::PeerConnectionState;
+use crate::net::dial_backoff_secs;
+use crate::store::{PeerRecord, Store};
+
+fn record(device_id: &str, name: Option<&str>, addrs: &[&str], last_seen_ms: i64) -> PeerRecord {
+	PeerRecord {
+		device_id: device_id.to_string(),
+		device_name: name.map(str::to_string),
+		addrs: addrs.iter().map(|a| a.to_string()).collect(),
+		capabilities: vec!["text".to_string()],
+		last_seen_ms,
+		last_good_addr: None,
+	}
+}
+
+#[test]
+fn peer_book_keeps_history_across_announcements() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut store = Store::open(dir.path()).unwrap();
+
+	store.upsert_peer("acct", &record("pc", Some("Office PC"), &["192.168.1.5:4433"], 1_000)).unwrap();
+	store.set_peer_last_good_addr("acct", "pc", "192.168.1.5:4433", 2_000).unwrap();
+	// 旧版本的宣告不带设备名；地址换了，上次成功的地址保留
+	store.upsert_peer("acct", &record("pc", None, &["192.168.1.9:4433"], 3_000)).unwrap();
+	// 时间不会倒退
+	store.touch_peer("acct", "pc", 500).unwrap();
+	// 静态对端握手后才知道是谁，此前没有宣告记录
+	store.set_peer_last_good_addr("acct", "nas", "10.0.0.2:4433", 2_500).unwrap();
+
+	let peers = store.list_peers("acct").unwrap();
+	assert_eq!(peers.len(), 2);
+	assert_eq!(peers[0], PeerRecord {
+		device_id: "pc".to_string(),
+		device_name: Some("Office PC".to_string()),
+		addrs: vec!["192.168.1.9:4433".to_string()],
+		capabilities: vec!["text".to_string()],
+		last_seen_ms: 3_000,
+		last_good_addr: Some("192.168.1.5:4433".to_string()),
+	});
+	assert_eq!(peers[1].device_id, "nas");
+	assert!(peers[1].addrs.is_empty());
+	assert_eq!(peers[1].last_good_addr.as_deref(), Some("10.0.0.2:4433"));
+	assert!(store.list_peers("other_account").unwrap().is_empty());
+
+	store.clear_core_db().unwrap();
+	assert!(store.list_peers("acct").unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn known_peers_are_dialed_at_startup() {
+	let uid = format!("peer_book_{}", uuid::Uuid::new_v4());
+	let (core_b, _rx_b, _dir_b) = create_test_core("pb_a", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["127.0.0.1".to_string()];
+	});
+	let addr = format!("127.0.0.1:{}", listen_port_async(&core_b).await);
+
+	// pb_z 的 device_id 更大，按 mDNS 不会主动拨号；只凭上次运行留下的地址簿连过去
+	let (core_a, _rx_a, dir_a) = create_test_core("pb_z", &uid, |cfg| {
+		let mut store = Store::open(&cfg.data_dir).unwrap();
+		store.upsert_peer(&cfg.account_uid, &record("pb_a", Some("Office Laptop"), &[&addr], 1_000)).unwrap();
+	});
+	let online = wait_for(Duration::from_secs(120), || async {
+		list_peers_async(&core_a).await.iter().any(|p| p.device_id == "pb_a" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(online, "known peer not dialed at startup");
+	let peer = list_peers_async(&core_a).await.into_iter().find(|p| p.device_id == "pb_a").unwrap();
+	assert_eq!(peer.last_good_addr.as_deref(), Some(addr.as_str()));
+	core_b.shutdown();
+	core_a.shutdown();
+	// shutdown 只是发出信号：等网络线程写完最后见到的时间
+	tokio::time::sleep(Duration::from_secs(3)).await;
+
+	let store = Store::open(dir_a.path()).unwrap();
+	let saved = store.list_peers(&uid).unwrap();
+	assert_eq!(saved.len(), 1);
+	assert_eq!(saved[0].last_good_addr.as_deref(), Some(addr.as_str()));
+	assert!(saved[0].last_seen_ms > 1_000);
+	drop(store);
+
+	// 重启后对端不在：仍列出，显示为离线并带上历史信息
+	let data_dir = dir_a.path().to_string_lossy().to_string();
+	let (core_a, _rx_a, _dir) = create_test_core("pb_z", &uid, |cfg| {
+		cfg.data_dir = data_dir.clone();
+		cfg.cache_dir = data_dir.clone();
+	});
+	let offline = wait_for(Duration::from_secs(60), || async {
+		list_peers_async(&core_a).await.iter().any(|p| p.device_id == "pb_a" && p.state == PeerConnectionState::Offline)
+	}).await;
+	assert!(offline, "known peer not listed as offline");
+	let peer = list_peers_async(&core_a).await.into_iter().find(|p| p.device_id == "pb_a").unwrap();
+	assert_eq!(peer.last_good_addr.as_deref(), Some(addr.as_str()));
+	assert_eq!(peer.last_seen_ts_ms, saved[0].last_seen_ms);
+	assert!(peer.device_name.is_some());
+	core_a.shutdown();
+}
+
+#[test]
+fn unconfirmed_peers_keep_being_retried_on_a_long_backoff() {
+	// 本次运行见过的设备：2s 起翻倍，最长 64s
+	let seen: Vec<u64> = (1..=8).map(|n| dial_backoff_secs(n, false)).collect();
+	assert_eq!(seen, vec![2, 4, 8, 16, 32, 64, 64, 64]);
+	// 地址簿里还没确认的设备：30s 起翻倍，封顶 30 分钟后一直按这个间隔重拨
+	let unconfirmed: Vec<u64> = (1..=8).map(|n| dial_backoff_secs(n, true)).collect();
+	assert_eq!(unconfirmed, vec![30, 60, 120, 240, 480, 960, 1800, 1800]);
+	assert_eq!(dial_backoff_secs(u32::MAX, true), 1800);
+}
@@ -0,0 +1,73 @@
+use super::common::*;
+use crate::model::HistoryFilter;
+
+#[test]
+fn pinned_items_survive_history_gc() {
+	let (core, _dirs) = mk_core("pin_history_gc", 2, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let keep = ingest_text(&core, "my address", ts);
+	core.pin_item(&keep.item_id).unwrap();
+	for i in 1..=4 {
+		ingest_text(&core, &format!("clip {i}"), ts + i);
+	}
+
+	// 未置顶的只保留最新 2 条，置顶条目不计入也不被清理
+	let items = core.list_history(10, None).unwrap();
+	let texts: Vec<_> = items.iter().map(|m| m.preview.text.clone().unwrap()).collect();
+	assert_eq!(texts, vec!["clip 4", "clip 3", "my address"]);
+	assert!(items[2].pinned);
+	assert!(core.get_item_meta(&keep.item_id).unwrap().unwrap().pinned);
+
+	// 取消置顶后，下一次 GC 会把它清掉
+	core.unpin_item(&keep.item_id).unwrap();
+	core.run_gc("Test").unwrap();
+	assert!(core.get_item_meta(&keep.item_id).unwrap().is_none());
+	assert!(core.pin_item(&keep.item_id).is_err());
+}
+
+#[test]
+fn pinned_content_is_not_evicted() {
+	// cap = 100 bytes，每张图 40 bytes
+	let (core, _dirs) = mk_core("pin_cache_gc", 1_000_000, 100);
+	let ts = crate::util::now_ms();
+
+	let pinned = ingest_image(&core, 1, ts);
+	core.pin_item(&pinned.item_id).unwrap();
+	let old = ingest_image(&core, 2, ts + 1);
+	let new = ingest_image(&core, 3, ts + 2);
+
+	// 总量 120 > 100：最旧的是置顶图，应跳过它淘汰第二张
+	assert!(core.inner.cas.blob_exists(&pinned.content.sha256));
+	assert!(!core.inner.cas.blob_exists(&old.content.sha256));
+	assert!(core.inner.cas.blob_exists(&new.content.sha256));
+}
+
+#[test]
+fn pinned_first_listing_pages() {
+	let (core, _dirs) = mk_core("pin_list", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let metas: Vec<_> = (0..5).map(|i| ingest_text(&core, &format!("item {i}"), ts + i)).collect();
+	core.pin_item(&metas[1].item_id).unwrap();
+	core.pin_item(&metas[3].item_id).unwrap();
+
+	let filter = HistoryFilter { pinned_first: true, ..Default::default() };
+	let mut ids = Vec::new();
+	let mut cursor = None;
+	loop {
+		let page = core.list_history_page(2, cursor, &filter).unwrap();
+		ids.extend(page.items.iter().map(|m| m.item_id.clone()));
+		cursor = page.next_cursor;
+		if cursor.is_none() {
+			break;
+		}
+	}
+	let expected: Vec<_> = [3, 1, 4, 2, 0].iter().map(|&i| metas[i].item_id.clone()).collect();
+	assert_eq!(ids, expected);
+
+	let only_pinned = HistoryFilter { pinned: Some(true), ..Default::default() };
+	let page = core.list_history_page(10, None, &only_pinned).unwrap();
+	let ids: Vec<_> = page.items.iter().map(|m| m.item_id.clone()).collect();
+	assert_eq!(ids, vec![metas[3].item_id.clone(), metas[1].item_id.clone()]);
+}
@@ -0,0 +1,136 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for, without_compression};
+use crate::api::PeerConnectionState;
+use crate::cas::{Cas, PartialInfo};
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+
+#[test]
+fn partial_resumes_from_last_checkpoint() {
+	let (core, _dirs) = mk_core("resume_partial", 1_000_000, 1_i64 << 60);
+	let cas = &core.inner.cas;
+	let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+	let sha = crate::util::sha256_hex(&data);
+	let key = Cas::partial_key("item-1", Some("file-1"), &sha);
+
+	// 第一次：写到 120_000 字节，只有前 100_000 字节记录了校验点
+	{
+		let claim = cas.claim_partial(&key).unwrap();
+		assert!(cas.claim_partial(&key).is_none(), "same content must not be written twice");
+		let (mut file, _) = cas.open_partial(&claim, &sha, data.len() as u64, 0).unwrap();
+		file.write_all(&data[..120_000]).unwrap();
+		let info = PartialInfo { sha256: sha.clone(), total_bytes: data.len() as u64, verified_bytes: 100_000 };
+		cas.save_partial(&key, &info).unwrap();
+	}
+
+	// 内容不同的记录不可用
+	assert!(cas.load_partial(&Cas::partial_key("item-1", Some("file-1"), &"0".repeat(64)), &"0".repeat(64)).is_none());
+	let info = cas.load_partial(&key, &sha).unwrap();
+	assert_eq!(info.verified_bytes, 100_000);
+
+	// 续传：截断到校验点，前缀计入完整文件的 Hash
+	let claim = cas.claim_partial(&key).unwrap();
+	assert!(cas.open_partial(&claim, &sha, data.len() as u64, 150_000).is_err());
+	let (mut file, mut hasher) = cas.open_partial(&claim, &sha, data.len() as u64, 100_000).unwrap();
+	assert_eq!(std::fs::metadata(cas.partial_path(&key)).unwrap().len(), 100_000);
+	file.write_all(&data[100_000..]).unwrap();
+	hasher.update(&data[100_000..]);
+	drop(file);
+	assert_eq!(hex::encode(hasher.finalize()), sha);
+
+	// 有传输占用时不清理
+	assert_eq!(cas.sweep_stale_partials(Duration::ZERO).unwrap().0, 0);
+	let blob = cas.commit_partial(&key, &sha).unwrap();
+	assert_eq!(std::fs::read(blob).unwrap(), data);
+	assert!(!cas.partial_path(&key).exists());
+	assert!(cas.load_partial(&key, &sha).is_none());
+	drop(claim);
+
+	// 长期没有进展的续传数据由 GC 清理
+	let other = Cas::partial_key("item-2", None, &sha);
+	{
+		let claim = cas.claim_partial(&other).unwrap();
+		let (mut file, _) = cas.open_partial(&claim, &sha, data.len() as u64, 0).unwrap();
+		file.write_all(&data[..10]).unwrap();
+	}
+	assert_eq!(cas.sweep_stale_partials(Duration::ZERO).unwrap().0, 2);
+	assert!(!cas.partial_path(&other).exists());
+}
+
+#[tokio::test]
+async fn transfer_resumes_after_sender_restart() {
+	let shared_uid = format!("resume_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("rs_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("rs_b", &shared_uid, without_compression);
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "rs_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let data: Vec<u8> = (0..48 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+	let sha = hex::encode(Sha256::digest(&data));
+	let src = dir_a.path().join("big.bin");
+	std::fs::write(&src, &data).unwrap();
+	let entry = ClipboardFileEntry {
+		rel_name: "big.bin".to_string(),
+		abs_path: Some(src.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	};
+	let c_a = core_a.clone();
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	// 续传需要知道完整文件的哈希
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+
+	let c_b = core_b.clone();
+	let (iid, fid) = (meta.item_id.clone(), meta.files[0].file_id.clone());
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+
+	// 收到一部分后发送端下线
+	let start = std::time::Instant::now();
+	loop {
+		assert!(start.elapsed() < Duration::from_secs(60), "no progress on B");
+		let evt = wait_event(&mut rx_b, "TRANSFER_PROGRESS", &transfer_id).await.expect("no TRANSFER_PROGRESS");
+		let v: serde_json::Value = serde_json::from_str(&evt).unwrap();
+		if v["payload"]["received"].as_u64().unwrap() >= 12 * 1024 * 1024 {
+			break;
+		}
+	}
+	let data_dir = dir_a.path().to_string_lossy().into_owned();
+	core_a.shutdown();
+	drop(core_a);
+
+	let key = Cas::partial_key(&meta.item_id, Some(&meta.files[0].file_id), &sha);
+	let kept = wait_for(Duration::from_secs(15), || async {
+		core_b.inner.cas.load_partial(&key, &sha).is_some_and(|i| i.verified_bytes > 0)
+	}).await;
+	assert!(kept, "partial data not kept after the session dropped");
+	assert!(!core_b.inner.cas.blob_exists(&sha), "transfer finished before the sender went away");
+
+	// 发送端以同一数据目录重启：重连后以原 transfer_id 从校验点续传
+	let (core_a2, _rx_a2, _unused) = create_test_core("rs_a", &shared_uid, |cfg| {
+		cfg.data_dir = data_dir.clone();
+		cfg.cache_dir = data_dir.clone();
+	});
+	let resumed = wait_event(&mut rx_b, "TRANSFER_RESUMED", &transfer_id).await.expect("transfer not resumed");
+	let resumed: serde_json::Value = serde_json::from_str(&resumed).unwrap();
+	assert!(resumed["payload"]["offset"].as_u64().unwrap() > 0);
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &transfer_id).await.is_some(), "resumed transfer did not finish");
+	assert_eq!(std::fs::read(core_b.inner.cas.blob_path(&sha)).unwrap().len(), data.len());
+	assert!(!Path::new(&core_b.inner.cas.partial_path(&key)).exists());
+
+	core_a2.shutdown();
+	core_b.shutdown();
+}
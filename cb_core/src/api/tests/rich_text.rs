@@ -0,0 +1,73 @@
+use crate::model::ItemKind;
+use super::common::*;
+
+#[test]
+fn ingest_html_with_text_fallback() {
+	let (core, _dirs) = mk_core("rich_html", 1_000_000, 1_i64 << 60);
+
+	let html = "<p>Hello&nbsp;<b>world</b></p><script>x()</script>";
+	let meta = core
+		.ingest_local_copy(crate::clipboard::ClipboardSnapshot::RichText {
+			html: Some(html.to_string()),
+			rtf: None,
+			plain_text: None,
+			ts_ms: crate::util::now_ms(),
+		})
+		.unwrap();
+
+	assert!(matches!(meta.kind, ItemKind::RichText));
+	assert_eq!(meta.content.mime, "text/html");
+	assert_eq!(meta.preview.text.as_deref(), Some("Hello world"));
+
+	// 富文本原文和纯文本降级都应进入 CAS
+	assert!(core.inner.cas.blob_exists(&meta.content.sha256));
+	let fallback = meta.text_fallback.clone().expect("text_fallback");
+	assert_eq!(fallback.mime, "text/plain");
+	assert_eq!(fallback.sha256, crate::util::sha256_hex("Hello world".as_bytes()));
+	assert!(core.inner.cas.blob_exists(&fallback.sha256));
+
+	// 历史列表能读回 text_fallback
+	let list = core.list_history(10, None).unwrap();
+	let got = list.iter().find(|m| m.item_id == meta.item_id).unwrap();
+	assert!(matches!(got.kind, ItemKind::RichText));
+	assert_eq!(got.text_fallback.as_ref().map(|c| c.sha256.as_str()), Some(fallback.sha256.as_str()));
+}
+
+#[test]
+fn ingest_rtf_uses_provided_plain_text() {
+	let (core, _dirs) = mk_core("rich_rtf", 1_000_000, 1_i64 << 60);
+
+	let meta = core
+		.ingest_local_copy(crate::clipboard::ClipboardSnapshot::RichText {
+			html: None,
+			rtf: Some(r"{\rtf1\ansi{\fonttbl\f0 Arial;}\f0 Hello \b bold\b0\par next}".to_string()),
+			plain_text: Some("Hello bold\nnext".to_string()),
+			ts_ms: crate::util::now_ms(),
+		})
+		.unwrap();
+
+	assert_eq!(meta.content.mime, "text/rtf");
+	let fallback = meta.text_fallback.expect("text_fallback");
+	assert_eq!(fallback.sha256, crate::util::sha256_hex("Hello bold\nnext".as_bytes()));
+}
+
+#[test]
+fn rtf_to_plain_text_strips_control_words() {
+	let plain = crate::clipboard::rtf_to_plain_text(r"{\rtf1\ansi{\fonttbl\f0 Arial;}\f0 Hello \b bold\b0\par next}");
+	assert_eq!(plain, "Hello bold\nnext");
+}
+
+#[test]
+fn rich_text_without_body_is_rejected() {
+	let (core, _dirs) = mk_core("rich_empty", 1_000_000, 1_i64 << 60);
+
+	let err = core
+		.ingest_local_copy(crate::clipboard::ClipboardSnapshot::RichText {
+			html: None,
+			rtf: None,
+			plain_text: Some("only plain".to_string()),
+			ts_ms: crate::util::now_ms(),
+		})
+		.unwrap_err();
+	assert!(err.to_string().contains("RICH_TEXT_EMPTY"));
+}
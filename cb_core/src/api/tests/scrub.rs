@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for};
+use crate::api::PeerConnectionState;
+
+#[test]
+fn scrub_quarantines_corrupt_blobs() {
+	let (core, dirs) = mk_core("scrub_local", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let good = ingest_image(&core, 1, ts);
+	let bad = ingest_image(&core, 2, ts + 1);
+	// 同样长度、内容被改写（位翻转）
+	std::fs::write(core.inner.cas.blob_path(&bad.content.sha256), vec![3u8; 40]).unwrap();
+
+	let corrupt = core.scrub_cas().unwrap();
+	assert_eq!(corrupt, vec![bad.content.sha256.clone()]);
+	assert!(core.inner.cas.blob_exists(&good.content.sha256));
+	assert!(!core.inner.cas.blob_exists(&bad.content.sha256));
+	{
+		let store = core.inner.store.lock().unwrap();
+		assert!(!store.get_cache_present(&bad.content.sha256).unwrap());
+		assert!(store.get_cache_present(&good.content.sha256).unwrap());
+	}
+	let quarantine = std::path::Path::new(&dirs.cache_dir).join("quarantine");
+	let kept: Vec<_> = std::fs::read_dir(&quarantine).unwrap().map(|e| e.unwrap().file_name()).collect();
+	assert_eq!(kept.len(), 1);
+	assert!(kept[0].to_string_lossy().starts_with(&bad.content.sha256));
+
+	// 第二轮不再报告
+	assert!(core.scrub_cas().unwrap().is_empty());
+}
+
+#[test]
+fn fast_path_refuses_truncated_blob() {
+	let (core, _dirs) = mk_core("scrub_truncated", 1_000_000, 1_i64 << 60);
+	let meta = ingest_image(&core, 1, crate::util::now_ms());
+	std::fs::write(core.inner.cas.blob_path(&meta.content.sha256), vec![1u8; 10]).unwrap();
+
+	// 本机条目无处重新拉取：不会把截断的内容交出去
+	assert!(core.ensure_content_cached(&meta.item_id, None).is_err());
+	assert!(!core.inner.cas.blob_exists(&meta.content.sha256));
+}
+
+#[tokio::test]
+async fn scrub_refetches_from_online_peer() {
+	let shared_uid = format!("scrub_sync_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("scrub_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("scrub_b", &shared_uid, |_| {});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_a).await;
+		peers.iter().any(|p| p.device_id == "scrub_b" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let meta = ingest_image(&core_a, 7, crate::util::now_ms());
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &meta.item_id).await.is_some());
+
+	let c_b = core_b.clone();
+	let iid = meta.item_id.clone();
+	let transfer_id = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, None))
+		.await
+		.unwrap()
+		.unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &transfer_id).await.is_some());
+
+	// B 的副本损坏：巡检后隔离，并自动向 A 重新拉取
+	let sha = meta.content.sha256.clone();
+	std::fs::write(core_b.inner.cas.blob_path(&sha), vec![0u8; 40]).unwrap();
+	let c_b = core_b.clone();
+	let corrupt = tokio::task::spawn_blocking(move || c_b.scrub_cas()).await.unwrap().unwrap();
+	assert_eq!(corrupt, vec![sha.clone()]);
+
+	let evt = wait_event(&mut rx_b, "CAS_CORRUPTION", &sha).await.expect("no CAS_CORRUPTION event");
+	let evt: serde_json::Value = serde_json::from_str(&evt).unwrap();
+	assert_eq!(evt["payload"]["item_ids"], serde_json::json!([meta.item_id]));
+	assert_eq!(evt["payload"]["refetch_item_ids"], serde_json::json!([meta.item_id]));
+
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &meta.item_id).await.is_some());
+	assert_eq!(core_b.inner.cas.verify_blob(&sha, 0).unwrap(), Some(true));
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
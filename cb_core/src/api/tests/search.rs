@@ -0,0 +1,119 @@
+use super::common::*;
+
+#[test]
+fn search_matches_text_body_and_highlights() {
+	let (core, _dirs) = mk_core("search_text", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	// 正文超过 300 字符，关键字只在预览之外
+	let long = format!("{} https://example.com/needle-page end", "x ".repeat(200));
+	let hit = ingest_text(&core, &long, ts);
+	let _miss = ingest_text(&core, "nothing to see here", ts + 1);
+
+	let page = core
+		.search_history("example.com/needle", &Default::default(), None, 10)
+		.unwrap();
+	assert_eq!(page.hits.len(), 1);
+	assert_eq!(page.hits[0].meta.item_id, hit.item_id);
+	assert!(page.hits[0].snippet.contains("<mark>"), "snippet: {}", page.hits[0].snippet);
+}
+
+#[test]
+fn search_short_cjk_terms_and_file_names() {
+	let (core, _dirs) = mk_core("search_cjk", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+
+	let cn = ingest_text(&core, "明天下午开会", ts);
+	let files = core
+		.ingest_local_copy(crate::clipboard::ClipboardSnapshot::FileList {
+			files: vec![crate::clipboard::ClipboardFileEntry {
+				rel_name: "季度报告.pdf".to_string(),
+				abs_path: None,
+				size_bytes: 10,
+				sha256: None,
+				is_dir: false,
+				mode: None,
+				mtime_ms: None,
+			}],
+			ts_ms: ts + 1,
+		})
+		.unwrap();
+
+	// 两个字的词不足 trigram 长度，走 LIKE 回退
+	let page = core.search_history("开会", &Default::default(), None, 10).unwrap();
+	assert_eq!(page.hits.len(), 1);
+	assert_eq!(page.hits[0].meta.item_id, cn.item_id);
+	assert_eq!(page.hits[0].snippet, "明天下午<mark>开会</mark>");
+
+	let page = core.search_history("报告.pdf", &Default::default(), None, 10).unwrap();
+	assert_eq!(page.hits.len(), 1);
+	assert_eq!(page.hits[0].meta.item_id, files.item_id);
+}
+
+#[test]
+fn search_pages_and_filters() {
+	let (core, _dirs) = mk_core("search_page", 1_000_000, 1_i64 << 60);
+	let ts = crate::util::now_ms();
+	for i in 0..3 {
+		ingest_text(&core, &format!("shared keyword {}", i), ts + i);
+	}
+
+	let first = core.search_history("keyword", &Default::default(), None, 2).unwrap();
+	assert_eq!(first.hits.len(), 2);
+	let cursor = first.next_cursor.clone().expect("next_cursor");
+	let second = core.search_history("keyword", &Default::default(), Some(cursor), 2).unwrap();
+	assert_eq!(second.hits.len(), 1);
+	assert!(second.next_cursor.is_none());
+
+	let images_only = crate::model::HistoryFilter {
+		kinds: vec![crate::model::ItemKind::Image],
+		..Default::default()
+	};
+	assert!(core.search_history("keyword", &images_only, None, 10).unwrap().hits.is_empty());
+
+	assert!(core.search_history("   ", &Default::default(), None, 10).is_err());
+}
+
+#[test]
+fn upgrade_indexes_bodies_already_cached_in_cas() {
+	use rusqlite::params;
+
+	let dirs = unique_dirs("search_backfill");
+	let data_dir = std::path::PathBuf::from(&dirs.data_dir);
+	crate::testsupport::legacy_db::create_legacy_core_db(&data_dir).unwrap();
+
+	// 旧版本地已缓存的长文本：预览只有开头，关键字在正文末尾
+	let body = format!("{} backfill-needle", "y ".repeat(200));
+	let sha = crate::util::sha256_hex(body.as_bytes());
+	crate::cas::Cas::new(&dirs.cache_dir)
+		.unwrap()
+		.put_if_absent(&sha, body.as_bytes(), "legacy-cached.tmp")
+		.unwrap();
+	let conn = rusqlite::Connection::open(data_dir.join("core.db")).unwrap();
+	conn.execute(
+		"INSERT INTO items VALUES ('legacy-cached', 'text', 'dev-old', 3000, ?1, 'text/plain', ?2, ?3, '[]', NULL)",
+		params![body.len() as i64, sha, r#"{"text":"y y y"}"#],
+	)
+	.unwrap();
+	conn.execute(
+		"INSERT INTO history(account_uid, item_id, sort_ts_ms, source_device_id, is_deleted) VALUES ('acct-uid-1', 'legacy-cached', 3000, 'dev-old', 0)",
+		[],
+	)
+	.unwrap();
+	conn.execute(
+		"INSERT INTO content_cache VALUES (?1, ?2, 1, 3000, 3000)",
+		params![sha, body.len() as i64],
+	)
+	.unwrap();
+	drop(conn);
+
+	let core = mk_core_in(&dirs, |_| {});
+	let page = core.search_history("backfill-needle", &Default::default(), None, 10).unwrap();
+	assert_eq!(page.hits.len(), 1);
+	assert_eq!(page.hits[0].meta.item_id, "legacy-cached");
+
+	// 补建只做一次：队列已清空
+	let conn = rusqlite::Connection::open(data_dir.join("core.db")).unwrap();
+	let queued: i64 = conn.query_row("SELECT COUNT(*) FROM search_backfill", [], |r| r.get(0)).unwrap();
+	assert_eq!(queued, 0);
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::m1_net::{create_test_core, list_peers_async, listen_port_async, wait_for};
+use crate::api::{Core, PeerConnectionState};
+use crate::net::duplicate_sessions;
+use crate::session::SessionRole;
+use crate::store::Store;
+
+fn count_logs(core: &Arc<Core>, needle: &str) -> usize {
+	core.inner.log_store.lock().unwrap().query_after_id(0, 0, Some(needle), 1000, None).unwrap().len()
+}
+
+#[tokio::test]
+async fn static_peers_are_validated_and_persisted() {
+	let uid = format!("static_{}", uuid::Uuid::new_v4());
+	let (core, _rx, dir) = create_test_core("sp_a", &uid, |cfg| {
+		cfg.app_config.static_peers = vec!["office-pc.invalid:4433".to_string(), "not an address".to_string()];
+	});
+
+	// 配置里的地址启动时并入 core.db，无效的忽略
+	assert!(core.list_static_peers().unwrap().iter().any(|p| p.addr == "office-pc.invalid:4433"));
+
+	assert!(core.add_static_peer("127.0.0.1:45999").unwrap());
+	assert!(!core.add_static_peer(" 127.0.0.1:45999 ").unwrap());
+	assert!(core.add_static_peer("[::1]:45999").unwrap());
+	for bad in ["", "host", "host:", ":4433", "[]:4433", "host:0", "host:70000", "my host:4433"] {
+		let err = core.add_static_peer(bad).unwrap_err();
+		assert!(err.to_string().starts_with("INVALID_PEER_ADDR"), "{bad:?}: {err}");
+	}
+
+	let mut addrs: Vec<String> = core.list_static_peers().unwrap().into_iter().map(|p| p.addr).collect();
+	addrs.sort();
+	assert_eq!(addrs, vec!["127.0.0.1:45999", "[::1]:45999", "office-pc.invalid:4433"]);
+	// 还没连上过，不知道对方是谁
+	assert!(core.list_static_peers().unwrap().iter().all(|p| p.device_id.is_none()));
+
+	assert!(core.remove_static_peer("[::1]:45999").unwrap());
+	assert!(!core.remove_static_peer("[::1]:45999").unwrap());
+	core.shutdown();
+
+	// 重启后仍在；握手后得知的 device_id 一并保存
+	let mut store = Store::open(dir.path()).unwrap();
+	store.set_static_peer_device(&uid, "127.0.0.1:45999", "sp_b").unwrap();
+	let peers = store.list_static_peers(&uid).unwrap();
+	assert_eq!(peers.len(), 2);
+	let peer = peers.iter().find(|p| p.addr == "127.0.0.1:45999").unwrap();
+	assert_eq!(peer.device_id.as_deref(), Some("sp_b"));
+	assert!(store.list_static_peers("other_account").unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn static_peer_pointing_at_self_is_not_dialed_again() {
+	let uid = format!("static_self_{}", uuid::Uuid::new_v4());
+	let (core, _rx, _dir) = create_test_core("ss_a", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["127.0.0.1".to_string()];
+	});
+	let port = listen_port_async(&core).await;
+	let c = core.clone();
+	assert!(tokio::task::spawn_blocking(move || c.add_static_peer(&format!("127.0.0.1:{port}"))).await.unwrap().unwrap());
+
+	// 握手时认出地址指向本机
+	let identified = wait_for(Duration::from_secs(120), || async {
+		core.list_static_peers().unwrap().iter().any(|p| p.device_id.as_deref() == Some("ss_a"))
+	}).await;
+	assert!(identified, "self connection not detected");
+
+	// 不把自己列为对端，也不再反复拨号
+	tokio::time::sleep(Duration::from_secs(3)).await;
+	assert!(list_peers_async(&core).await.iter().all(|p| p.device_id != "ss_a"));
+	assert_eq!(count_logs(&core, "Static peer identified"), 1);
+	core.shutdown();
+}
+
+#[tokio::test]
+async fn mutual_static_peers_keep_one_session() {
+	let uid = format!("static_dup_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, _dir_a) = create_test_core("sd_a", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["127.0.0.1".to_string()];
+	});
+	let port_a = listen_port_async(&core_a).await;
+	let (core_b, _rx_b, _dir_b) = create_test_core("sd_b", &uid, |cfg| {
+		cfg.app_config.network.bind = vec!["127.0.0.1".to_string()];
+		cfg.app_config.static_peers = vec![format!("127.0.0.1:{port_a}")];
+	});
+	let port_b = listen_port_async(&core_b).await;
+	// 双方互相配置了地址：A 还不知道拨入的就是 B，照样拨出
+	let c_a = core_a.clone();
+	assert!(tokio::task::spawn_blocking(move || c_a.add_static_peer(&format!("127.0.0.1:{port_b}"))).await.unwrap().unwrap());
+
+	for (core, peer) in [(&core_a, "sd_b"), (&core_b, "sd_a")] {
+		let identified = wait_for(Duration::from_secs(120), || async {
+			core.list_static_peers().unwrap().iter().any(|p| p.device_id.as_deref() == Some(peer))
+		}).await;
+		assert!(identified, "static peer identity not learned");
+	}
+	let closed = wait_for(Duration::from_secs(60), || async {
+		count_logs(&core_a, "Closing duplicate session") + count_logs(&core_b, "Closing duplicate session") > 0
+	}).await;
+	assert!(closed, "duplicate session not closed");
+
+	// 两端关的是同一条，留下的那条保持在线；两个方向的握手都通过指纹校验
+	tokio::time::sleep(Duration::from_secs(3)).await;
+	assert_eq!(count_logs(&core_a, "TLS_PIN_MISMATCH") + count_logs(&core_b, "TLS_PIN_MISMATCH"), 0);
+	for (core, peer) in [(&core_a, "sd_b"), (&core_b, "sd_a")] {
+		let peers = list_peers_async(core).await;
+		assert!(peers.iter().any(|p| p.device_id == peer && p.state == PeerConnectionState::Online), "{peer} went offline");
+	}
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
+
+#[test]
+fn duplicate_sessions_keep_the_one_started_by_the_smaller_device() {
+	use SessionRole::{Client, Server};
+	let sessions = |list: &[(&str, SessionRole)]| -> Vec<(usize, String, SessionRole)> {
+		list.iter().enumerate().map(|(i, (did, role))| (i, did.to_string(), *role)).collect()
+	};
+
+	// 双方互拨：两端都留下 device_id 较小的 a 发起的那条
+	let both = sessions(&[("b", Client), ("b", Server)]);
+	assert_eq!(duplicate_sessions("a", &both), vec![1]);
+	let both = sessions(&[("a", Client), ("a", Server)]);
+	assert_eq!(duplicate_sessions("b", &both), vec![0]);
+
+	// 同一方发起的多条：只由发起方关闭较新的
+	let mine = sessions(&[("b", Client), ("c", Client), ("b", Client)]);
+	assert_eq!(duplicate_sessions("a", &mine), vec![2]);
+	let theirs = sessions(&[("b", Server), ("b", Server)]);
+	assert!(duplicate_sessions("a", &theirs).is_empty());
+
+	// 各自只有一条：不关
+	assert!(duplicate_sessions("a", &sessions(&[("b", Server), ("c", Client)])).is_empty());
+}
+
@@ -0,0 +1,107 @@
+use std::io::Read;
+
+use sha2::{Digest, Sha256};
+
+use super::common::*;
+use crate::model::ItemKind;
+
+/// 逐块产生内容的 reader，不在内存中持有完整数据
+struct PatternReader {
+	remaining: usize,
+	pos: usize,
+}
+
+impl Read for PatternReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = buf.len().min(self.remaining).min(10_000);
+		for b in &mut buf[..n] {
+			*b = (self.pos % 251) as u8;
+			self.pos += 1;
+		}
+		self.remaining -= n;
+		Ok(n)
+	}
+}
+
+fn pattern_sha(len: usize) -> String {
+	let mut hasher = Sha256::new();
+	let mut r = PatternReader { remaining: len, pos: 0 };
+	std::io::copy(&mut r, &mut hasher).unwrap();
+	hex::encode(hasher.finalize())
+}
+
+fn tmp_is_empty(dirs: &TestDirs) -> bool {
+	let tmp = std::path::Path::new(&dirs.cache_dir).join("tmp");
+	std::fs::read_dir(tmp).map(|mut d| d.next().is_none()).unwrap_or(true)
+}
+
+#[test]
+fn reader_ingest_streams_large_image_into_cas() {
+	let (core, dirs) = mk_core("stream_image", 1_000_000, 1_i64 << 60);
+	let len = 5 * 1024 * 1024 + 123;
+
+	let meta = core
+		.ingest_from_reader(PatternReader { remaining: len, pos: 0 }, "image/png", crate::util::now_ms(), false)
+		.unwrap();
+	assert_eq!(meta.kind, ItemKind::Image);
+	assert_eq!(meta.size_bytes, len as i64);
+	assert_eq!(meta.content.sha256, pattern_sha(len));
+	assert!(core.inner.cas.blob_exists(&meta.content.sha256));
+	assert_eq!(std::fs::metadata(core.inner.cas.blob_path(&meta.content.sha256)).unwrap().len(), len as u64);
+	assert!(core.inner.store.lock().unwrap().get_cache_present(&meta.content.sha256).unwrap());
+	assert!(tmp_is_empty(&dirs));
+
+	// 非图片 MIME 不能走 reader 摄入
+	let err = core.ingest_from_reader(&b"abc"[..], "application/pdf", 1, false).unwrap_err();
+	assert!(err.to_string().contains("UNSUPPORTED_MIME"));
+}
+
+#[test]
+fn path_ingest_creates_single_file_list() {
+	let (core, dirs) = mk_core("stream_file", 1_000_000, 1_i64 << 60);
+	let src = std::path::Path::new(&dirs.cache_dir).join("report.pdf");
+	let data: Vec<u8> = (0..300_000u32).map(|i| (i % 97) as u8).collect();
+	std::fs::write(&src, &data).unwrap();
+
+	let meta = core.ingest_from_path(&src, "application/pdf", crate::util::now_ms(), false).unwrap();
+	assert_eq!(meta.kind, ItemKind::FileList);
+	assert_eq!(meta.files.len(), 1);
+	let file = &meta.files[0];
+	assert_eq!(file.rel_name, "report.pdf");
+	assert_eq!(file.size_bytes, data.len() as i64);
+	let sha = hex::encode(Sha256::digest(&data));
+	assert_eq!(file.sha256.as_deref(), Some(sha.as_str()));
+
+	// 文件内容与 manifest 都已在 CAS 中
+	assert!(core.inner.cas.blob_exists(&sha));
+	assert!(core.inner.cas.blob_exists(&meta.content.sha256));
+	assert!(core.inner.store.lock().unwrap().get_cache_present(&sha).unwrap());
+	assert!(tmp_is_empty(&dirs));
+
+	// 孤儿清扫不会误删流式写入的文件
+	core.run_gc("Test").unwrap();
+	assert!(core.inner.cas.blob_exists(&sha));
+}
+
+#[test]
+fn stream_ingest_rejects_over_hard_cap() {
+	let (core, dirs) = mk_core_with("stream_cap", |app| {
+		app.size_limits.hard_image_bytes = 100_000;
+		app.size_limits.hard_file_total_bytes = 100_000;
+	});
+
+	// reader 长度未知：写到超限时中止
+	let err = core
+		.ingest_from_reader(PatternReader { remaining: 200_000, pos: 0 }, "image/png", 1, true)
+		.unwrap_err();
+	assert!(err.to_string().contains("ITEM_TOO_LARGE"));
+	assert!(tmp_is_empty(&dirs));
+
+	// 路径摄入按文件大小提前拒绝
+	let src = std::path::Path::new(&dirs.cache_dir).join("big.bin");
+	std::fs::write(&src, vec![0u8; 200_000]).unwrap();
+	let err = core.ingest_from_path(&src, "application/octet-stream", 1, true).unwrap_err();
+	assert!(err.to_string().contains("ITEM_TOO_LARGE"));
+	assert!(tmp_is_empty(&dirs));
+	assert!(core.list_history(10, None).unwrap().is_empty());
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, without_compression};
+use crate::api::{Core, PeerConnectionState};
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+
+/// A 复制一个 size 字节的文件，等 B 收到带哈希的元数据
+async fn share_file(
+	core_a: &std::sync::Arc<Core>,
+	dir_a: &tempfile::TempDir,
+	rx_b: &mut tokio::sync::broadcast::Receiver<String>,
+	size: u32,
+) -> (String, String) {
+	let data: Vec<u8> = (0..size).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+	let src = dir_a.path().join("big.bin");
+	std::fs::write(&src, &data).unwrap();
+	let entry = ClipboardFileEntry {
+		rel_name: "big.bin".to_string(),
+		abs_path: Some(src.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	};
+	let c_a = core_a.clone();
+	let meta = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(rx_b, "ITEM_META_UPDATED", &meta.item_id).await.is_some());
+	(meta.item_id, meta.files[0].file_id.clone())
+}
+
+#[tokio::test]
+async fn heartbeats_stay_on_time_during_bulk_transfer() {
+	let shared_uid = format!("streams_hb_{}", uuid::Uuid::new_v4());
+	let (core_a, mut rx_a, dir_a) = create_test_core("hb_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("hb_b", &shared_uid, without_compression);
+	assert!(online_with(&core_b, &["hb_a"]).await, "Peers not connected");
+	assert!(online_with(&core_a, &["hb_b"]).await, "Peers not connected");
+
+	let (item_id, file_id) = share_file(&core_a, &dir_a, &mut rx_b, 48 * 1024 * 1024).await;
+	let transfer_id = fetch(&core_b, &item_id, Some(&file_id)).await.unwrap();
+	assert!(wait_event(&mut rx_a, "UPLOAD_PROGRESS", &transfer_id).await.is_some(), "upload not started");
+
+	// 数据走独立的流：传输进行中，双方在控制流上收到对端消息的间隔始终远小于心跳超时
+	let start = Instant::now();
+	let mut samples = 0;
+	loop {
+		match rx_b.try_recv() {
+			Ok(evt) if evt.contains("\"type\":\"CONTENT_CACHED\"") && evt.contains(&transfer_id) => break,
+			Ok(evt) => assert!(!evt.contains("\"type\":\"PEER_OFFLINE\""), "session dropped: {evt}"),
+			Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {}
+			Err(_) => {
+				assert!(start.elapsed() < Duration::from_secs(120), "transfer did not finish");
+				for (core, peer) in [(&core_a, "hb_b"), (&core_b, "hb_a")] {
+					let status = list_peers_async(core).await.into_iter().find(|p| p.device_id == peer).expect("peer gone");
+					assert_eq!(status.state, PeerConnectionState::Online);
+					let silent_ms = crate::util::now_ms() - status.last_seen_ts_ms;
+					assert!(silent_ms < 4000, "no control message from {peer} for {silent_ms}ms");
+				}
+				samples += 1;
+				tokio::time::sleep(Duration::from_millis(100)).await;
+			}
+		}
+	}
+	assert!(samples > 0);
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
+
+#[tokio::test]
+async fn cancelling_resets_only_the_data_stream() {
+	let shared_uid = format!("streams_cancel_{}", uuid::Uuid::new_v4());
+	let (core_a, mut rx_a, dir_a) = create_test_core("sc_a", &shared_uid, |cfg| {
+		// 限速让传输在取消时仍在进行
+		cfg.app_config.transfer.upload_bytes_per_sec = 4 * 1024 * 1024;
+	});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("sc_b", &shared_uid, without_compression);
+	assert!(online_with(&core_b, &["sc_a"]).await, "Peers not connected");
+
+	let (item_id, file_id) = share_file(&core_a, &dir_a, &mut rx_b, 32 * 1024 * 1024).await;
+
+	// 接收端取消：stop 数据流，发送端停止上传
+	let tid = fetch(&core_b, &item_id, Some(&file_id)).await.unwrap();
+	assert!(wait_event(&mut rx_b, "TRANSFER_PROGRESS", &tid).await.is_some());
+	core_b.cancel_transfer(&tid);
+	assert!(wait_event(&mut rx_b, "TRANSFER_CANCELLED", &tid).await.is_some(), "receiver cancel not reported");
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	while rx_a.try_recv().is_ok() {}
+	tokio::time::sleep(Duration::from_secs(1)).await;
+	loop {
+		match rx_a.try_recv() {
+			Ok(evt) => assert!(!(evt.contains("UPLOAD_PROGRESS") && evt.contains(&tid)), "upload continued after stop"),
+			Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {}
+			Err(_) => break,
+		}
+	}
+
+	// 发送端取消：reset 数据流，接收端得知传输取消
+	let tid = fetch(&core_b, &item_id, Some(&file_id)).await.unwrap();
+	assert!(wait_event(&mut rx_b, "TRANSFER_PROGRESS", &tid).await.is_some());
+	core_a.cancel_transfer(&tid);
+	assert!(wait_event(&mut rx_b, "TRANSFER_CANCELLED", &tid).await.is_some(), "sender cancel not reported");
+
+	// 会话不受影响，之后的传输照常完成
+	let peers = list_peers_async(&core_b).await;
+	assert!(peers.iter().any(|p| p.device_id == "sc_a" && p.state == PeerConnectionState::Online));
+	let c_a = core_a.clone();
+	let text = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::Text { text_utf8: "still connected".to_string(), ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &text.item_id).await.is_some());
+	let tid = fetch(&core_b, &text.item_id, None).await.unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &tid).await.is_some(), "text not cached after cancels");
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
@@ -0,0 +1,259 @@
+use std::time::{Duration, Instant};
+
+use super::common::*;
+use super::m1_net::{create_test_core, list_peers_async, wait_for, without_compression};
+use crate::api::PeerConnectionState;
+use crate::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
+use crate::session::scheduler::{priority_channel, RateLimiter, TransferPriority};
+
+#[tokio::test]
+async fn rate_limiter_paces_bulk_but_not_interactive() {
+	let limiter = RateLimiter::new(512 * 1024);
+
+	// 交互传输不等待令牌
+	let start = Instant::now();
+	limiter.acquire(1024 * 1024, TransferPriority::Interactive).await;
+	assert!(start.elapsed() < Duration::from_millis(100));
+
+	// 批量传输要先还清交互传输的透支（2s），再按 512KB/s 发送 512KB（1s）
+	let start = Instant::now();
+	for _ in 0..8 {
+		limiter.acquire(64 * 1024, TransferPriority::Bulk).await;
+	}
+	assert!(start.elapsed() >= Duration::from_millis(2700), "{:?}", start.elapsed());
+
+	// 不限速
+	let start = Instant::now();
+	RateLimiter::new(0).acquire(u32::MAX as u64, TransferPriority::Bulk).await;
+	assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn queued_interactive_chunks_go_first() {
+	let (tx, mut rx) = priority_channel::<&str>(8);
+	tx.get(TransferPriority::Bulk).send("bulk-1").await.unwrap();
+	tx.get(TransferPriority::Bulk).send("bulk-2").await.unwrap();
+	tx.get(TransferPriority::Interactive).send("text").await.unwrap();
+	assert_eq!(TransferPriority::for_request(Some("file-1")), TransferPriority::Bulk);
+	assert_eq!(TransferPriority::for_request(None), TransferPriority::Interactive);
+
+	assert_eq!(rx.recv().await, Some("text"));
+	assert_eq!(rx.recv().await, Some("bulk-1"));
+	drop(tx);
+	assert_eq!(rx.recv().await, Some("bulk-2"));
+	assert_eq!(rx.recv().await, None);
+}
+
+#[test]
+fn interactive_streams_go_first() {
+	assert!(TransferPriority::Interactive.stream_priority() > TransferPriority::Bulk.stream_priority());
+}
+
+#[tokio::test]
+async fn upload_limit_throttles_files_but_not_text() {
+	const LIMIT: i64 = 2 * 1024 * 1024;
+	let shared_uid = format!("throttle_{}", uuid::Uuid::new_v4());
+	let (core_a, mut rx_a, dir_a) = create_test_core("th_a", &shared_uid, |cfg| {
+		cfg.app_config.transfer.upload_bytes_per_sec = LIMIT;
+	});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("th_b", &shared_uid, without_compression);
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_b).await;
+		peers.iter().any(|p| p.device_id == "th_a" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	let data: Vec<u8> = (0..8 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+	let src = dir_a.path().join("big.bin");
+	std::fs::write(&src, &data).unwrap();
+	let entry = ClipboardFileEntry {
+		rel_name: "big.bin".to_string(),
+		abs_path: Some(src.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	};
+	let c_a = core_a.clone();
+	let files = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &files.item_id).await.is_some());
+	let c_a = core_a.clone();
+	let text = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::Text { text_utf8: "paste me now".to_string(), ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_ADDED", &text.item_id).await.is_some());
+
+	let start = Instant::now();
+	let c_b = core_b.clone();
+	let (iid, fid) = (files.item_id.clone(), files.files[0].file_id.clone());
+	let file_tid = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "TRANSFER_PROGRESS", &file_tid).await.is_some());
+
+	// 文件传输进行中，文字仍能很快到达
+	let text_start = Instant::now();
+	let c_b = core_b.clone();
+	let iid = text.item_id.clone();
+	let text_tid = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, None)).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &text_tid).await.is_some(), "text not cached");
+	assert!(text_start.elapsed() < Duration::from_secs(2), "text waited behind the file: {:?}", text_start.elapsed());
+
+	// 上传速率报告给发送端，下载速率报告给接收端
+	let upload = wait_event(&mut rx_a, "UPLOAD_PROGRESS", "bytes_per_sec").await.expect("no UPLOAD_PROGRESS");
+	let upload: serde_json::Value = serde_json::from_str(&upload).unwrap();
+	assert!(upload["payload"]["total"].as_u64().unwrap() >= data.len() as u64);
+	let mut rate = 0;
+	while rate == 0 {
+		let progress = wait_event(&mut rx_b, "TRANSFER_PROGRESS", &file_tid).await.expect("no TRANSFER_PROGRESS");
+		let progress: serde_json::Value = serde_json::from_str(&progress).unwrap();
+		rate = progress["payload"]["bytes_per_sec"].as_u64().unwrap();
+	}
+
+	assert!(wait_event(&mut rx_b, "CONTENT_CACHED", &file_tid).await.is_some(), "file not cached");
+	// 8MB 按 2MB/s：不少于约 3.5s
+	assert!(start.elapsed() >= Duration::from_millis(3500), "upload not throttled: {:?}", start.elapsed());
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
+
+#[tokio::test]
+async fn download_limit_caps_concurrent_downloads() {
+	const LIMIT: i64 = 2 * 1024 * 1024;
+	const FILE_BYTES: usize = 4 * 1024 * 1024;
+	let shared_uid = format!("throttle_dl_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("thd_a", &shared_uid, |_| {});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("thd_b", &shared_uid, |cfg| {
+		cfg.app_config.transfer.download_bytes_per_sec = LIMIT;
+	});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_b).await;
+		peers.iter().any(|p| p.device_id == "thd_a" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	// 三个随机内容的文件：压缩不会减少实际传输的字节数
+	let entries: Vec<_> = (0..3u32)
+		.map(|n| {
+			let mut data = vec![0u8; FILE_BYTES];
+			rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut data);
+			let src = dir_a.path().join(format!("part{n}.bin"));
+			std::fs::write(&src, &data).unwrap();
+			ClipboardFileEntry {
+				rel_name: format!("part{n}.bin"),
+				abs_path: Some(src.to_string_lossy().into_owned()),
+				size_bytes: data.len() as i64,
+				sha256: None,
+				is_dir: false,
+				mode: None,
+				mtime_ms: None,
+			}
+		})
+		.collect();
+	let c_a = core_a.clone();
+	let files = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: entries, ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &files.item_id).await.is_some());
+
+	// 同时拉取三个文件：下载限额是三者合计，而不是各自的上限
+	let start = Instant::now();
+	let mut received = std::collections::HashMap::new();
+	for f in &files.files {
+		let c_b = core_b.clone();
+		let (iid, fid) = (files.item_id.clone(), f.file_id.clone());
+		let tid = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+		received.insert(tid, 0u64);
+	}
+
+	let mut half_at = None;
+	let mut cached = 0;
+	while cached < received.len() && start.elapsed() < Duration::from_secs(60) {
+		let evt = match rx_b.try_recv() {
+			Ok(evt) => evt,
+			Err(_) => {
+				tokio::time::sleep(Duration::from_millis(20)).await;
+				continue;
+			}
+		};
+		let v: serde_json::Value = serde_json::from_str(&evt).unwrap();
+		let Some(slot) = v["payload"]["transfer_id"].as_str().and_then(|t| received.get_mut(t)) else { continue };
+		match v["type"].as_str() {
+			Some("TRANSFER_PROGRESS") => *slot = (*slot).max(v["payload"]["received"].as_u64().unwrap()),
+			Some("CONTENT_CACHED") => {
+				*slot = FILE_BYTES as u64;
+				cached += 1;
+			}
+			_ => continue,
+		}
+		if half_at.is_none() && received.values().sum::<u64>() >= 8 * 1024 * 1024 {
+			half_at = Some(start.elapsed());
+		}
+	}
+	assert_eq!(cached, received.len(), "downloads did not finish: {:?}", received);
+
+	// 合计 8MB 按 2MB/s：不少于约 3.5s（各自按上限节流时约 2.7s 即可达到）
+	let half_at = half_at.unwrap();
+	assert!(half_at >= Duration::from_millis(3500), "aggregate download rate over the limit: {:?}", half_at);
+	// 12MB 全部完成：不少于约 5.5s
+	assert!(start.elapsed() >= Duration::from_millis(5500), "downloads not throttled: {:?}", start.elapsed());
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
+
+#[tokio::test]
+async fn throttled_compressed_upload_of_incompressible_file_finishes() {
+	// 低于 512KB/s 时桶容量只有一块，压缩器一次吐出的多块必须分开取令牌
+	const LIMIT: i64 = 256 * 1024;
+	const FILE_BYTES: usize = 1024 * 1024;
+	let shared_uid = format!("throttle_cz_{}", uuid::Uuid::new_v4());
+	let (core_a, _rx_a, dir_a) = create_test_core("thc_a", &shared_uid, |cfg| {
+		cfg.app_config.transfer.upload_bytes_per_sec = LIMIT;
+	});
+	let (core_b, mut rx_b, _dir_b) = create_test_core("thc_b", &shared_uid, |_| {});
+
+	let connected = wait_for(Duration::from_secs(120), || async {
+		let peers = list_peers_async(&core_b).await;
+		peers.iter().any(|p| p.device_id == "thc_a" && p.state == PeerConnectionState::Online)
+	}).await;
+	assert!(connected, "Peers not connected");
+
+	// 随机内容、不在已压缩格式列表里：照常压缩传输，压缩后不会变小
+	let mut data = vec![0u8; FILE_BYTES];
+	rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut data);
+	let src = dir_a.path().join("firmware.bin");
+	std::fs::write(&src, &data).unwrap();
+	let entry = ClipboardFileEntry {
+		rel_name: "firmware.bin".to_string(),
+		abs_path: Some(src.to_string_lossy().into_owned()),
+		size_bytes: data.len() as i64,
+		sha256: None,
+		is_dir: false,
+		mode: None,
+		mtime_ms: None,
+	};
+	let c_a = core_a.clone();
+	let files = tokio::task::spawn_blocking(move || {
+		c_a.ingest_local_copy(ClipboardSnapshot::FileList { files: vec![entry], ts_ms: crate::util::now_ms() })
+	}).await.unwrap().unwrap();
+	assert!(wait_event(&mut rx_b, "ITEM_META_UPDATED", &files.item_id).await.is_some());
+
+	let start = Instant::now();
+	let c_b = core_b.clone();
+	let (iid, fid) = (files.item_id.clone(), files.files[0].file_id.clone());
+	let tid = tokio::task::spawn_blocking(move || c_b.ensure_content_cached(&iid, Some(&fid))).await.unwrap().unwrap();
+	let cached = wait_event(&mut rx_b, "CONTENT_CACHED", &tid).await.expect("throttled compressed upload never finished");
+	let cached: serde_json::Value = serde_json::from_str(&cached).unwrap();
+	assert_eq!(cached["payload"]["compression"], serde_json::json!("zstd"));
+	assert_eq!(std::fs::read(cached["payload"]["local_ref"]["local_path"].as_str().unwrap()).unwrap(), data);
+	// 1MB 按 256KB/s：不少于约 3s
+	assert!(start.elapsed() >= Duration::from_millis(3000), "upload not throttled: {:?}", start.elapsed());
+
+	core_a.shutdown();
+	core_b.shutdown();
+}
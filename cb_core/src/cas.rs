@@ -1,12 +1,64 @@
+use std::collections::HashSet;
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sha2::{Digest, Sha256};
+
+/// 还原到视图文件 / 目录上的属性（来自 FileMeta）
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileAttrs {
+    /// Unix 权限位；为保证缓存目录可清理，还原时总会保留属主读写（目录另加执行）权限
+    pub mode: Option<u32>,
+    pub mtime_ms: Option<i64>,
+}
+
+/// materialize_tree 的一项：目录，或内容为 sha256 的文件
+#[derive(Clone, Debug)]
+pub struct TreeEntry<'a> {
+    pub rel_name: &'a str,
+    pub is_dir: bool,
+    pub sha256: Option<&'a str>,
+    pub attrs: FileAttrs,
+}
+
+/// 可续传的未完成下载：partial/<key>.part 是已收到的前缀，<key>.json 记录校验点
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PartialInfo {
+    /// 完整文件的 sha256
+    pub sha256: String,
+    pub total_bytes: u64,
+    /// 已 flush + fsync 落盘的字节数，续传从这里开始
+    pub verified_bytes: u64,
+}
+
+/// 正在写入某个 partial 的占用标记，drop 时释放（同一内容同时只允许一个传输写入）
+#[derive(Debug)]
+pub struct PartialClaim {
+    claims: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl PartialClaim {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl Drop for PartialClaim {
+    fn drop(&mut self) {
+        self.claims.lock().unwrap().remove(&self.key);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Cas {
     cache_dir: PathBuf,
     blobs_dir: PathBuf,
     tmp_dir: PathBuf,
+    partial_dir: PathBuf,
+    partial_claims: Arc<Mutex<HashSet<String>>>,
 }
 
 impl Cas {
@@ -14,10 +66,12 @@ impl Cas {
         let cache_dir = cache_dir.as_ref().to_path_buf();
         let blobs_dir = cache_dir.join("blobs").join("sha256");
         let tmp_dir = cache_dir.join("tmp");
+        let partial_dir = cache_dir.join("partial");
         fs::create_dir_all(&blobs_dir)?;
         fs::create_dir_all(&tmp_dir)?;
+        fs::create_dir_all(&partial_dir)?;
         println!("[cas] blobs_dir = {:?}", blobs_dir);
-        Ok(Self { cache_dir, blobs_dir, tmp_dir })
+        Ok(Self { cache_dir, blobs_dir, tmp_dir, partial_dir, partial_claims: Arc::default() })
     }
 
     pub fn blob_path(&self, sha256_hex: &str) -> PathBuf {
@@ -143,6 +197,110 @@ impl Cas {
         }
     }
 
+    /// 续传记录的键：同一条目、同一文件、同一内容才能接着写
+    pub fn partial_key(item_id: &str, file_id: Option<&str>, sha256: &str) -> String {
+        crate::util::sha256_hex(format!("{}\n{}\n{}", item_id, file_id.unwrap_or(""), sha256).as_bytes())
+    }
+
+    pub fn partial_path(&self, key: &str) -> PathBuf {
+        self.partial_dir.join(format!("{}.part", key))
+    }
+
+    fn partial_info_path(&self, key: &str) -> PathBuf {
+        self.partial_dir.join(format!("{}.json", key))
+    }
+
+    /// 读取续传记录；内容不符或数据文件短于校验点时整条丢弃
+    pub fn load_partial(&self, key: &str, sha256: &str) -> Option<PartialInfo> {
+        let info: PartialInfo = serde_json::from_slice(&fs::read(self.partial_info_path(key)).ok()?).ok()?;
+        let len = fs::metadata(self.partial_path(key)).map(|m| m.len()).unwrap_or(0);
+        if info.sha256 != sha256 || len < info.verified_bytes || info.verified_bytes > info.total_bytes {
+            self.remove_partial(key);
+            return None;
+        }
+        Some(info)
+    }
+
+    /// 写入校验点（先写临时文件再 rename，避免崩溃时留下半截记录）
+    pub fn save_partial(&self, key: &str, info: &PartialInfo) -> anyhow::Result<()> {
+        let tmp = self.partial_dir.join(format!("{}.json.tmp", key));
+        fs::write(&tmp, serde_json::to_vec(info)?)?;
+        fs::rename(&tmp, self.partial_info_path(key))?;
+        Ok(())
+    }
+
+    pub fn remove_partial(&self, key: &str) {
+        let _ = fs::remove_file(self.partial_path(key));
+        let _ = fs::remove_file(self.partial_info_path(key));
+    }
+
+    /// 占用 partial；已被其他传输占用时返回 None
+    pub fn claim_partial(&self, key: &str) -> Option<PartialClaim> {
+        let mut claims = self.partial_claims.lock().unwrap();
+        if !claims.insert(key.to_string()) {
+            return None;
+        }
+        Some(PartialClaim { claims: self.partial_claims.clone(), key: key.to_string() })
+    }
+
+    /// 打开 partial 准备从 offset 继续写：截掉校验点之后的内容，并把已有前缀喂给 hasher，
+    /// 最终得到完整文件的 sha256。offset 为 0 时重新开始。
+    pub fn open_partial(&self, claim: &PartialClaim, sha256: &str, total_bytes: u64, offset: u64) -> anyhow::Result<(fs::File, Sha256)> {
+        let key = claim.key();
+        let mut hasher = Sha256::new();
+        let path = self.partial_path(key);
+        if offset == 0 {
+            let file = fs::File::create(&path)?;
+            self.save_partial(key, &PartialInfo { sha256: sha256.to_string(), total_bytes, verified_bytes: 0 })?;
+            return Ok((file, hasher));
+        }
+
+        match self.load_partial(key, sha256) {
+            Some(info) if info.verified_bytes >= offset && info.total_bytes == total_bytes => {}
+            _ => anyhow::bail!("RESUME_MISMATCH: no partial data up to offset {}", offset),
+        }
+        let mut file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        file.set_len(offset)?;
+        let mut reader = std::io::BufReader::new(&mut file);
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        drop(reader);
+        file.seek(SeekFrom::End(0))?;
+        Ok((file, hasher))
+    }
+
+    /// partial 已写完且校验通过：转正为 blob 并删除记录
+    pub fn commit_partial(&self, key: &str, sha256: &str) -> anyhow::Result<PathBuf> {
+        let res = self.commit_tmp_file(&self.partial_path(key), sha256);
+        let _ = fs::remove_file(self.partial_info_path(key));
+        res
+    }
+
+    /// 清理超过 max_age 没有进展、且当前没有传输在写的 partial。返回 (文件数, 字节数)
+    pub fn sweep_stale_partials(&self, max_age: Duration) -> anyhow::Result<(i64, i64)> {
+        let mut files = 0;
+        let mut bytes = 0;
+        for e in fs::read_dir(&self.partial_dir)? {
+            let e = e?;
+            let m = e.metadata()?;
+            let name = e.file_name().to_string_lossy().into_owned();
+            let key = name.split('.').next().unwrap_or("");
+            if !m.is_file() || !is_older_than(&m, max_age) || self.partial_claims.lock().unwrap().contains(key) {
+                continue;
+            }
+            fs::remove_file(e.path())?;
+            files += 1;
+            bytes += m.len() as i64;
+        }
+        Ok((files, bytes))
+    }
+
     /// M3: 为 Blob 创建一个带后缀的“视图” (用于 Image/File)
     /// 策略：尝试硬链接，不支持则复制。文件名为 <sha256>.<ext>，放在 blobs/views/ 目录下 (或 cache_dir/files)
     pub fn materialize_blob(&self, sha256: &str, ext: &str) -> anyhow::Result<PathBuf> {
@@ -177,34 +335,327 @@ impl Cas {
     }
 
     /// M3-3: 为 Blob 创建指定文件名的“视图”
-    /// 路径: <cache_dir>/downloads/<transfer_id>/<filename>
-    /// 这样可以隔离不同传输的同名文件，且方便 Shell 访问
-    pub fn materialize_file(&self, sha256: &str, transfer_id: &str, filename: &str) -> anyhow::Result<PathBuf> {
+    /// 路径: <cache_dir>/downloads/<transfer_id>/<rel_name>
+    /// 这样可以隔离不同传输的同名文件，且方便 Shell 访问。
+    /// rel_name 可以带多级目录（来自对端，经 safe_rel_path 校验，不会写到下载目录之外）
+    pub fn materialize_file(&self, sha256: &str, transfer_id: &str, rel_name: &str, attrs: &FileAttrs) -> anyhow::Result<PathBuf> {
         let blob_path = self.blob_path(sha256);
         if !blob_path.exists() {
             anyhow::bail!("Blob not found: {}", sha256);
         }
 
         // 目录结构：cache/downloads/transfer_id/
-        let download_dir = self.cache_dir.join("downloads").join(transfer_id);
-        fs::create_dir_all(&download_dir)?;
-
-        let target_path = download_dir.join(filename);
+        let download_dir = self.download_dir(transfer_id)?;
+        let target_path = download_dir.join(safe_rel_path(rel_name)?);
 
         // 如果文件已存在且 Hash 一致，直接返回（断点续传/幂等优化）
-        if target_path.exists() {
+        if !target_path.exists() {
             // 这里简略跳过 hash 校验，假设路径独占
-            return Ok(target_path);
+            self.place_view(&blob_path, &target_path, attrs)?;
         }
 
-        // 尝试硬链接，失败则复制
-        if fs::hard_link(&blob_path, &target_path).is_err() {
-            fs::copy(&blob_path, &target_path)?;
-        }
+        // 记录该下载对应的 blob，供 GC 判断视图是否已成孤儿
+        self.add_download_marker(transfer_id, &[sha256])?;
 
         Ok(target_path)
     }
 
+    /// 在 downloads/<dir_name>/ 下还原一棵目录树：创建全部目录（包括空目录），
+    /// 为 blob 已缓存的文件建立视图，并还原权限与修改时间。
+    /// 任何一项路径不安全时整体拒绝、不写入任何内容。
+    /// 返回 (根目录, blob 尚未缓存而跳过的 entries 下标)；可在拉取后再次调用补齐。
+    pub fn materialize_tree(&self, dir_name: &str, entries: &[TreeEntry<'_>]) -> anyhow::Result<(PathBuf, Vec<usize>)> {
+        let rels = entries
+            .iter()
+            .map(|e| safe_rel_path(e.rel_name))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let root = self.download_dir(dir_name)?;
+        let mut missing = Vec::new();
+        let mut present = Vec::new();
+        let mut dirs = Vec::new();
+        for (idx, (entry, rel)) in entries.iter().zip(&rels).enumerate() {
+            let target = root.join(rel);
+            if entry.is_dir {
+                fs::create_dir_all(&target)?;
+                dirs.push((rel.components().count(), target, entry.attrs));
+                continue;
+            }
+            let Some(sha) = entry.sha256.filter(|s| self.blob_exists(s)) else {
+                missing.push(idx);
+                continue;
+            };
+            if !target.exists() {
+                self.place_view(&self.blob_path(sha), &target, &entry.attrs)?;
+            }
+            present.push(sha);
+        }
+
+        // 目录属性最后还原，且由深到浅：子项的创建不会再改动父目录的修改时间
+        dirs.sort_by_key(|(depth, _, _)| std::cmp::Reverse(*depth));
+        for (_, dir, attrs) in &dirs {
+            apply_attrs(dir, attrs, true);
+        }
+
+        self.add_download_marker(dir_name, &present)?;
+        Ok((root, missing))
+    }
+
+    /// downloads/<name>/：name 只能是单个安全的路径分量（transfer_id / item_id 可能来自对端）
+    fn download_dir(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let rel = safe_rel_path(name)?;
+        if rel.components().count() != 1 {
+            anyhow::bail!("INVALID_REL_PATH: {}", name);
+        }
+        let dir = self.cache_dir.join("downloads").join(rel);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// 建立单个视图文件：优先硬链接；需要还原的权限与 blob 不同时复制一份，避免改动 blob 本身
+    fn place_view(&self, blob_path: &Path, target: &Path, attrs: &FileAttrs) -> anyhow::Result<()> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if mode_differs(blob_path, attrs) || fs::hard_link(blob_path, target).is_err() {
+            fs::copy(blob_path, target)?;
+        }
+        apply_attrs(target, attrs, false);
+        Ok(())
+    }
+
+    /// downloads/<name>.blob：记录下载目录引用的 sha256，每行一个
+    fn download_marker_path(&self, transfer_id: &str) -> PathBuf {
+        self.cache_dir.join("downloads").join(format!("{}.blob", transfer_id))
+    }
+
+    fn add_download_marker(&self, name: &str, shas: &[&str]) -> anyhow::Result<()> {
+        let marker = self.download_marker_path(name);
+        let existing = fs::read_to_string(&marker).unwrap_or_default();
+        let mut lines: Vec<&str> = existing.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        for sha in shas {
+            if !lines.contains(sha) {
+                lines.push(sha);
+            }
+        }
+        fs::write(&marker, lines.join("\n"))?;
+        Ok(())
+    }
+
+    /// blob 的实际大小是否与记录一致（不存在时返回 false）。用于快速发现被截断的 blob
+    pub fn blob_size_matches(&self, sha256_hex: &str, total_bytes: i64) -> bool {
+        self.blob_path(sha256_hex)
+            .metadata()
+            .is_ok_and(|m| m.len() as i64 == total_bytes)
+    }
+
+    /// 重新计算 blob 的 sha256 并与文件名比对；bytes_per_sec > 0 时按该速率限速读取。
+    /// 返回 `None` 表示 blob 不存在（巡检期间被 GC 删除等）。
+    pub fn verify_blob(&self, sha256_hex: &str, bytes_per_sec: i64) -> anyhow::Result<Option<bool>> {
+        use std::io::Read;
+
+        let mut file = match fs::File::open(self.blob_path(sha256_hex)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        let start = Instant::now();
+        let mut read_total = 0u64;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            read_total += n as u64;
+            if bytes_per_sec > 0 {
+                let due = Duration::from_secs_f64(read_total as f64 / bytes_per_sec as f64);
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        }
+        Ok(Some(hex::encode(hasher.finalize()) == sha256_hex))
+    }
+
+    /// 巡检一轮：逐个重算 blob 的哈希，返回损坏的 sha256 列表。
+    /// should_stop 返回 true 时提前结束（Core 关闭）。
+    pub fn scrub_pass(&self, bytes_per_sec: i64, should_stop: impl Fn() -> bool) -> anyhow::Result<Vec<String>> {
+        let mut corrupt = Vec::new();
+        for (sha, _) in self.list_blobs()? {
+            if should_stop() {
+                break;
+            }
+            if sha.len() != 64 {
+                continue;
+            }
+            if self.verify_blob(&sha, bytes_per_sec)? == Some(false) {
+                corrupt.push(sha);
+            }
+        }
+        Ok(corrupt)
+    }
+
+    /// 把损坏的 blob 移到 quarantine/（保留现场便于排查），返回隔离后的路径；blob 不存在时返回 None
+    pub fn quarantine_blob(&self, sha256_hex: &str) -> anyhow::Result<Option<PathBuf>> {
+        let src = self.blob_path(sha256_hex);
+        if !src.exists() {
+            return Ok(None);
+        }
+        let dir = self.cache_dir.join("quarantine");
+        fs::create_dir_all(&dir)?;
+        let dst = dir.join(format!("{}.{}", sha256_hex, crate::util::now_ms()));
+        fs::rename(&src, &dst)?;
+        Ok(Some(dst))
+    }
+
+    /// 列出 blobs/ 中的全部 blob（sha256, 字节数）
+    pub fn list_blobs(&self) -> anyhow::Result<Vec<(String, i64)>> {
+        let mut out = Vec::new();
+        for prefix in fs::read_dir(&self.blobs_dir)? {
+            let prefix = prefix?;
+            if !prefix.file_type()?.is_dir() {
+                continue;
+            }
+            for e in fs::read_dir(prefix.path())? {
+                let e = e?;
+                let m = e.metadata()?;
+                if m.is_file() {
+                    out.push((e.file_name().to_string_lossy().into_owned(), m.len() as i64));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// 清理 tmp/ 中超过 max_age 未修改的文件（中断的传输、崩溃残留的写入）。
+    /// 返回 (文件数, 字节数)
+    pub fn sweep_stale_tmp(&self, max_age: Duration) -> anyhow::Result<(i64, i64)> {
+        let mut files = 0;
+        let mut bytes = 0;
+        for e in fs::read_dir(&self.tmp_dir)? {
+            let e = e?;
+            let m = e.metadata()?;
+            if m.is_file() && is_older_than(&m, max_age) {
+                fs::remove_file(e.path())?;
+                files += 1;
+                bytes += m.len() as i64;
+            }
+        }
+        Ok((files, bytes))
+    }
+
+    /// 清理对应 blob 已不存在的视图文件：
+    /// - files/<sha256>.<ext>
+    /// - downloads/<transfer_id>/（按 <transfer_id>.blob 记录的 sha256 判断；
+    ///   没有记录的旧目录超过 max_age 后清理）
+    ///
+    /// 返回 (文件数, 字节数)
+    pub fn sweep_orphan_views(&self, max_age: Duration) -> anyhow::Result<(i64, i64)> {
+        let mut files = 0;
+        let mut bytes = 0;
+
+        let views_dir = self.cache_dir.join("files");
+        if views_dir.exists() {
+            for e in fs::read_dir(&views_dir)? {
+                let e = e?;
+                let m = e.metadata()?;
+                let name = e.file_name().to_string_lossy().into_owned();
+                let sha = name.split('.').next().unwrap_or("");
+                if m.is_file() && (sha.len() != 64 || !self.blob_exists(sha)) {
+                    fs::remove_file(e.path())?;
+                    files += 1;
+                    bytes += m.len() as i64;
+                }
+            }
+        }
+
+        let downloads_dir = self.cache_dir.join("downloads");
+        if downloads_dir.exists() {
+            for e in fs::read_dir(&downloads_dir)? {
+                let e = e?;
+                let m = e.metadata()?;
+                let name = e.file_name().to_string_lossy().into_owned();
+                if !m.is_dir() {
+                    // 目录已不存在的残留记录
+                    if let Some(transfer_id) = name.strip_suffix(".blob") {
+                        if !downloads_dir.join(transfer_id).exists() {
+                            let _ = fs::remove_file(e.path());
+                        }
+                    }
+                    continue;
+                }
+                // 所引用的 blob 全部不存在才算孤儿；没有记录任何 blob 的（如只有空目录的树）按时间判断
+                let marker = self.download_marker_path(&name);
+                let orphan = match fs::read_to_string(&marker) {
+                    Ok(content) => {
+                        let mut shas = content.lines().map(str::trim).filter(|l| !l.is_empty()).peekable();
+                        if shas.peek().is_none() {
+                            is_older_than(&m, max_age)
+                        } else {
+                            shas.all(|sha| sha.len() != 64 || !self.blob_exists(sha))
+                        }
+                    }
+                    Err(_) => is_older_than(&m, max_age),
+                };
+                if !orphan {
+                    continue;
+                }
+                let (n, b) = dir_usage(&e.path())?;
+                files += n;
+                bytes += b;
+                fs::remove_dir_all(e.path())?;
+                let _ = fs::remove_file(&marker);
+            }
+        }
+
+        Ok((files, bytes))
+    }
+
+    /// 流式写入：把 reader 的内容复制到 tmp/<tmp_name>，同时计算 sha256，内存占用固定为一个缓冲区。
+    /// 超过 max_bytes 时中止并删除临时文件（错误码 ITEM_TOO_LARGE）。
+    /// 返回 (临时文件路径, sha256, 字节数)，之后用 commit_tmp_file 转正。
+    pub fn write_tmp_from_reader(
+        &self,
+        mut reader: impl std::io::Read,
+        tmp_name: &str,
+        max_bytes: i64,
+    ) -> anyhow::Result<(PathBuf, String, i64)> {
+        let tmp_path = self.tmp_dir.join(tmp_name);
+        let res = (|| -> anyhow::Result<(String, i64)> {
+            let mut file = std::io::BufWriter::new(fs::File::create(&tmp_path)?);
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut total = 0i64;
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                total += n as i64;
+                if total > max_bytes {
+                    anyhow::bail!("ITEM_TOO_LARGE");
+                }
+                hasher.update(&buf[..n]);
+                file.write_all(&buf[..n])?;
+            }
+            file.flush()?;
+            Ok((hex::encode(hasher.finalize()), total))
+        })();
+
+        match res {
+            Ok((sha, total)) => Ok((tmp_path, sha, total)),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                Err(e)
+            }
+        }
+    }
+
     /// 辅助方法：直接将内存数据写入 Blob，返回 sha256
     pub fn put_blob(&self, data: &[u8]) -> anyhow::Result<String> {
         // 1. 计算 Hash
@@ -273,6 +724,16 @@ impl Cas {
             }
         }
 
+        // 删除未完成的续传数据
+        if self.partial_dir.exists() {
+            for entry in fs::read_dir(&self.partial_dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+
         // 删除 files 目录（materialize_blob 创建的视图文件）
         let files_dir = self.cache_dir.join("files");
         if files_dir.exists() {
@@ -285,6 +746,12 @@ impl Cas {
             }
         }
 
+        // 删除隔离的损坏 blob
+        let quarantine_dir = self.cache_dir.join("quarantine");
+        if quarantine_dir.exists() {
+            fs::remove_dir_all(&quarantine_dir)?;
+        }
+
         // 删除 downloads 目录（materialize_file 创建的文件）
         let downloads_dir = self.cache_dir.join("downloads");
         if downloads_dir.exists() {
@@ -310,3 +777,89 @@ impl Cas {
         Ok(())
     }
 }
+
+/// 把对端提供的相对路径（`/` 或 `\` 分隔）转换为安全的相对 PathBuf。
+/// 拒绝空路径、绝对路径、`.` / `..` 分量、盘符或 NTFS 流（含 `:`）以及 NUL，
+/// 保证拼接到任意目录之后都不会逃逸到该目录之外。
+pub fn safe_rel_path(rel: &str) -> anyhow::Result<PathBuf> {
+    if rel.starts_with(['/', '\\']) {
+        anyhow::bail!("INVALID_REL_PATH: {}", rel);
+    }
+    let mut out = PathBuf::new();
+    for part in rel.split(['/', '\\']).filter(|p| !p.is_empty()) {
+        if part == "." || part == ".." || part.contains([':', '\0']) {
+            anyhow::bail!("INVALID_REL_PATH: {}", rel);
+        }
+        out.push(part);
+    }
+    if out.as_os_str().is_empty() {
+        anyhow::bail!("INVALID_REL_PATH: {}", rel);
+    }
+    Ok(out)
+}
+
+/// 还原权限位后的实际模式：总保留属主读写（目录另加执行），保证缓存目录之后仍可清理
+#[cfg(unix)]
+fn effective_mode(mode: u32, is_dir: bool) -> u32 {
+    (mode & 0o7777) | if is_dir { 0o700 } else { 0o600 }
+}
+
+/// 需要还原的权限与 blob 当前权限不同（硬链接共享 inode，不能直接改）
+fn mode_differs(blob_path: &Path, attrs: &FileAttrs) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let (Some(mode), Ok(m)) = (attrs.mode, fs::metadata(blob_path)) {
+            return m.permissions().mode() & 0o7777 != effective_mode(mode, false);
+        }
+    }
+    let _ = (blob_path, attrs);
+    false
+}
+
+/// 尽力还原修改时间与权限（失败不影响内容本身）；先改时间，再改权限
+fn apply_attrs(path: &Path, attrs: &FileAttrs, is_dir: bool) {
+    if let Some(ms) = attrs.mtime_ms.filter(|ms| *ms >= 0) {
+        let t = std::time::UNIX_EPOCH + Duration::from_millis(ms as u64);
+        let file = if is_dir {
+            fs::File::open(path)
+        } else {
+            fs::OpenOptions::new().write(true).open(path)
+        };
+        if let Ok(f) = file {
+            let _ = f.set_modified(t);
+        }
+    }
+    #[cfg(unix)]
+    if let Some(mode) = attrs.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(effective_mode(mode, is_dir)));
+    }
+}
+
+/// 递归统计目录下的文件数与字节数（不跟随符号链接）
+fn dir_usage(dir: &Path) -> anyhow::Result<(i64, i64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    for e in fs::read_dir(dir)? {
+        let e = e?;
+        let m = fs::symlink_metadata(e.path())?;
+        if m.is_dir() {
+            let (n, b) = dir_usage(&e.path())?;
+            files += n;
+            bytes += b;
+        } else {
+            files += 1;
+            bytes += m.len() as i64;
+        }
+    }
+    Ok((files, bytes))
+}
+
+/// 文件最后修改时间是否早于 max_age 之前（取不到修改时间时视为否）
+fn is_older_than(m: &fs::Metadata, max_age: Duration) -> bool {
+    m.modified()
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > max_age)
+}
@@ -1,25 +1,66 @@
+use std::path::PathBuf;
+
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 
 use crate::model::{FileMeta, ItemContent, ItemKind, ItemMeta, ItemPreview};
 use crate::util::{sha256_hex, truncate_chars};
-use crate::policy::{SizeLimits, MetaStrategy, PolicyOutcome, decide};
+use crate::policy::{ExpiryPolicy, SizeLimits, MetaStrategy, PolicyOutcome, decide};
 
 const FILELIST_MIME: &str = "application/x-clipbridge-filelist+json";
+const HTML_MIME: &str = "text/html";
+const RTF_MIME: &str = "text/rtf";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClipboardFileEntry {
+    /// 相对路径，多级目录以 `/` 分隔
     pub rel_name: String,
 	#[serde(default)]
 	pub abs_path: Option<String>,
     pub size_bytes: i64,
     pub sha256: Option<String>,
+	/// 目录条目；abs_path 指向本地目录时，规划摄入前会展开其中的全部子目录与文件
+	#[serde(default)]
+	pub is_dir: bool,
+	#[serde(default)]
+	pub mode: Option<u32>,
+	#[serde(default)]
+	pub mtime_ms: Option<i64>,
 }
 
 pub enum ClipboardSnapshot {
     Text { text_utf8: String, ts_ms: i64 },
     Image { bytes: Vec<u8>, mime: String, ts_ms: i64 },
     FileList { files: Vec<ClipboardFileEntry>, ts_ms: i64 },
+    /// 富文本：html / rtf 至少提供一个（同时提供时以 html 为主表示）；
+    /// plain_text 为壳侧读到的纯文本，缺省时由核心从标记中提取
+    RichText { html: Option<String>, rtf: Option<String>, plain_text: Option<String>, ts_ms: i64 },
+    /// 同一次复制的多种格式：primary 决定 item 类型与预览，extras 作为额外表示一并入库
+    Multi { primary: Box<ClipboardSnapshot>, extras: Vec<ClipboardRepresentation> },
+}
+
+/// 剪贴板中的一种额外格式（原始字节）
+#[derive(Clone, Debug)]
+pub struct ClipboardRepresentation {
+    pub mime: String,
+    pub bytes: Vec<u8>,
+}
+
+impl ClipboardSnapshot {
+    /// 展开 Multi，返回真正决定 item 类型的主快照
+    pub fn primary(&self) -> &ClipboardSnapshot {
+        match self {
+            ClipboardSnapshot::Multi { primary, .. } => primary.primary(),
+            other => other,
+        }
+    }
+
+    fn collect_extras<'a>(&'a self, out: &mut Vec<&'a ClipboardRepresentation>) {
+        if let ClipboardSnapshot::Multi { primary, extras } = self {
+            primary.collect_extras(out);
+            out.extend(extras.iter());
+        }
+    }
 }
 
 pub struct LocalIngestDeps<'a> {
@@ -34,6 +75,17 @@ pub struct IngestPlan {
     pub strategy: MetaStrategy,
     pub needs_user_confirm: bool,
     pub content_bytes: Vec<u8>, // 真正要写入 CAS 的字节（FileList 写的是 manifest JSON）
+    pub fallback_bytes: Option<Vec<u8>>, // RichText 的纯文本降级内容，同样写入 CAS
+    pub representation_bytes: Vec<Vec<u8>>, // 与 meta.representations 一一对应
+    pub streamed_blob: Option<StreamedBlob>, // 流式摄入：已写入 CAS 临时文件、待转正的正文
+}
+
+/// 流式摄入时已写入 CAS 临时文件的内容（见 Cas::write_tmp_from_reader）
+#[derive(Clone, Debug)]
+pub struct StreamedBlob {
+    pub tmp_path: PathBuf,
+    pub sha256: String,
+    pub total_bytes: i64,
 }
 
 fn snapshot_content_bytes(snap: &ClipboardSnapshot) -> Vec<u8> {
@@ -44,7 +96,301 @@ fn snapshot_content_bytes(snap: &ClipboardSnapshot) -> Vec<u8> {
             // 稳定序列化：把 file list 当成“manifest”，CAS 去重的是 manifest
             serde_json::to_vec(files).unwrap_or_else(|_| b"[]".to_vec())
         }
+        ClipboardSnapshot::RichText { html, rtf, .. } => {
+            html.as_deref().or(rtf.as_deref()).unwrap_or("").as_bytes().to_vec()
+        }
+        ClipboardSnapshot::Multi { primary, .. } => snapshot_content_bytes(primary),
+    }
+}
+
+/// 根据 MIME 推断额外格式适用的大小限制类别
+fn kind_for_mime(mime: &str) -> ItemKind {
+    let m = mime.to_ascii_lowercase();
+    if m.starts_with("image/") {
+        ItemKind::Image
+    } else if m == HTML_MIME || m == RTF_MIME {
+        ItemKind::RichText
+    } else if m.starts_with("text/") {
+        ItemKind::Text
+    } else {
+        ItemKind::FileList
+    }
+}
+
+/// Multi 的额外格式：跳过与主格式（及已出现过的）同 MIME 的重复项
+fn extra_representations<'a>(snap: &'a ClipboardSnapshot, primary_mime: &str) -> Vec<(ItemContent, &'a [u8])> {
+    let mut extras = Vec::new();
+    snap.collect_extras(&mut extras);
+
+    let mut seen: Vec<String> = vec![primary_mime.to_ascii_lowercase()];
+    let mut out = Vec::new();
+    for r in extras {
+        let mime = r.mime.to_ascii_lowercase();
+        if seen.contains(&mime) {
+            continue;
+        }
+        seen.push(mime);
+        out.push((
+            ItemContent { mime: r.mime.clone(), sha256: sha256_hex(&r.bytes), total_bytes: r.bytes.len() as i64 },
+            r.bytes.as_slice(),
+        ));
+    }
+    out
+}
+
+/// RichText 的纯文本表示：优先用壳侧给的，否则从 html / rtf 中提取
+fn rich_text_plain(html: &Option<String>, rtf: &Option<String>, plain_text: &Option<String>) -> String {
+    if let Some(p) = plain_text {
+        return p.clone();
+    }
+    if let Some(h) = html {
+        return html_to_plain_text(h);
+    }
+    rtf.as_deref().map(rtf_to_plain_text).unwrap_or_default()
+}
+
+fn push_char_collapsed(out: &mut String, c: char) {
+    if c.is_whitespace() {
+        if !out.is_empty() && !out.ends_with([' ', '\n', '\t']) {
+            out.push(' ');
+        }
+    } else {
+        out.push(c);
+    }
+}
+
+fn decode_html_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let num = name.strip_prefix('#')?;
+            let code = match num.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => num.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// 从 HTML 提取纯文本（用于预览与纯文本降级）
+///
+/// 不追求完整的 HTML 解析：去掉标签与 script/style 内容，块级标签换行，
+/// 单元格用 tab 分隔，解码常见实体，合并连续空白。
+pub fn html_to_plain_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => {
+                // 未闭合的残缺标签直接丢弃
+                let Some(end) = rest.find('>') else { break };
+                let tag = rest[1..end].trim().to_ascii_lowercase();
+                rest = &rest[end + 1..];
+
+                let closing = tag.starts_with('/');
+                let name: String = tag
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect();
+
+                if !closing && (name == "script" || name == "style") {
+                    // 跳过整个 script/style 内容
+                    let close = format!("</{}", name);
+                    match rest.to_ascii_lowercase().find(&close) {
+                        Some(pos) => {
+                            rest = &rest[pos..];
+                            if let Some(gt) = rest.find('>') {
+                                rest = &rest[gt + 1..];
+                            }
+                        }
+                        None => rest = "",
+                    }
+                    continue;
+                }
+
+                match name.as_str() {
+                    "br" | "p" | "div" | "tr" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "table" => {
+                        let trimmed = out.trim_end_matches(' ').len();
+                        out.truncate(trimmed);
+                        if !out.is_empty() && !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                    }
+                    "td" | "th" if closing => {
+                        let trimmed = out.trim_end_matches(' ').len();
+                        out.truncate(trimmed);
+                        out.push('\t');
+                    }
+                    _ => {}
+                }
+            }
+            '&' => {
+                let decoded = rest[1..]
+                    .find(';')
+                    .filter(|&pos| pos <= 10)
+                    .and_then(|pos| decode_html_entity(&rest[1..pos + 1]).map(|ch| (ch, pos + 2)));
+                match decoded {
+                    Some((ch, consumed)) => {
+                        push_char_collapsed(&mut out, ch);
+                        rest = &rest[consumed..];
+                    }
+                    None => {
+                        out.push('&');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            _ => {
+                push_char_collapsed(&mut out, c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    out.lines().map(|l| l.trim_end()).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// 从 RTF 提取纯文本（用于预览与纯文本降级）
+///
+/// 只处理常见控制字：\par / \line / \tab / \'hh / \uN，
+/// 并跳过 fonttbl、colortbl、stylesheet、info、pict 以及 {\* ...} 目标组。
+pub fn rtf_to_plain_text(rtf: &str) -> String {
+    const SKIP_DESTS: [&str; 6] = ["fonttbl", "colortbl", "stylesheet", "info", "pict", "header"];
+
+    let chars: Vec<char> = rtf.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    // 每层 group 是否处于被跳过的目标组中
+    let mut skip_stack: Vec<bool> = vec![false];
+    // \uN 之后需要跳过的替代字符数
+    let mut pending_skip = 0usize;
+
+    while i < chars.len() {
+        let skipping = *skip_stack.last().unwrap_or(&false);
+        match chars[i] {
+            '{' => {
+                skip_stack.push(skipping);
+                i += 1;
+                if chars.get(i) == Some(&'\\') && chars.get(i + 1) == Some(&'*') {
+                    if let Some(top) = skip_stack.last_mut() {
+                        *top = true;
+                    }
+                    i += 2;
+                }
+            }
+            '}' => {
+                if skip_stack.len() > 1 {
+                    skip_stack.pop();
+                }
+                i += 1;
+            }
+            '\\' => {
+                i += 1;
+                let Some(&next) = chars.get(i) else { break };
+                if next == '\\' || next == '{' || next == '}' {
+                    if !skipping {
+                        out.push(next);
+                    }
+                    i += 1;
+                    continue;
+                }
+                if next == '\'' {
+                    let hex: String = chars.iter().skip(i + 1).take(2).collect();
+                    i += 3;
+                    if pending_skip > 0 {
+                        pending_skip -= 1;
+                    } else if !skipping {
+                        if let Ok(b) = u8::from_str_radix(&hex, 16) {
+                            out.push(b as char);
+                        }
+                    }
+                    continue;
+                }
+                if !next.is_ascii_alphabetic() {
+                    // 控制符号（如 \~ \- \_），只保留不换行空格
+                    if next == '~' && !skipping {
+                        out.push(' ');
+                    }
+                    i += 1;
+                    continue;
+                }
+
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let num_start = i;
+                if i < chars.len() && chars[i] == '-' {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let param: Option<i32> = chars[num_start..i].iter().collect::<String>().parse().ok();
+                if chars.get(i) == Some(&' ') {
+                    i += 1;
+                }
+
+                if SKIP_DESTS.contains(&word.as_str()) {
+                    if let Some(top) = skip_stack.last_mut() {
+                        *top = true;
+                    }
+                    continue;
+                }
+                if skipping {
+                    continue;
+                }
+                match word.as_str() {
+                    "par" | "line" | "row" => out.push('\n'),
+                    "tab" | "cell" => out.push('\t'),
+                    "u" => {
+                        if let Some(code) = param {
+                            let code = if code < 0 { code + 65536 } else { code } as u32;
+                            if let Some(ch) = char::from_u32(code) {
+                                out.push(ch);
+                            }
+                            pending_skip = 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            '\r' | '\n' => i += 1,
+            c => {
+                if pending_skip > 0 {
+                    pending_skip -= 1;
+                } else if !skipping {
+                    out.push(c);
+                }
+                i += 1;
+            }
+        }
+    }
+
+    out.lines().map(|l| l.trim_end()).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// 文本类内容转为可供全文检索的纯文本；非文本 MIME 返回 None
+pub fn searchable_text(mime: &str, bytes: &[u8]) -> Option<String> {
+    let m = mime.to_ascii_lowercase();
+    if !m.starts_with("text/") {
+        return None;
     }
+    let text = String::from_utf8_lossy(bytes);
+    Some(match m.as_str() {
+        HTML_MIME => html_to_plain_text(&text),
+        RTF_MIME => rtf_to_plain_text(&text),
+        _ => text.into_owned(),
+    })
 }
 
 pub fn build_item_meta(deps: &LocalIngestDeps<'_>, snap: &ClipboardSnapshot) -> ItemMeta {
@@ -67,7 +413,10 @@ pub fn build_item_meta(deps: &LocalIngestDeps<'_>, snap: &ClipboardSnapshot) ->
                 preview: ItemPreview { text: Some(preview), ..Default::default() },
                 content: ItemContent { mime: "text/plain".to_string(), sha256: sha, total_bytes: bytes.len() as i64 },
                 files: vec![],
-                expires_ts_ms: Some(*ts_ms + 7 * 24 * 3600 * 1000),
+                expires_ts_ms: None, // 由 make_ingest_plan 按 ExpiryPolicy 填写
+                text_fallback: None,
+                representations: vec![],
+                pinned: false,
             }
         }
         ClipboardSnapshot::Image { bytes, mime, ts_ms } => {
@@ -85,7 +434,10 @@ pub fn build_item_meta(deps: &LocalIngestDeps<'_>, snap: &ClipboardSnapshot) ->
                 preview: ItemPreview::default(),
                 content: ItemContent { mime: mime.clone(), sha256: sha, total_bytes: bytes.len() as i64 },
                 files: vec![],
-                expires_ts_ms: Some(*ts_ms + 7 * 24 * 3600 * 1000),
+                expires_ts_ms: None, // 由 make_ingest_plan 按 ExpiryPolicy 填写
+                text_fallback: None,
+                representations: vec![],
+                pinned: false,
             }
         }
         ClipboardSnapshot::FileList { files, ts_ms } => {
@@ -100,6 +452,10 @@ pub fn build_item_meta(deps: &LocalIngestDeps<'_>, snap: &ClipboardSnapshot) ->
 				size_bytes: f.size_bytes,
 				sha256: f.sha256.clone(),
 				local_path: f.abs_path.clone(),
+				local_mtime_ms: None,
+				is_dir: f.is_dir,
+				mode: f.mode,
+				mtime_ms: f.mtime_ms,
 			}).collect();
 
             ItemMeta {
@@ -110,12 +466,52 @@ pub fn build_item_meta(deps: &LocalIngestDeps<'_>, snap: &ClipboardSnapshot) ->
                 source_device_id: deps.device_id.to_string(),
                 source_device_name: Some(deps.device_name.to_string()),
                 size_bytes: total_files_bytes, // 注意：FileList 的 size_bytes 是“文件总大小”
-                preview: ItemPreview { file_count: Some(metas.len() as u32), ..Default::default() },
+                preview: ItemPreview { file_count: Some(metas.iter().filter(|f| !f.is_dir).count() as u32), ..Default::default() },
                 content: ItemContent { mime: FILELIST_MIME.to_string(), sha256: sha, total_bytes: manifest.len() as i64 },
                 files: metas,
-                expires_ts_ms: Some(*ts_ms + 7 * 24 * 3600 * 1000),
+                expires_ts_ms: None, // 由 make_ingest_plan 按 ExpiryPolicy 填写
+                text_fallback: None,
+                representations: vec![],
+                pinned: false,
             }
         }
+        ClipboardSnapshot::RichText { html, rtf, plain_text, ts_ms } => {
+            let bytes = snapshot_content_bytes(snap);
+            let sha = sha256_hex(&bytes);
+            let mime = if html.is_some() { HTML_MIME } else { RTF_MIME };
+
+            let plain = rich_text_plain(html, rtf, plain_text);
+            let preview = truncate_chars(&plain, 300);
+
+            ItemMeta {
+                ty: "ItemMeta".to_string(),
+                item_id,
+                kind: ItemKind::RichText,
+                created_ts_ms: *ts_ms,
+                source_device_id: deps.device_id.to_string(),
+                source_device_name: Some(deps.device_name.to_string()),
+                size_bytes: bytes.len() as i64,
+                preview: ItemPreview { text: Some(preview), ..Default::default() },
+                content: ItemContent { mime: mime.to_string(), sha256: sha, total_bytes: bytes.len() as i64 },
+                files: vec![],
+                expires_ts_ms: None, // 由 make_ingest_plan 按 ExpiryPolicy 填写
+                text_fallback: Some(ItemContent {
+                    mime: "text/plain".to_string(),
+                    sha256: sha256_hex(plain.as_bytes()),
+                    total_bytes: plain.len() as i64,
+                }),
+                representations: vec![],
+                pinned: false,
+            }
+        }
+        ClipboardSnapshot::Multi { primary, .. } => {
+            let mut meta = build_item_meta(deps, primary);
+            meta.representations = extra_representations(snap, &meta.content.mime)
+                .into_iter()
+                .map(|(c, _)| c)
+                .collect();
+            meta
+        }
     }
 }
 
@@ -123,9 +519,26 @@ pub fn make_ingest_plan(
 	deps: &LocalIngestDeps<'_>,
 	snap: &ClipboardSnapshot,
 	limits: &SizeLimits,
+	expiry: &ExpiryPolicy,
 	force: bool,
 ) -> anyhow::Result<IngestPlan> {
-    let meta = build_item_meta(deps, snap);
+    if let ClipboardSnapshot::RichText { html: None, rtf: None, .. } = snap.primary() {
+        anyhow::bail!("RICH_TEXT_EMPTY");
+    }
+    if let ClipboardSnapshot::FileList { files, .. } = snap.primary() {
+        for f in files {
+            crate::cas::safe_rel_path(&f.rel_name)?;
+        }
+    }
+
+    let mut meta = build_item_meta(deps, snap);
+    meta.expires_ts_ms = expiry.expires_at(&meta.kind, meta.created_ts_ms);
+    // 记下复制时刻源文件的修改时间（大小已在快照里）：之后补算哈希、发送内容时都以此为准
+    for f in meta.files.iter_mut().filter(|f| !f.is_dir) {
+        if let Some(md) = f.local_path.as_deref().and_then(|p| std::fs::metadata(p).ok()) {
+            f.local_mtime_ms = crate::util::file_mtime_ms(&md);
+        }
+    }
 
     let outcome = decide(meta.kind.clone(), meta.size_bytes, force, limits);
     let (strategy, needs_user_confirm) = match outcome {
@@ -133,7 +546,182 @@ pub fn make_ingest_plan(
         PolicyOutcome::Allowed { strategy, needs_user_confirm } => (strategy, needs_user_confirm),
     };
 
+    // 额外格式超出其类别硬限制时单独丢弃，不影响主格式入库
+    let mut representations = Vec::new();
+    let mut representation_bytes = Vec::new();
+    for (content, bytes) in extra_representations(snap, &meta.content.mime) {
+        let kind = kind_for_mime(&content.mime);
+        if let PolicyOutcome::RejectedHardCap { .. } = decide(kind, content.total_bytes, force, limits) {
+            continue;
+        }
+        representations.push(content);
+        representation_bytes.push(bytes.to_vec());
+    }
+    meta.representations = representations;
+
     let content_bytes = snapshot_content_bytes(snap);
+    let fallback_bytes = match snap.primary() {
+        ClipboardSnapshot::RichText { html, rtf, plain_text, .. } => {
+            Some(rich_text_plain(html, rtf, plain_text).into_bytes())
+        }
+        _ => None,
+    };
+
+    Ok(IngestPlan {
+        meta,
+        strategy,
+        needs_user_confirm,
+        content_bytes,
+        fallback_bytes,
+        representation_bytes,
+        streamed_blob: None,
+    })
+}
 
-    Ok(IngestPlan { meta, strategy, needs_user_confirm, content_bytes })
+/// 展开 FileList 中的目录条目：abs_path 指向本地目录时递归列出其中的子目录与文件，
+/// rel_name 以该目录的 rel_name 为前缀；符号链接跳过，已列出的同名条目不重复添加。
+/// 缺少权限位 / 修改时间的条目从本地文件补齐。
+pub fn expand_dir_entries(files: &[ClipboardFileEntry]) -> anyhow::Result<Vec<ClipboardFileEntry>> {
+    fn fill_attrs(e: &mut ClipboardFileEntry, md: &std::fs::Metadata) {
+        #[cfg(unix)]
+        if e.mode.is_none() {
+            use std::os::unix::fs::PermissionsExt;
+            e.mode = Some(md.permissions().mode() & 0o7777);
+        }
+        if e.mtime_ms.is_none() {
+            e.mtime_ms = crate::util::file_mtime_ms(md);
+        }
+    }
+
+    fn walk(dir: &std::path::Path, prefix: &str, out: &mut Vec<ClipboardFileEntry>) -> anyhow::Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+        children.sort_by_key(|e| e.file_name());
+        for child in children {
+            let md = std::fs::symlink_metadata(child.path())?;
+            if md.file_type().is_symlink() {
+                continue;
+            }
+            let mut entry = ClipboardFileEntry {
+                rel_name: format!("{}/{}", prefix, child.file_name().to_string_lossy()),
+                abs_path: Some(child.path().to_string_lossy().into_owned()),
+                size_bytes: if md.is_dir() { 0 } else { md.len() as i64 },
+                sha256: None,
+                is_dir: md.is_dir(),
+                mode: None,
+                mtime_ms: None,
+            };
+            fill_attrs(&mut entry, &md);
+            let rel = entry.rel_name.clone();
+            out.push(entry);
+            if md.is_dir() {
+                walk(&child.path(), &rel, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut out: Vec<ClipboardFileEntry> = Vec::with_capacity(files.len());
+    for f in files {
+        let mut entry = f.clone();
+        let md = entry.abs_path.as_deref().and_then(|p| std::fs::metadata(p).ok());
+        if let Some(md) = &md {
+            entry.is_dir |= md.is_dir();
+            fill_attrs(&mut entry, md);
+        }
+        if entry.is_dir {
+            entry.size_bytes = 0;
+        }
+        let expand = entry.is_dir && md.is_some();
+        entry.rel_name = entry.rel_name.trim_end_matches(['/', '\\']).to_string();
+        let rel = entry.rel_name.clone();
+        out.push(entry);
+        if expand {
+            walk(std::path::Path::new(f.abs_path.as_deref().unwrap_or_default()), &rel, &mut out)?;
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    out.retain(|e| seen.insert(e.rel_name.clone()));
+    Ok(out)
+}
+
+/// 流式摄入时按图片入库的 MIME（image/*）
+pub fn is_image_mime(mime: &str) -> bool {
+    mime.get(..6).is_some_and(|p| p.eq_ignore_ascii_case("image/")) && mime.len() > 6
+}
+
+/// 流式摄入的图片：正文已在 CAS 临时文件中，content_bytes 留空
+pub fn make_streamed_image_plan(
+	deps: &LocalIngestDeps<'_>,
+	mime: &str,
+	blob: StreamedBlob,
+	ts_ms: i64,
+	limits: &SizeLimits,
+	expiry: &ExpiryPolicy,
+	force: bool,
+) -> anyhow::Result<IngestPlan> {
+    let meta = ItemMeta {
+        ty: "ItemMeta".to_string(),
+        item_id: Uuid::new_v4().to_string(),
+        kind: ItemKind::Image,
+        created_ts_ms: ts_ms,
+        source_device_id: deps.device_id.to_string(),
+        source_device_name: Some(deps.device_name.to_string()),
+        size_bytes: blob.total_bytes,
+        preview: ItemPreview::default(),
+        content: ItemContent { mime: mime.to_string(), sha256: blob.sha256.clone(), total_bytes: blob.total_bytes },
+        files: vec![],
+        expires_ts_ms: expiry.expires_at(&ItemKind::Image, ts_ms),
+        text_fallback: None,
+        representations: vec![],
+        pinned: false,
+    };
+
+    let (strategy, needs_user_confirm) = match decide(ItemKind::Image, meta.size_bytes, force, limits) {
+        PolicyOutcome::RejectedHardCap { code } => anyhow::bail!(code),
+        PolicyOutcome::Allowed { strategy, needs_user_confirm } => (strategy, needs_user_confirm),
+    };
+
+    Ok(IngestPlan {
+        meta,
+        strategy,
+        needs_user_confirm,
+        content_bytes: vec![],
+        fallback_bytes: None,
+        representation_bytes: vec![],
+        streamed_blob: Some(blob),
+    })
+}
+
+/// 流式摄入的单个文件：按单文件 FileList 入库（content_bytes 为 manifest），
+/// 文件的 sha256 已知，正文在 CAS 临时文件中
+pub fn make_streamed_file_plan(
+	deps: &LocalIngestDeps<'_>,
+	path: &std::path::Path,
+	blob: StreamedBlob,
+	ts_ms: i64,
+	limits: &SizeLimits,
+	expiry: &ExpiryPolicy,
+	force: bool,
+) -> anyhow::Result<IngestPlan> {
+    let rel_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    let abs_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let snap = ClipboardSnapshot::FileList {
+        files: vec![ClipboardFileEntry {
+            rel_name,
+            abs_path: Some(abs_path.to_string_lossy().into_owned()),
+            size_bytes: blob.total_bytes,
+            sha256: Some(blob.sha256.clone()),
+            is_dir: false,
+            mode: None,
+            mtime_ms: None,
+        }],
+        ts_ms,
+    };
+    let mut plan = make_ingest_plan(deps, &snap, limits, expiry, force)?;
+    plan.streamed_blob = Some(blob);
+    Ok(plan)
 }
@@ -1,10 +1,15 @@
 // cb_core/src/discovery/mod.rs
 
-use mdns_sd::{ResolvedService, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use mdns_sd::{IfKind, ResolvedService, ScopedIp, ServiceDaemon, ServiceEvent, ServiceInfo};
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task;
 
+use crate::transport::BoundAddr;
+
 /// 文档 3.4.2.1 定义的服务类型 (QUIC)
 const SERVICE_TYPE: &str = "_clipbridge._udp.local.";
 
@@ -23,12 +28,15 @@ pub struct PeerCandidate {
     pub device_id: String,
     pub addrs: Vec<String>, // "ip:port"
     pub capabilities: Vec<String>,
+    /// 设备显示名（TXT "name"）；旧版本不宣告
+    #[serde(default)]
+    pub device_name: Option<String>,
     // last_seen 不需要在此传输，接收方收到即视为当前在线
 }
 
 pub struct DiscoveryService {
-    /// 用于停止 mDNS 任务
-    shutdown_tx: mpsc::Sender<()>,
+    /// 用于停止 mDNS 任务，任务发完注销宣告后回复
+    shutdown_tx: mpsc::Sender<oneshot::Sender<()>>,
 }
 
 
@@ -55,22 +63,13 @@ fn parse_peer_candidate(info: &Box<ResolvedService>, local_account_uid: &str, lo
     let addrs: Vec<String> = info
         .get_addresses()
         .iter()
-        .map(|ip| {
-            // 1. 获取原始字符串
-            // mdns-sd 的 to_string() 对于 IPv6 Link-Local 会自动带上 %scope_id
-            // 例如: "fe80::1234:5678%en0" 或 "192.168.1.5"
-            let ip_str = ip.to_string();
-
-            // 2. 根据是否包含冒号判断 IPv6
-            if ip_str.contains(':') {
-                // IPv6: 必须加上方括号 [ ]
-                // 结果示例: "[fe80::1234:5678%en0]:8080"
-                format!("[{}]:{}", ip_str, port)
-            } else {
-                // IPv4: 直接拼接
-                // 结果示例: "192.168.1.5:8080"
-                format!("{}:{}", ip_str, port)
-            }
+        .map(|ip| match ip {
+            // IPv6 必须加上方括号；链路本地地址带上网卡序号（"%en0" 这种网卡名 SocketAddr 解析不了）
+            // 结果示例: "[fe80::1234:5678%3]:8080"
+            ScopedIp::V6(v6) if v6.scope_id().index != 0 => format!("[{}%{}]:{}", v6.addr(), v6.scope_id().index, port),
+            ScopedIp::V6(v6) => format!("[{}]:{}", v6.addr(), port),
+            // IPv4: 直接拼接，结果示例: "192.168.1.5:8080"
+            ip => format!("{}:{}", ip.to_ip_addr(), port),
         })
         .collect();
 
@@ -85,21 +84,63 @@ fn parse_peer_candidate(info: &Box<ResolvedService>, local_account_uid: &str, lo
         .filter(|s| !s.is_empty())
         .collect();
 
+    let device_name = info.get_property_val_str("name").filter(|n| !n.is_empty()).map(str::to_string);
+
     Some(PeerCandidate {
         device_id: peer_did.to_string(),
         addrs,
         capabilities,
+        device_name,
     })
 }
 
+/// 要宣告的地址：绑定到通配地址时交给 mDNS 按网卡自动填写（只限已绑定的地址族），
+/// 绑定到具体地址时原样宣告
+#[derive(Debug, PartialEq)]
+struct AdvertisePlan {
+    addrs: Vec<IpAddr>,
+    auto_v4: bool,
+    auto_v6: bool,
+}
+
+impl AdvertisePlan {
+    fn from_bound(bound: &[BoundAddr]) -> Self {
+        let mut plan = Self { addrs: Vec::new(), auto_v4: false, auto_v6: false };
+        for b in bound {
+            let ip = b.addr.ip();
+            if !ip.is_unspecified() {
+                if !plan.addrs.contains(&ip) {
+                    plan.addrs.push(ip);
+                }
+            } else if ip.is_ipv4() {
+                plan.auto_v4 = true;
+            } else {
+                plan.auto_v6 = true;
+                plan.auto_v4 |= b.dual_stack;
+            }
+        }
+        plan
+    }
+
+    fn uses_v4(&self) -> bool {
+        self.auto_v4 || self.addrs.iter().any(|a| a.is_ipv4())
+    }
+
+    fn uses_v6(&self) -> bool {
+        self.auto_v6 || self.addrs.iter().any(|a| a.is_ipv6())
+    }
+}
+
 impl DiscoveryService {
     /// 启动发现服务 (发布 + 监听)
     ///
     /// - `cfg`: 本机配置 (用于发布自己的 info)
+    /// - `port` / `bound`: 传输层实际监听的端口与地址，只宣告这些
     /// - `event_tx`: 发送发现结果的通道
     pub fn spawn(
         cfg: crate::api::CoreConfig,
-        port: u16, // <--- 新增
+        port: u16,
+        bound: &[BoundAddr],
         event_tx: mpsc::Sender<DiscoveryEvent>,
     ) -> anyhow::Result<Self> {
         let mdns = ServiceDaemon::new()?;
@@ -107,26 +148,41 @@ impl DiscoveryService {
         let hostname = format!("{}.local.", cfg.device_id);
 
 
+        // TXT 单项不能超过 255 字节
+        let device_name: String = cfg.device_name.chars().take(60).collect();
         let properties = [
             ("acct", cfg.account_uid.as_str()),
             ("did", cfg.device_id.as_str()),
             ("proto", "1"),
             ("cap", "txt,img,file"),
+            ("name", device_name.as_str()),
         ];
 
-        let my_service = ServiceInfo::new(
+        let plan = AdvertisePlan::from_bound(bound);
+        let mut my_service = ServiceInfo::new(
             SERVICE_TYPE,
             &cfg.device_id,
             &hostname,
-            "",
+            &plan.addrs[..],
             port,
             &properties[..],
-        )?.enable_addr_auto();
-
+        )?;
+        if plan.auto_v4 || plan.auto_v6 {
+            my_service = my_service.enable_addr_auto();
+        }
+        // 没有绑定的地址族既不宣告也不收听：对方发来的这类地址我们也连不上
+        if !plan.uses_v4() {
+            mdns.disable_interface(IfKind::IPv4)?;
+        }
+        if !plan.uses_v6() {
+            mdns.disable_interface(IfKind::IPv6)?;
+        }
+
+        let my_fullname = my_service.get_fullname().to_string();
         mdns.register(my_service)?;
 
         // 4. 启动监听任务
-        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<oneshot::Sender<()>>(1);
         let receiver = mdns.browse(SERVICE_TYPE)?;
         let local_device_id = cfg.device_id.clone();
         let local_account_uid = cfg.account_uid.clone();
@@ -136,8 +192,15 @@ impl DiscoveryService {
 
             loop {
                 tokio::select! {
-                    _ = shutdown_rx.recv() => {
+                    done_tx = shutdown_rx.recv() => {
+                        // 先发送注销宣告，对端立即收到 CandidateLost，而不是等 TTL 过期
+                        if let Ok(done) = mdns.unregister(&my_fullname) {
+                            let _ = tokio::time::timeout(Duration::from_secs(1), done.recv_async()).await;
+                        }
                         let _ = mdns.shutdown();
+                        if let Some(done_tx) = done_tx {
+                            let _ = done_tx.send(());
+                        }
                         break;
                     }
 
@@ -171,7 +234,10 @@ impl DiscoveryService {
 
     /// 停止发现服务
     pub async fn shutdown(&self) {
-        let _ = self.shutdown_tx.send(()).await;
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.shutdown_tx.send(done_tx).await.is_ok() {
+            let _ = done_rx.await;
+        }
     }
 }
 
@@ -198,4 +264,25 @@ mod tests {
             .collect();
         assert_eq!(capabilities, vec!["txt", "img", "file"]);
     }
+
+    #[test]
+    fn test_advertise_only_bound_addrs() {
+        use super::AdvertisePlan;
+        use crate::transport::BoundAddr;
+        let bound = |addr: &str, dual_stack: bool| BoundAddr { addr: addr.parse().unwrap(), dual_stack };
+
+        // 双栈通配：两个地址族都自动宣告
+        let plan = AdvertisePlan::from_bound(&[bound("[::]:4433", true)]);
+        assert_eq!(plan, AdvertisePlan { addrs: vec![], auto_v4: true, auto_v6: true });
+        // 只监听 IPv4
+        let plan = AdvertisePlan::from_bound(&[bound("0.0.0.0:4433", false)]);
+        assert!(plan.uses_v4() && !plan.uses_v6());
+        // 只监听 IPv6
+        let plan = AdvertisePlan::from_bound(&[bound("[::]:4433", false)]);
+        assert!(!plan.uses_v4() && plan.uses_v6());
+        // 具体地址原样宣告，不自动补充
+        let plan = AdvertisePlan::from_bound(&[bound("192.168.1.5:4433", false), bound("[fd00::5]:4433", false)]);
+        assert_eq!(plan.addrs, vec!["192.168.1.5".parse::<std::net::IpAddr>().unwrap(), "fd00::5".parse().unwrap()]);
+        assert!(!plan.auto_v4 && !plan.auto_v6);
+    }
 }
@@ -0,0 +1,321 @@
+// cb_core/src/item_fetch.rs
+
+//! FileList 整体拉取：一个父传输下按有限并发逐个拉取文件，汇总进度，
+//! 全部到齐后还原目录树并只发出一个 ITEM_CACHED 事件；任一文件失败则整体失败。
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::api::Core;
+use crate::model::ItemMeta;
+use crate::util::now_ms;
+
+/// 同时进行的子传输数
+pub const ITEM_FETCH_CONCURRENCY: usize = 4;
+/// 超过这个时间没有任何子传输事件即视为失败（对端掉线时子传输不会再有事件）
+const ITEM_FETCH_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+/// 汇总进度事件的最小间隔
+const PROGRESS_INTERVAL_MS: i64 = 200;
+/// 登记之前就已结束的子传输事件最多暂存多少个
+const EARLY_EVENTS_MAX: usize = 256;
+
+pub(crate) enum FetchMsg {
+    Progress { transfer_id: String, received: u64, bytes_per_sec: u64 },
+    Cached { transfer_id: String },
+    Failed { transfer_id: String, code: String, message: String },
+    Cancel,
+}
+
+impl FetchMsg {
+    fn transfer_id(&self) -> Option<&str> {
+        match self {
+            FetchMsg::Progress { transfer_id, .. }
+            | FetchMsg::Cached { transfer_id }
+            | FetchMsg::Failed { transfer_id, .. } => Some(transfer_id),
+            FetchMsg::Cancel => None,
+        }
+    }
+}
+
+/// 子传输 / 父传输到整体拉取线程的路由表
+#[derive(Default)]
+pub struct FetchRoutes {
+    /// 子传输 transfer_id → 所属父传输的通道
+    children: HashMap<String, Sender<FetchMsg>>,
+    /// 父传输 id → 通道（用于取消；为空时不必路由）
+    parents: HashMap<String, Sender<FetchMsg>>,
+    /// ensure_content_cached 返回 transfer_id 之前就已结束的子传输
+    early: VecDeque<FetchMsg>,
+}
+
+impl FetchRoutes {
+    /// 父传输 id 对应的通道（cancel_transfer 使用）
+    pub(crate) fn parent(&self, parent_id: &str) -> Option<Sender<FetchMsg>> {
+        self.parents.get(parent_id).cloned()
+    }
+
+    /// 网络层 / Core 在发出子传输的进度、结束事件时同时调用：交给所属的父传输，
+    /// 尚未登记的先暂存（进度除外）
+    pub(crate) fn notify(&mut self, msg: FetchMsg) {
+        if self.parents.is_empty() {
+            return;
+        }
+        let tid = msg.transfer_id().unwrap_or_default();
+        if let Some(tx) = self.children.get(tid) {
+            let _ = tx.send(msg);
+        } else if !matches!(msg, FetchMsg::Progress { .. }) {
+            if self.early.len() >= EARLY_EVENTS_MAX {
+                self.early.pop_front();
+            }
+            self.early.push_back(msg);
+        }
+    }
+}
+
+/// 登记父传输并启动整体拉取线程，返回父传输 id
+pub(crate) fn start(core: &Core, meta: ItemMeta) -> anyhow::Result<String> {
+    let parent_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = std::sync::mpsc::channel();
+    core.inner.fetch_routes.lock().unwrap().parents.insert(parent_id.clone(), tx.clone());
+
+    let batch = ItemFetch::new(core.clone(), parent_id.clone(), meta, tx, rx);
+    let spawned = std::thread::Builder::new()
+        .name("cb-item-fetch".to_string())
+        .spawn(move || batch.run());
+    if let Err(e) = spawned {
+        core.inner.fetch_routes.lock().unwrap().parents.remove(&parent_id);
+        return Err(e.into());
+    }
+    Ok(parent_id)
+}
+
+struct ItemFetch {
+    core: Core,
+    parent_id: String,
+    meta: ItemMeta,
+    tx: Sender<FetchMsg>,
+    rx: Receiver<FetchMsg>,
+    /// 待拉取的文件（meta.files 下标）
+    queue: VecDeque<usize>,
+    /// 子传输 transfer_id → 文件下标
+    in_flight: HashMap<String, usize>,
+    /// 登记过的全部子传输（结束时从路由表移除）
+    registered: Vec<String>,
+    /// 每个文件已收到的字节数
+    received: Vec<u64>,
+    /// 进行中子传输的当前速率（字节/秒）
+    rates: HashMap<String, u64>,
+    done_files: usize,
+    total_files: usize,
+    total_bytes: u64,
+    last_progress_ms: i64,
+}
+
+impl ItemFetch {
+    fn new(core: Core, parent_id: String, meta: ItemMeta, tx: Sender<FetchMsg>, rx: Receiver<FetchMsg>) -> Self {
+        let mut received = vec![0u64; meta.files.len()];
+        let mut queue = VecDeque::new();
+        let mut done_files = 0;
+        for (idx, f) in meta.files.iter().enumerate().filter(|(_, f)| !f.is_dir) {
+            // 已缓存的文件不再拉取
+            if f.sha256.as_deref().is_some_and(|sha| core.inner.cas.blob_exists(sha)) {
+                received[idx] = f.size_bytes.max(0) as u64;
+                done_files += 1;
+            } else {
+                queue.push_back(idx);
+            }
+        }
+        let total_files = meta.files.iter().filter(|f| !f.is_dir).count();
+        let total_bytes = meta.files.iter().filter(|f| !f.is_dir).map(|f| f.size_bytes.max(0) as u64).sum();
+        Self {
+            core,
+            parent_id,
+            meta,
+            tx,
+            rx,
+            queue,
+            in_flight: HashMap::new(),
+            registered: Vec::new(),
+            received,
+            rates: HashMap::new(),
+            done_files,
+            total_files,
+            total_bytes,
+            last_progress_ms: 0,
+        }
+    }
+
+    fn run(mut self) {
+        let result = self.fetch_all();
+        self.unregister();
+        match result {
+            Ok(()) => self.finish(),
+            Err((file_id, code, message)) => {
+                for tid in self.in_flight.keys() {
+                    self.core.cancel_transfer(tid);
+                }
+                self.fail(file_id, &code, &message);
+            }
+        }
+    }
+
+    /// 拉取全部文件；失败时返回 (文件 id, 错误码, 说明)
+    fn fetch_all(&mut self) -> Result<(), (Option<String>, String, String)> {
+        let mut last_event = Instant::now();
+        loop {
+            while self.in_flight.len() < ITEM_FETCH_CONCURRENCY {
+                let Some(idx) = self.queue.pop_front() else { break };
+                let file_id = self.meta.files[idx].file_id.clone();
+                let tid = self
+                    .core
+                    .ensure_content_cached(&self.meta.item_id, Some(&file_id))
+                    .map_err(|e| (Some(file_id.clone()), "FETCH_FAILED".to_string(), format!("{e:#}")))?;
+                self.register_child(&tid);
+                self.in_flight.insert(tid, idx);
+            }
+            if self.in_flight.is_empty() {
+                return Ok(());
+            }
+
+            let msg = match self.rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.core.inner.is_shutdown.load(Ordering::Acquire) {
+                        return Err((None, "CORE_SHUTDOWN".to_string(), "core shut down".to_string()));
+                    }
+                    if last_event.elapsed() > ITEM_FETCH_IDLE_TIMEOUT {
+                        return Err((None, "FETCH_TIMEOUT".to_string(), "no progress from source device".to_string()));
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+            last_event = Instant::now();
+
+            match msg {
+                FetchMsg::Cancel => {
+                    return Err((None, "TRANSFER_CANCELLED".to_string(), "cancelled by user".to_string()));
+                }
+                FetchMsg::Progress { transfer_id, received, bytes_per_sec } => {
+                    if let Some(&idx) = self.in_flight.get(&transfer_id) {
+                        self.received[idx] = received;
+                        self.rates.insert(transfer_id, bytes_per_sec);
+                        self.emit_progress(false);
+                    }
+                }
+                FetchMsg::Cached { transfer_id } => {
+                    if let Some(idx) = self.in_flight.remove(&transfer_id) {
+                        self.rates.remove(&transfer_id);
+                        self.received[idx] = self.meta.files[idx].size_bytes.max(0) as u64;
+                        self.done_files += 1;
+                        self.emit_progress(true);
+                    }
+                }
+                FetchMsg::Failed { transfer_id, code, message } => {
+                    if let Some(idx) = self.in_flight.remove(&transfer_id) {
+                        return Err((Some(self.meta.files[idx].file_id.clone()), code, message));
+                    }
+                }
+            }
+        }
+    }
+
+    /// 登记子传输；若它在 transfer_id 返回之前就已结束，补投暂存的事件
+    fn register_child(&mut self, tid: &str) {
+        self.registered.push(tid.to_string());
+        let mut routes = self.core.inner.fetch_routes.lock().unwrap();
+        routes.children.insert(tid.to_string(), self.tx.clone());
+        let early = std::mem::take(&mut routes.early);
+        for msg in early {
+            if msg.transfer_id() == Some(tid) {
+                let _ = self.tx.send(msg);
+            } else {
+                routes.early.push_back(msg);
+            }
+        }
+    }
+
+    fn unregister(&self) {
+        let mut routes = self.core.inner.fetch_routes.lock().unwrap();
+        routes.parents.remove(&self.parent_id);
+        for tid in &self.registered {
+            routes.children.remove(tid);
+        }
+    }
+
+    fn emit_progress(&mut self, force: bool) {
+        let now = now_ms();
+        if !force && now - self.last_progress_ms < PROGRESS_INTERVAL_MS {
+            return;
+        }
+        self.last_progress_ms = now;
+        self.core.inner.emit_json(serde_json::json!({
+            "type": "ITEM_FETCH_PROGRESS",
+            "ts_ms": now,
+            "payload": {
+                "transfer_id": self.parent_id,
+                "item_id": self.meta.item_id,
+                "done_files": self.done_files,
+                "total_files": self.total_files,
+                "received_bytes": self.received.iter().sum::<u64>(),
+                "total_bytes": self.total_bytes,
+                "bytes_per_sec": self.rates.values().sum::<u64>()
+            }
+        }));
+    }
+
+    /// 全部到齐：还原目录树，发出 ITEM_CACHED
+    fn finish(&self) {
+        let view = match self.core.materialize_item_tree(&self.meta.item_id) {
+            Ok(view) if view.missing_file_ids.is_empty() => view,
+            Ok(view) => {
+                let file_id = view.missing_file_ids.first().cloned();
+                return self.fail(file_id, "CONTENT_MISSING", "file content missing after fetch");
+            }
+            Err(e) => return self.fail(None, "MATERIALIZE_FAILED", &format!("{e:#}")),
+        };
+        {
+            let mut log_store = self.core.inner.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Transfer",
+                &format!("File list cached: item_id={}, files={}", self.meta.item_id, self.total_files),
+                Some(&format!("文件列表已全部缓存: 项目ID={}，文件数={}", self.meta.item_id, self.total_files)),
+            );
+        }
+        self.core.inner.emit_json(serde_json::json!({
+            "type": "ITEM_CACHED",
+            "ts_ms": now_ms(),
+            "payload": {
+                "transfer_id": self.parent_id,
+                "item_id": self.meta.item_id,
+                "root_path": view.root_path,
+                "total_files": self.total_files,
+                "total_bytes": self.total_bytes
+            }
+        }));
+    }
+
+    fn fail(&self, file_id: Option<String>, code: &str, message: &str) {
+        {
+            let mut log_store = self.core.inner.log_store.lock().unwrap();
+            let _ = log_store.log_warn(
+                "Transfer",
+                &format!("File list fetch failed: item_id={}, file_id={:?}, code={}, message={}", self.meta.item_id, file_id, code, message),
+                Some(&format!("文件列表拉取失败: 项目ID={}，文件ID={:?}，错误码={}，说明={}", self.meta.item_id, file_id, code, message)),
+            );
+        }
+        self.core.inner.emit_json(serde_json::json!({
+            "type": "ITEM_FETCH_FAILED",
+            "ts_ms": now_ms(),
+            "payload": {
+                "transfer_id": self.parent_id,
+                "item_id": self.meta.item_id,
+                "file_id": file_id,
+                "code": code,
+                "message": message
+            }
+        }));
+    }
+}
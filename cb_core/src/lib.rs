@@ -6,10 +6,12 @@ pub mod util;
 pub mod policy;
 
 pub mod runtime;
+pub mod migrate;
 pub mod store;
 pub mod logs;
 pub mod stats;
 pub mod cas;
+pub mod item_fetch;
 pub mod clipboard;
 pub mod testsupport;
 pub mod net;
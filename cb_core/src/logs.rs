@@ -1,4 +1,5 @@
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use crate::migrate::{self, Migration};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -98,9 +99,9 @@ impl LogStore {
         fs::create_dir_all(data_dir)?;
         let db_path: PathBuf = data_dir.join("logs.db");
 
-        let conn = Connection::open(db_path)?;
+        let mut conn = Connection::open(db_path)?;
         Self::init_pragmas(&conn)?;
-        Self::init_schema(&conn)?;
+        Self::init_schema(&mut conn)?;
         Ok(Self { conn })
     }
 
@@ -115,8 +116,19 @@ impl LogStore {
         Ok(())
     }
 
-    fn init_schema(conn: &Connection) -> anyhow::Result<()> {
-        conn.execute_batch(
+    /// 按 `PRAGMA user_version` 执行尚未执行的迁移
+    fn init_schema(conn: &mut Connection) -> anyhow::Result<()> {
+        migrate::run_migrations(conn, "logs.db", Self::MIGRATIONS)
+    }
+
+    /// logs.db 的迁移列表。只能在末尾追加，已发布的步骤不得修改。
+    const MIGRATIONS: &'static [Migration] = &[
+        Migration { version: 1, name: "initial", up: Self::migrate_v1_initial },
+    ];
+
+    /// v1：最初的表结构
+    fn migrate_v1_initial(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
             r#"
             CREATE TABLE IF NOT EXISTS logs (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
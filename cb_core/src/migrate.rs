@@ -0,0 +1,79 @@
+// cb_core/src/migrate.rs
+//
+// core.db / logs.db / stats.db 共用的 schema 迁移框架。
+// 版本号记录在 PRAGMA user_version 中（0 表示从未迁移过的新库或旧版无版本库），
+// 迁移按版本号递增依次执行，每一步在独立事务中完成并同时写入新的 user_version。
+
+use rusqlite::{Connection, Transaction};
+
+/// 单个迁移步骤：把数据库从 version - 1 升级到 version
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: fn(&Transaction<'_>) -> anyhow::Result<()>,
+}
+
+/// 数据库版本高于当前程序支持的版本时返回的错误码
+pub const STORE_SCHEMA_TOO_NEW: &str = "STORE_SCHEMA_TOO_NEW";
+
+/// 读取数据库当前的 schema 版本
+pub fn schema_version(conn: &Connection) -> anyhow::Result<u32> {
+    let v: u32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    Ok(v)
+}
+
+/// 依次执行所有高于当前版本的迁移。
+///
+/// - `migrations` 必须从 1 开始连续递增；
+/// - 数据库版本高于最新迁移时拒绝打开（`STORE_SCHEMA_TOO_NEW`），避免旧程序写坏新库；
+/// - 任一步失败则该步事务回滚，数据库停留在上一个版本。
+pub fn run_migrations(conn: &mut Connection, db_name: &str, migrations: &[Migration]) -> anyhow::Result<()> {
+    for (idx, m) in migrations.iter().enumerate() {
+        if m.version as usize != idx + 1 {
+            anyhow::bail!("{} migrations out of order: #{} has version {}", db_name, idx + 1, m.version);
+        }
+    }
+    let latest = migrations.len() as u32;
+
+    let current = schema_version(conn)?;
+    if current > latest {
+        anyhow::bail!(
+            "{}: {} schema version {} is newer than supported version {}",
+            STORE_SCHEMA_TOO_NEW,
+            db_name,
+            current,
+            latest
+        );
+    }
+
+    for m in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        (m.up)(&tx).map_err(|e| anyhow::anyhow!("{} migration v{} ({}) failed: {:#}", db_name, m.version, m.name, e))?;
+        tx.pragma_update(None, "user_version", m.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// 如果表中缺少指定列则 ALTER TABLE 补上（用于让迁移步骤可重复执行）
+pub fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |r| r.get::<_, String>(1))?
+        .filter_map(|c| c.ok())
+        .any(|c| c == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, decl))?;
+    }
+    Ok(())
+}
+
+/// 表是否存在
+pub fn table_exists(conn: &Connection, table: &str) -> anyhow::Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name=?1)",
+        [table],
+        |r| r.get(0),
+    )?;
+    Ok(exists)
+}
@@ -6,6 +6,8 @@ pub enum ItemKind {
     Text,
     Image,
     FileList,
+    /// 富文本（HTML / RTF），附带纯文本降级表示
+    RichText,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -34,12 +36,26 @@ pub struct ItemContent {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FileMeta {
     pub file_id: String,
+    /// 相对路径，多级目录以 `/` 分隔
     pub rel_name: String,
     pub size_bytes: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sha256: Option<String>,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	pub local_path: Option<String>,
+	/// 计算 sha256 时源文件的修改时间：与 local_path 一样只在本机有效，
+	/// 用于发现复制之后被改动的源文件
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub local_mtime_ms: Option<i64>,
+	/// 目录条目（包括空目录）：没有内容，不能单独拉取
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub is_dir: bool,
+	/// Unix 权限位
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub mode: Option<u32>,
+	/// 源文件 / 目录的修改时间（毫秒），还原目录树时使用
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub mtime_ms: Option<i64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -59,15 +75,191 @@ pub struct ItemMeta {
     pub files: Vec<FileMeta>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_ts_ms: Option<i64>,
+    /// RichText 的纯文本降级内容：对端无法渲染 HTML/RTF 时可单独拉取
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_fallback: Option<ItemContent>,
+    /// 同一次复制的其它格式（不含主格式 content），按来源端偏好排序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub representations: Vec<ItemContent>,
+    /// 本机置顶（收藏）标记：只在本地历史中有效，不随元数据广播
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+	!*b
 }
 // [新增] 隐私清洗方法
 impl ItemMeta {
 	pub fn sanitize_for_broadcast(&mut self) {
 		self.source_device_name = None; // 可选：隐藏设备名细节
+		self.pinned = false;
 		// 关键：清除所有文件的本地路径
 		for f in &mut self.files {
 			f.local_path = None;
+			f.local_mtime_ms = None;
+		}
+	}
+
+	/// 按 MIME 查找表示：主格式、额外格式、纯文本降级依次匹配
+	pub fn find_representation(&self, mime: &str) -> Option<&ItemContent> {
+		std::iter::once(&self.content)
+			.chain(self.representations.iter())
+			.chain(self.text_fallback.iter())
+			.find(|c| c.mime.eq_ignore_ascii_case(mime))
+	}
+
+	/// 按偏好列表选出要拉取的表示；都不匹配（或列表为空）时返回主格式
+	pub fn pick_representation(&self, preferred_mimes: &[String]) -> &ItemContent {
+		preferred_mimes
+			.iter()
+			.find_map(|m| self.find_representation(m))
+			.unwrap_or(&self.content)
+	}
+}
+
+/// FileList 目录树在本机缓存目录中的视图（见 Core::materialize_item_tree）
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TreeView {
+	pub root_path: String,
+	/// 内容尚未缓存、没有还原的文件；拉取后再次调用即可补齐
+	pub missing_file_ids: Vec<String>,
+}
+
+/// 删除历史条目的范围
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteScope {
+	/// 只删除本机的历史与缓存
+	#[default]
+	Local,
+	/// 同时通知在线的对端设备删除，并拒绝之后对该条目的内容拉取
+	All,
+}
+
+/// 历史分页游标：上一页最后一条记录的 (sort_ts_ms, history_id)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HistoryCursor {
+	pub sort_ts_ms: i64,
+	pub history_id: i64,
+	/// 上一页最后一条是否置顶；仅在 pinned_first 模式下参与比较
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub pinned: bool,
+}
+
+/// 历史查询过滤条件，所有字段缺省即不过滤
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+	/// 只返回这些类型；为空表示全部
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub kinds: Vec<ItemKind>,
+	/// 只返回该设备产生的条目
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub source_device_id: Option<String>,
+	/// 时间范围下界（含）
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub since_ts_ms: Option<i64>,
+	/// 时间范围上界（不含）
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub until_ts_ms: Option<i64>,
+	/// true：只返回正文已缓存在本地的条目；false：只返回未缓存的
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cached: Option<bool>,
+	/// true：只返回置顶条目；false：只返回未置顶的
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pinned: Option<bool>,
+	/// 置顶优先：先列出全部置顶条目，再按时间倒序列出其余条目
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub pinned_first: bool,
+}
+
+/// 历史分页结果；next_cursor 为 None 表示没有更多数据
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+	pub items: Vec<ItemMeta>,
+	pub next_cursor: Option<HistoryCursor>,
+}
+
+/// 全文检索命中：snippet 中用 <mark></mark> 标出关键字
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+	pub meta: ItemMeta,
+	pub snippet: String,
+}
+
+/// 全文检索分页结果，游标与 HistoryPage 通用
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchPage {
+	pub hits: Vec<SearchHit>,
+	pub next_cursor: Option<HistoryCursor>,
+}
+
+/// 缓存被回收的原因
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReclaimReason {
+	/// 所属条目已过期
+	Expired,
+	/// 所属条目被用户删除
+	Deleted,
+	/// 不再被任何存活条目引用（例如历史被 History GC 裁剪）
+	Unreferenced,
+	/// 超过缓存上限按 LRU 淘汰（条目仍在，可重新拉取）
+	Lru,
+	/// blobs/ 中没有任何条目引用的文件
+	OrphanBlob,
+	/// tmp/ 中长时间未更新的临时文件，以及长期没有进展的续传数据（partial/）
+	StaleTmp,
+	/// files/、downloads/ 中对应 blob 已不存在的视图文件
+	OrphanView,
+}
+
+/// 某一原因下回收的文件数与字节数
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReclaimStat {
+	pub reason: ReclaimReason,
+	pub files: i64,
+	pub bytes: i64,
+}
+
+/// 一次 GC 的结果
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GcRun {
+	pub ts_ms: i64,
+	/// 触发原因（Startup / AfterIngest / 壳侧传入的字符串）
+	pub trigger: String,
+	pub reclaimed: Vec<ReclaimStat>,
+}
+
+/// 缓存占用与回收情况
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheReport {
+	/// blobs/ 目录实际占用字节数
+	pub blob_bytes: i64,
+	/// 已缓存（present=1）的内容条数与字节数
+	pub present_count: i64,
+	pub present_bytes: i64,
+	/// 其中被置顶条目引用、不会被淘汰的字节数
+	pub pinned_bytes: i64,
+	/// Cache GC 上限（gc_cas_max_bytes）
+	pub cap_bytes: i64,
+	/// 最近一次 GC
+	pub last_gc: Option<GcRun>,
+	/// 本次启动以来按原因累计的回收量
+	pub reclaimed_total: Vec<ReclaimStat>,
+}
+
+/// 把一次回收累加到按原因分组的统计中
+pub(crate) fn add_reclaim(stats: &mut Vec<ReclaimStat>, reason: ReclaimReason, files: i64, bytes: i64) {
+	if files == 0 {
+		return;
+	}
+	match stats.iter_mut().find(|s| s.reason == reason) {
+		Some(s) => {
+			s.files += files;
+			s.bytes += bytes;
 		}
+		None => stats.push(ReclaimStat { reason, files, bytes }),
 	}
 }
 
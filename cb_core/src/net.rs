@@ -1,20 +1,37 @@
 // cb_core/src/net/mod.rs
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
+use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::interval;
 
 use crate::discovery::{DiscoveryEvent, DiscoveryService, PeerCandidate};
-use crate::session::{SessionActor, SessionCmd, SessionHandle, SessionRole};
-use crate::transport::Transport;
+use crate::session::{PendingPull, SessionActor, SessionCmd, SessionHandle, SessionRole, TransferScheduler};
+use crate::transport::{Connection, Transport};
 use crate::util::now_ms;
 use crate::api::{PeerConnectionState, PeerStatus};
-use crate::store::Store;
+use crate::store::{PeerRecord, Store};
 use crate::logs::LogStore;
+use crate::item_fetch::{FetchMsg, FetchRoutes};
 use std::sync::Mutex;
 
+/// 会话断开后等待重连续传的最长时间，超时后拉取以 PEER_OFFLINE 失败
+const RESUME_WAIT_MS: i64 = 5 * 60 * 1000;
+/// 向其他设备询问是否持有某份内容的超时
+const HAVE_QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// 换源续传没找到持有者时，隔多久再问一次
+const FAILOVER_RETRY_MS: i64 = 5_000;
+/// Happy Eyeballs：相邻两次连接尝试之间的间隔
+const DIAL_STAGGER: Duration = Duration::from_millis(250);
+/// 拨号静态对端、还不知道对方 device_id 时，会话与退避记录以 "static:<addr>" 标识
+const STATIC_PEER_PREFIX: &str = "static:";
+/// 地址簿里本次运行还没确认过的设备：组播不稳定时可能一直等不到宣告，按更长的退避一直重拨
+const UNCONFIRMED_RETRY_BASE_SECS: u64 = 30;
+const UNCONFIRMED_RETRY_MAX_SECS: u64 = 30 * 60;
+
 /// 网络层管理器
 pub struct NetManager {
     config: crate::api::CoreConfig,
@@ -29,11 +46,42 @@ pub struct NetManager {
 
     // 退避记录: device_id -> (失败次数, 下次重试的最早时间戳)
     backoff_map: HashMap<String, BackoffState>,
-    known_peers: HashMap<String, PeerCandidate>,
+    known_peers: HashMap<String, KnownPeer>,
+    // 因对端换了地址而主动关闭的会话：回收后跳过退避，立即按新地址重拨
+    redial_after_close: HashSet<String>,
+    // 每个对端上次连接成功的地址，下次拨号优先尝试
+    last_good_addrs: HashMap<String, SocketAddr>,
+    // 静态对端: addr -> 握手后得知的 device_id
+    static_peers: HashMap<String, Option<String>>,
+    // 会话断开时未完成的拉取，等对端重新上线后续传
+    resume_queue: Vec<QueuedResume>,
+    // 换源查询的结果（后台询问其他设备，结果回到主循环处理）
+    failover_tx: mpsc::Sender<FailoverResult>,
+    failover_rx: mpsc::Receiver<FailoverResult>,
+    // 入站握手在独立任务里完成，成功的连接回到主循环创建会话
+    accepted_tx: mpsc::Sender<Connection>,
+    accepted_rx: mpsc::Receiver<Connection>,
+    // 出站拨号同样在独立任务里竞速，结果回到主循环创建会话或进入退避
+    dialed_tx: mpsc::Sender<DialResult>,
+    dialed_rx: mpsc::Receiver<DialResult>,
+    // 全部会话共享的传输限速与优先级
+    scheduler: Arc<TransferScheduler>,
     cas: crate::cas::Cas,
     cmd_rx: mpsc::Receiver<NetCmd>,
     discovery_rx: mpsc::Receiver<DiscoveryEvent>,
     event_sink: Arc<dyn crate::api::CoreEventSink>,
+    // 子传输的进度 / 结束同时交给 FileList 整体拉取
+    fetch_routes: Arc<Mutex<FetchRoutes>>,
+}
+
+/// 地址簿条目（持久化在 core.db 的 peers 表）：mDNS 最近一次宣告的地址。
+/// lost 表示当前不在网络上：宣告过期或注销，或是启动时从 core.db 载入、本次运行还没见过。
+/// unconfirmed 只标记后一种：mDNS 宣告或拨号成功之前按长退避重拨；宣告注销的设备则等它重新出现
+struct KnownPeer {
+    candidate: PeerCandidate,
+    last_seen_ms: i64,
+    lost: bool,
+    unconfirmed: bool,
 }
 
 /// 管理退避状态的结构体
@@ -42,10 +90,44 @@ struct BackoffState {
     next_retry_ts: i64,
 }
 
+struct QueuedResume {
+    device_id: String,
+    pull: PendingPull,
+    interrupted_ts: i64,
+    // 正在后台询问其他设备
+    searching: bool,
+    next_failover_ts: i64,
+}
+
+struct DialResult {
+    device_id: String,
+    outcome: DialOutcome,
+}
+
+enum DialOutcome {
+    Connected(Connection, SocketAddr),
+    Failed,
+    // 没有本机能连的地址（地址族不匹配 / 域名解析失败），一个都没试
+    NoUsableAddr,
+}
+
+struct FailoverResult {
+    transfer_id: String,
+    sha256: String,
+    holder: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum NetCmd {
     BroadcastMeta(crate::model::ItemMeta),
+    /// 通知所有在线对端删除条目
+    BroadcastDelete {
+        item_id: String,
+        deleted_ts_ms: i64,
+    },
     GetPeers(oneshot::Sender<Vec<PeerStatus>>),
+    /// 实际监听的地址（端口配置为 0 时由系统分配）
+    GetListenAddrs(oneshot::Sender<Vec<SocketAddr>>),
     Shutdown,
     /// 发起内容拉取请求 (Core -> Session)
     EnsureContentCached {
@@ -54,6 +136,8 @@ pub enum NetCmd {
         file_id: Option<String>,
         // 强制重传？通常 false
         force: bool,
+        // 指定要拉取的表示（None 表示主格式）
+        mime: Option<String>,
         // 返回 transfer_id (即 req_id)
         reply: tokio::sync::oneshot::Sender<anyhow::Result<String>>,
     },
@@ -62,6 +146,27 @@ pub enum NetCmd {
     CancelTransfer {
         transfer_id: String,
     },
+
+    /// 静态对端列表变化（已写入 core.db）
+    AddStaticPeer(String),
+    RemoveStaticPeer(String),
+
+    /// 测试用：注入一条发现事件，与 mDNS 送来的走同一条处理路径
+    #[cfg(test)]
+    InjectDiscovery(DiscoveryEvent),
+}
+
+/// 校验并规范化静态对端地址："host:port"，IPv6 写作 "[::1]:port"
+pub(crate) fn normalize_static_peer(addr: &str) -> anyhow::Result<String> {
+    let addr = addr.trim();
+    let valid = addr.rsplit_once(':').is_some_and(|(host, port)| {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        !host.is_empty() && !host.contains(char::is_whitespace) && port.parse::<u16>().is_ok_and(|p| p != 0)
+    });
+    if !valid {
+        anyhow::bail!("INVALID_PEER_ADDR: {}", addr);
+    }
+    Ok(addr.to_string())
 }
 
 impl NetManager {
@@ -71,6 +176,7 @@ impl NetManager {
         store: Arc<Mutex<Store>>,
         cas: crate::cas::Cas,
         log_store: Arc<Mutex<LogStore>>,
+        fetch_routes: Arc<Mutex<FetchRoutes>>,
     ) -> anyhow::Result<mpsc::Sender<NetCmd>> {
         let (cmd_tx, cmd_rx) = mpsc::channel(32);
 
@@ -85,25 +191,33 @@ impl NetManager {
 
                 rt.block_on(async move {
                     // 2. 在 Runtime 内部进行初始化 (Transport 需要绑定 Socket)
-                    match Transport::new(0, &config.data_dir, &config.device_id, &config.account_uid) {
+                    match Transport::new(&config.app_config.network, &config.data_dir, &config.device_id, &config.account_uid) {
                         Ok(transport) => {
                             let transport = Arc::new(transport);
                             let port = transport.local_port().unwrap_or(0);
-                            let is_ipv4 = transport.is_ipv4();
+                            let bound = transport.bound_addrs();
+                            let listen: Vec<String> = bound.iter().map(|b| b.addr.to_string()).collect();
 
                             // 记录 Transport 初始化成功
                             {
                                 let mut log_store = log_store.lock().unwrap();
                                 let _ = log_store.log_info(
                                     "Network",
-                                    &format!("Transport initialized, local port: {}, IPv4: {}", port, is_ipv4),
-                                    Some(&format!("传输层已初始化，本地端口: {}，IPv4: {}", port, is_ipv4)),
+                                    &format!("Transport initialized, listening on: {}", listen.join(", ")),
+                                    Some(&format!("传输层已初始化，监听地址: {}", listen.join(", "))),
                                 );
+                                if let Some(err) = transport.dual_stack_fallback() {
+                                    let _ = log_store.log_warn(
+                                        "Network",
+                                        &format!("Dual-stack bind failed, listening on IPv4 only: {}", err),
+                                        Some(&format!("双栈绑定失败，仅监听 IPv4: {}", err)),
+                                    );
+                                }
                             }
 
-                            // 3. 启动 Discovery
+                            // 3. 启动 Discovery：只宣告实际绑定的地址
                             let (disc_tx, disc_rx) = mpsc::channel(32);
-                            match DiscoveryService::spawn(config.clone(), port, disc_tx) {
+                            match DiscoveryService::spawn(config.clone(), port, &bound, disc_tx) {
                                 Ok(discovery) => {
                                     // 记录 Discovery 启动成功
                                     {
@@ -115,6 +229,12 @@ impl NetManager {
                                         );
                                     }
 
+                                    let (failover_tx, failover_rx) = mpsc::channel(32);
+                                    let (accepted_tx, accepted_rx) = mpsc::channel(8);
+                                    let (dialed_tx, dialed_rx) = mpsc::channel(8);
+                                    let scheduler = Arc::new(TransferScheduler::new(&config.app_config.transfer));
+                                    let static_peers = load_static_peers(&config, &store, &log_store);
+                                    let (known_peers, last_good_addrs) = load_known_peers(&config, &store, &log_store);
                                     let manager = Self {
                                         config,
                                         transport,
@@ -125,10 +245,22 @@ impl NetManager {
                                         sessions: Vec::new(),
                                         pending_dials: HashSet::new(),
                                         backoff_map: HashMap::new(),
-                                        known_peers: HashMap::new(),
+                                        known_peers,
+                                        redial_after_close: HashSet::new(),
+                                        last_good_addrs,
+                                        static_peers,
+                                        resume_queue: Vec::new(),
+                                        failover_tx,
+                                        failover_rx,
+                                        accepted_tx,
+                                        accepted_rx,
+                                        dialed_tx,
+                                        dialed_rx,
+                                        scheduler,
                                         cmd_rx,
                                         discovery_rx: disc_rx,
                                         event_sink,
+                                        fetch_routes,
                                     };
                                     // 4. 运行主循环
                                     manager.run().await;
@@ -145,13 +277,26 @@ impl NetManager {
                             }
                         }
                         Err(e) => {
-                            let mut log_store = log_store.lock().unwrap();
-                            let _ = log_store.log_error(
-                                "Network",
-                                &format!("Transport initialization failed: {}", e),
-                                Some(&format!("传输层初始化失败: {}", e)),
-                                Some(&e.to_string()),
-                            );
+                            {
+                                let mut log_store = log_store.lock().unwrap();
+                                let _ = log_store.log_error(
+                                    "Network",
+                                    &format!("Transport initialization failed: {}", e),
+                                    Some(&format!("传输层初始化失败: {}", e)),
+                                    Some(&e.to_string()),
+                                );
+                            }
+                            // 端口被占用等情况要让外壳看到，而不是静默地没有网络
+                            let evt = serde_json::json!({
+                                "type": "CORE_ERROR",
+                                "ts_ms": now_ms(),
+                                "payload": {
+                                    "code": error_code(&e, "TRANSPORT_INIT_FAILED"),
+                                    "message": format!("{e:#}"),
+                                    "context": { "listen_port": config.app_config.network.listen_port, "bind": config.app_config.network.bind },
+                                }
+                            });
+                            event_sink.emit(evt.to_string());
                         }
                     }
                 });
@@ -163,6 +308,7 @@ impl NetManager {
     async fn run(mut self) {
         // 每秒检查一次，用于快速响应重连
         let mut cleanup_ticker = interval(Duration::from_secs(1));
+        self.dial_known_peers();
 
         loop {
             tokio::select! {
@@ -170,17 +316,23 @@ impl NetManager {
                 cmd = self.cmd_rx.recv() => {
                     match cmd {
                         Some(NetCmd::BroadcastMeta(meta)) => self.broadcast_meta(meta).await,
+                        Some(NetCmd::BroadcastDelete { item_id, deleted_ts_ms }) => {
+                            self.broadcast_delete(item_id, deleted_ts_ms).await
+                        }
                         Some(NetCmd::GetPeers(reply_tx)) => {
                             // [New] 处理查询请求
                             let peers = self.get_peers_info();
                             let _ = reply_tx.send(peers);
                         }
+                        Some(NetCmd::GetListenAddrs(reply_tx)) => {
+                            let _ = reply_tx.send(self.transport.bound_addrs().into_iter().map(|b| b.addr).collect());
+                        }
                         Some(NetCmd::Shutdown) => {
                             self.shutdown().await;
                             break;
                         }
 
-                        Some(NetCmd::EnsureContentCached { item_id, file_id, force: _, reply }) => {
+                        Some(NetCmd::EnsureContentCached { item_id, file_id, force: _, mime, reply }) => {
                             // 1. 查库找 owner (A 的 device_id)
                             let owner_res = {
                                 let store = self.store.lock().unwrap();
@@ -195,10 +347,13 @@ impl NetManager {
                                     let _ = session.cmd_tx.send(SessionCmd::RequestTransfer {
                                         item_id,
                                         file_id,
+                                        mime,
+                                        sha256: None,
                                         reply_tx: reply,
                                     }).await;
                                 } else {
-                                    let _ = reply.send(Err(anyhow::anyhow!("Device {} not online", device_id)));
+                                    // 来源设备不在线：CAS 按哈希去重，其他在线设备可能已缓存同一份内容
+                                    self.request_from_holder(device_id, item_id, file_id, mime, reply);
                                 }
                             } else {
                                  let _ = reply.send(Err(anyhow::anyhow!("Item not found or no owner info")));
@@ -206,6 +361,21 @@ impl NetManager {
                         }
 
                         Some(NetCmd::CancelTransfer { transfer_id }) => {
+                            // 还在等待重连续传的拉取直接取消
+                            if let Some(pos) = self.resume_queue.iter().position(|q| q.pull.transfer_id == transfer_id) {
+                                self.resume_queue.remove(pos);
+                                let evt = serde_json::json!({
+                                    "type": "TRANSFER_CANCELLED",
+                                    "payload": { "transfer_id": transfer_id }
+                                });
+                                self.event_sink.emit(evt.to_string());
+                                self.fetch_routes.lock().unwrap().notify(FetchMsg::Failed {
+                                    transfer_id,
+                                    code: "TRANSFER_CANCELLED".to_string(),
+                                    message: "child transfer cancelled".to_string(),
+                                });
+                                continue;
+                            }
                             // 广播给所有 session 尝试取消 (因为 NetManager 不记录 transfer_id 属于哪个 session)
                             // 或者 SessionHandle 可以返回它正在处理的 transfer_ids?
                             // 简单做法：群发，SessionActor 发现不是自己的会忽略
@@ -213,6 +383,21 @@ impl NetManager {
                                 let _ = s.cmd_tx.send(SessionCmd::CancelTransfer { transfer_id: transfer_id.clone() }).await;
                             }
                         }
+                        Some(NetCmd::AddStaticPeer(addr)) => {
+                            self.static_peers.entry(addr.clone()).or_insert(None);
+                            // 新地址立刻拨号，不必等下一次维护
+                            self.backoff_map.remove(&static_key(&addr));
+                            self.dial_static_peers(now_ms());
+                        }
+                        Some(NetCmd::RemoveStaticPeer(addr)) => {
+                            // 已建立的会话保持不变，断开后不再重拨
+                            self.static_peers.remove(&addr);
+                            self.backoff_map.remove(&static_key(&addr));
+                        }
+                        #[cfg(test)]
+                        Some(NetCmd::InjectDiscovery(event)) => {
+                            self.handle_discovery_event(event).await;
+                        }
                         None => break,
                     }
                 }
@@ -224,33 +409,53 @@ impl NetManager {
                     }
                 }
 
-                // 3. 入站连接
-                conn = self.transport.accept() => {
-                    if let Some(conn) = conn {
-                        let addr = conn.remote_address();
-                        let mut log_store = self.log_store.lock().unwrap();
-                        let _ = log_store.log_info(
-                            "Network",
-                            &format!("Incoming connection accepted from: {}, spawning server session", addr),
-                            Some(&format!("已接受入站连接: {}，正在创建服务器会话", addr)),
-                        );
-                        drop(log_store);
-
-                        let handle = SessionActor::spawn(
-                            SessionRole::Server,
-                            conn,
-                            self.config.clone(),
-                            self.event_sink.clone(),
-                            self.store.clone(),
-                            self.cas.clone(),
-                            None,
-                            self.log_store.clone(),
-                        );
-                        self.sessions.push(handle);
+                // 3. 入站连接：握手放到后台，避免其他分支先就绪时把握手中的连接丢掉
+                incoming = self.transport.accept_incoming() => {
+                    if let Some(incoming) = incoming {
+                        let accepted_tx = self.accepted_tx.clone();
+                        tokio::spawn(async move {
+                            if let Some(conn) = Transport::handshake(incoming).await {
+                                let _ = accepted_tx.send(conn).await;
+                            }
+                        });
                     }
                 }
 
-                // 4. 定期维护 (清理死链 + 管理退避)
+                Some(conn) = self.accepted_rx.recv() => {
+                    let addr = conn.remote_address();
+                    let mut log_store = self.log_store.lock().unwrap();
+                    let _ = log_store.log_info(
+                        "Network",
+                        &format!("Incoming connection accepted from: {}, spawning server session", addr),
+                        Some(&format!("已接受入站连接: {}，正在创建服务器会话", addr)),
+                    );
+                    drop(log_store);
+
+                    let handle = SessionActor::spawn(
+                        SessionRole::Server,
+                        conn,
+                        self.config.clone(),
+                        self.event_sink.clone(),
+                        self.store.clone(),
+                        self.cas.clone(),
+                        None,
+                        self.log_store.clone(),
+                        self.scheduler.clone(),
+                        self.fetch_routes.clone(),
+                    );
+                    self.sessions.push(handle);
+                }
+
+                Some(res) = self.dialed_rx.recv() => {
+                    self.handle_dial_result(res);
+                }
+
+                // 4. 换源查询结果
+                Some(res) = self.failover_rx.recv() => {
+                    self.handle_failover(res).await;
+                }
+
+                // 5. 定期维护 (清理死链 + 管理退避)
                 _ = cleanup_ticker.tick() => {
                     self.maintain_sessions().await;
                 }
@@ -278,24 +483,28 @@ impl NetManager {
                 _ => (true, true), // 默认都允许
             };
 
+            let known = self.known_peers.get(&device_id);
             peers.push(PeerStatus {
+                device_name: known.and_then(|k| k.candidate.device_name.clone()),
+                last_good_addr: self.last_good_addrs.get(&device_id).map(|a| a.to_string()),
                 device_id,
-                device_name: None, // TODO: 从 HELLO 消息或事件中获取设备名称
                 state,
-                last_seen_ts_ms: now, // TODO: 从 session 或 known_peers 中获取真实时间
+                last_seen_ts_ms: s.last_seen_ts_ms(),
                 share_to_peer: share_to,
                 accept_from_peer: accept_from,
             });
         }
 
         // 2. 再把 known_peers 里有但 session 里没有的加为 "Discovered" (可选，文档建议展示所有已发现设备)
-        for (did, _) in &self.known_peers {
+        for (did, known) in &self.known_peers {
             if peers.iter().any(|p| &p.device_id == did) {
                 continue;
             }
 
-            // 优先级：Backoff > Connecting > Discovered
-            let state = if let Some(bo) = self.backoff_map.get(did) {
+            // 优先级：Offline（已从网络上消失）> Backoff > Connecting > Discovered
+            let state = if known.lost && !self.pending_dials.contains(did) {
+                PeerConnectionState::Offline
+            } else if let Some(bo) = self.backoff_map.get(did) {
                 if now < bo.next_retry_ts {
                     PeerConnectionState::Backoff
                 } else if self.pending_dials.contains(did) {
@@ -318,9 +527,10 @@ impl NetManager {
 
             peers.push(PeerStatus {
                 device_id: did.clone(),
-                device_name: None, // TODO: 从 discovery 或事件中获取设备名称
+                device_name: known.candidate.device_name.clone(),
+                last_good_addr: self.last_good_addrs.get(did).map(|a| a.to_string()),
                 state,
-                last_seen_ts_ms: now, // TODO: 从 known_peers 中获取真实时间
+                last_seen_ts_ms: known.last_seen_ms,
                 share_to_peer: share_to,
                 accept_from_peer: accept_from,
             });
@@ -331,6 +541,7 @@ impl NetManager {
 
     async fn maintain_sessions(&mut self) {
         let now = now_ms();
+        self.learn_static_peer_ids();
 
         // --- A. 成功连接的“真正”判定 ---
         // 只有当 Session 状态变为 Online 时，才清除退避记录（清零）
@@ -343,13 +554,26 @@ impl NetManager {
                 }
             }
         }
+        self.close_duplicate_sessions().await;
 
         // --- B. 清理死链 & 生成/升级退避 ---
         let mut dead_ids = Vec::new();
+        let mut interrupted = Vec::new();
+        let mut last_seen = Vec::new();
         self.sessions.retain(|s| {
             if s.is_finished() { // 彻底挂了
                 if !s.device_id().starts_with("pending") {
                     dead_ids.push(s.device_id().clone());
+                    last_seen.push((s.device_id(), s.last_seen_ts_ms()));
+                }
+                for pull in s.take_interrupted() {
+                    interrupted.push(QueuedResume {
+                        device_id: s.device_id(),
+                        pull,
+                        interrupted_ts: now,
+                        searching: false,
+                        next_failover_ts: now,
+                    });
                 }
                 false
             } else {
@@ -357,7 +581,19 @@ impl NetManager {
             }
         });
 
+        for (did, seen) in last_seen {
+            self.note_peer_seen(&did, seen);
+        }
+
+        let mut redials = Vec::new();
         for did in dead_ids {
+            if self.redial_after_close.remove(&did) {
+                self.pending_dials.remove(&did);
+                if let Some(known) = self.known_peers.get(&did).filter(|k| !k.lost) {
+                    redials.push(known.candidate.clone());
+                }
+                continue;
+            }
             let entry = self.backoff_map.entry(did.clone()).or_insert(BackoffState {
                 fail_count: 0,
                 next_retry_ts: 0,
@@ -379,6 +615,21 @@ impl NetManager {
             );
         }
 
+        if !interrupted.is_empty() {
+            let mut log_store = self.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Network",
+                &format!("Transfers interrupted by session loss, waiting to resume: count={}", interrupted.len()),
+                Some(&format!("会话断开导致传输中断，等待重连后续传: 数量={}", interrupted.len())),
+            );
+        }
+        self.resume_queue.extend(interrupted);
+        self.resume_interrupted(now).await;
+
+        for peer in redials {
+            self.perform_dial(peer);
+        }
+
         // --- C. 检查退避到期 & 执行重连 ---
         // 只有当 (当前时间 > 重试时间) 且 (不在正在拨号列表) 时才尝试
         let mut peers_to_dial = Vec::new();
@@ -386,8 +637,10 @@ impl NetManager {
         for (did, state) in &self.backoff_map {
             if now >= state.next_retry_ts && !self.pending_dials.contains(did) {
                 // 【关键修复】不再依赖 cached_candidate，而是去地址簿(known_peers)里查
-                if let Some(candidate) = self.known_peers.get(did) {
-                    peers_to_dial.push(candidate.clone());
+                // 宣告已注销的设备不再按退避重拨，等它重新出现（CandidateFound 会立即拨号）；
+                // 从 core.db 载入、还没确认过的设备照常重拨（note_dial_failure 给的是长退避）
+                if let Some(known) = self.known_peers.get(did).filter(|k| !k.lost || k.unconfirmed) {
+                    peers_to_dial.push(known.candidate.clone());
                 } else {
                     // 极端情况：由于还没收到过 Discovery 就连过了（不太可能），或者数据丢失
                     // 只能等下一次 Discovery
@@ -397,21 +650,275 @@ impl NetManager {
         }
 
         for peer in peers_to_dial {
-            println!("[Net] Backoff expired for {}. Retrying...", peer.device_id);
             // 重试时更新下一次时间，防止下一帧重复触发（直到再次失败进入 B 步骤，或者成功进入 A 步骤）
             if let Some(state) = self.backoff_map.get_mut(&peer.device_id) {
                 // 临时推迟一点点，避免在此次拨号尚未完成时重复进入此循环
                 state.next_retry_ts = now + 5000;
             }
-            self.perform_dial(peer).await;
+            self.perform_dial(peer);
+        }
+
+        // --- D. 静态对端：不依赖 mDNS，按配置的地址拨号 ---
+        self.dial_static_peers(now);
+    }
+
+    /// 以地址拨出的会话握手后得知对方的 device_id：记下来，之后按 device_id 判断在线与退避
+    fn learn_static_peer_ids(&mut self) {
+        let mut learned = Vec::new();
+        for s in &self.sessions {
+            let Some(addr) = s.initial_id.strip_prefix(STATIC_PEER_PREFIX) else { continue };
+            let Some(did) = s.peer_id.lock().unwrap().clone() else { continue };
+            if let Some(slot) = self.static_peers.get_mut(addr) {
+                if slot.as_deref() != Some(did.as_str()) {
+                    *slot = Some(did.clone());
+                    learned.push((addr.to_string(), did));
+                }
+            }
+        }
+
+        for (addr, did) in learned {
+            self.pending_dials.remove(&static_key(&addr));
+            self.backoff_map.remove(&static_key(&addr));
+            if let Some(good) = self.last_good_addrs.remove(&static_key(&addr)) {
+                if did != self.config.device_id {
+                    let _ = self.store.lock().unwrap().set_peer_last_good_addr(&self.config.account_uid, &did, &good.to_string(), now_ms());
+                }
+                self.last_good_addrs.insert(did.clone(), good);
+            }
+            if did != self.config.device_id {
+                // 会话结束后按 device_id 进入退避，重拨仍由 dial_static_peers 负责
+                self.pending_dials.insert(did.clone());
+            }
+            let _ = self.store.lock().unwrap().set_static_peer_device(&self.config.account_uid, &addr, &did);
+            let mut log_store = self.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Network",
+                &format!("Static peer identified: addr={}, device_id={}", addr, did),
+                Some(&format!("静态对端已识别: 地址={}，设备ID={}", addr, did)),
+            );
+        }
+    }
+
+    /// 双方互相配置了静态地址时会同时拨号，得到两条到同一设备的会话，按 duplicate_sessions 收掉多余的
+    async fn close_duplicate_sessions(&mut self) {
+        let online: Vec<(usize, String, SessionRole)> = self.sessions.iter().enumerate()
+            .filter(|(_, s)| s.is_online())
+            .map(|(i, s)| (i, s.device_id(), s.role))
+            .collect();
+        let to_close: Vec<(usize, String)> = duplicate_sessions(&self.config.device_id, &online)
+            .into_iter()
+            .map(|i| (i, self.sessions[i].device_id()))
+            .collect();
+
+        for (i, did) in to_close {
+            {
+                let mut log_store = self.log_store.lock().unwrap();
+                let _ = log_store.log_info(
+                    "Network",
+                    &format!("Closing duplicate session: device_id={}, role={:?}", did, self.sessions[i].role),
+                    Some(&format!("正在关闭重复会话: 设备ID={}，角色={:?}", did, self.sessions[i].role)),
+                );
+            }
+            self.sessions[i].shutdown().await;
+        }
+    }
+
+    /// 拨号还没有会话的静态对端；解析失败与连接失败一样进入退避
+    fn dial_static_peers(&mut self, now: i64) {
+        let targets: Vec<(String, String)> = self.static_peers.iter()
+            .filter_map(|(addr, did)| {
+                // 地址指向本机
+                if did.as_deref() == Some(self.config.device_id.as_str()) {
+                    return None;
+                }
+                let key = did.clone().unwrap_or_else(|| static_key(addr));
+                let connected = self.sessions.iter()
+                    .any(|s| s.device_id() == key || s.initial_id == static_key(addr));
+                let waiting = self.backoff_map.get(&key).is_some_and(|b| now < b.next_retry_ts);
+                (!connected && !waiting && !self.pending_dials.contains(&key)).then(|| (addr.clone(), key))
+            })
+            .collect();
+
+        for (addr, key) in targets {
+            // 还不知道 device_id 时以地址标识会话，握手后由 learn_static_peer_ids 改记；域名在拨号任务里解析
+            self.perform_dial(PeerCandidate { device_id: key, addrs: vec![addr], capabilities: Vec::new(), device_name: None });
+        }
+    }
+
+    fn is_static_peer(&self, key: &str) -> bool {
+        self.static_peers.iter().any(|(addr, did)| did.as_deref() == Some(key) || static_key(addr) == key)
+    }
+
+    /// 对端重新上线后以原 transfer_id 续传；期间向其他持有同一内容的在线设备换源续传；
+    /// 等待过久的拉取以 PEER_OFFLINE 失败
+    async fn resume_interrupted(&mut self, now: i64) {
+        let queue = std::mem::take(&mut self.resume_queue);
+        for mut queued in queue {
+            if let Some(session) = self.sessions.iter().find(|s| s.device_id() == queued.device_id && s.is_online()) {
+                let _ = session.cmd_tx.send(SessionCmd::ResumeTransfer(queued.pull)).await;
+            } else if queued.searching {
+                self.resume_queue.push(queued);
+            } else if now - queued.interrupted_ts > RESUME_WAIT_MS {
+                let payload = crate::model::CoreErrorPayload {
+                    code: "PEER_OFFLINE".to_string(),
+                    message: format!("Device {} did not come back online", queued.device_id),
+                    scope: "Transfer".to_string(),
+                    retryable: true,
+                    affects_session: false,
+                    detail: Some(serde_json::json!({ "transfer_id": queued.pull.transfer_id })),
+                };
+                let evt = serde_json::json!({
+                    "type": "TRANSFER_FAILED",
+                    "ts_ms": now,
+                    "payload": payload
+                });
+                self.event_sink.emit(evt.to_string());
+                self.fetch_routes.lock().unwrap().notify(FetchMsg::Failed {
+                    transfer_id: queued.pull.transfer_id,
+                    code: payload.code,
+                    message: payload.message,
+                });
+            } else {
+                if now >= queued.next_failover_ts {
+                    self.start_failover(&mut queued, now);
+                }
+                self.resume_queue.push(queued);
+            }
         }
     }
 
+    /// 在线且不是 exclude 的会话：内容的候选持有者
+    fn holder_candidates(&self, exclude: &str) -> Vec<SessionHandle> {
+        self.sessions.iter().filter(|s| s.is_online() && s.device_id() != exclude).cloned().collect()
+    }
+
+    /// 来源设备不在线时，向已缓存这份内容的其他在线设备按哈希拉取；询问在后台进行，不阻塞主循环
+    fn request_from_holder(
+        &self,
+        owner: String,
+        item_id: String,
+        file_id: Option<String>,
+        mime: Option<String>,
+        reply: oneshot::Sender<anyhow::Result<String>>,
+    ) {
+        let sha256 = {
+            let store = self.store.lock().unwrap();
+            store.get_content_sha256(&item_id, file_id.as_deref(), mime.as_deref()).ok().flatten()
+        };
+        let candidates = self.holder_candidates(&owner);
+        let (Some(sha256), false) = (sha256, candidates.is_empty()) else {
+            let _ = reply.send(Err(anyhow::anyhow!("Device {} not online", owner)));
+            return;
+        };
+        let log_store = self.log_store.clone();
+        tokio::spawn(async move {
+            let Some(holder) = find_holder(candidates, &sha256).await else {
+                let _ = reply.send(Err(anyhow::anyhow!("Device {} not online and no online peer has the content", owner)));
+                return;
+            };
+            {
+                let mut log_store = log_store.lock().unwrap();
+                let _ = log_store.log_info(
+                    "Network",
+                    &format!("Source device offline, fetching from another holder: item_id={}, source={}, holder={}",
+                            item_id, owner, holder.device_id()),
+                    Some(&format!("来源设备不在线，改从其他持有该内容的设备拉取: 项目ID={}，来源={}，持有者={}",
+                            item_id, owner, holder.device_id())),
+                );
+            }
+            let _ = holder.cmd_tx.send(SessionCmd::RequestTransfer {
+                item_id,
+                file_id,
+                mime,
+                sha256: Some(sha256),
+                reply_tx: reply,
+            }).await;
+        });
+    }
+
+    /// 在后台询问其他在线设备是否持有中断拉取的内容，结果由 handle_failover 处理
+    fn start_failover(&self, queued: &mut QueuedResume, now: i64) {
+        let pull = &queued.pull;
+        let sha256 = pull.sha256.clone().or_else(|| {
+            let store = self.store.lock().unwrap();
+            store.get_content_sha256(&pull.item_id, pull.file_id.as_deref(), pull.mime.as_deref()).ok().flatten()
+        });
+        let candidates = self.holder_candidates(&queued.device_id);
+        let (Some(sha256), false) = (sha256, candidates.is_empty()) else {
+            queued.next_failover_ts = now + FAILOVER_RETRY_MS;
+            return;
+        };
+        queued.searching = true;
+        let transfer_id = pull.transfer_id.clone();
+        let result_tx = self.failover_tx.clone();
+        tokio::spawn(async move {
+            let holder = find_holder(candidates, &sha256).await.map(|s| s.device_id());
+            let _ = result_tx.send(FailoverResult { transfer_id, sha256, holder }).await;
+        });
+    }
+
+    async fn handle_failover(&mut self, res: FailoverResult) {
+        // 询问期间已被取消、或原设备已重新上线续传
+        let Some(pos) = self.resume_queue.iter().position(|q| q.pull.transfer_id == res.transfer_id) else { return };
+        let holder = res.holder.as_deref().and_then(|did| {
+            self.sessions.iter().find(|s| s.device_id() == did && s.is_online()).cloned()
+        });
+        let Some(holder) = holder else {
+            let queued = &mut self.resume_queue[pos];
+            queued.searching = false;
+            queued.next_failover_ts = now_ms() + FAILOVER_RETRY_MS;
+            return;
+        };
+
+        let mut queued = self.resume_queue.remove(pos);
+        queued.pull.sha256 = Some(res.sha256);
+        let to_device = holder.device_id();
+        {
+            let mut log_store = self.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Network",
+                &format!("Resuming interrupted transfer from another holder: transfer_id={}, from={}, to={}",
+                        queued.pull.transfer_id, queued.device_id, to_device),
+                Some(&format!("改从其他持有该内容的设备续传: 传输ID={}，原设备={}，新设备={}",
+                        queued.pull.transfer_id, queued.device_id, to_device)),
+            );
+        }
+        let evt = serde_json::json!({
+            "type": "TRANSFER_SOURCE_CHANGED",
+            "ts_ms": now_ms(),
+            "payload": {
+                "transfer_id": queued.pull.transfer_id,
+                "from_device_id": queued.device_id,
+                "to_device_id": to_device
+            }
+        });
+        self.event_sink.emit(evt.to_string());
+        let _ = holder.cmd_tx.send(SessionCmd::ResumeTransfer(queued.pull)).await;
+    }
+
     async fn handle_discovery_event(&mut self, event: DiscoveryEvent) {
         match event {
             DiscoveryEvent::CandidateFound(peer) => {
                 // 【新增】更新地址簿：这是我们唯一的记忆来源
-                self.known_peers.insert(peer.device_id.clone(), peer.clone());
+                let old_addrs = self.known_peers.get(&peer.device_id).map(|k| k.candidate.addrs.clone());
+                let now = now_ms();
+                let _ = self.store.lock().unwrap().upsert_peer(&self.config.account_uid, &PeerRecord {
+                    device_id: peer.device_id.clone(),
+                    device_name: peer.device_name.clone(),
+                    addrs: peer.addrs.clone(),
+                    capabilities: peer.capabilities.clone(),
+                    last_seen_ms: now,
+                    last_good_addr: None,
+                });
+                let mut known = KnownPeer { candidate: peer.clone(), last_seen_ms: now, lost: false, unconfirmed: false };
+                // 这次宣告没带设备名（旧版本）时保留之前知道的
+                if known.candidate.device_name.is_none() {
+                    known.candidate.device_name = self.known_peers.get(&peer.device_id).and_then(|k| k.candidate.device_name.clone());
+                }
+                self.known_peers.insert(peer.device_id.clone(), known);
+                if let Some(old_addrs) = old_addrs.filter(|old| addrs_changed(old, &peer.addrs)) {
+                    self.on_peer_address_changed(&peer, old_addrs).await;
+                }
 
                 if self.config.device_id >= peer.device_id { return; }
                 if self.sessions.iter().any(|s| s.device_id() == peer.device_id) { return; }
@@ -428,9 +935,62 @@ impl NetManager {
                     }
                 }
 
-                self.perform_dial(peer).await;
+                self.perform_dial(peer);
+            }
+            DiscoveryEvent::CandidateLost(device_id) => {
+                // 保留地址簿条目：列表里显示为离线，重新出现时照常拨号
+                // 已建立的会话不在这里关闭，是否断线仍由心跳和 QUIC 超时判断
+                let Some(known) = self.known_peers.get_mut(&device_id) else { return };
+                known.lost = true;
+                let mut log_store = self.log_store.lock().unwrap();
+                let _ = log_store.log_info(
+                    "Network",
+                    &format!("Peer no longer announced on the network: device_id={}", device_id),
+                    Some(&format!("对等设备已停止在网络上宣告: 设备ID={}", device_id)),
+                );
+            }
+        }
+    }
+
+    /// 对端换了地址（DHCP 续租、笔记本切换网络）。
+    /// QUIC 只有客户端能迁移：对端拨入的会话由对端自己迁移过来；
+    /// 我方拨出的会话如果还连着已不在宣告里的旧地址，关掉它并在回收后立即按新地址重拨
+    async fn on_peer_address_changed(&mut self, peer: &PeerCandidate, old_addrs: Vec<String>) {
+        {
+            let mut log_store = self.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Network",
+                &format!("Peer address changed: device_id={}, old={:?}, new={:?}", peer.device_id, old_addrs, peer.addrs),
+                Some(&format!("对等设备地址已变化: 设备ID={}，旧地址={:?}，新地址={:?}", peer.device_id, old_addrs, peer.addrs)),
+            );
+        }
+        let evt = serde_json::json!({
+            "type": "PEER_ADDRESS_CHANGED",
+            "ts_ms": now_ms(),
+            "payload": {
+                "device_id": peer.device_id,
+                "old_addrs": old_addrs,
+                "new_addrs": peer.addrs,
+            }
+        });
+        self.event_sink.emit(evt.to_string());
+
+        for s in &self.sessions {
+            if s.role != SessionRole::Client || s.device_id() != peer.device_id || s.is_finished() {
+                continue;
+            }
+            if session_addr_stale(s.remote_addr, &peer.addrs) {
+                {
+                    let mut log_store = self.log_store.lock().unwrap();
+                    let _ = log_store.log_info(
+                        "Network",
+                        &format!("Peer moved away, closing session to redial: device_id={}, old_addr={}", peer.device_id, s.remote_addr),
+                        Some(&format!("对等设备地址已变化，关闭会话后重拨: 设备ID={}，原地址={}", peer.device_id, s.remote_addr)),
+                    );
+                }
+                self.redial_after_close.insert(peer.device_id.clone());
+                s.shutdown().await;
             }
-            _ => {}
         }
     }
 
@@ -466,77 +1026,162 @@ impl NetManager {
 
         for session in &self.sessions {
             if session.is_online() {
-                let _ = session.cmd_tx.send(SessionCmd::SendMeta(meta.clone())).await;
+                let _ = session.cmd_tx.send(SessionCmd::SendMeta(Box::new(meta.clone()))).await;
             }
         }
     }
 
-    async fn perform_dial(&mut self, peer: PeerCandidate) {
-        // 1. 获取本机 Socket 的“血统”
-        let i_am_v4 = self.transport.is_ipv4();
-
-        // 2. 智能过滤：只保留跟我血统一致的地址
-        let valid_addrs: Vec<std::net::SocketAddr> = peer.addrs.iter()
-            .filter_map(|s| s.parse().ok())
-            .filter(|addr: &std::net::SocketAddr| {
-                // 如果我是 v4，我只要 v4；如果我是 v6，我只要 v6
-                addr.is_ipv4() == i_am_v4
-            })
-            .collect();
+    /// 删除通知不携带内容，不受 DenyAll 限制：之前共享出去的条目也要能收回
+    async fn broadcast_delete(&self, item_id: String, deleted_ts_ms: i64) {
+        let online_count = self.sessions.iter().filter(|s| s.is_online()).count();
+        {
+            let mut log_store = self.log_store.lock().unwrap();
+            let _ = log_store.log_info(
+                "Network",
+                &format!("Broadcasting item deletion to {} online peers: item_id={}", online_count, item_id),
+                Some(&format!("正在向 {} 个在线对等设备广播删除: 项目ID={}", online_count, item_id)),
+            );
+        }
 
-        // 3. 如果 mDNS 这次只发了不匹配的地址（比如我是v4，对方只发了v6）
-        // 直接忽略，不要报错，不要 Backoff，静静等待下一波更新
-        if valid_addrs.is_empty() {
-            // println!("[Net] Skipped {} (Protocol mismatch: I am v4={}, Peer has {:?})",
-            //          peer.device_id, i_am_v4, peer.addrs);
-            return;
+        for session in &self.sessions {
+            if session.is_online() {
+                let _ = session.cmd_tx.send(SessionCmd::SendDelete {
+                    item_id: item_id.clone(),
+                    deleted_ts_ms,
+                }).await;
+            }
         }
+    }
 
-        println!("[Net] Initiating connection to {} (Compatible Addrs: {:?})...", peer.device_id, valid_addrs);
+    /// 在后台任务里对候选地址做 Happy Eyeballs 竞速，结果经 dialed_rx 回到 handle_dial_result
+    fn perform_dial(&mut self, peer: PeerCandidate) {
+        println!("[Net] Initiating connection to {} (Addrs: {:?})...", peer.device_id, peer.addrs);
         self.pending_dials.insert(peer.device_id.clone());
 
-        let mut success = false;
+        let transport = self.transport.clone();
+        let preferred = self.last_good_addrs.get(&peer.device_id).copied();
+        let dialed_tx = self.dialed_tx.clone();
+        let log_store = self.log_store.clone();
+        tokio::spawn(async move {
+            let addrs = resolve_dial_addrs(&transport, &peer.addrs, &log_store).await;
+            let outcome = if addrs.is_empty() {
+                DialOutcome::NoUsableAddr
+            } else {
+                match race_connect(&transport, dial_order(addrs, preferred)).await {
+                    Some((conn, addr)) => DialOutcome::Connected(conn, addr),
+                    None => DialOutcome::Failed,
+                }
+            };
+            let _ = dialed_tx.send(DialResult { device_id: peer.device_id, outcome }).await;
+        });
+    }
 
-        // 3. 【修改】只遍历有效的 valid_addrs
-        for addr in valid_addrs {
-            match self.transport.connect(&addr.to_string()).await { // 注意：quinn connect 接受 &str 或 SocketAddr
-                Ok(conn) => {
-                    let handle = SessionActor::spawn(
-                        SessionRole::Client,
-                        conn,
-                        self.config.clone(),
-                        self.event_sink.clone(),
-                        self.store.clone(),
-                        self.cas.clone(),
-                        Some(peer.device_id.clone()),
-                        self.log_store.clone(),
+    fn handle_dial_result(&mut self, res: DialResult) {
+        match res.outcome {
+            DialOutcome::Connected(conn, addr) => {
+                {
+                    let mut log_store = self.log_store.lock().unwrap();
+                    let _ = log_store.log_info(
+                        "Network",
+                        &format!("Connected to peer: device_id={}, addr={}", res.device_id, addr),
+                        Some(&format!("已连接对等设备: 设备ID={}，地址={}", res.device_id, addr)),
                     );
-                    self.sessions.push(handle);
-                    success = true;
-                    break;
                 }
-                Err(e) => {
-                    println!("[Net] Failed to connect to {}: {}", addr, e);
+                self.last_good_addrs.insert(res.device_id.clone(), addr);
+                if !res.device_id.starts_with(STATIC_PEER_PREFIX) {
+                    let now = now_ms();
+                    let _ = self.store.lock().unwrap().set_peer_last_good_addr(&self.config.account_uid, &res.device_id, &addr.to_string(), now);
+                    // 连得上就说明它在：之后断线照常按退避重拨
+                    if let Some(known) = self.known_peers.get_mut(&res.device_id) {
+                        known.lost = false;
+                        known.unconfirmed = false;
+                        known.last_seen_ms = known.last_seen_ms.max(now);
+                    }
+                }
+                let handle = SessionActor::spawn(
+                    SessionRole::Client,
+                    conn,
+                    self.config.clone(),
+                    self.event_sink.clone(),
+                    self.store.clone(),
+                    self.cas.clone(),
+                    Some(res.device_id),
+                    self.log_store.clone(),
+                    self.scheduler.clone(),
+                    self.fetch_routes.clone(),
+                );
+                self.sessions.push(handle);
+            }
+            // 所有地址都试过但连不上：进入退避
+            DialOutcome::Failed => self.note_dial_failure(&res.device_id),
+            // mDNS 这次只发了本机连不上的地址（比如我只监听 v4，对方只发了 v6）：
+            // 不报错，不退避，静静等待下一波更新；静态对端解析失败、地址簿里还没确认的设备则照常退避
+            DialOutcome::NoUsableAddr => {
+                let unconfirmed = self.known_peers.get(&res.device_id).is_some_and(|k| k.unconfirmed);
+                if self.is_static_peer(&res.device_id) || unconfirmed {
+                    self.note_dial_failure(&res.device_id);
+                } else {
+                    self.pending_dials.remove(&res.device_id);
                 }
             }
         }
+    }
 
-        // 4. 处理结果
-        if !success {
-            self.pending_dials.remove(&peer.device_id);
-
-            // 只有当“真的尝试了 IPv4 地址但连不上”时，才触发退避
-            let now = now_ms();
-            let entry = self.backoff_map.entry(peer.device_id.clone()).or_insert(BackoffState { fail_count: 0, next_retry_ts: 0 });
-            entry.fail_count += 1;
-            let delay = 2u64.pow(entry.fail_count.min(6));
-            entry.next_retry_ts = now + (delay * 1000) as i64;
+    /// 启动时按地址簿直接拨号，不等 mDNS：组播不稳定的网络里对端可能很久都不会重新宣告。
+    /// 对方未必发现得了我们，所以不按 device_id 大小决定谁拨号，重复的会话由 close_duplicate_sessions 收掉；
+    /// 静态对端仍由 dial_static_peers 负责
+    fn dial_known_peers(&mut self) {
+        let static_dids: HashSet<&String> = self.static_peers.values().flatten().collect();
+        let mut dials = Vec::new();
+        for known in self.known_peers.values() {
+            let did = &known.candidate.device_id;
+            if static_dids.contains(did) {
+                continue;
+            }
+            let mut peer = known.candidate.clone();
+            if let Some(addr) = self.last_good_addrs.get(did).map(|a| a.to_string()) {
+                if !peer.addrs.contains(&addr) {
+                    peer.addrs.push(addr);
+                }
+            }
+            if !peer.addrs.is_empty() {
+                dials.push(peer);
+            }
+        }
+        for peer in dials {
+            self.perform_dial(peer);
+        }
+    }
 
-            println!("[Net] Dial failed for {}. Backoff {}s", peer.device_id, delay);
+    /// 会话结束（或退出）时记下最后一次收到对端消息的时间
+    fn note_peer_seen(&mut self, device_id: &str, seen_ms: i64) {
+        if let Some(known) = self.known_peers.get_mut(device_id) {
+            known.last_seen_ms = known.last_seen_ms.max(seen_ms);
         }
+        let _ = self.store.lock().unwrap().touch_peer(&self.config.account_uid, device_id, seen_ms);
+    }
+
+    fn note_dial_failure(&mut self, device_id: &str) {
+        self.pending_dials.remove(device_id);
+
+        let now = now_ms();
+        let unconfirmed = self.known_peers.get(device_id).is_some_and(|k| k.unconfirmed);
+        let entry = self.backoff_map.entry(device_id.to_string()).or_insert(BackoffState { fail_count: 0, next_retry_ts: 0 });
+        entry.fail_count += 1;
+        let delay = dial_backoff_secs(entry.fail_count, unconfirmed);
+        entry.next_retry_ts = now + (delay * 1000) as i64;
+
+        println!("[Net] Dial failed for {}. Backoff {}s", device_id, delay);
     }
 
-    async fn shutdown(&self) {
+    async fn shutdown(&mut self) {
+        let seen: Vec<(String, i64)> = self.sessions.iter()
+            .filter(|s| s.is_online())
+            .map(|s| (s.device_id(), s.last_seen_ts_ms()))
+            .collect();
+        for (did, ts) in seen {
+            self.note_peer_seen(&did, ts);
+        }
         self.discovery.shutdown().await;
         self.transport.shutdown();
         for s in &self.sessions {
@@ -545,3 +1190,224 @@ impl NetManager {
     }
 }
 
+/// 拨号失败后的退避秒数：2、4、8 … 64 秒；地址簿里还没确认过的设备从 30 秒起翻倍，最长 30 分钟
+pub(crate) fn dial_backoff_secs(fail_count: u32, unconfirmed: bool) -> u64 {
+    if unconfirmed {
+        (UNCONFIRMED_RETRY_BASE_SECS << fail_count.saturating_sub(1).min(10)).min(UNCONFIRMED_RETRY_MAX_SECS)
+    } else {
+        2u64.pow(fail_count.min(6))
+    }
+}
+
+/// 解析候选地址（"ip:port"，静态对端也可以是 "host:port"），只保留本机绑定的 socket 能连到的
+async fn resolve_dial_addrs(transport: &Transport, addrs: &[String], log_store: &Mutex<LogStore>) -> Vec<SocketAddr> {
+    let mut resolved = Vec::new();
+    for addr in addrs {
+        match addr.parse::<SocketAddr>() {
+            Ok(a) => resolved.push(a),
+            Err(_) => match tokio::net::lookup_host(addr.as_str()).await {
+                Ok(found) => resolved.extend(found),
+                Err(e) => {
+                    let mut log_store = log_store.lock().unwrap();
+                    let _ = log_store.log_warn(
+                        "Network",
+                        &format!("Failed to resolve peer address: addr={}, error={}", addr, e),
+                        Some(&format!("对端地址解析失败: 地址={}，错误={}", addr, e)),
+                    );
+                }
+            },
+        }
+    }
+    resolved.retain(|a| transport.can_reach(a));
+    resolved
+}
+
+/// 在线会话（下标、对端 device_id、角色）中本端应关闭的重复会话。
+/// 两端按同一规则取舍：优先保留 device_id 较小一方发起的连接；
+/// 同一方发起的多条只由发起方关闭较新的，避免两端各关一条
+pub(crate) fn duplicate_sessions(own: &str, online: &[(usize, String, SessionRole)]) -> Vec<usize> {
+    let mut by_peer: HashMap<&str, Vec<(usize, SessionRole)>> = HashMap::new();
+    for (i, did, role) in online {
+        by_peer.entry(did.as_str()).or_default().push((*i, *role));
+    }
+
+    let mut to_close = Vec::new();
+    for (did, sessions) in by_peer {
+        if sessions.len() < 2 {
+            continue;
+        }
+        let preferred = std::cmp::min(own, did);
+        let initiator = |role: SessionRole| match role {
+            SessionRole::Client => own,
+            SessionRole::Server => did,
+        };
+        let &(keep, keep_role) = sessions.iter().min_by_key(|&&(i, role)| (initiator(role) != preferred, i)).unwrap();
+        for &(i, role) in &sessions {
+            if i == keep {
+                continue;
+            }
+            if initiator(role) != initiator(keep_role) || role == SessionRole::Client {
+                to_close.push(i);
+            }
+        }
+    }
+    to_close.sort_unstable();
+    to_close
+}
+
+/// 拨号顺序：上次连接成功的地址最先，其余按 IPv6、IPv4 交替排列（RFC 8305）
+pub(crate) fn dial_order(addrs: Vec<SocketAddr>, preferred: Option<SocketAddr>) -> Vec<SocketAddr> {
+    let mut unique = Vec::new();
+    for a in addrs {
+        if !unique.contains(&a) {
+            unique.push(a);
+        }
+    }
+    let mut ordered = Vec::with_capacity(unique.len());
+    if let Some(p) = preferred.filter(|p| unique.contains(p)) {
+        ordered.push(p);
+        unique.retain(|a| *a != p);
+    }
+    let (mut v6, mut v4): (VecDeque<_>, VecDeque<_>) = unique.into_iter().partition(|a| a.is_ipv6());
+    while !v6.is_empty() || !v4.is_empty() {
+        ordered.extend(v6.pop_front());
+        ordered.extend(v4.pop_front());
+    }
+    ordered
+}
+
+/// Happy Eyeballs：按顺序每隔 DIAL_STAGGER 发起一次连接（前一个已失败则立即发起下一个），
+/// 第一个完成 QUIC 握手的胜出，其余尝试随之丢弃。一个不通的地址不再让后面的地址干等整个超时
+pub(crate) async fn race_connect(transport: &Transport, addrs: Vec<SocketAddr>) -> Option<(Connection, SocketAddr)> {
+    let mut waiting: VecDeque<SocketAddr> = addrs.into();
+    let mut attempts = FuturesUnordered::new();
+    let mut next_start = tokio::time::Instant::now();
+    loop {
+        tokio::select! {
+            Some((addr, res)) = attempts.next(), if !attempts.is_empty() => {
+                match res {
+                    Ok(conn) => return Some((conn, addr)),
+                    Err(e) => {
+                        println!("[Net] Failed to connect to {}: {}", addr, e);
+                        next_start = tokio::time::Instant::now();
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(next_start), if !waiting.is_empty() => {
+                let addr = waiting.pop_front().unwrap();
+                attempts.push(async move { (addr, transport.connect(&addr.to_string()).await) });
+                next_start = tokio::time::Instant::now() + DIAL_STAGGER;
+            }
+            else => return None,
+        }
+    }
+}
+
+/// 两次宣告的地址集合是否不同（忽略顺序和重复）
+pub(crate) fn addrs_changed(old: &[String], new: &[String]) -> bool {
+    let old: HashSet<&String> = old.iter().collect();
+    let new: HashSet<&String> = new.iter().collect();
+    old != new
+}
+
+/// 会话连着的地址是否已不在对端最新宣告的地址里；地址都无法解析时无从判断，视为未失效
+pub(crate) fn session_addr_stale(remote: SocketAddr, addrs: &[String]) -> bool {
+    let canonical = |a: SocketAddr| (a.ip().to_canonical(), a.port());
+    let announced: Vec<SocketAddr> = addrs.iter().filter_map(|a| a.parse().ok()).collect();
+    !announced.is_empty() && !announced.iter().any(|a| canonical(*a) == canonical(remote))
+}
+
+/// 错误信息以错误码开头时（如 "PORT_IN_USE: ..."）取出错误码
+fn error_code(e: &anyhow::Error, fallback: &str) -> String {
+    let msg = e.to_string();
+    match msg.split_once(": ") {
+        Some((code, _)) if !code.is_empty() && code.chars().all(|c| c.is_ascii_uppercase() || c == '_') => code.to_string(),
+        _ => fallback.to_string(),
+    }
+}
+
+fn static_key(addr: &str) -> String {
+    format!("{STATIC_PEER_PREFIX}{addr}")
+}
+
+/// 载入地址簿：都先标记为 lost / unconfirmed，等 mDNS 宣告或拨号成功
+fn load_known_peers(
+    config: &crate::api::CoreConfig,
+    store: &Arc<Mutex<Store>>,
+    log_store: &Arc<Mutex<LogStore>>,
+) -> (HashMap<String, KnownPeer>, HashMap<String, SocketAddr>) {
+    let loaded = store.lock().unwrap().list_peers(&config.account_uid);
+    let records = match loaded {
+        Ok(records) => records,
+        Err(e) => {
+            let mut log_store = log_store.lock().unwrap();
+            let _ = log_store.log_error(
+                "Network",
+                &format!("Failed to load peer address book: {}", e),
+                Some(&format!("加载对端地址簿失败: {}", e)),
+                Some(&e.to_string()),
+            );
+            return (HashMap::new(), HashMap::new());
+        }
+    };
+
+    let mut known_peers = HashMap::new();
+    let mut last_good_addrs = HashMap::new();
+    for r in records {
+        if r.device_id == config.device_id {
+            continue;
+        }
+        if let Some(addr) = r.last_good_addr.as_deref().and_then(|a| a.parse().ok()) {
+            last_good_addrs.insert(r.device_id.clone(), addr);
+        }
+        let candidate = PeerCandidate {
+            device_id: r.device_id.clone(),
+            addrs: r.addrs,
+            capabilities: r.capabilities,
+            device_name: r.device_name,
+        };
+        known_peers.insert(r.device_id, KnownPeer { candidate, last_seen_ms: r.last_seen_ms, lost: true, unconfirmed: true });
+    }
+    (known_peers, last_good_addrs)
+}
+
+fn load_static_peers(
+    config: &crate::api::CoreConfig,
+    store: &Arc<Mutex<Store>>,
+    log_store: &Arc<Mutex<LogStore>>,
+) -> HashMap<String, Option<String>> {
+    let loaded = store.lock().unwrap().list_static_peers(&config.account_uid);
+    match loaded {
+        Ok(peers) => peers.into_iter().map(|p| (p.addr, p.device_id)).collect(),
+        Err(e) => {
+            let mut log_store = log_store.lock().unwrap();
+            let _ = log_store.log_error(
+                "Network",
+                &format!("Failed to load static peers: {}", e),
+                Some(&format!("加载静态对端失败: {}", e)),
+                Some(&e.to_string()),
+            );
+            HashMap::new()
+        }
+    }
+}
+
+/// 并行询问候选会话是否持有 sha256 对应的内容，返回第一个回答“有”的
+async fn find_holder(candidates: Vec<SessionHandle>, sha256: &str) -> Option<SessionHandle> {
+    let mut asks: FuturesUnordered<_> = candidates
+        .into_iter()
+        .map(|session| async move {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            let query = SessionCmd::QueryHave { sha256s: vec![sha256.to_string()], reply_tx };
+            session.cmd_tx.send(query).await.ok()?;
+            let have = tokio::time::timeout(HAVE_QUERY_TIMEOUT, reply_rx).await.ok()?.ok()?;
+            have.iter().any(|s| s == sha256).then_some(session)
+        })
+        .collect();
+    while let Some(answer) = asks.next().await {
+        if answer.is_some() {
+            return answer;
+        }
+    }
+    None
+}
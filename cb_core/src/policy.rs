@@ -19,6 +19,27 @@ pub struct AppConfig {
 	/// GC 配置：CAS 缓存最大字节数
 	#[serde(default = "default_gc_cas")]
 	pub gc_cas_max_bytes: i64,
+
+	/// 各类型条目的有效期
+	#[serde(default)]
+	pub expiry: ExpiryPolicy,
+
+	/// CAS 完整性巡检
+	#[serde(default)]
+	pub scrub: ScrubPolicy,
+
+	/// 传输限速
+	#[serde(default)]
+	pub transfer: TransferPolicy,
+
+	/// 监听端口与绑定地址
+	#[serde(default)]
+	pub network: NetworkPolicy,
+
+	/// 手动配置的对端地址（"host:port"），用于组播不通、mDNS 发现不到对方的网络；
+	/// 启动时并入 core.db，与 Core::add_static_peer 添加的地址一起拨号
+	#[serde(default)]
+	pub static_peers: Vec<String>,
 }
 
 impl Default for AppConfig {
@@ -28,6 +49,11 @@ impl Default for AppConfig {
 			global_policy: GlobalPolicy::default(),
 			gc_history_max_items: default_gc_history(),
 			gc_cas_max_bytes: default_gc_cas(),
+			expiry: ExpiryPolicy::default(),
+			scrub: ScrubPolicy::default(),
+			transfer: TransferPolicy::default(),
+			network: NetworkPolicy::default(),
+			static_peers: Vec::new(),
 		}
 	}
 }
@@ -35,6 +61,125 @@ impl Default for AppConfig {
 fn default_gc_history() -> i64 { 50_000 }
 fn default_gc_cas() -> i64 { 1024 * 1024 * 1024 } // 1GB
 
+/// 条目有效期（毫秒，从创建时间算起）；<= 0 表示永不过期。
+/// 过期条目会被 GC 清理，也不再向对端提供内容。
+///
+/// 置顶是有意的豁免：用户置顶即表示“一直保留”，置顶期间过期时间不生效；
+/// 取消置顶后若已过期，下一次 GC 即清理。置顶只在本机生效，对端仍按自己的规则拒绝提供已过期的内容。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExpiryPolicy {
+	#[serde(default = "default_ttl")]
+	pub text_ttl_ms: i64,
+	#[serde(default = "default_ttl")]
+	pub image_ttl_ms: i64,
+	#[serde(default = "default_ttl")]
+	pub file_list_ttl_ms: i64,
+	#[serde(default = "default_ttl")]
+	pub rich_text_ttl_ms: i64,
+}
+
+fn default_ttl() -> i64 { 7 * 24 * 3600 * 1000 } // 7 天
+
+impl Default for ExpiryPolicy {
+	fn default() -> Self {
+		Self {
+			text_ttl_ms: default_ttl(),
+			image_ttl_ms: default_ttl(),
+			file_list_ttl_ms: default_ttl(),
+			rich_text_ttl_ms: default_ttl(),
+		}
+	}
+}
+
+impl ExpiryPolicy {
+	/// 计算条目的过期时间；None 表示永不过期
+	pub fn expires_at(&self, kind: &ItemKind, created_ts_ms: i64) -> Option<i64> {
+		let ttl = match kind {
+			ItemKind::Text => self.text_ttl_ms,
+			ItemKind::Image => self.image_ttl_ms,
+			ItemKind::FileList => self.file_list_ttl_ms,
+			ItemKind::RichText => self.rich_text_ttl_ms,
+		};
+		(ttl > 0).then(|| created_ts_ms.saturating_add(ttl))
+	}
+}
+
+/// CAS 完整性巡检：后台按限速逐个重算 blob 的 sha256，发现损坏即隔离并尝试重新拉取。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScrubPolicy {
+	/// 重算哈希的速率上限（字节/秒）；<= 0 表示关闭后台巡检
+	#[serde(default = "default_scrub_rate")]
+	pub bytes_per_sec: i64,
+	/// 两轮巡检之间的间隔（毫秒）
+	#[serde(default = "default_scrub_interval")]
+	pub interval_ms: i64,
+}
+
+fn default_scrub_rate() -> i64 { 4 * 1024 * 1024 } // 4MB/s
+fn default_scrub_interval() -> i64 { 6 * 3600 * 1000 } // 6 小时
+
+impl Default for ScrubPolicy {
+	fn default() -> Self {
+		Self {
+			bytes_per_sec: default_scrub_rate(),
+			interval_ms: default_scrub_interval(),
+		}
+	}
+}
+
+/// 传输限速（字节/秒，全部对端合计）；<= 0 表示不限速。
+/// 文字 / 图片等交互传输优先，批量的文件传输在限额内让路。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransferPolicy {
+	#[serde(default)]
+	pub upload_bytes_per_sec: i64,
+	/// 接收端按读出的字节（压缩后）限速，全部下载共用
+	#[serde(default)]
+	pub download_bytes_per_sec: i64,
+	/// 传输压缩（zstd）；双方都开启时才生效，已压缩的格式不再压缩
+	#[serde(default = "default_compression")]
+	pub compression: bool,
+}
+
+fn default_compression() -> bool { true }
+
+impl Default for TransferPolicy {
+	fn default() -> Self {
+		Self {
+			upload_bytes_per_sec: 0,
+			download_bytes_per_sec: 0,
+			compression: default_compression(),
+		}
+	}
+}
+
+/// 监听配置。固定端口便于配置防火墙规则和静态对端；mDNS 只宣告实际绑定的地址。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkPolicy {
+	/// 监听端口；0 表示每次启动随机分配
+	#[serde(default)]
+	pub listen_port: u16,
+	/// 绑定的 IP 地址或网卡名（如 "192.168.1.5"、"::"、"eth0"）；为空表示所有网卡
+	#[serde(default)]
+	pub bind: Vec<String>,
+	/// 双栈：IPv6 通配地址同时接收 IPv4；未配置 bind 时监听 "[::]"（否则只监听 "0.0.0.0"），
+	/// 按网卡名绑定时也包括该网卡的 IPv6 地址
+	#[serde(default = "default_dual_stack")]
+	pub dual_stack: bool,
+}
+
+fn default_dual_stack() -> bool { true }
+
+impl Default for NetworkPolicy {
+	fn default() -> Self {
+		Self {
+			listen_port: 0,
+			bind: Vec::new(),
+			dual_stack: default_dual_stack(),
+		}
+	}
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SizeLimits {
     // 软限制，在尝试拉取时如果超出限制外壳会弹窗确认
@@ -49,8 +194,17 @@ pub struct SizeLimits {
 
     // 文字自动拉取限制，超出了就不会在分析元数据的同时发送正文
     pub text_auto_prefetch_bytes: i64,
+
+    // 富文本（HTML / RTF）限制，按主表示的字节数计算
+    #[serde(default = "default_soft_rich_text")]
+    pub soft_rich_text_bytes: i64,
+    #[serde(default = "default_hard_rich_text")]
+    pub hard_rich_text_bytes: i64,
 }
 
+fn default_soft_rich_text() -> i64 { 4 * 1024 * 1024 } // 4MB
+fn default_hard_rich_text() -> i64 { 64 * 1024 * 1024 } // 64MB
+
 impl Default for SizeLimits {
     fn default() -> Self {
         Self {
@@ -61,6 +215,20 @@ impl Default for SizeLimits {
             hard_image_bytes: 256 * 1024 * 1024,           // 256MB
             hard_file_total_bytes: 2 * 1024 * 1024 * 1024, // 2GB
             text_auto_prefetch_bytes: 256 * 1024,          // 256KB
+            soft_rich_text_bytes: default_soft_rich_text(),
+            hard_rich_text_bytes: default_hard_rich_text(),
+        }
+    }
+}
+
+impl SizeLimits {
+    /// 该类型条目的硬限制
+    pub fn hard_bytes(&self, kind: &ItemKind) -> i64 {
+        match kind {
+            ItemKind::Text => self.hard_text_bytes,
+            ItemKind::Image => self.hard_image_bytes,
+            ItemKind::FileList => self.hard_file_total_bytes,
+            ItemKind::RichText => self.hard_rich_text_bytes,
         }
     }
 }
@@ -95,12 +263,7 @@ pub enum PolicyOutcome {
 /// force=true 表示“用户已确认超出软限制仍要同步”
 pub fn decide(kind: ItemKind, size_bytes: i64, force: bool, limits: &SizeLimits) -> PolicyOutcome {
     // 1) hard cap：必须拒绝
-    let hard = match kind {
-        ItemKind::Text => limits.hard_text_bytes,
-        ItemKind::Image => limits.hard_image_bytes,
-        ItemKind::FileList => limits.hard_file_total_bytes,
-    };
-    if size_bytes > hard {
+    if size_bytes > limits.hard_bytes(&kind) {
         return PolicyOutcome::RejectedHardCap { code: "ITEM_TOO_LARGE" };
     }
 
@@ -109,6 +272,7 @@ pub fn decide(kind: ItemKind, size_bytes: i64, force: bool, limits: &SizeLimits)
         ItemKind::Text => limits.soft_text_bytes,
         ItemKind::Image => limits.soft_image_bytes,
         ItemKind::FileList => limits.soft_file_total_bytes,
+        ItemKind::RichText => limits.soft_rich_text_bytes,
     };
 
     if size_bytes <= soft {
@@ -152,4 +316,14 @@ mod tests {
         let out = decide(ItemKind::Image, lim.hard_image_bytes + 1, true, &lim);
         assert_eq!(out, PolicyOutcome::RejectedHardCap { code: "ITEM_TOO_LARGE" });
     }
+
+    #[test]
+    fn rich_text_over_soft_is_lazy_and_needs_confirm() {
+        let lim = SizeLimits::default();
+        let out = decide(ItemKind::RichText, lim.soft_rich_text_bytes + 1, false, &lim);
+        assert_eq!(out, PolicyOutcome::Allowed {
+            strategy: MetaStrategy::MetaOnlyLazy,
+            needs_user_confirm: true
+        });
+    }
 }
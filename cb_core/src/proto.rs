@@ -3,21 +3,20 @@ use serde::{Deserialize, Serialize};
 use anyhow::Result;
 use bytes::Bytes;
 
-// 协议版本号
-pub const PROTOCOL_VERSION: u32 = 2;
+// 协议版本号（v3：内容数据改走每个传输独立的 QUIC 单向流）
+pub const PROTOCOL_VERSION: u32 = 3;
 
+/// 主双向流只承载控制消息；内容数据走独立的单向流，见 encode_stream_header
 #[derive(Debug)]
 pub enum CBFrame {
     Control(CtrlMsg), // JSON 信令 (Type=1)
-	Data {
-		transfer_id: String,
-		data: Bytes,
-	},
 }
 
 // 帧类型常量
 const FRAME_TYPE_CTRL: u8 = 0x01;
-const FRAME_TYPE_DATA: u8 = 0x02;
+
+/// 取消传输时 reset（发送端）/ stop（接收端）数据流使用的错误码
+pub const STREAM_CANCELLED: u32 = 1;
 
 
 // 鉴权成功后的会话标记
@@ -44,6 +43,9 @@ pub enum CtrlMsg {
         reply_to: Option<String>,
         server_device_id: String,
         protocol_version: u32,
+        /// 服务端的能力（与 Hello.capabilities 对应）；旧版本对端不带此字段
+        #[serde(default)]
+        capabilities: Vec<String>,
     },
 
     // 3. 鉴权失败
@@ -98,6 +100,13 @@ pub enum CtrlMsg {
         item: crate::api::ItemMeta,
     },
 
+    // 用户删除条目（scope=all）：收到后删除本地副本，并拒绝之后对它的 ContentGet
+    ItemDelete {
+        msg_id: Option<String>,
+        item_id: String,
+        deleted_ts_ms: i64,
+    },
+
     // 通用错误
     Error {
         reply_to: Option<String>,
@@ -118,7 +127,23 @@ pub enum CtrlMsg {
         item_id: String,
         // 如果是 FileList，这里指定具体要下哪个文件；如果是 Text/Image，这里留空或忽略
         file_id: Option<String>,
-        offset: Option<u64>, // 支持断点续传（预留）
+        offset: Option<u64>, // 断点续传：接收端已校验落盘的字节数
+        // 指定要拉取的表示（多格式 item / RichText 纯文本降级）；缺省为主格式
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime: Option<String>,
+        // 按内容寻址：向非来源设备拉取时带上，对端从自己的 CAS 中按哈希发送
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// 询问对端的 CAS 中有哪些内容 (B -> 任一在线设备)
+    HaveQuery {
+        msg_id: Option<String>,
+        sha256s: Vec<String>,
+    },
+    /// 回答 HaveQuery：sha256s 是询问中本端已缓存的那部分
+    HaveReply {
+        reply_to: Option<String>,
+        sha256s: Vec<String>,
     },
     /// 准备发送内容头 (A -> B)
     ContentBegin {
@@ -128,10 +153,12 @@ pub enum CtrlMsg {
         total_bytes: u64,
         sha256: String,
         mime: String,
-    },
-    /// 内容传输结束 (A -> B)
-    ContentEnd {
-        req_id: String,
+        /// 实际从哪个偏移开始发送（续传）；旧版本对端不带此字段
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        offset: Option<u64>,
+        /// 数据流采用的压缩（如 "zstd"）；不带表示原始字节
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compression: Option<String>,
     },
     /// 取消传输 (双向)
     ContentCancel {
@@ -164,25 +191,6 @@ impl Encoder<CBFrame> for CBFrameCodec {
                 dst.put_u8(FRAME_TYPE_CTRL); // Type = 1
                 dst.put_slice(&json_bytes);
             }
-			CBFrame::Data { transfer_id, data } => {
-				// 格式: [Len(4)][Type(1)][ID_Len(2)][ID_Bytes][Data]
-				let id_bytes = transfer_id.as_bytes();
-				let id_len = id_bytes.len();
-				if id_len > 65535 { return Err(anyhow::anyhow!("Transfer ID too long")); }
-
-				let frame_len = 1 + 2 + id_len + data.len(); // Type + ID_Len + ID + Data
-				if frame_len > MAX_FRAME_SIZE { return Err(anyhow::anyhow!("Frame too large")); }
-
-				dst.reserve(4 + frame_len);
-				dst.put_u32_le(frame_len as u32);
-				dst.put_u8(FRAME_TYPE_DATA);
-
-				// 写入 ID
-				dst.put_u16_le(id_len as u16);
-				dst.put_slice(id_bytes);
-				// 写入数据
-				dst.put_slice(&data);
-			}
         }
         Ok(())
     }
@@ -217,27 +225,13 @@ impl Decoder for CBFrameCodec {
         let type_byte = src[0];
 
         // 读取 Payload
-		let mut payload = src.split_to(len).split_off(1);
+		let payload = src.split_to(len).split_off(1);
 
         match type_byte {
             FRAME_TYPE_CTRL => {
                 let msg: CtrlMsg = serde_json::from_slice(&payload)?;
                 Ok(Some(CBFrame::Control(msg)))
             }
-            FRAME_TYPE_DATA => {
-				// 解码 ID
-				if payload.len() < 2 { return Err(anyhow::anyhow!("Data frame too short")); }
-				let id_len = u16::from_le_bytes([payload[0], payload[1]]) as usize;
-				payload.advance(2);
-
-				if payload.len() < id_len { return Err(anyhow::anyhow!("Data frame truncated ID")); }
-				let id_bytes = payload.split_to(id_len);
-				let transfer_id = String::from_utf8(id_bytes.to_vec())?;
-
-				// 剩下的就是 data
-				let data = payload.freeze();
-				Ok(Some(CBFrame::Data { transfer_id, data }))
-            }
             _ => {
                 // 未知帧类型，如果是兼容性考虑可以选择忽略，这里先报错
                 Err(anyhow::anyhow!("Unknown frame type: {}", type_byte))
@@ -245,3 +239,25 @@ impl Decoder for CBFrameCodec {
         }
     }
 }
+
+/// 内容数据流的流头：[ID_Len(2)][ID_Bytes]，之后直到流结束都是内容本身（从 ContentBegin.offset 起）。
+/// 流正常结束即传输结束；任一方取消时以 STREAM_CANCELLED reset / stop 该流。
+pub fn encode_stream_header(transfer_id: &str) -> Result<Bytes> {
+    let id_bytes = transfer_id.as_bytes();
+    if id_bytes.len() > u16::MAX as usize {
+        return Err(anyhow::anyhow!("Transfer ID too long"));
+    }
+    let mut buf = BytesMut::with_capacity(2 + id_bytes.len());
+    buf.put_u16_le(id_bytes.len() as u16);
+    buf.put_slice(id_bytes);
+    Ok(buf.freeze())
+}
+
+/// 读取数据流的流头，返回 transfer_id
+pub async fn read_stream_header<R: tokio::io::AsyncRead + Unpin>(recv: &mut R) -> Result<String> {
+    use tokio::io::AsyncReadExt;
+    let id_len = recv.read_u16_le().await? as usize;
+    let mut id_bytes = vec![0u8; id_len];
+    recv.read_exact(&mut id_bytes).await?;
+    Ok(String::from_utf8(id_bytes)?)
+}
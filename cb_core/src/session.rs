@@ -1,9 +1,13 @@
 // cb_core/src/session.rs
 
 mod actor;
+pub(crate) mod compression;
+pub(crate) mod scheduler;
 pub use actor::SessionActor; // 导出 Actor 供 NetManager 使用
+pub use scheduler::TransferScheduler;
 
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
 use tokio::sync::mpsc;
 use crate::api::PeerConnectionState;
 
@@ -45,19 +49,46 @@ pub enum SessionRole {
 #[derive(Debug)]
 pub enum SessionCmd {
     /// 发送元数据
-    SendMeta(crate::model::ItemMeta),
+    SendMeta(Box<crate::model::ItemMeta>),
+    /// 通知对端删除条目
+    SendDelete {
+        item_id: String,
+        deleted_ts_ms: i64,
+    },
     /// 关闭会话
     Shutdown,                         
     /// 请求向对端拉取文件 (B 端发起)
     RequestTransfer {
         item_id: String,
         file_id: Option<String>,
+        /// 指定要拉取的表示（None 表示主格式）
+        mime: Option<String>,
+        /// 按内容哈希向非来源设备拉取（None 表示向来源设备按 item 拉取）
+        sha256: Option<String>,
         reply_tx: tokio::sync::oneshot::Sender<anyhow::Result<String>>, // 返回 transfer_id
     },
     /// 取消本地发起的传输 (B 端取消)
     CancelTransfer {
         transfer_id: String,
     },
+    /// 重连后以原 transfer_id 续传断开时未完成的拉取
+    ResumeTransfer(PendingPull),
+    /// 询问对端的 CAS 中有哪些内容，返回其中已缓存的 sha256
+    QueryHave {
+        sha256s: Vec<String>,
+        reply_tx: tokio::sync::oneshot::Sender<Vec<String>>,
+    },
+}
+
+/// 本端发起、尚未完成的拉取（会话断开时交给 NetManager，重连后续传）
+#[derive(Clone, Debug)]
+pub struct PendingPull {
+    pub transfer_id: String,
+    pub item_id: String,
+    pub file_id: Option<String>,
+    pub mime: Option<String>,
+    /// 按内容哈希寻址（向非来源设备拉取 / 换源续传时设置）
+    pub sha256: Option<String>,
 }
 
 /// Session 对外暴露的句柄 (线程安全)
@@ -65,12 +96,20 @@ pub enum SessionCmd {
 pub struct SessionHandle {
     /// 初始 ID (pending_xxx)
     pub initial_id: String,
+    /// 本端是拨号方 (Client) 还是接受方 (Server)
+    pub role: SessionRole,
     /// 真实的对端设备 ID (握手成功后更新)
     pub peer_id: Arc<Mutex<Option<String>>>,
     /// 会话状态
     pub state: Arc<Mutex<SessionState>>,
 
     pub cmd_tx: mpsc::Sender<SessionCmd>,
+    /// 会话结束时仍未完成的拉取
+    pub interrupted: Arc<Mutex<Vec<PendingPull>>>,
+    /// 最后一次在控制流上收到对端消息的时间（心跳至少每 HEARTBEAT_INTERVAL 一次）
+    pub last_seen: Arc<AtomicI64>,
+    /// 建立连接时对端的地址
+    pub remote_addr: std::net::SocketAddr,
 }
 
 impl SessionHandle {
@@ -81,6 +120,10 @@ impl SessionHandle {
         lock.clone().unwrap_or_else(|| self.initial_id.clone())
     }
 
+    pub fn last_seen_ts_ms(&self) -> i64 {
+        self.last_seen.load(Ordering::Relaxed)
+    }
+
     pub fn is_online(&self) -> bool {
         let s = self.state.lock().unwrap();
         matches!(*s, SessionState::Online)
@@ -107,6 +150,11 @@ impl SessionHandle {
         }
     }
 
+    /// 取出会话结束时未完成的拉取（只在会话结束后有内容）
+    pub fn take_interrupted(&self) -> Vec<PendingPull> {
+        std::mem::take(&mut *self.interrupted.lock().unwrap())
+    }
+
     pub async fn shutdown(&self) {
         let _ = self.cmd_tx.send(SessionCmd::Shutdown).await;
     }
@@ -1,6 +1,7 @@
 // cb_core/src/session/actor.rs
 
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::Duration;
 use std::collections::HashMap;
 use std::io::SeekFrom;
@@ -10,8 +11,9 @@ use tokio::sync::{mpsc, oneshot};
 use tokio::time::{interval, MissedTickBehavior};
 use tokio::io::AsyncSeekExt;
 use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::sync::CancellationToken;
 use std::path::PathBuf;
-use tokio::fs::{File, OpenOptions};
+use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
 use sha2::{Digest, Sha256};
 
@@ -28,14 +30,22 @@ use opaque_ke::{
 };
 use rand::rngs::OsRng;
 
-use crate::proto::{CBFrameCodec, CtrlMsg, PROTOCOL_VERSION, AuthSessionFlags, CBFrame};
-use crate::transport::{Connection, SendStream, RecvStream};
+use crate::proto::{CBFrameCodec, CtrlMsg, PROTOCOL_VERSION, AuthSessionFlags, CBFrame, STREAM_CANCELLED};
+use crate::transport::{Connection, SendStream, RecvStream, ReadError};
 use crate::store::Store;
+use crate::cas::{Cas, PartialClaim, PartialInfo};
 use crate::util::{now_ms, sha256_hex};
-use super::{SessionCmd, SessionHandle, SessionRole, SessionState, HandshakeStep};
+use crate::item_fetch::{FetchMsg, FetchRoutes};
+use super::{PendingPull, SessionCmd, SessionHandle, SessionRole, SessionState, HandshakeStep, TransferScheduler};
+use super::scheduler::{priority_channel, PriorityQueue, PrioritySender, ThroughputMeter, TransferPriority, CHUNK_BYTES};
+use super::compression::{self, Compressor, Decompressor};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
 const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(6);
+/// 续传校验点间隔：每收到这么多字节 flush + fsync 一次并记录偏移
+const PARTIAL_CHECKPOINT_BYTES: u64 = 4 * 1024 * 1024;
+/// 拿不到对端证书时的指纹（服务端一侧）
+const UNKNOWN_FINGERPRINT: &str = "unknown";
 
 /// 定义接收状态
 enum ReceiverState {
@@ -49,6 +59,11 @@ enum ReceiverState {
         received_bytes: u64,
         total_bytes: u64,
         last_progress_emit: i64, // 用于节流 progress 事件
+        meter: ThroughputMeter,
+        /// 取消时 stop 数据流（数据流可能晚于 ContentBegin 到达）
+        stream_cancel: CancellationToken,
+        /// 数据流的压缩方式（ContentBegin 声明）
+        compression: Option<String>,
     },
 	// 已触发 Finish，等待落地结果（避免重复 Finish / 重复 End）
 	Committing {
@@ -86,22 +101,73 @@ enum ReceiverState {
 	},
 }
 
+/// 进行中的上传（本端作为发送端）；移除即 reset 它的数据流
+struct Upload {
+	cancel: CancellationToken,
+	sent_bytes: u64,
+	total_bytes: u64,
+	last_progress_emit: i64,
+	meter: ThroughputMeter,
+}
+
+impl Drop for Upload {
+	fn drop(&mut self) {
+		self.cancel.cancel();
+	}
+}
+
 /// 定义发送任务的消息
 enum UploadMsg {
-	Chunk { transfer_id: String, data: bytes::Bytes },
-	Done { transfer_id: String, sha256: String },
+	Progress { transfer_id: String, bytes: u64 },
+	Done { transfer_id: String },
 	Error { transfer_id: String, err: String },
 }
 
-enum ReceiverTaskMsg {
+/// 数据流（每个传输一条 QUIC 单向流）上发生的事，回到 Actor 处理
+pub(super) enum StreamEvent {
+	/// 对端打开了数据流，已读出流头
+	Opened { transfer_id: String, recv: RecvStream },
+	/// 一段数据已交给 Writer 任务
+	Received { transfer_id: String, bytes: u64 },
+	/// 流正常结束：数据已全部交给 Writer 任务
+	Finished { transfer_id: String },
+	/// 发送端 reset 了数据流（原因由控制流上的 ContentCancel 或会话结束给出）
+	Reset { transfer_id: String, code: u64 },
+	/// Writer 任务已退出（写入失败），数据流已 stop
+	WriteFailed { transfer_id: String },
+	/// 压缩数据无法解压，数据流已 stop
+	DecodeFailed { transfer_id: String, err: String },
+}
+
+pub(super) enum ReceiverTaskMsg {
 	Chunk(bytes::Bytes),
 	Finish {
-		expected_sha256: String, // 这里指本次传输片段的 Hash
+		expected_sha256: String, // 完整文件的 Hash
 		reply_tx: oneshot::Sender<Result<PathBuf>>, // 返回最终文件路径
 	},
+	/// 放弃传输并丢弃已收到的数据（直接关闭通道则保留续传数据）
 	Cancel,
 }
 
+/// 接收端的写入目标：可续传的 partial，或不可续传的 tmp 文件
+struct PreparedWrite {
+	file: std::fs::File,
+	/// 已写入前缀的 Hash 状态
+	hasher: Sha256,
+	path: PathBuf,
+	/// 持有时写入 partial（可续传）
+	claim: Option<PartialClaim>,
+	sha256: String,
+	total_bytes: u64,
+	offset: u64,
+}
+
+/// 续传前的准备（截断 + 计算已有前缀的 Hash）在后台完成后回到 Actor
+struct PreparedPull {
+	transfer_id: String,
+	prepared: Result<PreparedWrite>,
+}
+
 /// 临时文件守护者 (RAII)，任务结束/Panic时自动删除残留文件
 struct TempFileGuard {
 	path: PathBuf,
@@ -121,6 +187,7 @@ impl Drop for TempFileGuard {
 
 pub struct SessionActor {
     role: SessionRole,
+    conn: Connection,
     writer: FramedWrite<SendStream, CBFrameCodec>,
     reader: FramedRead<RecvStream, CBFrameCodec>,
     config: Arc<CoreConfig>,
@@ -130,16 +197,32 @@ pub struct SessionActor {
     state: SessionState,
     remote_device_id: Option<String>,
     remote_fingerprint: String,
+    /// 对端在 Hello / HelloAck 中声明的能力
+    peer_capabilities: Vec<String>,
     last_active_at: i64,
+    last_seen_ref: Arc<AtomicI64>,
     cmd_rx: mpsc::Receiver<SessionCmd>,
     store: Arc<Mutex<Store>>,
     log_store: Arc<Mutex<crate::logs::LogStore>>,
     opaque_client_state: Option<CbClientLoginState>,
     opaque_server_state: Option<CbServerLoginState>,
 	receivers: HashMap<String, ReceiverState>,
-	senders: HashMap<String, tokio::task::AbortHandle>,
+	senders: HashMap<String, Upload>,
     cas: crate::cas::Cas,
-	upload_tx: mpsc::Sender<UploadMsg>,
+	upload_tx: PrioritySender<UploadMsg>,
+	scheduler: Arc<TransferScheduler>,
+	/// 子传输的进度 / 结束同时交给 FileList 整体拉取
+	fetch_routes: Arc<Mutex<FetchRoutes>>,
+	stream_tx: mpsc::Sender<StreamEvent>,
+	/// 先于 ContentBegin 到达的数据流
+	incoming_streams: HashMap<String, RecvStream>,
+	/// 本端发起、尚未完成的拉取
+	pulls: HashMap<String, PendingPull>,
+	/// 已准备好续传写入、等待 ContentBegin 的拉取
+	prepared: HashMap<String, PreparedWrite>,
+	prepared_tx: mpsc::Sender<PreparedPull>,
+	/// 已发出、等待 HaveReply 的内容询问
+	have_queries: HashMap<String, oneshot::Sender<Vec<String>>>,
 }
 
 impl SessionActor {
@@ -152,10 +235,14 @@ impl SessionActor {
         cas: crate::cas::Cas,
         expected_peer_id: Option<String>,
         log_store: Arc<Mutex<crate::logs::LogStore>>,
+        scheduler: Arc<TransferScheduler>,
+        fetch_routes: Arc<Mutex<FetchRoutes>>,
     ) -> SessionHandle {
         let (cmd_tx, cmd_rx) = mpsc::channel(32);
         let state_ref = Arc::new(Mutex::new(SessionState::TransportReady));
         let peer_id_ref = Arc::new(Mutex::new(None));
+        let interrupted = Arc::new(Mutex::new(Vec::new()));
+        let last_seen_ref = Arc::new(AtomicI64::new(now_ms()));
 
         let fingerprint = match conn.peer_identity() {
             Some(id) => {
@@ -163,19 +250,23 @@ impl SessionActor {
                 if let Some(cert) = certs.first() {
                     sha256_hex(cert.as_ref())
                 } else {
-                    "unknown".to_string()
+                    UNKNOWN_FINGERPRINT.to_string()
                 }
             }
-            None => "unknown".to_string(),
+            None => UNKNOWN_FINGERPRINT.to_string(),
         };
 
         let initial_did = expected_peer_id.unwrap_or_else(|| "pending_server".to_string());
 
         let handle = SessionHandle {
             initial_id: initial_did.clone(),
+            role,
             peer_id: peer_id_ref.clone(),
             state: state_ref.clone(),
             cmd_tx,
+            interrupted: interrupted.clone(),
+            last_seen: last_seen_ref.clone(),
+            remote_addr: conn.remote_address(),
         };
 
         let actor_log_id = initial_did.clone();
@@ -184,7 +275,7 @@ impl SessionActor {
         let config_arc = Arc::new(config);
 
         tokio::spawn(async move {
-			let (upload_tx, upload_rx) = mpsc::channel(32);
+			let (upload_tx, upload_rx) = priority_channel(32);
             if let Err(e) = Self::run_actor(
                 role,
                 conn,
@@ -194,11 +285,15 @@ impl SessionActor {
                 cas,
                 state_ref_clone,
                 peer_id_ref_clone,
+                last_seen_ref,
                 cmd_rx,
                 fingerprint,
 				upload_tx,
 				upload_rx,
                 log_store,
+                interrupted,
+                scheduler,
+                fetch_routes,
             ).await {
                 eprintln!("[Session] Actor {} error: {:?}", actor_log_id, e);
             }
@@ -216,11 +311,15 @@ impl SessionActor {
         cas: crate::cas::Cas,
         state_ref: Arc<Mutex<SessionState>>,
         peer_id_ref: Arc<Mutex<Option<String>>>,
+        last_seen_ref: Arc<AtomicI64>,
         cmd_rx: mpsc::Receiver<SessionCmd>,
         fingerprint: String,
-		upload_tx: mpsc::Sender<UploadMsg>,
-		mut upload_rx: mpsc::Receiver<UploadMsg>,
+		upload_tx: PrioritySender<UploadMsg>,
+		mut upload_rx: PriorityQueue<UploadMsg>,
         log_store: Arc<Mutex<crate::logs::LogStore>>,
+        interrupted: Arc<Mutex<Vec<PendingPull>>>,
+        scheduler: Arc<TransferScheduler>,
+        fetch_routes: Arc<Mutex<FetchRoutes>>,
     ) -> Result<()> {
         let (send, recv) = match role {
             SessionRole::Client => conn.open_bi().await.context("Client open_bi failed")?,
@@ -229,6 +328,8 @@ impl SessionActor {
 
         let writer = FramedWrite::new(send, CBFrameCodec);
         let reader = FramedRead::new(recv, CBFrameCodec);
+        let (prepared_tx, mut prepared_rx) = mpsc::channel(8);
+        let (stream_tx, mut stream_rx) = mpsc::channel(64);
 
         // 记录会话创建
         {
@@ -242,6 +343,7 @@ impl SessionActor {
 
         let mut actor = Self {
             role,
+            conn,
             writer,
             reader,
             config,
@@ -253,7 +355,9 @@ impl SessionActor {
             state: SessionState::TransportReady,
             remote_device_id: None,
             remote_fingerprint: fingerprint,
+            peer_capabilities: Vec::new(),
             last_active_at: now_ms(),
+            last_seen_ref,
             cmd_rx,
             opaque_client_state: None,
             opaque_server_state: None,
@@ -261,6 +365,14 @@ impl SessionActor {
 			senders: HashMap::new(),
             cas,
 			upload_tx,
+			scheduler,
+			fetch_routes,
+			stream_tx,
+			incoming_streams: HashMap::new(),
+			pulls: HashMap::new(),
+			prepared: HashMap::new(),
+			prepared_tx,
+			have_queries: HashMap::new(),
         };
 
         actor.start_handshake().await?;
@@ -276,6 +388,7 @@ impl SessionActor {
                         match msg {
                             Some(Ok(frame)) => {
                                 actor.last_active_at = now_ms();
+                                actor.last_seen_ref.store(actor.last_active_at, Ordering::Relaxed);
                                 if let Err(e) = actor.handle_frame(frame).await {
                                      eprintln!("[Session] Frame error: {:?}", e);
                                      // 严重错误断开连接
@@ -299,6 +412,7 @@ impl SessionActor {
                                     // 不要把发送端的本地路径告诉接收端
                                     for f in &mut meta.files {
                                         f.local_path = None;
+                                        f.local_mtime_ms = None;
                                     }
 
                                     {
@@ -314,7 +428,16 @@ impl SessionActor {
 
                                     actor.send_ctrl(CtrlMsg::ItemMeta {
                                         msg_id: Some(msg_id),
-                                        item: meta
+                                        item: *meta
+                                    }).await?;
+                                }
+                            }
+                            Some(SessionCmd::SendDelete { item_id, deleted_ts_ms }) => {
+                                if actor.state == SessionState::Online {
+                                    actor.send_ctrl(CtrlMsg::ItemDelete {
+                                        msg_id: Some(uuid::Uuid::new_v4().to_string()),
+                                        item_id,
+                                        deleted_ts_ms,
                                     }).await?;
                                 }
                             }
@@ -325,34 +448,66 @@ impl SessionActor {
                                 }).await;
                                 break;
                             }
-                            Some(SessionCmd::RequestTransfer { item_id, file_id, reply_tx }) => {
+                            Some(SessionCmd::RequestTransfer { item_id, file_id, mime, sha256, reply_tx }) => {
                                 // M3: B 端发起拉取
-                                let _ = actor.start_pull_request(item_id, file_id, reply_tx).await;
+                                let _ = actor.start_pull_request(item_id, file_id, mime, sha256, reply_tx).await;
+                            }
+                            Some(SessionCmd::ResumeTransfer(pull)) => {
+                                actor.begin_pull(pull).await?;
                             }
                             Some(SessionCmd::CancelTransfer { transfer_id }) => {
                                 actor.handle_local_cancel(transfer_id).await?;
                             }
+                            Some(SessionCmd::QueryHave { sha256s, reply_tx }) => {
+                                // 未上线时直接丢弃 reply_tx，调用方按“没有”处理
+                                if actor.state == SessionState::Online {
+                                    let msg_id = uuid::Uuid::new_v4().to_string();
+                                    actor.have_queries.insert(msg_id.clone(), reply_tx);
+                                    actor.send_ctrl(CtrlMsg::HaveQuery { msg_id: Some(msg_id), sha256s }).await?;
+                                }
+                            }
                             None => break,
                         }
                     }
 
+                    // 对端为某个传输打开的数据流
+                    incoming = actor.conn.accept_uni() => {
+                        match incoming {
+                            Ok(recv) => actor.accept_data_stream(recv),
+                            Err(_) => break, // 连接已关闭
+                        }
+                    }
+
+					Some(evt) = stream_rx.recv() => {
+						actor.handle_stream_event(evt).await?;
+					}
+
 					Some(msg) = upload_rx.recv() => {
                         match msg {
-                            UploadMsg::Chunk { transfer_id, data } => {
-                                actor.send_data_chunk(transfer_id, data).await?;
+                            UploadMsg::Progress { transfer_id, bytes } => {
+                                actor.note_upload_progress(&transfer_id, bytes);
                             }
-                            UploadMsg::Done { transfer_id, sha256: _ } => {
-                                actor.send_ctrl(CtrlMsg::ContentEnd { req_id: transfer_id.clone() }).await?;
+                            UploadMsg::Done { transfer_id } => {
+                                // 数据流已 finish，接收端读到流结束即完成
                                 actor.senders.remove(&transfer_id);
                             }
                             UploadMsg::Error { transfer_id, err } => {
-                                actor.senders.remove(&transfer_id);
-                                // 可选：发送 Error 给对方
+                                // 数据流已 reset；接收端 stop 引起的则对方已不在接收，通知是无害的
+                                if actor.senders.remove(&transfer_id).is_some() {
+                                    actor.send_ctrl(CtrlMsg::ContentCancel {
+                                        req_id: transfer_id.clone(),
+                                        reason: "UPLOAD_FAILED".into(),
+                                    }).await?;
+                                }
 								eprintln!("[Session] Upload error for {}: {}", transfer_id, err);
                             }
                         }
                     }
 
+					Some(ready) = prepared_rx.recv() => {
+						actor.handle_prepared_pull(ready).await?;
+					}
+
                     // 3. 心跳
                     _ = heartbeat_ticker.tick() => {
                         actor.tick_heartbeat().await?;
@@ -362,6 +517,10 @@ impl SessionActor {
             Ok(())
         }.await;
 
+        // 数据流的读写任务各自持有连接：显式关闭，让它们立即结束（接收端借此保存续传数据）
+        actor.conn.close(0u32.into(), b"session closed");
+        // 未完成的拉取交给 NetManager，重连后续传（须在标记 Terminated 之前写入）
+        *interrupted.lock().unwrap() = actor.pulls.drain().map(|(_, pull)| pull).collect();
         actor.update_state(SessionState::Terminated);
         if let Some(did) = &actor.remote_device_id {
             let reason = match &run_result {
@@ -425,45 +584,18 @@ impl SessionActor {
         self.writer.send(CBFrame::Control(msg)).await.context("Failed to send CtrlMsg")
     }
 
-	async fn send_data_chunk(&mut self, transfer_id: String, data: bytes::Bytes) -> Result<()> {
-		// [修改] 增加超时控制，防止网络拥塞阻塞心跳
-		// 如果 500ms 发不出去，认为网络拥塞严重，报错断开传输，保住连接
-		let data_size = data.len();
-		let send_future = self.writer.send(CBFrame::Data { transfer_id: transfer_id.clone(), data });
-		match tokio::time::timeout(Duration::from_millis(500), send_future).await {
-			Ok(Ok(_)) => {
-				// 数据块发送成功（Debug 级别，避免日志过多）
-				let mut log_store = self.log_store.lock().unwrap();
-				let _ = log_store.log_debug(
-					"Session",
-					&format!("Data chunk sent: transfer_id={}, size={} bytes", transfer_id, data_size),
-					Some(&format!("数据块已发送: 传输ID={}，大小={} 字节", transfer_id, data_size)),
-				);
-				Ok(())
-			}
-			Ok(Err(e)) => Err(e.into()), // 协议栈错误
-			Err(_) => {
-				// 超时：主动移除 Sender 任务并通知对方 Cancel
-				if let Some(handle) = self.senders.remove(&transfer_id) {
-					handle.abort();
-				}
-				{
-					let mut log_store = self.log_store.lock().unwrap();
-					let _ = log_store.log_warn(
-						"Session",
-						&format!("Data chunk send timeout (network congestion): transfer_id={}, cancelling transfer", transfer_id),
-						Some(&format!("数据块发送超时（网络拥塞）: 传输ID={}，正在取消传输", transfer_id)),
-					);
-				}
-				// 尝试发一个 Cancel 包（尽力而为）
-				let _ = self.send_ctrl(CtrlMsg::ContentCancel {
-					req_id: transfer_id,
-					reason: "Network congested/timeout".into()
-				}).await;
-				anyhow::bail!("Send data chunk timeout (network congestion)");
-			}
-		}
-	}
+    /// 对端协议版本不同（数据流格式不兼容）：记录后由调用方断开
+    fn log_version_mismatch(&self, remote_device_id: &str, remote_version: u32) {
+        let mut log_store = self.log_store.lock().unwrap();
+        let _ = log_store.log_error(
+            "Session",
+            &format!("Protocol version mismatch, disconnecting: remote_device_id={}, remote_version={}, local_version={}",
+                    remote_device_id, remote_version, PROTOCOL_VERSION),
+            Some(&format!("协议版本不一致，断开连接: 远程设备ID={}，远程版本={}，本地版本={}",
+                    remote_device_id, remote_version, PROTOCOL_VERSION)),
+            Some("PROTOCOL_VERSION_MISMATCH"),
+        );
+    }
 
     async fn start_handshake(&mut self) -> Result<()> {
         {
@@ -483,7 +615,7 @@ impl SessionActor {
                     protocol_version: PROTOCOL_VERSION,
                     device_id: self.config.device_id.clone(),
                     account_uid: self.config.account_uid.clone(),
-                    capabilities: vec!["text".into(), "image".into(), "file".into()],
+                    capabilities: compression::local_capabilities(self.config.app_config.transfer.compression),
                     client_nonce: Some(uuid::Uuid::new_v4().to_string()),
                 };
                 {
@@ -509,13 +641,12 @@ impl SessionActor {
     async fn handle_frame(&mut self, frame: CBFrame) -> Result<()> {
         match frame {
             CBFrame::Control(msg) => self.handle_control_msg(msg).await,
-			CBFrame::Data { transfer_id, data } => self.handle_data_chunk(transfer_id, data).await,
         }
     }
 
     async fn handle_control_msg(&mut self, msg: CtrlMsg) -> Result<()> {
         match msg {
-            CtrlMsg::Hello { device_id, account_uid, msg_id, .. } => {
+            CtrlMsg::Hello { device_id, account_uid, msg_id, capabilities, protocol_version, .. } => {
                 if self.role == SessionRole::Server {
                     {
                         let mut log_store = self.log_store.lock().unwrap();
@@ -527,6 +658,32 @@ impl SessionActor {
                                     device_id, account_uid)),
                         );
                     }
+                    if protocol_version != PROTOCOL_VERSION {
+                        self.log_version_mismatch(&device_id, protocol_version);
+                        let _ = self.send_ctrl(CtrlMsg::AuthFail {
+                            reply_to: msg_id,
+                            code: "PROTOCOL_VERSION_MISMATCH".into(),
+                        }).await;
+                        let _ = self.send_ctrl(CtrlMsg::Close {
+                            msg_id: None,
+                            reason: "Protocol version mismatch".into(),
+                        }).await;
+                        // 给对端一点时间收到 AuthFail 再断开（断开会丢弃尚未送达的数据）
+                        let _ = tokio::time::timeout(Duration::from_millis(500), self.conn.closed()).await;
+                        anyhow::bail!("Protocol version mismatch: remote={}, local={}", protocol_version, PROTOCOL_VERSION);
+                    }
+                    if device_id == self.config.device_id {
+                        // 连到了自己（静态对端地址指向本机）：照常回 HelloAck 让拨号方识别出来，然后断开
+                        let _ = self.send_ctrl(CtrlMsg::HelloAck {
+                            reply_to: msg_id,
+                            server_device_id: self.config.device_id.clone(),
+                            protocol_version: PROTOCOL_VERSION,
+                            capabilities: Vec::new(),
+                        }).await;
+                        // 拨号方收到 HelloAck 后自行断开；立即断开会丢掉还没送达的 HelloAck
+                        let _ = tokio::time::timeout(Duration::from_millis(500), self.conn.closed()).await;
+                        anyhow::bail!("Connected to self");
+                    }
                     if account_uid != self.config.account_uid {
                         {
                             let mut log_store = self.log_store.lock().unwrap();
@@ -558,15 +715,17 @@ impl SessionActor {
                             Some(&format!("账号验证已完成: 远程设备ID={}，已验证=true", device_id)),
                         );
                     }
+                    self.peer_capabilities = capabilities;
                     self.send_ctrl(CtrlMsg::HelloAck {
                         reply_to: msg_id,
                         server_device_id: self.config.device_id.clone(),
                         protocol_version: PROTOCOL_VERSION,
+                        capabilities: compression::local_capabilities(self.config.app_config.transfer.compression),
                     }).await?;
                     self.update_state(SessionState::Handshaking(HandshakeStep::OpaqueStart));
                 }
             }
-            CtrlMsg::HelloAck { server_device_id, .. } => {
+            CtrlMsg::HelloAck { server_device_id, capabilities, protocol_version, .. } => {
                 if self.role == SessionRole::Client {
                     {
                         let mut log_store = self.log_store.lock().unwrap();
@@ -578,7 +737,17 @@ impl SessionActor {
                                     server_device_id)),
                         );
                     }
+                    if server_device_id == self.config.device_id {
+                        // 只让 NetManager 记下这个地址指向本机；不设 remote_device_id，断开时不发 PEER_OFFLINE
+                        *self.peer_id_ref.lock().unwrap() = Some(server_device_id);
+                        anyhow::bail!("Connected to self");
+                    }
+                    if protocol_version != PROTOCOL_VERSION {
+                        self.log_version_mismatch(&server_device_id, protocol_version);
+                        anyhow::bail!("Protocol version mismatch: remote={}, local={}", protocol_version, PROTOCOL_VERSION);
+                    }
                     self.update_remote_id(server_device_id.clone());
+                    self.peer_capabilities = capabilities;
                     self.start_opaque_login().await?;
                 }
             }
@@ -629,14 +798,51 @@ impl SessionActor {
                                 did, item.item_id, msg_id)),
                     );
                 }
-                if self.state == SessionState::Online {
+                if item.expires_ts_ms.is_some_and(|t| t <= now_ms()) {
+                    let mut log_store = self.log_store.lock().unwrap();
+                    let _ = log_store.log_warn(
+                        "Session",
+                        &format!("Rejected expired metadata from peer: item_id={}, expires_ts_ms={:?}",
+                                item.item_id, item.expires_ts_ms),
+                        Some(&format!("拒绝对等设备发来的已过期元数据: 项目ID={}，过期时间={:?}",
+                                item.item_id, item.expires_ts_ms)),
+                    );
+                } else if let Some(bad) = item.files.iter().find(|f| crate::cas::safe_rel_path(&f.rel_name).is_err()) {
+                    // 文件路径可能逃逸出下载目录（绝对路径、`..` 等）：整条元数据丢弃
+                    let mut log_store = self.log_store.lock().unwrap();
+                    let _ = log_store.log_warn(
+                        "Session",
+                        &format!("Rejected metadata with unsafe file path from peer: item_id={}, rel_name={:?}",
+                                item.item_id, bad.rel_name),
+                        Some(&format!("拒绝对等设备发来的含不安全文件路径的元数据: 项目ID={}，路径={:?}",
+                                item.item_id, bad.rel_name)),
+                    );
+                } else if self.state == SessionState::Online {
                     let store = self.store.clone();
                     let account_uid = self.config.account_uid.clone();
                     let item_clone = item.clone();
-					let is_new = tokio::task::spawn_blocking(move || {
+					let (is_new, updated) = tokio::task::spawn_blocking(move || -> Result<(bool, Option<crate::model::ItemMeta>)> {
 						let mut guard = store.lock().unwrap();
-						guard.insert_remote_item(&account_uid, &item_clone, now_ms())
+						if guard.insert_remote_item(&account_uid, &item_clone, now_ms())? {
+							return Ok((true, None));
+						}
+						// 已有条目：来源端在后台补算文件哈希后会重新广播，只接受来源设备自己的更新
+						let owner = guard.get_item_owner(&item_clone.item_id)?;
+						if owner.as_deref() != Some(item_clone.source_device_id.as_str())
+							|| !guard.fill_file_hashes(&item_clone.item_id, &item_clone.files, now_ms())?
+						{
+							return Ok((false, None));
+						}
+						Ok((false, guard.get_item_meta(&account_uid, &item_clone.item_id, true)?))
 					}).await??;
+                    if let Some(meta) = updated {
+                        let json = serde_json::json!({
+                            "type": "ITEM_META_UPDATED",
+                            "ts_ms": now_ms(),
+                            "payload": { "meta": meta }
+                        });
+                        self.sink.emit(json.to_string());
+                    }
                     if is_new {
                         let json = serde_json::json!({
                             "type": "ITEM_META_ADDED",
@@ -658,19 +864,42 @@ impl SessionActor {
                     }
                 }
             }
-            CtrlMsg::Error { code, message, .. } => anyhow::bail!("Remote error {}: {:?}", code, message),
+            CtrlMsg::ItemDelete { item_id, .. } => {
+                if self.state == SessionState::Online {
+                    self.handle_item_delete(item_id).await?;
+                }
+            }
+            CtrlMsg::Error { reply_to, code, message } => {
+                // 针对某个拉取的错误只让该传输失败，不断开会话（否则重连后会反复续传同一请求）
+                match reply_to.filter(|tid| self.pulls.contains_key(tid)) {
+                    Some(tid) => {
+                        self.forget_pull(&tid);
+                        self.receivers.remove(&tid);
+                        self.emit_transfer_failed(&tid, &code, message.as_deref().unwrap_or("Remote error"));
+                    }
+                    None => anyhow::bail!("Remote error {}: {:?}", code, message),
+                }
+            }
             CtrlMsg::Close { .. } => anyhow::bail!("Remote closed connection"),
 
             // === M3: 传输逻辑 ===
-			CtrlMsg::ContentGet { msg_id, item_id, file_id, offset } => {
+			CtrlMsg::ContentGet { msg_id, item_id, file_id, offset, mime, sha256 } => {
 				let transfer_id = msg_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
-				self.handle_content_get(transfer_id, item_id, file_id, offset).await?;
+				self.handle_content_get(transfer_id, item_id, file_id, offset, mime, sha256).await?;
 			}
-            CtrlMsg::ContentBegin { req_id, item_id, file_id, total_bytes, sha256, mime} => {
-                self.handle_content_begin(req_id, item_id, file_id, total_bytes, sha256, mime).await?;
-            }
-            CtrlMsg::ContentEnd { req_id } => {
-                self.handle_content_end(req_id).await?;
+			CtrlMsg::HaveQuery { msg_id, sha256s } => {
+				if self.state == SessionState::Online {
+					let have = sha256s.into_iter().filter(|s| is_sha256_hex(s) && self.cas.blob_exists(s)).collect();
+					self.send_ctrl(CtrlMsg::HaveReply { reply_to: msg_id, sha256s: have }).await?;
+				}
+			}
+			CtrlMsg::HaveReply { reply_to, sha256s } => {
+				if let Some(tx) = reply_to.and_then(|id| self.have_queries.remove(&id)) {
+					let _ = tx.send(sha256s);
+				}
+			}
+            CtrlMsg::ContentBegin { req_id, item_id, file_id, total_bytes, sha256, mime, offset, compression } => {
+                self.handle_content_begin(req_id, item_id, file_id, total_bytes, sha256, mime, offset, compression).await?;
             }
 			CtrlMsg::ContentCancel { req_id, reason } => {
 				{
@@ -681,27 +910,28 @@ impl SessionActor {
 						Some(&format!("传输已取消: 传输ID={}，原因={}", req_id, reason)),
 					);
 				}
-				// 1. 如果我是发送者：在 senders map 里找
-				if let Some(handle) = self.senders.remove(&req_id) {
-					handle.abort(); // 终止发送任务
-				}
+				// 1. 如果我是发送者：移除即 reset 数据流、终止发送任务
+				self.senders.remove(&req_id);
 
-				// 2. 如果我是接收者：调用 handle_content_cancel
-				self.handle_content_cancel(req_id, reason).await?;
+				// 2. 如果我是接收者：调用 handle_content_cancel（保留已收到的数据，之后可续传）
+				self.handle_content_cancel(req_id, reason, false).await?;
 			}
         }
         Ok(())
     }
 
 	// --- M3 Receiver Logic ---
+	#[allow(clippy::too_many_arguments)]
 	async fn handle_content_begin(
 		&mut self,
 		req_id: String,
 		item_id: String,
 		file_id: Option<String>,
 		total_bytes: u64,
-		file_sha256: String, // 注意：这是整个文件的 Hash，Resume 时不需要校验它
-		mime: String
+		file_sha256: String, // 整个文件的 Hash，续传时也按完整文件校验
+		mime: String,
+		begin_offset: Option<u64>,
+		compression: Option<String>,
 	) -> Result<()> {
 		{
 			let mut log_store = self.log_store.lock().unwrap();
@@ -718,10 +948,56 @@ impl SessionActor {
 			// 幂等处理：如果是重复的 Begin，忽略即可
 			return Ok(());
 		}
+		let Some(pull) = self.pulls.get(&req_id) else {
+			// 不是本端发起（或已取消）的拉取
+			return Ok(());
+		};
+		// 按哈希向其他设备拉取时，对端发来的必须是同一份内容
+		if pull.sha256.as_ref().is_some_and(|want| *want != file_sha256) {
+			self.forget_pull(&req_id);
+			let _ = self.send_ctrl(CtrlMsg::ContentCancel { req_id: req_id.clone(), reason: "CONTENT_MISMATCH".into() }).await;
+			self.emit_transfer_failed(&req_id, "CONTENT_MISMATCH", &format!("Peer offered {} instead of the requested content", file_sha256));
+			return Ok(());
+		}
+		// 只接受本端声明过的压缩
+		if let Some(c) = compression.as_deref().filter(|c| *c != compression::ZSTD) {
+			self.forget_pull(&req_id);
+			let _ = self.send_ctrl(CtrlMsg::ContentCancel { req_id: req_id.clone(), reason: "UNSUPPORTED_COMPRESSION".into() }).await;
+			self.emit_transfer_failed(&req_id, "UNSUPPORTED_COMPRESSION", &format!("Unsupported compression: {}", c));
+			return Ok(());
+		}
 
-		let tmp_path = self.cas.get_tmp_path(&req_id);
-		if let Some(p) = tmp_path.parent() {
-			tokio::fs::create_dir_all(p).await?;
+		// 发送端实际采用的偏移；旧版本对端不回报，按请求时的偏移处理
+		let prepared = self.prepared.remove(&req_id);
+		let offset = begin_offset.unwrap_or_else(|| prepared.as_ref().map_or(0, |p| p.offset));
+		let opened = self.open_receive_target(prepared, &item_id, file_id.as_deref(), &file_sha256, total_bytes, offset);
+		let target = match opened {
+			Ok(target) => target,
+			Err(e) => {
+				let msg = format!("{:#}", e);
+				let code = if msg.starts_with("RESUME_MISMATCH") { "RESUME_MISMATCH" } else { "WRITE_FAILED" };
+				self.forget_pull(&req_id);
+				let _ = self.send_ctrl(CtrlMsg::ContentCancel { req_id: req_id.clone(), reason: code.into() }).await;
+				self.emit_transfer_failed(&req_id, code, &msg);
+				return Ok(());
+			}
+		};
+
+		if offset > 0 {
+			{
+				let mut log_store = self.log_store.lock().unwrap();
+				let _ = log_store.log_info(
+					"Session",
+					&format!("Transfer resumed: transfer_id={}, offset={}, total_bytes={}", req_id, offset, total_bytes),
+					Some(&format!("传输已续传: 传输ID={}，起始偏移={}，总字节数={}", req_id, offset, total_bytes)),
+				);
+			}
+			let evt = serde_json::json!({
+				"type": "TRANSFER_RESUMED",
+				"ts_ms": now_ms(),
+				"payload": { "transfer_id": req_id, "offset": offset, "total": total_bytes }
+			});
+			self.sink.emit(evt.to_string());
 		}
 
 		// 启动独立的 Writer Task
@@ -730,54 +1006,59 @@ impl SessionActor {
 		// Clone 需要的数据传入 Task
 		let cas_clone = self.cas.clone();
 		let store_clone = self.store.clone();
-		let tmp_path_clone = tmp_path.clone();
 		let tid = req_id.clone();
 		let iid = item_id.clone();
 		let fid = file_id.clone();
 		let mime_clone = mime.clone();
-		// 如果 CAS 中存在该文件，说明是断点续传，需要以 Append 模式打开
-		let is_resume = tmp_path.exists();
 
 		tokio::spawn(async move {
-			// RAII Guard: 任务退出时如果没 commit，自动删除 tmp 文件
-			let mut guard = TempFileGuard { path: tmp_path_clone.clone(), committed: false };
-
-			// 打开文件
-			let mut opts = OpenOptions::new();
-			opts.write(true).create(true);
-			if is_resume {
-				opts.append(true); // 续传追加
-			} else {
-				opts.truncate(true); // 新传覆盖
-			}
-
-			let file_res = opts.open(&guard.path).await;
-			if let Err(e) = file_res {
-				eprintln!("[Session] Writer open failed: {}", e);
-				return;
-			}
-			let mut writer = tokio::io::BufWriter::new(file_res.unwrap());
-			let mut hasher = Sha256::new(); // 计算本次传输片段的 Hash
+			let PreparedWrite { file, mut hasher, path, claim, sha256, total_bytes, offset } = target;
+			// RAII Guard: 不可续传的 tmp 文件在任务异常退出时删除；partial 由下面显式处理
+			let mut guard = TempFileGuard { path, committed: claim.is_some() };
+			let discard = |cas: &Cas, claim: &Option<PartialClaim>| {
+				if let Some(c) = claim {
+					cas.remove_partial(c.key());
+				}
+			};
+			let mut writer = tokio::io::BufWriter::new(File::from_std(file));
+			let mut written = offset;
+			let mut checkpoint = offset;
 
 			while let Some(msg) = rx.recv().await {
 				match msg {
 					ReceiverTaskMsg::Chunk(data) => {
 						if let Err(e) = writer.write_all(&data).await {
 							eprintln!("[Session] Write failed: {}", e);
+							discard(&cas_clone, &claim);
 							return; // 触发 Drop 删除
 						}
 						hasher.update(&data);
+						written += data.len() as u64;
+
+						// 定期落盘并记录校验点，断线 / 崩溃后从这里续传
+						if let Some(c) = &claim {
+							if written - checkpoint >= PARTIAL_CHECKPOINT_BYTES {
+								if writer.flush().await.is_err() || writer.get_ref().sync_data().await.is_err() {
+									continue;
+								}
+								let info = PartialInfo { sha256: sha256.clone(), total_bytes, verified_bytes: written };
+								if cas_clone.save_partial(c.key(), &info).is_ok() {
+									checkpoint = written;
+								}
+							}
+						}
 					}
 					ReceiverTaskMsg::Finish { expected_sha256, reply_tx } => {
 						// 1. Flush & Sync
-						if let Err(_) = writer.flush().await { return; }
+						if writer.flush().await.is_err() { return; }
 						let mut f = writer.into_inner();
-						if let Err(_) = f.shutdown().await { return; }
+						if f.shutdown().await.is_err() { return; }
 						drop(f); // 关闭文件句柄
 
-						// 2. 校验 Hash (本次传输片段)
+						// 2. 校验完整文件的 Hash（续传时包含之前收到的前缀）
 						let calculated = hex::encode(hasher.finalize());
 						if calculated != expected_sha256 {
+							discard(&cas_clone, &claim);
 							let _ = reply_tx.send(Err(anyhow::anyhow!("Checksum mismatch")));
 							return; // 触发 Drop 删除
 						}
@@ -787,24 +1068,19 @@ impl SessionActor {
 						let guard_path = guard.path.clone(); // Clone path before move
 
 						let commit_res = tokio::task::spawn_blocking(move || {
-							// A. 转正 Blob (CAS) - 注意：Resume 场景下这里应该合并 Hash，
-							// 但简化起见，我们假设 CAS 接口能处理或仅作为临时存储。
-							// 实际 M3 完整版应计算全量 Hash 存入 blobs。
-							// 这里我们直接用 expected_sha256 (片段Hash) 做 blob 名可能不对，
-							// 但为了让流程跑通，先产生一个 Blob。
-							// *修正*：M3 协议中 ContentBegin 里的 _file_sha256 才是最终 Blob ID。
-							// 但 Writer Task 没有那个值。
-							// 暂定：用片段 Hash 存，或者如果不做全量校验，直接 commit。
-
-							// 这里复用原本的逻辑
-							let blob_path = cas_clone.commit_tmp_file(&guard_path, &expected_sha256)?;
+							// A. 转正 Blob (CAS)
+							let blob_path = match &claim {
+								Some(c) => cas_clone.commit_partial(c.key(), &expected_sha256)?,
+								None => cas_clone.commit_tmp_file(&guard_path, &expected_sha256)?,
+							};
 
 							// B. 决定落地路径 (Materialize)
 							if let Some(real_fid) = fid {
 								// FileList 模式
 								let store = store_clone.lock().unwrap();
 								if let Some(fmeta) = store.get_file_meta(&iid, &real_fid)? {
-									cas_clone.materialize_file(&expected_sha256, &tid, &fmeta.rel_name)
+									let attrs = crate::cas::FileAttrs { mode: fmeta.mode, mtime_ms: fmeta.mtime_ms };
+									cas_clone.materialize_file(&expected_sha256, &tid, &fmeta.rel_name, &attrs)
 								} else {
 									Ok(blob_path)
 								}
@@ -813,6 +1089,8 @@ impl SessionActor {
 								let ext = match mime_clone.as_str() {
 									"image/png" => Some("png"),
 									"image/jpeg" | "image/jpg" => Some("jpg"),
+									"text/html" => Some("html"),
+									"text/rtf" => Some("rtf"),
 									_ => None
 								};
 								if let Some(extension) = ext {
@@ -833,10 +1111,18 @@ impl SessionActor {
 						return; // 任务结束
 					}
 					ReceiverTaskMsg::Cancel => {
+						discard(&cas_clone, &claim);
 						return; // 触发 Drop 删除
 					}
 				}
 			}
+
+			// 通道关闭（会话断开）：落盘并记录最后的校验点，保留给续传
+			if let Some(c) = &claim {
+				if writer.flush().await.is_ok() && writer.get_ref().sync_data().await.is_ok() {
+					let _ = cas_clone.save_partial(c.key(), &PartialInfo { sha256, total_bytes, verified_bytes: written });
+				}
+			}
 		});
 
 		let receiver_state = ReceiverState::Receiving {
@@ -846,33 +1132,159 @@ impl SessionActor {
 			expected_sha256: file_sha256,
 			mime,
 			tx, // 保存 Sender
-			received_bytes: 0,
-			total_bytes, // 注意：如果是 Resume，这里的 total_bytes 可能是剩余量或总量，取决于协议约定，此处仅做显示
+			received_bytes: offset, // 续传时从已有前缀算起，进度按完整文件显示
+			total_bytes,
 			last_progress_emit: 0,
+			meter: ThroughputMeter::new(offset),
+			stream_cancel: CancellationToken::new(),
+			compression,
 		};
-		self.receivers.insert(req_id, receiver_state);
+		self.receivers.insert(req_id.clone(), receiver_state);
+		if let Some(recv) = self.incoming_streams.remove(&req_id) {
+			self.attach_data_stream(req_id, recv);
+		}
+		Ok(())
+	}
+
+	/// 确定 ContentBegin 之后的写入目标。
+	/// - 有准备好的 partial 且偏移一致：接着写
+	/// - 偏移为 0：重新开始（内容已知时写入可续传的 partial，同一内容已有传输在写则退回 tmp 文件）
+	/// - 其余情况说明双方对已收到的数据认识不一致：RESUME_MISMATCH
+	fn open_receive_target(
+		&self,
+		prepared: Option<PreparedWrite>,
+		item_id: &str,
+		file_id: Option<&str>,
+		sha256: &str,
+		total_bytes: u64,
+		offset: u64,
+	) -> Result<PreparedWrite> {
+		if let Some(p) = prepared {
+			if p.offset == offset && p.sha256 == sha256 && p.total_bytes == total_bytes {
+				return Ok(p);
+			}
+			if offset > 0 {
+				if let Some(c) = &p.claim {
+					self.cas.remove_partial(c.key());
+				}
+				anyhow::bail!("RESUME_MISMATCH: sender resumed at {} but {} bytes are prepared", offset, p.offset);
+			}
+			// 发送端从头发送（例如偏移超出文件长度）：丢弃准备好的前缀
+		}
+		if offset > 0 {
+			anyhow::bail!("RESUME_MISMATCH: sender resumed at {} without local partial data", offset);
+		}
+
+		let claim = if is_sha256_hex(sha256) {
+			self.cas.claim_partial(&Cas::partial_key(item_id, file_id, sha256))
+		} else {
+			None
+		};
+		let (file, hasher, path) = match &claim {
+			Some(c) => {
+				let (file, hasher) = self.cas.open_partial(c, sha256, total_bytes, 0)?;
+				(file, hasher, self.cas.partial_path(c.key()))
+			}
+			None => {
+				let path = self.cas.get_tmp_path(&uuid::Uuid::new_v4().to_string());
+				if let Some(p) = path.parent() {
+					std::fs::create_dir_all(p)?;
+				}
+				(std::fs::File::create(&path)?, Sha256::new(), path)
+			}
+		};
+		Ok(PreparedWrite { file, hasher, path, claim, sha256: sha256.to_string(), total_bytes, offset: 0 })
+	}
+
+	/// 对端打开的数据流：后台读出流头后交回 Actor（见 attach_data_stream）
+	fn accept_data_stream(&self, mut recv: RecvStream) {
+		let events = self.stream_tx.clone();
+		tokio::spawn(async move {
+			match tokio::time::timeout(HEARTBEAT_TIMEOUT, crate::proto::read_stream_header(&mut recv)).await {
+				Ok(Ok(transfer_id)) => {
+					let _ = events.send(StreamEvent::Opened { transfer_id, recv }).await;
+				}
+				_ => {
+					let _ = recv.stop(STREAM_CANCELLED.into());
+				}
+			}
+		});
+	}
+
+	async fn handle_stream_event(&mut self, evt: StreamEvent) -> Result<()> {
+		match evt {
+			StreamEvent::Opened { transfer_id, recv } => self.attach_data_stream(transfer_id, recv),
+			StreamEvent::Received { transfer_id, bytes } => self.note_stream_received(&transfer_id, bytes),
+			StreamEvent::Finished { transfer_id } => self.handle_content_end(transfer_id).await?,
+			StreamEvent::Reset { transfer_id, code } => {
+				// 发送端主动取消时随后会发来 ContentCancel；会话关闭时 reset 先于断开到达，
+				// 这里不能当作取消处理，否则拉取不会在重连后续传
+				let mut log_store = self.log_store.lock().unwrap();
+				let _ = log_store.log_info(
+					"Session",
+					&format!("Data stream reset by sender: transfer_id={}, code={}", transfer_id, code),
+					Some(&format!("发送端重置了数据流: 传输ID={}，错误码={}", transfer_id, code)),
+				);
+			}
+			StreamEvent::WriteFailed { transfer_id } => {
+				if self.receivers.remove(&transfer_id).is_some() {
+					self.forget_pull(&transfer_id);
+					self.emit_transfer_failed(&transfer_id, "WRITE_FAILED", "Writer task stopped");
+				}
+			}
+			StreamEvent::DecodeFailed { transfer_id, err } => {
+				if self.receivers.remove(&transfer_id).is_some() {
+					self.forget_pull(&transfer_id);
+					self.emit_transfer_failed(&transfer_id, "DECOMPRESS_FAILED", &err);
+				}
+			}
+		}
 		Ok(())
 	}
 
-	async fn handle_data_chunk(&mut self, transfer_id: String, data: bytes::Bytes) -> Result<()> {
-		if let Some(receiver) = self.receivers.get_mut(&transfer_id) {
+	/// 为数据流启动读取任务：数据直接交给 Writer 任务，不经过 Actor，
+	/// 写盘慢只会让这条流的 QUIC 流控生效，不会拖住控制流上的心跳。
+	/// ContentBegin 还没处理时先暂存，不认识的传输直接 stop。
+	fn attach_data_stream(&mut self, transfer_id: String, mut recv: RecvStream) {
+		let Some(ReceiverState::Receiving { tx, stream_cancel, compression, file_id, received_bytes, total_bytes, .. }) = self.receivers.get(&transfer_id) else {
+			if self.pulls.contains_key(&transfer_id) {
+				self.incoming_streams.insert(transfer_id, recv);
+			} else {
+				let _ = recv.stop(STREAM_CANCELLED.into());
+			}
+			return;
+		};
+		let (writer, cancel, events) = (tx.clone(), stream_cancel.clone(), self.stream_tx.clone());
+		// 压缩流解压后最多还能有多少字节（续传时扣掉已有前缀）
+		let decompress_limit = compression.is_some().then(|| total_bytes.saturating_sub(*received_bytes));
+		let priority = TransferPriority::for_request(file_id.as_deref());
+		let scheduler = self.scheduler.clone();
+		tokio::spawn(async move {
+			let evt = tokio::select! {
+				evt = pump_data_stream(&mut recv, &transfer_id, decompress_limit, priority, &scheduler, &writer, &events) => evt,
+				_ = cancel.cancelled() => None,
+			};
+			// 没有读到流结束（取消 / 写入失败）：stop，发送端随即停止
+			if !matches!(evt, Some(StreamEvent::Finished { .. } | StreamEvent::Reset { .. })) {
+				let _ = recv.stop(STREAM_CANCELLED.into());
+			}
+			if let Some(evt) = evt {
+				let _ = events.send(evt).await;
+			}
+		});
+	}
+
+	fn note_stream_received(&mut self, transfer_id: &str, chunk_size: u64) {
+		if let Some(receiver) = self.receivers.get_mut(transfer_id) {
 			match receiver {
 				ReceiverState::Receiving {
-					tx,
 					received_bytes,
 					total_bytes,
 					last_progress_emit,
+					meter,
 					..
 				} => {
-					let chunk_size = data.len();
-					*received_bytes += chunk_size as u64;
-
-					// 发送给 Writer 任务 (非阻塞)
-					if let Err(_) = tx.send(ReceiverTaskMsg::Chunk(data)).await {
-						// 任务已死 (比如写入失败)，停止接收
-						self.receivers.remove(&transfer_id);
-						return Ok(());
-					}
+					*received_bytes += chunk_size;
 
 					// 记录数据块接收（Debug 级别，避免日志过多）
 					{
@@ -889,36 +1301,42 @@ impl SessionActor {
 					// 进度节流
 					let now = now_ms();
 					if now - *last_progress_emit > 200 {
+						let bytes_per_sec = meter.update(*received_bytes);
 						let progress_evt = serde_json::json!({
                             "type": "TRANSFER_PROGRESS",
                             "payload": {
                                 "transfer_id": transfer_id,
                                 "received": *received_bytes,
-                                "total": *total_bytes
+                                "total": *total_bytes,
+                                "bytes_per_sec": bytes_per_sec
                             }
                         });
 						self.sink.emit(progress_evt.to_string());
+						self.fetch_routes.lock().unwrap().notify(FetchMsg::Progress {
+							transfer_id: transfer_id.to_string(),
+							received: *received_bytes,
+							bytes_per_sec,
+						});
 						*last_progress_emit = now;
 					}
 				}
 				_ => {}
 			}
 		}
-		Ok(())
 	}
 
 	async fn handle_content_end(&mut self, req_id: String) -> Result<()> {
+		self.forget_pull(&req_id);
 		if let Some(state) = self.receivers.remove(&req_id) {
 			if let ReceiverState::Receiving {
 				tx, item_id, file_id, transfer_id,
-				expected_sha256, total_bytes,
+				expected_sha256, total_bytes, mime, compression,
 				..
 			} = state {
 				// 1. 创建回传通道
 				let (reply_tx, reply_rx) = oneshot::channel();
 
-				// 2. 发送 Finish 指令给 Writer Task
-				// 注意：这里的 sha256 是发送端传来的“本次传输片段 Hash”
+				// 2. 发送 Finish 指令给 Writer Task（sha256 是 ContentBegin 中完整文件的 Hash）
 				if let Err(_) = tx.send(ReceiverTaskMsg::Finish { expected_sha256: expected_sha256.clone(), reply_tx }).await {
 					self.emit_transfer_failed(&req_id, "WRITE_TASK_DEAD", "Writer task crashed");
 					return Ok(());
@@ -940,20 +1358,34 @@ impl SessionActor {
 						}
 						let store = self.store.clone();
 						let sha_for_db = expected_sha256.clone();
-						let iid = item_id.clone();
 						let fid = file_id.clone();
 						let final_path_str = final_path.to_string_lossy().to_string();
 
 						// 【新增】查询 DB 获取完整元数据
+						let iid = item_id.clone();
+						let body_path = final_path.clone();
 						let meta_info = tokio::task::spawn_blocking(move || {
+							// 文本正文到达后写入全文索引（FileList 子文件不索引）
+							let body = if fid.is_none() && total_bytes as usize <= crate::store::SEARCH_BODY_MAX_BYTES {
+								std::fs::read(&body_path)
+									.ok()
+									.and_then(|bytes| crate::clipboard::searchable_text(&mime, &bytes))
+							} else {
+								None
+							};
+
 							let mut guard = store.lock().unwrap();
 							guard.mark_cache_present(&sha_for_db, now_ms())?; // 原有逻辑 [cite: 298]
+							if let Some(body) = body {
+								guard.index_item_body(&iid, &body)?;
+							}
 
-							// 补充查询
-							let mime = guard.get_item_mime(&iid)?.unwrap_or_default();
+							// 以 ContentBegin 中的 MIME 为准（RichText 降级时与 item 主 MIME 不同）
 							// 简单判断类型
 							let kind = if fid.is_some() {
 								"file"
+							} else if mime == "text/html" || mime == "text/rtf" {
+								"rich_text"
 							} else if mime.starts_with("text/") {
 								"text"
 							} else if mime.starts_with("image/") {
@@ -984,10 +1416,12 @@ impl SessionActor {
 								"transfer_id": transfer_id,
 								"item_id": item_id,
 								"file_id": file_id,
-								"local_ref": local_ref
+								"local_ref": local_ref,
+								"compression": compression
 							}
 						});
 						self.sink.emit(evt.to_string());
+						self.fetch_routes.lock().unwrap().notify(FetchMsg::Cached { transfer_id: transfer_id.clone() });
 					}
 					Ok(Err(e)) => self.emit_transfer_failed(&req_id, "COMMIT_FAILED", &e.to_string()),
 					Err(_) => self.emit_transfer_failed(&req_id, "COMMIT_TIMEOUT", "Writer task dropped reply"),
@@ -997,18 +1431,63 @@ impl SessionActor {
 		Ok(())
 	}
 
-	async fn handle_content_cancel(&mut self, req_id: String, _reason: String) -> Result<()> {
+	/// discard_partial：用户主动取消时丢弃已收到的数据；对端取消（拥塞等）则保留，之后可续传
+	async fn handle_content_cancel(&mut self, req_id: String, reason: String, discard_partial: bool) -> Result<()> {
+		self.forget_pull(&req_id);
 		if let Some(state) = self.receivers.remove(&req_id) {
-			if let ReceiverState::Receiving { tx, transfer_id, .. } = state {
-				// 发送 Cancel，触发 Writer Task 的 Guard Drop 清理文件
-				let _ = tx.send(ReceiverTaskMsg::Cancel).await;
+			if let ReceiverState::Receiving { tx, transfer_id, stream_cancel, .. } = state {
+				// stop 数据流，发送端随即停止发送
+				stream_cancel.cancel();
+				// 发送 Cancel 让 Writer Task 清理文件；直接 drop tx 则保留续传数据
+				if discard_partial {
+					let _ = tx.send(ReceiverTaskMsg::Cancel).await;
+				}
 
 				let evt = serde_json::json!({
                     "type": "TRANSFER_CANCELLED",
                     "payload": { "transfer_id": transfer_id }
                 });
 				self.sink.emit(evt.to_string());
+				self.notify_child_cancelled(transfer_id);
+			}
+		} else if reason == "ITEM_DELETED" || reason == "ITEM_EXPIRED" || reason == "SOURCE_FILE_CHANGED" {
+			// 发送端在 ContentBegin 之前拒绝了我们的拉取
+			self.emit_transfer_failed(&req_id, &reason, &format!("Item is no longer available on the source device: {}", reason));
+		}
+		Ok(())
+	}
+
+	/// 对端通知删除条目：删除本地副本与不再被引用的缓存，不再继续转发
+	async fn handle_item_delete(&mut self, item_id: String) -> Result<()> {
+		let device_id = self.remote_device_id.clone().unwrap_or_else(|| "unknown".to_string());
+		let store = self.store.clone();
+		let cas = self.cas.clone();
+		let iid = item_id.clone();
+		let deleted = tokio::task::spawn_blocking(move || -> Result<bool> {
+			let orphans = store.lock().unwrap().delete_item(&iid, now_ms())?;
+			let Some(orphans) = orphans else { return Ok(false) };
+			for sha in orphans {
+				cas.remove_blob(&sha)?;
 			}
+			Ok(true)
+		}).await??;
+
+		{
+			let mut log_store = self.log_store.lock().unwrap();
+			let _ = log_store.log_info(
+				"Session",
+				&format!("Item deletion received from peer: device_id={}, item_id={}, applied={}", device_id, item_id, deleted),
+				Some(&format!("收到对等设备的删除通知: 设备ID={}，项目ID={}，已删除={}", device_id, item_id, deleted)),
+			);
+		}
+
+		if deleted {
+			let evt = serde_json::json!({
+				"type": "ITEM_DELETED",
+				"ts_ms": now_ms(),
+				"payload": { "item_id": item_id, "scope": "all", "origin_device_id": device_id }
+			});
+			self.sink.emit(evt.to_string());
 		}
 		Ok(())
 	}
@@ -1027,7 +1506,7 @@ impl SessionActor {
 
 		// 根据文档规则硬编码属性
 		let (retryable, affects_session) = match code {
-			"PERMISSION_DENIED" | "ITEM_NOT_FOUND" => (false, false),
+			"PERMISSION_DENIED" | "ITEM_NOT_FOUND" | "ITEM_DELETED" | "ITEM_EXPIRED" => (false, false),
 			"CONN_TIMEOUT" => (true, true),
 			_ => (false, false),
 		};
@@ -1048,42 +1527,101 @@ impl SessionActor {
         "payload": payload
     });
 		self.sink.emit(evt.to_string());
+		self.fetch_routes.lock().unwrap().notify(FetchMsg::Failed {
+			transfer_id: tid.to_string(),
+			code: payload.code,
+			message: payload.message,
+		});
+	}
+
+	fn notify_child_cancelled(&self, transfer_id: String) {
+		self.fetch_routes.lock().unwrap().notify(FetchMsg::Failed {
+			transfer_id,
+			code: "TRANSFER_CANCELLED".to_string(),
+			message: "child transfer cancelled".to_string(),
+		});
 	}
 
     // --- M3 Sender Logic ---
-	async fn handle_content_get(&mut self, transfer_id: String, item_id: String, file_id: Option<String>, offset: Option<u64>) -> Result<()> {
+	#[allow(clippy::too_many_arguments)]
+	async fn handle_content_get(
+		&mut self,
+		transfer_id: String,
+		item_id: String,
+		file_id: Option<String>,
+		offset: Option<u64>,
+		mime: Option<String>,
+		want_sha256: Option<String>,
+	) -> Result<()> {
+		// 已过期或已被用户删除的条目不再提供内容
+		let refused = {
+			let store = self.store.lock().unwrap();
+			if store.is_item_expired(&item_id, now_ms())? {
+				Some("ITEM_EXPIRED")
+			} else if store.is_item_deleted(&item_id)? {
+				Some("ITEM_DELETED")
+			} else {
+				None
+			}
+		};
+		if let Some(reason) = refused {
+			self.send_ctrl(CtrlMsg::ContentCancel {
+				req_id: transfer_id,
+				reason: reason.into(),
+			}).await?;
+			return Ok(());
+		}
+
 		// [修改] 1. 查找文件路径 (补全了 CAS 和 Local Path 的双重查找)
-		let (file_path_res, mime_val) = {
+		let (file_path_res, mime_val, changed_source, file_name) = {
 			let store = self.store.lock().unwrap();
 
 			// A. 获取目标的 SHA256 和 (可选的) 本地路径
 			//    如果是 FileList 子文件，查 files_json；如果是 Text/Image，查 item 主表
+			let mut file_name = None;
 			let (target_sha, local_path_opt) = if let Some(fid) = &file_id {
-				// Case 1: FileList 中的子文件
-				if let Some(fmeta) = store.get_file_meta(&item_id, fid)? {
-					(fmeta.sha256.unwrap_or_default(), fmeta.local_path)
-				} else {
-					(String::new(), None)
+				// Case 1: FileList 中的子文件（本地源文件连同记录的大小、修改时间）
+				match store.get_file_meta(&item_id, fid)? {
+					// 目录条目没有内容
+					Some(fmeta) if !fmeta.is_dir => {
+						file_name = Some(fmeta.rel_name.clone());
+						let local = fmeta.local_path.map(|p| (p, fmeta.size_bytes, fmeta.local_mtime_ms, fmeta.rel_name));
+						(fmeta.sha256.unwrap_or_default(), local)
+					}
+					_ => (String::new(), None),
 				}
+			} else if let Some(m) = &mime {
+				// Case 2: 指定 MIME 的表示（额外格式 / RichText 纯文本降级）
+				let sha = store.get_item_representation(&item_id, m)?.map(|c| c.sha256).unwrap_or_default();
+				(sha, None)
 			} else {
-				// Case 2: Text/Image (Item 本身就是内容)
+				// Case 3: Text/Image (Item 本身就是内容)
 				let sha = store.get_item_sha256(&item_id)?.unwrap_or_default();
 				// Text/Image 通常没有 local_path，除非是极少数情况，这里暂定 None
 				(sha, None)
 			};
 
 			// B. 获取 MIME (用于通知接收端)
-			let mime = store.get_item_mime(&item_id)?.unwrap_or("application/octet-stream".to_string());
+			let mime = match (&mime, &file_id) {
+				(Some(m), None) => m.clone(),
+				_ => store.get_item_mime(&item_id)?.unwrap_or("application/octet-stream".to_string()),
+			};
 
 			// C. 决定最终读取路径
 			let mut final_path = None;
+			let mut changed_source = None;
 
 			// 优先级 1: 本地原始文件 (Local Path)
 			// 场景: 用户刚复制了一个 2GB 视频，还没进 CAS，或者为了支持 tail/resuming
-			if let Some(lp_str) = local_path_opt {
+			// 复制之后被改动过的源文件不再发送，避免用旧哈希发出不同的内容
+			if let Some((lp_str, size_bytes, mtime_ms, rel_name)) = local_path_opt {
 				let p = PathBuf::from(lp_str);
-				if p.exists() {
-					final_path = Some((p, target_sha.clone()));
+				if let Ok(md) = std::fs::metadata(&p) {
+					if crate::util::source_file_changed(&md, size_bytes, mtime_ms) {
+						changed_source = Some(rel_name);
+					} else {
+						final_path = Some((p, target_sha.clone()));
+					}
 				}
 			}
 
@@ -1096,16 +1634,55 @@ impl SessionActor {
 				}
 			}
 
-			(final_path, mime)
+			(final_path, mime, changed_source, file_name)
 		};
 
+		// 按内容哈希寻址（本机可能不是来源设备）：只发送哈希一致的内容，否则从 CAS 中按哈希取
+		let file_path_res = match &want_sha256 {
+			Some(want) if file_path_res.as_ref().map(|(_, sha)| sha) != Some(want) => {
+				(is_sha256_hex(want) && self.cas.blob_exists(want)).then(|| (self.cas.blob_path(want), want.clone()))
+			}
+			_ => file_path_res,
+		};
+
+		if let (None, Some(rel_name)) = (&file_path_res, changed_source) {
+			{
+				let mut log_store = self.log_store.lock().unwrap();
+				let _ = log_store.log_warn(
+					"Session",
+					&format!("Refused to send changed source file: item_id={}, file={}", item_id, rel_name),
+					Some(&format!("源文件在复制后被修改，拒绝发送: 项目ID={}，文件={}", item_id, rel_name)),
+				);
+			}
+			let evt = serde_json::json!({
+				"type": "SOURCE_FILE_CHANGED",
+				"ts_ms": now_ms(),
+				"payload": { "item_id": item_id, "file_id": file_id, "rel_name": rel_name }
+			});
+			self.sink.emit(evt.to_string());
+			self.send_ctrl(CtrlMsg::ContentCancel {
+				req_id: transfer_id,
+				reason: "SOURCE_FILE_CHANGED".into(),
+			}).await?;
+			return Ok(());
+		}
+
 		if let Some((path, sha256)) = file_path_res {
 			let file = File::open(&path).await?;
 			let meta = file.metadata().await?;
 			let total_bytes = meta.len();
 
-			// [新增] 处理断点续传 offset
-			let start_offset = offset.unwrap_or(0);
+			// 断点续传：从接收端已校验落盘的偏移开始发送；超出文件长度则从头发送
+			let start_offset = offset.filter(|o| *o <= total_bytes).unwrap_or(0);
+			let priority = TransferPriority::for_request(file_id.as_deref());
+			// 对端也支持时，按类型和剩余字节数决定是否压缩
+			let compression = compression::choose(
+				&self.peer_capabilities,
+				self.config.app_config.transfer.compression,
+				&mime_val,
+				file_name.as_deref(),
+				total_bytes - start_offset,
+			);
 
 			// 发送 Header
 			self.send_ctrl(CtrlMsg::ContentBegin {
@@ -1115,55 +1692,54 @@ impl SessionActor {
 				total_bytes,
 				sha256, // 注意：这是整个文件的 Hash
 				mime: mime_val,
+				offset: Some(start_offset),
+				compression: compression.map(str::to_string),
 			}).await?;
 
-			// [新增] 启动独立任务读取文件
-			let tx = self.upload_tx.clone();
+			// 启动独立任务：打开这次传输的数据流并写入文件内容
+			let tx = self.upload_tx.get(priority).clone();
 			let tid = transfer_id.clone();
-
-			let handle = tokio::spawn(async move {
-				let mut file = match File::open(&path).await {
-					Ok(f) => f,
+			let conn = self.conn.clone();
+			let scheduler = self.scheduler.clone();
+			let cancel = CancellationToken::new();
+			let cancelled = cancel.clone();
+
+			tokio::spawn(async move {
+				let mut send = match conn.open_uni().await {
+					Ok(s) => s,
 					Err(e) => { let _ = tx.send(UploadMsg::Error { transfer_id: tid, err: e.to_string() }).await; return; }
 				};
+				// 交互传输的流先于批量传输的流发送
+				let _ = send.set_priority(priority.stream_priority());
 
-				// 断点续传 Seek
-				if start_offset > 0 {
-					if let Err(_e) = file.seek(SeekFrom::Start(start_offset)).await {
-						return;
+				let sent = tokio::select! {
+					r = send_file_stream(&mut send, &path, start_offset, compression.is_some(), &tid, priority, &scheduler, &tx) => Some(r),
+					_ = cancelled.cancelled() => None,
+				};
+				match sent {
+					Some(Ok(())) => {
+						// 接收端按 ContentBegin 中的完整文件 Hash 校验（续传时包含它已有的前缀）
+						let _ = send.finish();
+						let _ = tx.send(UploadMsg::Done { transfer_id: tid }).await;
 					}
-				}
-
-				let mut reader = BufReader::new(file);
-				let mut buf = vec![0u8; 64 * 1024]; // 64KB buffer
-				let mut hasher = Sha256::new();
-
-				loop {
-					match reader.read(&mut buf).await {
-						Ok(0) => break, // EOF
-						Ok(n) => {
-							hasher.update(&buf[..n]);
-							// 使用 Bytes::copy_from_slice 会发生一次内存拷贝，
-							// 但对于 64KB chunk 来说开销可控。
-							// 若极致优化可用 BytesMut，但此处保持简单即可。
-							let data = bytes::Bytes::copy_from_slice(&buf[..n]);
-
-							if tx.send(UploadMsg::Chunk { transfer_id: tid.clone(), data }).await.is_err() {
-								break; // Actor 挂了或取消了
-							}
-						}
-						Err(_) => break,
+					Some(Err(e)) => {
+						let _ = send.reset(STREAM_CANCELLED.into());
+						let _ = tx.send(UploadMsg::Error { transfer_id: tid, err: format!("{:#}", e) }).await;
+					}
+					// 取消：reset 让接收端立即知道（不 reset 的话 drop 会正常结束这条流）
+					None => {
+						let _ = send.reset(STREAM_CANCELLED.into());
 					}
 				}
-
-				// 这里计算的是“本次传输部分”的 Hash
-				// 在 Resume 场景下，这与 ContentBegin 里的完整 Hash 不同
-				// 接收端需要根据协议逻辑决定如何校验（目前 V3 简化版接收端已适配校验片段 Hash）
-				let final_sha = hex::encode(hasher.finalize());
-				let _ = tx.send(UploadMsg::Done { transfer_id: tid, sha256: final_sha }).await;
 			});
 
-			self.senders.insert(transfer_id, handle.abort_handle());
+			self.senders.insert(transfer_id, Upload {
+				cancel,
+				sent_bytes: start_offset,
+				total_bytes,
+				last_progress_emit: 0,
+				meter: ThroughputMeter::new(start_offset),
+			});
 		} else {
 			// 找不到文件，发送错误
 			self.send_ctrl(CtrlMsg::Error {
@@ -1179,6 +1755,8 @@ impl SessionActor {
         &mut self,
         item_id: String,
         file_id: Option<String>,
+        mime: Option<String>,
+        sha256: Option<String>,
         reply_tx: tokio::sync::oneshot::Sender<anyhow::Result<String>>
     ) -> Result<()> {
         let transfer_id = uuid::Uuid::new_v4().to_string();
@@ -1192,20 +1770,115 @@ impl SessionActor {
                         transfer_id, item_id, file_id)),
             );
         }
-        self.send_ctrl(CtrlMsg::ContentGet {
-            msg_id: Some(transfer_id.clone()),
-            item_id,
-            file_id,
-            offset: Some(0),
-        }).await?;
-        let _ = reply_tx.send(Ok(transfer_id));
-        Ok(())
+        let _ = reply_tx.send(Ok(transfer_id.clone()));
+        self.begin_pull(PendingPull { transfer_id, item_id, file_id, mime, sha256 }).await
     }
 
+	/// 发出拉取请求。本地有这份内容的续传数据时，先在后台截断到校验点并计算前缀 Hash，
+	/// 准备好后再带着 offset 请求（见 handle_prepared_pull），避免大文件阻塞 Actor。
+	async fn begin_pull(&mut self, pull: PendingPull) -> Result<()> {
+		let transfer_id = pull.transfer_id.clone();
+		let partial = self.find_partial(&pull)?;
+		self.pulls.insert(transfer_id.clone(), pull);
+
+		let Some((claim, sha256, info)) = partial else {
+			return self.send_content_get(&transfer_id, 0).await;
+		};
+		let cas = self.cas.clone();
+		let ready_tx = self.prepared_tx.clone();
+		tokio::spawn(async move {
+			let prepared = tokio::task::spawn_blocking(move || {
+				let (file, hasher) = cas.open_partial(&claim, &sha256, info.total_bytes, info.verified_bytes)?;
+				let path = cas.partial_path(claim.key());
+				Ok(PreparedWrite { file, hasher, path, claim: Some(claim), sha256, total_bytes: info.total_bytes, offset: info.verified_bytes })
+			}).await.unwrap_or_else(|e| Err(e.into()));
+			let _ = ready_tx.send(PreparedPull { transfer_id, prepared }).await;
+		});
+		Ok(())
+	}
+
+	/// 查找这次拉取可用的续传数据（内容哈希已知、有已落盘的前缀且没有其他传输在写）
+	fn find_partial(&self, pull: &PendingPull) -> Result<Option<(PartialClaim, String, PartialInfo)>> {
+		let sha256 = match &pull.sha256 {
+			Some(sha) => Some(sha.clone()),
+			None => self.store.lock().unwrap().get_content_sha256(&pull.item_id, pull.file_id.as_deref(), pull.mime.as_deref())?,
+		};
+		let Some(sha256) = sha256.filter(|s| is_sha256_hex(s)) else { return Ok(None) };
+		let key = Cas::partial_key(&pull.item_id, pull.file_id.as_deref(), &sha256);
+		let Some(info) = self.cas.load_partial(&key, &sha256).filter(|i| i.verified_bytes > 0) else { return Ok(None) };
+		Ok(self.cas.claim_partial(&key).map(|claim| (claim, sha256, info)))
+	}
+
+	async fn handle_prepared_pull(&mut self, ready: PreparedPull) -> Result<()> {
+		// 准备期间已被取消：丢弃（续传数据保留在磁盘上）
+		if !self.pulls.contains_key(&ready.transfer_id) {
+			return Ok(());
+		}
+		let offset = match ready.prepared {
+			Ok(prepared) => {
+				let offset = prepared.offset;
+				self.prepared.insert(ready.transfer_id.clone(), prepared);
+				offset
+			}
+			Err(e) => {
+				let mut log_store = self.log_store.lock().unwrap();
+				let _ = log_store.log_warn(
+					"Session",
+					&format!("Partial data unusable, restarting transfer: transfer_id={}, error={:#}", ready.transfer_id, e),
+					Some(&format!("续传数据不可用，从头传输: 传输ID={}，错误={:#}", ready.transfer_id, e)),
+				);
+				0
+			}
+		};
+		self.send_content_get(&ready.transfer_id, offset).await
+	}
+
+	async fn send_content_get(&mut self, transfer_id: &str, offset: u64) -> Result<()> {
+		let Some(pull) = self.pulls.get(transfer_id).cloned() else { return Ok(()) };
+		self.send_ctrl(CtrlMsg::ContentGet {
+			msg_id: Some(pull.transfer_id),
+			item_id: pull.item_id,
+			file_id: pull.file_id,
+			offset: Some(offset),
+			mime: pull.mime,
+			sha256: pull.sha256,
+		}).await
+	}
+
+	/// 拉取结束（完成、失败或取消），返回它是否还在进行中
+	fn forget_pull(&mut self, transfer_id: &str) -> bool {
+		if let Some(mut recv) = self.incoming_streams.remove(transfer_id) {
+			let _ = recv.stop(STREAM_CANCELLED.into());
+		}
+		self.prepared.remove(transfer_id);
+		self.pulls.remove(transfer_id).is_some()
+	}
+
+	/// 上传进度与当前速率（节流到每 200ms 一次）
+	fn note_upload_progress(&mut self, transfer_id: &str, bytes: u64) {
+		let Some(upload) = self.senders.get_mut(transfer_id) else { return };
+		upload.sent_bytes += bytes;
+		let now = now_ms();
+		if now - upload.last_progress_emit > 200 {
+			upload.last_progress_emit = now;
+			let evt = serde_json::json!({
+				"type": "UPLOAD_PROGRESS",
+				"ts_ms": now,
+				"payload": {
+					"transfer_id": transfer_id,
+					"sent": upload.sent_bytes,
+					"total": upload.total_bytes,
+					"bytes_per_sec": upload.meter.update(upload.sent_bytes)
+				}
+			});
+			self.sink.emit(evt.to_string());
+		}
+	}
+
 	async fn handle_local_cancel(&mut self, transfer_id: String) -> Result<()> {
 		// 1. 尝试作为 Sender 取消
-		if let Some(handle) = self.senders.remove(&transfer_id) {
-			handle.abort(); // 杀掉读文件任务
+		if self.senders.remove(&transfer_id).is_some() {
+			// 移除即 reset 数据流、终止读文件任务
 			self.send_ctrl(CtrlMsg::ContentCancel {
 				req_id: transfer_id.clone(),
 				reason: "User cancelled".into()
@@ -1236,7 +1909,19 @@ impl SessionActor {
 				);
 			}
 			// 调用上面的处理函数清理资源
-			self.handle_content_cancel(transfer_id, "User cancelled".into()).await?;
+			self.handle_content_cancel(transfer_id, "User cancelled".into(), true).await?;
+		} else if self.forget_pull(&transfer_id) {
+			// 还没收到 ContentBegin：通知发送端不必再发
+			self.send_ctrl(CtrlMsg::ContentCancel {
+				req_id: transfer_id.clone(),
+				reason: "User cancelled".into()
+			}).await?;
+			let evt = serde_json::json!({
+				"type": "TRANSFER_CANCELLED",
+				"payload": { "transfer_id": transfer_id }
+			});
+			self.sink.emit(evt.to_string());
+			self.notify_child_cancelled(transfer_id);
 		}
 		Ok(())
 	}
@@ -1312,9 +1997,15 @@ impl SessionActor {
         let uid = self.config.account_uid.clone();
         let did = self.remote_device_id.clone().context("missing remote device id")?;
         let rfp = self.remote_fingerprint.clone();
+        // 服务端看不到拨入方的证书：账号已由 OPAQUE 验证，指纹只由拨号方记录和校验。
+        // 否则两端各记一份（一边是 "unknown"），静态对端 / 地址簿从另一个方向拨号时永远对不上
+        if rfp == UNKNOWN_FINGERPRINT {
+            return Ok(());
+        }
         tokio::task::spawn_blocking(move || {
             let store = Store::open(&data_dir)?;
-            match store.get_peer_fingerprint(&uid, &did)? {
+            // 旧版本在服务端记下的 "unknown" 视同没有记录
+            match store.get_peer_fingerprint(&uid, &did)?.filter(|fp| fp != UNKNOWN_FINGERPRINT) {
                 Some(saved_fp) => {
                     if saved_fp != rfp { anyhow::bail!("TLS_PIN_MISMATCH: saved={}, got={}", saved_fp, rfp); }
                 }
@@ -1377,3 +2068,120 @@ impl SessionActor {
         Ok(())
     }
 }
+
+/// 把文件从 start_offset 起写入数据流（流头 + 内容），按限速节流并回报进度
+#[allow(clippy::too_many_arguments)]
+async fn send_file_stream(
+	send: &mut SendStream,
+	path: &std::path::Path,
+	start_offset: u64,
+	compressed: bool,
+	transfer_id: &str,
+	priority: TransferPriority,
+	scheduler: &TransferScheduler,
+	progress: &mpsc::Sender<UploadMsg>,
+) -> Result<()> {
+	let mut file = File::open(path).await?;
+	// 断点续传 Seek
+	if start_offset > 0 {
+		file.seek(SeekFrom::Start(start_offset)).await?;
+	}
+	send.write_all(&crate::proto::encode_stream_header(transfer_id)?).await?;
+
+	let mut encoder = compressed.then(Compressor::new).transpose()?;
+	let mut reader = BufReader::new(file);
+	let mut buf = vec![0u8; 64 * 1024]; // 64KB buffer
+	loop {
+		let n = reader.read(&mut buf).await?;
+		if n == 0 {
+			// EOF：结束压缩帧
+			if let Some(enc) = encoder {
+				write_paced(send, enc.finish()?.into(), priority, scheduler).await?;
+			}
+			return Ok(());
+		}
+		let data = match encoder.take() {
+			Some(enc) => {
+				let (enc, out) = enc.push_blocking(buf[..n].to_vec()).await?;
+				encoder = Some(enc);
+				out.into()
+			}
+			None => bytes::Bytes::copy_from_slice(&buf[..n]),
+		};
+		write_paced(send, data, priority, scheduler).await?;
+		// 进度按原始字节计算
+		let _ = progress.send(UploadMsg::Progress { transfer_id: transfer_id.to_string(), bytes: n as u64 }).await;
+	}
+}
+
+/// 按实际发出的字节（压缩后）限速后写入数据流。
+/// 压缩器一次可能吐出不止一块，按块取令牌（桶容量只保证容得下一块）
+async fn write_paced(
+	send: &mut SendStream,
+	mut data: bytes::Bytes,
+	priority: TransferPriority,
+	scheduler: &TransferScheduler,
+) -> Result<()> {
+	while !data.is_empty() {
+		let piece = data.split_to(data.len().min(CHUNK_BYTES));
+		// 全局上传限速（交互传输优先）
+		scheduler.upload().acquire(piece.len() as u64, priority).await;
+		send.write_chunk(piece).await?;
+	}
+	Ok(())
+}
+
+/// 把数据流的内容（压缩的先解压）交给 Writer 任务，返回流结束时要交回 Actor 的事件。
+/// decompress_limit 为 Some 时按压缩流解压，解压出的字节超过它即失败。
+/// None 表示连接已断开：会话随之结束，Writer 任务保存续传数据。
+pub(super) async fn pump_data_stream(
+	recv: &mut RecvStream,
+	transfer_id: &str,
+	decompress_limit: Option<u64>,
+	priority: TransferPriority,
+	scheduler: &TransferScheduler,
+	writer: &mpsc::Sender<ReceiverTaskMsg>,
+	events: &mpsc::Sender<StreamEvent>,
+) -> Option<StreamEvent> {
+	let decode_failed = |e: anyhow::Error| StreamEvent::DecodeFailed { transfer_id: transfer_id.to_string(), err: format!("{:#}", e) };
+	let mut decoder = match decompress_limit.map(Decompressor::new).transpose() {
+		Ok(d) => d,
+		Err(e) => return Some(decode_failed(e)),
+	};
+	loop {
+		match recv.read_chunk(64 * 1024, true).await {
+			Ok(Some(chunk)) => {
+				// 全局下载限速按收到的字节（压缩后）计；交互传输优先
+				scheduler.download().acquire(chunk.bytes.len() as u64, priority).await;
+				let data = match decoder.take() {
+					Some(d) => match d.push_blocking(chunk.bytes).await {
+						Ok((d, out)) => {
+							decoder = Some(d);
+							if out.is_empty() {
+								continue;
+							}
+							bytes::Bytes::from(out)
+						}
+						Err(e) => return Some(decode_failed(e)),
+					},
+					None => chunk.bytes,
+				};
+				// 进度按解压后的字节计算，与 total_bytes / 续传偏移一致
+				let bytes = data.len() as u64;
+				if writer.send(ReceiverTaskMsg::Chunk(data)).await.is_err() {
+					return Some(StreamEvent::WriteFailed { transfer_id: transfer_id.to_string() });
+				}
+				let _ = events.send(StreamEvent::Received { transfer_id: transfer_id.to_string(), bytes }).await;
+			}
+			Ok(None) => return Some(StreamEvent::Finished { transfer_id: transfer_id.to_string() }),
+			Err(ReadError::Reset(code)) => {
+				return Some(StreamEvent::Reset { transfer_id: transfer_id.to_string(), code: code.into_inner() });
+			}
+			Err(_) => return None,
+		}
+	}
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+	s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
@@ -0,0 +1,134 @@
+// cb_core/src/session/compression.rs
+
+//! 内容传输压缩：双方在 Hello / HelloAck 的 capabilities 中都声明 "zstd" 才启用。
+//! 发送端按 MIME / 文件名和待发送的字节数逐个传输决定，并在 ContentBegin.compression 中声明；
+//! 压缩只作用于数据流，接收端解压后照常按完整文件的 sha256 校验，续传偏移也按解压后的字节计算。
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+pub const ZSTD: &str = "zstd";
+
+/// 太小的内容压缩收益抵不过帧开销
+const MIN_COMPRESS_BYTES: u64 = 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+/// 本身已经压缩过的格式，再压一遍只浪费 CPU
+const PRECOMPRESSED_MIMES: &[&str] = &[
+	"image/png", "image/jpeg", "image/gif", "image/webp", "image/heic", "image/avif",
+	"application/zip", "application/gzip", "application/x-7z-compressed", "application/vnd.rar",
+	"application/x-rar-compressed", "application/x-bzip2", "application/x-xz", "application/zstd",
+	"application/pdf",
+];
+const PRECOMPRESSED_EXTS: &[&str] = &[
+	"png", "jpg", "jpeg", "gif", "webp", "heic", "avif",
+	"zip", "gz", "tgz", "bz2", "xz", "7z", "rar", "zst",
+	"docx", "xlsx", "pptx", "jar", "apk", "pdf",
+	"mp3", "aac", "m4a", "ogg", "flac", "mp4", "mkv", "mov", "avi", "webm",
+];
+
+/// 本端在握手中声明的能力
+pub fn local_capabilities(compression_enabled: bool) -> Vec<String> {
+	let mut caps = vec!["text".to_string(), "image".to_string(), "file".to_string()];
+	if compression_enabled {
+		caps.push(ZSTD.to_string());
+	}
+	caps
+}
+
+/// 决定这次传输用什么压缩；name 是 FileList 子文件的文件名（按扩展名判断），bytes 是实际要发送的字节数
+pub fn choose(
+	peer_caps: &[String],
+	enabled: bool,
+	mime: &str,
+	name: Option<&str>,
+	bytes: u64,
+) -> Option<&'static str> {
+	if !enabled || bytes < MIN_COMPRESS_BYTES || !peer_caps.iter().any(|c| c == ZSTD) {
+		return None;
+	}
+	(!is_precompressed(mime, name)).then_some(ZSTD)
+}
+
+fn is_precompressed(mime: &str, name: Option<&str>) -> bool {
+	let mime = mime.to_ascii_lowercase();
+	if mime.starts_with("video/") || mime.starts_with("audio/") || PRECOMPRESSED_MIMES.contains(&mime.as_str()) {
+		return true;
+	}
+	name.and_then(|n| std::path::Path::new(n).extension())
+		.map(|e| e.to_string_lossy().to_ascii_lowercase())
+		.is_some_and(|e| PRECOMPRESSED_EXTS.contains(&e.as_str()))
+}
+
+/// 发送端的流式压缩：每次喂入原始数据，取出已经产生的压缩数据（可能为空）
+pub struct Compressor(zstd::stream::write::Encoder<'static, Vec<u8>>);
+
+impl Compressor {
+	pub fn new() -> Result<Self> {
+		Ok(Self(zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?))
+	}
+
+	pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+		self.0.write_all(data)?;
+		Ok(std::mem::take(self.0.get_mut()))
+	}
+
+	/// 同 push，但在阻塞线程池里压缩，不占用会话所在的运行时
+	pub async fn push_blocking(mut self, data: Vec<u8>) -> Result<(Self, Vec<u8>)> {
+		tokio::task::spawn_blocking(move || {
+			let out = self.push(&data)?;
+			Ok((self, out))
+		}).await?
+	}
+
+	/// 结束压缩帧，返回剩余的压缩数据
+	pub fn finish(self) -> Result<Vec<u8>> {
+		Ok(self.0.finish()?)
+	}
+}
+
+/// 接收端的流式解压：每次喂入收到的数据，取出解压后的数据。
+/// 解压结果超过 limit（这次传输剩余的原始字节数）即报错，防止很小的压缩数据解出巨量内容
+pub struct Decompressor(zstd::stream::write::Decoder<'static, LimitedSink>);
+
+impl Decompressor {
+	pub fn new(limit: u64) -> Result<Self> {
+		Ok(Self(zstd::stream::write::Decoder::new(LimitedSink { buf: Vec::new(), remaining: limit })?))
+	}
+
+	pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+		self.0.write_all(data).context("Corrupted compressed stream")?;
+		self.0.flush().context("Corrupted compressed stream")?;
+		Ok(std::mem::take(&mut self.0.get_mut().buf))
+	}
+
+	/// 同 push，但在阻塞线程池里解压
+	pub async fn push_blocking(mut self, data: bytes::Bytes) -> Result<(Self, Vec<u8>)> {
+		tokio::task::spawn_blocking(move || {
+			let out = self.push(&data)?;
+			Ok((self, out))
+		}).await?
+	}
+}
+
+/// 解压输出：写入超过剩余额度时报错，解压器随即停止
+struct LimitedSink {
+	buf: Vec<u8>,
+	remaining: u64,
+}
+
+impl Write for LimitedSink {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		if data.len() as u64 > self.remaining {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "decompressed data exceeds the declared size"));
+		}
+		self.remaining -= data.len() as u64;
+		self.buf.extend_from_slice(data);
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
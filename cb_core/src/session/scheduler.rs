@@ -0,0 +1,173 @@
+// cb_core/src/session/scheduler.rs
+
+//! 传输调度：全部会话共享的限速（令牌桶）与数据流优先级。
+//! 交互传输（文字、图片、富文本）优先于批量传输（FileList 子文件）。
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::policy::TransferPolicy;
+
+/// 数据块大小，令牌桶容量至少能容纳一块；一次 acquire 不能超过一块，否则批量传输永远等不到
+pub(crate) const CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferPriority {
+    /// 文字 / 图片 / 富文本粘贴：用户在等
+    Interactive,
+    /// FileList 子文件
+    Bulk,
+}
+
+impl TransferPriority {
+    pub fn for_request(file_id: Option<&str>) -> Self {
+        if file_id.is_some() { Self::Bulk } else { Self::Interactive }
+    }
+
+    /// 数据流的 QUIC 优先级：同一连接上优先级高的流先发送
+    pub fn stream_priority(self) -> i32 {
+        match self {
+            Self::Interactive => 1,
+            Self::Bulk => 0,
+        }
+    }
+}
+
+/// 令牌桶限速；bytes_per_sec 为 0 表示不限速
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self { bytes_per_sec, bucket: Mutex::new(Bucket { tokens: 0.0, updated: Instant::now() }) }
+    }
+
+    /// 批量传输等到有足够的令牌；交互传输直接扣除（允许透支），批量传输随后等待更久，从而为它让路
+    pub async fn acquire(&self, bytes: u64, priority: TransferPriority) {
+        if self.bytes_per_sec == 0 {
+            return;
+        }
+        let rate = self.bytes_per_sec as f64;
+        // 桶容量：约 250ms 的流量，且不小于一个数据块
+        let burst = (rate / 4.0).max(CHUNK_BYTES as f64);
+        loop {
+            let wait = {
+                let mut b = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                b.tokens = (b.tokens + now.duration_since(b.updated).as_secs_f64() * rate).min(burst);
+                b.updated = now;
+                if priority == TransferPriority::Interactive || b.tokens >= bytes as f64 {
+                    b.tokens -= bytes as f64;
+                    return;
+                }
+                Duration::from_secs_f64((bytes as f64 - b.tokens) / rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 全部会话共享的调度器：上传、下载各一个令牌桶
+pub struct TransferScheduler {
+    upload: RateLimiter,
+    download: RateLimiter,
+}
+
+impl TransferScheduler {
+    pub fn new(policy: &TransferPolicy) -> Self {
+        Self {
+            upload: RateLimiter::new(policy.upload_bytes_per_sec.max(0) as u64),
+            download: RateLimiter::new(policy.download_bytes_per_sec.max(0) as u64),
+        }
+    }
+
+    pub fn upload(&self) -> &RateLimiter {
+        &self.upload
+    }
+
+    /// 接收端限速：每条数据流读出数据前从这里取令牌。数据流与控制流分开，
+    /// 少读只会让这条流的 QUIC 流控生效，发送端随之放慢，不影响心跳。
+    pub fn download(&self) -> &RateLimiter {
+        &self.download
+    }
+}
+
+/// 会话内的上传通知队列：交互传输的进度 / 结束消息总是先于排队中的批量消息处理
+pub struct PriorityQueue<T> {
+    interactive: mpsc::Receiver<T>,
+    bulk: mpsc::Receiver<T>,
+}
+
+pub struct PrioritySender<T> {
+    interactive: mpsc::Sender<T>,
+    bulk: mpsc::Sender<T>,
+}
+
+impl<T> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        Self { interactive: self.interactive.clone(), bulk: self.bulk.clone() }
+    }
+}
+
+pub fn priority_channel<T>(capacity: usize) -> (PrioritySender<T>, PriorityQueue<T>) {
+    let (interactive_tx, interactive_rx) = mpsc::channel(capacity);
+    let (bulk_tx, bulk_rx) = mpsc::channel(capacity);
+    (
+        PrioritySender { interactive: interactive_tx, bulk: bulk_tx },
+        PriorityQueue { interactive: interactive_rx, bulk: bulk_rx },
+    )
+}
+
+impl<T> PrioritySender<T> {
+    pub fn get(&self, priority: TransferPriority) -> &mpsc::Sender<T> {
+        match priority {
+            TransferPriority::Interactive => &self.interactive,
+            TransferPriority::Bulk => &self.bulk,
+        }
+    }
+}
+
+impl<T> PriorityQueue<T> {
+    pub async fn recv(&mut self) -> Option<T> {
+        tokio::select! {
+            biased;
+            Some(msg) = self.interactive.recv() => Some(msg),
+            Some(msg) = self.bulk.recv() => Some(msg),
+            else => None,
+        }
+    }
+}
+
+/// 单个传输的速率统计（字节/秒，指数平滑）
+pub struct ThroughputMeter {
+    last_bytes: u64,
+    last_at: Instant,
+    rate: f64,
+}
+
+impl ThroughputMeter {
+    pub fn new(start_bytes: u64) -> Self {
+        Self { last_bytes: start_bytes, last_at: Instant::now(), rate: 0.0 }
+    }
+
+    /// 记录当前累计字节数，返回平滑后的速率
+    pub fn update(&mut self, total_bytes: u64) -> u64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_at).as_secs_f64();
+        if elapsed >= 0.25 {
+            let current = total_bytes.saturating_sub(self.last_bytes) as f64 / elapsed;
+            self.rate = if self.rate == 0.0 { current } else { self.rate * 0.5 + current * 0.5 };
+            self.last_bytes = total_bytes;
+            self.last_at = now;
+        }
+        self.rate as u64
+    }
+}
@@ -67,6 +67,7 @@ struct TestContext {
     store: Arc<Mutex<Store>>,
     cas: Cas,
     log_store: Arc<Mutex<LogStore>>,
+    scheduler: Arc<TransferScheduler>,
 }
 
 async fn setup(name: &str, password: &str) -> TestContext {
@@ -95,9 +96,11 @@ async fn setup(name: &str, password: &str) -> TestContext {
     let log_store = Arc::new(Mutex::new(LogStore::open(&config.data_dir).expect("Failed to init LogStore")));
     let sink = Arc::new(TestSink::new());
     // 端口传 0 让系统自动分配，避免端口冲突
-    let transport = Arc::new(Transport::new(0, &config.data_dir, &config.device_id, &config.account_uid).unwrap());
+    let transport = Arc::new(Transport::new(&config.app_config.network, &config.data_dir, &config.device_id, &config.account_uid).unwrap());
 
-    TestContext { config, sink, transport, store, cas, log_store}
+    let scheduler = Arc::new(TransferScheduler::new(&config.app_config.transfer));
+
+    TestContext { config, sink, transport, store, cas, log_store, scheduler }
 }
 
 // 建立两个 Transport 之间的真实连接
@@ -138,7 +141,9 @@ async fn test_handshake_success() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         None,
-        srv_ctx.log_store.clone()
+        srv_ctx.log_store.clone(),
+        srv_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     // Client 端知道自己要连 srv_ok
@@ -150,7 +155,9 @@ async fn test_handshake_success() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         Some("srv_ok".to_string()),
-        cli_ctx.log_store.clone()
+        cli_ctx.log_store.clone(),
+        cli_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     // 断言：双方都应该收到 PEER_ONLINE
@@ -190,7 +197,9 @@ async fn test_auth_fail_tag_mismatch() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         None,
-        srv_ctx.log_store.clone()
+        srv_ctx.log_store.clone(),
+        srv_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     let _cli_handle = SessionActor::spawn(
@@ -201,7 +210,9 @@ async fn test_auth_fail_tag_mismatch() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         Some("srv_diff".to_string()),
-        cli_ctx.log_store.clone()
+        cli_ctx.log_store.clone(),
+        cli_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     // 等待一会
@@ -236,7 +247,9 @@ async fn test_tofu_reject_changed_fingerprint() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         None,
-        srv_ctx.log_store.clone()
+        srv_ctx.log_store.clone(),
+        srv_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     let _cli_handle = SessionActor::spawn(
@@ -247,7 +260,9 @@ async fn test_tofu_reject_changed_fingerprint() {
         srv_ctx.store.clone(),
         srv_ctx.cas.clone(),
         Some("srv_hack".to_string()),
-        cli_ctx.log_store.clone()
+        cli_ctx.log_store.clone(),
+        cli_ctx.scheduler.clone(),
+        Arc::default()
     );
 
     // Client 会完成握手（因为 Tag 是对的），但在最后一步 AuthOk 处理时，
@@ -258,4 +273,145 @@ async fn test_tofu_reject_changed_fingerprint() {
 
     // 断言：Client 绝对不能 Online
     cli_ctx.sink.assert_no_event("PEER_ONLINE");
-}
\ No newline at end of file
+}
+/// 以指定协议版本手动完成 Hello，返回对端的第一条回复
+async fn raw_hello(conn: &Connection, protocol_version: u32) -> Option<crate::proto::CtrlMsg> {
+    use crate::proto::{CBFrame, CBFrameCodec, CtrlMsg};
+    use futures::{SinkExt, StreamExt};
+
+    let (send, recv) = conn.open_bi().await.unwrap();
+    let mut writer = tokio_util::codec::FramedWrite::new(send, CBFrameCodec);
+    let mut reader = tokio_util::codec::FramedRead::new(recv, CBFrameCodec);
+    writer.send(CBFrame::Control(CtrlMsg::Hello {
+        msg_id: Some("hello-1".to_string()),
+        protocol_version,
+        device_id: "cli_old".to_string(),
+        account_uid: "test_uid".to_string(),
+        capabilities: Vec::new(),
+        client_nonce: None,
+    })).await.unwrap();
+    match tokio::time::timeout(Duration::from_secs(2), reader.next()).await {
+        Ok(Some(Ok(CBFrame::Control(msg)))) => Some(msg),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_server_rejects_other_protocol_version() {
+    let srv_ctx = setup("srv_version", "tag_same").await;
+    let cli_ctx = setup("cli_version", "tag_same").await;
+    let (srv_conn, cli_conn) = link_peers(&srv_ctx, &cli_ctx).await;
+
+    let _srv_handle = SessionActor::spawn(
+        SessionRole::Server,
+        srv_conn,
+        srv_ctx.config.clone(),
+        srv_ctx.sink.clone(),
+        srv_ctx.store.clone(),
+        srv_ctx.cas.clone(),
+        None,
+        srv_ctx.log_store.clone(),
+        srv_ctx.scheduler.clone(),
+        Arc::default()
+    );
+
+    // 旧版本的 Hello：服务端回 AuthFail 并断开，不进入 OPAQUE
+    let reply = raw_hello(&cli_conn, crate::proto::PROTOCOL_VERSION - 1).await;
+    match reply {
+        Some(crate::proto::CtrlMsg::AuthFail { code, .. }) => assert_eq!(code, "PROTOCOL_VERSION_MISMATCH"),
+        other => panic!("expected AuthFail, got {:?}", other),
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    srv_ctx.sink.assert_no_event("PEER_ONLINE");
+}
+
+#[tokio::test]
+async fn test_client_rejects_other_protocol_version() {
+    use crate::proto::{CBFrame, CBFrameCodec, CtrlMsg};
+    use futures::{SinkExt, StreamExt};
+
+    let srv_ctx = setup("srv_old", "tag_same").await;
+    let cli_ctx = setup("cli_new", "tag_same").await;
+    let (srv_conn, cli_conn) = link_peers(&srv_ctx, &cli_ctx).await;
+
+    let _cli_handle = SessionActor::spawn(
+        SessionRole::Client,
+        cli_conn,
+        cli_ctx.config.clone(),
+        cli_ctx.sink.clone(),
+        cli_ctx.store.clone(),
+        cli_ctx.cas.clone(),
+        Some("srv_old".to_string()),
+        cli_ctx.log_store.clone(),
+        cli_ctx.scheduler.clone(),
+        Arc::default()
+    );
+
+    // 手动扮演旧版本服务端：回一个版本不同的 HelloAck
+    let (send, recv) = srv_conn.accept_bi().await.unwrap();
+    let mut writer = tokio_util::codec::FramedWrite::new(send, CBFrameCodec);
+    let mut reader = tokio_util::codec::FramedRead::new(recv, CBFrameCodec);
+    let hello = reader.next().await.unwrap().unwrap();
+    assert!(matches!(hello, CBFrame::Control(CtrlMsg::Hello { protocol_version: crate::proto::PROTOCOL_VERSION, .. })));
+    writer.send(CBFrame::Control(CtrlMsg::HelloAck {
+        reply_to: None,
+        server_device_id: "srv_old".to_string(),
+        protocol_version: crate::proto::PROTOCOL_VERSION - 1,
+        capabilities: Vec::new(),
+    })).await.unwrap();
+
+    // 客户端不再继续 OPAQUE 登录
+    let next = tokio::time::timeout(Duration::from_secs(2), reader.next()).await;
+    assert!(
+        !matches!(next, Ok(Some(Ok(CBFrame::Control(CtrlMsg::OpaqueStart { .. }))))),
+        "client continued the handshake with a peer on another protocol version"
+    );
+    cli_ctx.sink.assert_no_event("PEER_ONLINE");
+}
+
+#[tokio::test]
+async fn test_oversized_compressed_stream_is_rejected() {
+    use super::actor::{pump_data_stream, ReceiverTaskMsg, StreamEvent};
+    use super::compression::Compressor;
+    use super::scheduler::TransferPriority;
+
+    let srv_ctx = setup("srv_bomb", "tag_same").await;
+    let cli_ctx = setup("cli_bomb", "tag_same").await;
+    let (srv_conn, cli_conn) = link_peers(&srv_ctx, &cli_ctx).await;
+
+    // 声明 1MB，实际压缩的是 64MB 的零：几 KB 的压缩数据就能解出全部内容
+    let declared = 1024 * 1024u64;
+    let mut enc = Compressor::new().unwrap();
+    let mut wire = Vec::new();
+    for _ in 0..64 {
+        wire.extend(enc.push(&vec![0u8; 1024 * 1024]).unwrap());
+    }
+    wire.extend(enc.finish().unwrap());
+    let sender = tokio::spawn(async move {
+        let mut send = cli_conn.open_uni().await.unwrap();
+        let _ = send.write_all(&wire).await;
+        let _ = send.finish();
+        let _ = send.stopped().await;
+    });
+
+    let mut recv = srv_conn.accept_uni().await.unwrap();
+    let (writer, mut written) = tokio::sync::mpsc::channel(1024);
+    let (events, _events_rx) = tokio::sync::mpsc::channel(1024);
+    let evt = tokio::time::timeout(
+        Duration::from_secs(30),
+        pump_data_stream(&mut recv, "bomb", Some(declared), TransferPriority::Bulk, &srv_ctx.scheduler, &writer, &events),
+    ).await.expect("pump did not stop");
+    assert!(matches!(evt, Some(StreamEvent::DecodeFailed { .. })), "oversized stream was not rejected");
+
+    // 交给 Writer 的数据不超过声明的大小
+    drop(writer);
+    let mut total = 0u64;
+    while let Some(msg) = written.recv().await {
+        if let ReceiverTaskMsg::Chunk(data) = msg {
+            total += data.len() as u64;
+        }
+    }
+    assert!(total <= declared, "{} bytes handed to the writer", total);
+    let _ = recv.stop(0u32.into());
+    sender.abort();
+}
@@ -1,8 +1,15 @@
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::model::{FileMeta, ItemKind, ItemMeta};
+use crate::migrate::{self, Migration};
+use crate::model::{
+    FileMeta, HistoryCursor, HistoryFilter, HistoryPage, ItemContent, ItemKind, ItemMeta, SearchHit, SearchPage,
+};
+
+/// 正文全文索引的最大字节数（超出部分不索引）
+pub const SEARCH_BODY_MAX_BYTES: usize = 1024 * 1024;
 
 pub struct Store {
     pub(crate) conn: Connection,
@@ -12,6 +19,16 @@ pub struct CacheRow {
     pub present: bool,
 }
 
+/// 条目对某个 blob 的一处引用：主格式（file_id、mime 都为空）、
+/// 额外表示 / 纯文本降级（mime）、或文件列表中的文件（file_id）
+#[derive(Clone, Debug)]
+pub struct BlobRef {
+    pub item_id: String,
+    pub owner_device_id: String,
+    pub file_id: Option<String>,
+    pub mime: Option<String>,
+}
+
 /// PeerRule 结构体：设备共享策略规则
 pub struct PeerRule {
     pub account_uid: String,
@@ -21,15 +38,59 @@ pub struct PeerRule {
     pub updated_at_ms: i64,
 }
 
+/// 手动配置的对端地址；device_id 在第一次握手成功后记下
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct StaticPeer {
+    pub addr: String,
+    pub device_id: Option<String>,
+    pub added_at_ms: i64,
+}
+
+/// 地址簿：见过的对端设备。重启后据此直接拨号，不必等 mDNS 重新宣告
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct PeerRecord {
+    pub device_id: String,
+    pub device_name: Option<String>,
+    /// 最近一次宣告的地址 ("ip:port")
+    pub addrs: Vec<String>,
+    pub capabilities: Vec<String>,
+    pub last_seen_ms: i64,
+    /// 上次连接成功的地址，下次拨号优先尝试
+    pub last_good_addr: Option<String>,
+}
+
+/// 存活条目：不是墓碑，且至少还有一条未软删除的历史。只有它们引用的 blob 需要保留。
+const LIVE_ITEMS_SQL: &str = r#"SELECT i.* FROM items i
+    WHERE i.deleted_ts_ms IS NULL
+      AND EXISTS(SELECT 1 FROM history h WHERE h.item_id = i.item_id AND h.is_deleted = 0)"#;
+
+/// 置顶条目：它们引用的 blob 不参与 LRU 淘汰
+const PINNED_ITEMS_SQL: &str = r#"SELECT i.* FROM history h JOIN items i ON i.item_id = h.item_id
+    WHERE h.pinned=1 AND h.is_deleted=0"#;
+
+/// 生成 CTE：`{name}_items` 为 `items_sql` 选出的条目，`{name}_blobs(sha)` 为它们引用的全部 blob
+/// （主格式、纯文本降级、额外表示、文件列表中已知 sha256 的文件）。
+fn blob_refs_cte(name: &str, items_sql: &str) -> String {
+    format!(
+        r#"{name}_items AS ({items_sql}),
+        {name}_blobs(sha) AS (
+            SELECT sha256_hex FROM {name}_items
+            UNION SELECT json_extract(text_fallback_json, '$.sha256') FROM {name}_items
+            UNION SELECT json_extract(r.value, '$.sha256') FROM {name}_items, json_each({name}_items.representations_json) r
+            UNION SELECT json_extract(f.value, '$.sha256') FROM {name}_items, json_each({name}_items.files_json) f
+        )"#
+    )
+}
+
 impl Store {
     pub fn open(data_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
         let data_dir = data_dir.as_ref();
         fs::create_dir_all(data_dir)?;
         let db_path: PathBuf = data_dir.join("core.db");
 
-        let conn = Connection::open(db_path)?;
+        let mut conn = Connection::open(db_path)?;
         Self::init_pragmas(&conn)?;
-        Self::init_schema(&conn)?;
+        Self::init_schema(&mut conn)?;
         Ok(Self { conn })
     }
 
@@ -44,25 +105,42 @@ impl Store {
         Ok(())
     }
 
-    /// 初始化数据库模式（统一的初始化函数，合并了所有迁移）
-    ///
-    /// 此函数直接创建完整的数据库结构，不进行版本检查。
-    /// 开发阶段，每次启动都重新创建表结构（使用 CREATE TABLE IF NOT EXISTS）。
+    /// 初始化数据库模式：按 `PRAGMA user_version` 依次执行 `MIGRATIONS` 中尚未执行的步骤。
     ///
     /// # 模式
     /// - `items`: 存储项的元数据
     /// - `history`: 追踪项的历史数据
     /// - `content_cache`: 存储内容缓存信息
     /// - `trusted_peers`: 记录已信任的设备指纹
-    ///
-    /// # 参数
-    /// - `conn`: 指向 SQLite 数据库连接的引用
+    /// - `peer_rules`: 设备共享策略
+    /// - `static_peers`: 手动配置的对端地址
+    /// - `peers`: 见过的对端设备（地址簿）
+    /// - `items_fts`: 历史全文索引
     ///
     /// # 返回值
     /// - `Ok(())`: 如果初始化成功
-    /// - `Err(anyhow::Error)`: 如果初始化失败
-    fn init_schema(conn: &Connection) -> anyhow::Result<()> {
-        conn.execute_batch(
+    /// - `Err(anyhow::Error)`: 迁移失败，或数据库版本高于当前程序（`STORE_SCHEMA_TOO_NEW`）
+    fn init_schema(conn: &mut Connection) -> anyhow::Result<()> {
+        migrate::run_migrations(conn, "core.db", Self::MIGRATIONS)
+    }
+
+    /// core.db 的迁移列表。只能在末尾追加，已发布的步骤不得修改。
+    /// 每一步都要能在“已部分具备该结构”的库上重复执行（无版本号的旧库会从 v1 开始跑）。
+    const MIGRATIONS: &'static [Migration] = &[
+        Migration { version: 1, name: "initial", up: Self::migrate_v1_initial },
+        Migration { version: 2, name: "item_representations", up: Self::migrate_v2_item_representations },
+        Migration { version: 3, name: "history_search", up: Self::migrate_v3_history_search },
+        Migration { version: 4, name: "history_pinned", up: Self::migrate_v4_history_pinned },
+        Migration { version: 5, name: "item_tombstones", up: Self::migrate_v5_item_tombstones },
+        Migration { version: 6, name: "file_cache_rows", up: Self::migrate_v6_file_cache_rows },
+        Migration { version: 7, name: "static_peers", up: Self::migrate_v7_static_peers },
+        Migration { version: 8, name: "peers", up: Self::migrate_v8_peers },
+        Migration { version: 9, name: "search_body_backfill", up: Self::migrate_v9_search_body_backfill },
+    ];
+
+    /// v1：最初的表结构
+    fn migrate_v1_initial(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
             r#"
             -- items 表：存储项的元数据
             CREATE TABLE IF NOT EXISTS items (
@@ -128,14 +206,189 @@ impl Store {
         Ok(())
     }
 
+    /// v2：RichText 纯文本降级与多格式表示
+    fn migrate_v2_item_representations(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        migrate::add_column_if_missing(tx, "items", "text_fallback_json", "TEXT")?;
+        migrate::add_column_if_missing(tx, "items", "representations_json", "TEXT")?;
+        Ok(())
+    }
+
+    /// v3：历史分页索引 + 全文索引 items_fts。
+    /// trigram 分词，支持中文与 URL 等任意子串检索；建表时从 items 回填预览文本与文件名
+    /// （正文要等内容缓存后再写入）。
+    fn migrate_v3_history_search(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_history_account_page ON history(account_uid, is_deleted, sort_ts_ms DESC, history_id DESC);",
+        )?;
+        if migrate::table_exists(tx, "items_fts")? {
+            return Ok(());
+        }
+
+        tx.execute_batch(
+            r#"
+            CREATE VIRTUAL TABLE items_fts USING fts5(
+              item_id UNINDEXED,
+              preview,
+              body,
+              file_names,
+              tokenize = 'trigram'
+            );
+            INSERT INTO items_fts(item_id, preview, body, file_names)
+            SELECT
+              item_id,
+              COALESCE(json_extract(preview_json, '$.text'), ''),
+              '',
+              COALESCE((SELECT group_concat(json_extract(f.value, '$.rel_name'), char(10))
+                        FROM json_each(items.files_json) f), '')
+            FROM items;
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// v4：历史置顶标记 + 置顶优先分页索引
+    fn migrate_v4_history_pinned(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        migrate::add_column_if_missing(tx, "history", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        tx.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_history_account_pinned ON history(account_uid, is_deleted, pinned DESC, sort_ts_ms DESC, history_id DESC);",
+        )?;
+        Ok(())
+    }
+
+    /// v5：用户删除的条目保留一行墓碑（deleted_ts_ms 非空），防止重放的元数据把它带回来
+    fn migrate_v5_item_tombstones(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        migrate::add_column_if_missing(tx, "items", "deleted_ts_ms", "INTEGER")?;
+        Ok(())
+    }
+
+    /// v6：文件列表中已知 sha256 的文件也登记到 content_cache，纳入引用计数与 LRU
+    fn migrate_v6_file_cache_rows(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
+            r#"
+            INSERT OR IGNORE INTO content_cache(sha256_hex, total_bytes, present, last_access_ts_ms, created_ts_ms)
+            SELECT json_extract(f.value, '$.sha256'),
+                   COALESCE(json_extract(f.value, '$.size_bytes'), 0),
+                   0, items.created_ts_ms, items.created_ts_ms
+            FROM items, json_each(items.files_json) f
+            WHERE items.deleted_ts_ms IS NULL AND json_extract(f.value, '$.sha256') IS NOT NULL;
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// v7：手动配置的对端地址（组播不通的网络里不经 mDNS 直接拨号）
+    fn migrate_v7_static_peers(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS static_peers (
+                account_uid TEXT NOT NULL,
+                addr TEXT NOT NULL,
+                device_id TEXT,
+                added_at_ms INTEGER NOT NULL,
+                PRIMARY KEY (account_uid, addr)
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// v8：对端地址簿（重启后不必等 mDNS 重新宣告）
+    fn migrate_v8_peers(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS peers (
+                account_uid TEXT NOT NULL,
+                device_id TEXT NOT NULL,
+                device_name TEXT,
+                addrs_json TEXT NOT NULL DEFAULT '[]',
+                capabilities_json TEXT NOT NULL DEFAULT '[]',
+                last_seen_ms INTEGER NOT NULL,
+                last_good_addr TEXT,
+                PRIMARY KEY (account_uid, device_id)
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// v9：v3 建索引时正文留空，已缓存在 CAS 里的文本正文排队，由 Core 启动时读出补进索引
+    fn migrate_v9_search_body_backfill(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS search_backfill (
+                item_id TEXT PRIMARY KEY
+            );
+            INSERT OR IGNORE INTO search_backfill(item_id)
+            SELECT item_id FROM items_fts WHERE body = '';
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// 写入一条全文索引（正文留空，由 index_item_body 补充）
+    fn insert_search_row(tx: &rusqlite::Transaction<'_>, meta: &ItemMeta) -> anyhow::Result<()> {
+        let file_names: Vec<&str> = meta.files.iter().map(|f| f.rel_name.as_str()).collect();
+        tx.execute(
+            "INSERT INTO items_fts(item_id, preview, body, file_names) VALUES (?1, ?2, '', ?3)",
+            params![meta.item_id, meta.preview.text.as_deref().unwrap_or(""), file_names.join("\n")],
+        )?;
+        Ok(())
+    }
+
     fn kind_to_str(k: &ItemKind) -> &'static str {
         match k {
             ItemKind::Text => "text",
             ItemKind::Image => "image",
             ItemKind::FileList => "file_list",
+            ItemKind::RichText => "rich_text",
+        }
+    }
+
+    fn kind_from_str(s: &str) -> ItemKind {
+        match s {
+            "text" => ItemKind::Text,
+            "image" => ItemKind::Image,
+            "file_list" => ItemKind::FileList,
+            "rich_text" => ItemKind::RichText,
+            _ => ItemKind::Text,
         }
     }
 
+    /// content_cache 中登记一份内容（已存在则保留原状态）
+    fn insert_cache_row_if_absent(
+        tx: &rusqlite::Transaction<'_>,
+        sha256_hex: &str,
+        total_bytes: i64,
+        now_ms: i64,
+        created_ts_ms: i64,
+    ) -> anyhow::Result<()> {
+        tx.execute(
+            r#"INSERT OR IGNORE INTO content_cache
+               (sha256_hex, total_bytes, present, last_access_ts_ms, created_ts_ms)
+               VALUES (?1, ?2, 0, ?3, ?4)"#,
+            params![sha256_hex, total_bytes, now_ms, created_ts_ms],
+        )?;
+        Ok(())
+    }
+
+    /// 登记条目引用的全部内容：主格式、纯文本降级、额外表示，以及已知 sha256 的文件
+    fn insert_cache_rows(tx: &rusqlite::Transaction<'_>, meta: &ItemMeta, now_ms: i64) -> anyhow::Result<()> {
+        let created = meta.created_ts_ms;
+        Self::insert_cache_row_if_absent(tx, &meta.content.sha256, meta.content.total_bytes, now_ms, created)?;
+        if let Some(fallback) = &meta.text_fallback {
+            Self::insert_cache_row_if_absent(tx, &fallback.sha256, fallback.total_bytes, now_ms, created)?;
+        }
+        for r in &meta.representations {
+            Self::insert_cache_row_if_absent(tx, &r.sha256, r.total_bytes, now_ms, created)?;
+        }
+        for f in &meta.files {
+            if let Some(sha) = &f.sha256 {
+                Self::insert_cache_row_if_absent(tx, sha, f.size_bytes, now_ms, created)?;
+            }
+        }
+        Ok(())
+    }
+
     /// 向数据库插入元数据和历史信息。
     ///
     /// 此函数在单个事务中执行以下操作：
@@ -205,20 +458,21 @@ pub fn insert_meta_and_history(
     ) -> anyhow::Result<CacheRow> {
         let tx = self.conn.transaction()?;
 
-        tx.execute(
-            r#"INSERT OR IGNORE INTO content_cache
-               (sha256_hex, total_bytes, present, last_access_ts_ms, created_ts_ms)
-               VALUES (?1, ?2, 0, ?3, ?4)"#,
-            params![meta.content.sha256, meta.content.total_bytes, now_ms, meta.created_ts_ms],
-        )?;
+        Self::insert_cache_rows(&tx, meta, now_ms)?;
 
         let preview_json = serde_json::to_string(&meta.preview)?;
         let files_json = serde_json::to_string(&meta.files)?;
+        let fallback_json = meta.text_fallback.as_ref().map(serde_json::to_string).transpose()?;
+        let representations_json = if meta.representations.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&meta.representations)?)
+        };
 
         tx.execute(
             r#"INSERT INTO items
-               (item_id, kind, owner_device_id, created_ts_ms, size_bytes, mime, sha256_hex, preview_json, files_json, expires_ts_ms)
-               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"#,
+               (item_id, kind, owner_device_id, created_ts_ms, size_bytes, mime, sha256_hex, preview_json, files_json, expires_ts_ms, text_fallback_json, representations_json)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"#,
             params![
                 meta.item_id,
                 Self::kind_to_str(&meta.kind),
@@ -229,10 +483,14 @@ pub fn insert_meta_and_history(
                 meta.content.sha256,
                 preview_json,
                 files_json,
-                meta.expires_ts_ms
+                meta.expires_ts_ms,
+                fallback_json,
+                representations_json
             ],
         )?;
 
+        Self::insert_search_row(&tx, meta)?;
+
         // history：sort_ts_ms 固定用 created_ts_ms
         // 如果同 (account_uid, item_id) 已经存在，unique index 会挡住；这里用 OR IGNORE 保证幂等
         tx.execute(
@@ -276,6 +534,15 @@ pub fn insert_meta_and_history(
         Ok(n)
     }
 
+    /// blob 在磁盘上但记录为未缓存时补回 present=1（不改动 LRU 时间）
+    pub fn repair_cache_present(&mut self, sha256_hex: &str) -> anyhow::Result<usize> {
+        let n = self.conn.execute(
+            "UPDATE content_cache SET present=1 WHERE sha256_hex=?1 AND present=0",
+            params![sha256_hex],
+        )?;
+        Ok(n)
+    }
+
     pub fn touch_cache(&mut self, sha256_hex: &str, now_ms: i64) -> anyhow::Result<usize> {
         let n = self.conn.execute(
             r#"UPDATE content_cache
@@ -314,129 +581,235 @@ pub fn insert_meta_and_history(
         Ok(n)
     }
 
-    /// 从具有指定账号 UID 的用户历史记录中获取项元数据列表。
+    /// 从具有指定账号 UID 的用户历史记录中分页获取项元数据。
     ///
-    /// 此函数查询数据库，以检索由给定 `account_uid` 标识的用户历史中存在的项元数据。
-    /// 查询受指定的数量限制 (`limit`)。返回的项按历史排序时间戳 (`h.sort_ts_ms`) 倒序排列，
-    /// 如果时间戳相同，则按 `history_id` 倒序排列。
+    /// 返回的项按历史排序时间戳 (`h.sort_ts_ms`) 倒序排列，时间戳相同时按 `history_id` 倒序。
+    /// 分页采用 keyset 方式：`cursor` 为上一页最后一条的 `(sort_ts_ms, history_id)`，
+    /// 本页只返回严格排在它之后的记录，因此翻页期间有新记录插入也不会重复或遗漏。
     ///
     /// # 参数
     ///
-    /// - `account_uid`: 指向表示用户账号唯一标识符的字符串切片的引用。
-    /// - `limit`: 要从历史记录中检索的项元数据的最大数量。
+    /// - `account_uid`: 用户账号唯一标识符。
+    /// - `limit`: 本页最多返回的条数，至少为 1（为 0 时返回 `HISTORY_LIMIT_ZERO`，
+    ///   否则空页的 `next_cursor = None` 会被误读为已到末尾）。
+    /// - `cursor`: 上一页返回的 `next_cursor`；`None` 表示从最新一条开始。
+    /// - `filter`: 过滤条件（类型、来源设备、时间范围、本地是否已缓存），字段均可选。
     ///
     /// # 返回值
     ///
-    /// 成功时返回一个包裹在 `anyhow::Result` 中的 `ItemMeta` 对象向量，
-    /// 如果在数据库查询或数据解析过程中发生任何失败，则返回错误。
-    ///
-    /// `ItemMeta` 对象包含关于项的元数据，包括：
-    /// - `item_id`: 项的唯一标识符。
-    /// - `kind`: 项的类型（例如：`Text`, `Image`, `FileList`）。
-    /// - `source_device_id`: 创建该项的设备 ID。
-    /// - `source_device_name`: (可选) 源设备的名称（当前实现中始终为 `None`）。
-    /// - `created_ts_ms`: 自 Unix 纪元以来的创建时间戳（毫秒）。
-    /// - `size_bytes`: 项的大小（字节）。
-    /// - `preview`: 表示项内容预览的数据结构。
-    /// - `content`: 关联的 `ItemContent` 对象，包含 MIME 类型、SHA-256 哈希和总字节数等额外元数据。
-    /// - `files`: 关联文件元数据的向量 (`FileMeta`)。
-    /// - `expires_ts_ms`: 项的过期时间戳（如果适用）。
+    /// 返回 `HistoryPage`：`items` 为本页数据；若后面还有数据，`next_cursor` 指向本页最后一条，
+    /// 否则为 `None`。
     ///
     /// # 数据库模式
     ///
-    /// 该查询通过连接三张表来获取数据：
-    /// - `history`: 追踪项的历史引用。
-    /// - `items`: 包含单个项的详细信息。
-    /// - `content_cache`: 存储缓存内容的聚合元数据。
-    ///
-    /// 仅包含与指定账号关联且未删除 (`h.is_deleted=0`) 的历史记录。
-    /// 检索以下列：
-    /// - `item_id`, `kind`, `owner_device_id`, `created_ts_ms`, `size_bytes`, `mime`, `sha256_hex`,
-    ///   `preview_json`, `files_json`, `expires_ts_ms` 和 `total_bytes`。
-    ///
-    /// # 内部处理
-    ///
-    /// - 项的类型（`text`, `image`, `file_list`）被映射到 `ItemKind` 枚举。
-    /// - 项预览 (`preview_json`) 和文件列表 (`files_json`) 的 JSON 字符串被解析为
-    ///   相应的数据结构（分别是 `ItemPreview` 和 `Vec<FileMeta>`）。
-    /// - 任何 JSON 解析失败都会优雅地回退到默认值。
-    ///
-    /// # 错误
-    ///
-    /// 在以下任何情况下，此函数都会返回错误：
-    /// - 准备或执行 SQL 查询失败。
-    /// - 数据库行中缺失或包含无效数据。
-    /// - 解析 `preview_json` 或 `files_json` 时发生 JSON 错误。
+    /// 该查询连接 `history`、`items`、`content_cache` 三张表，仅包含未删除 (`h.is_deleted=0`) 的记录。
+    /// JSON 列（`preview_json`、`files_json` 等）解析失败时回退到默认值。
     ///
     /// # 示例
     ///
     /// ```rust,ignore
-    /// let account_uid = "user123";
-    /// let limit = 10;
-    /// match db.list_history_metas(account_uid, limit) {
-    ///     Ok(item_metas) => {
-    ///         for item_meta in item_metas {
-    ///             println!("{:?}", item_meta);
-    ///         }
+    /// let mut cursor = None;
+    /// loop {
+    ///     let page = db.list_history_metas("user123", 50, cursor.as_ref(), &HistoryFilter::default())?;
+    ///     for item_meta in &page.items {
+    ///         println!("{:?}", item_meta);
+    ///     }
+    ///     match page.next_cursor {
+    ///         Some(c) => cursor = Some(c),
+    ///         None => break,
     ///     }
-    ///     Err(err) => eprintln!("获取历史元数据失败: {:?}", err),
     /// }
     /// ```
-    pub fn list_history_metas(&self, account_uid: &str, limit: usize) -> anyhow::Result<Vec<ItemMeta>> {
-        let mut stmt = self.conn.prepare(
+    pub fn list_history_metas(
+        &self,
+        account_uid: &str,
+        limit: usize,
+        cursor: Option<&HistoryCursor>,
+        filter: &HistoryFilter,
+    ) -> anyhow::Result<HistoryPage> {
+        let rows = self.query_history(account_uid, limit, cursor, filter, None)?;
+        let (rows, next_cursor) = Self::split_page(rows, limit);
+        Ok(HistoryPage {
+            items: rows.into_iter().map(|(meta, _)| meta).collect(),
+            next_cursor,
+        })
+    }
+
+    /// 在历史中全文检索：匹配预览文本、已缓存的文本正文以及文件名。
+    ///
+    /// `query` 按空白切分为多个词，所有词都必须命中（AND）。不少于 3 个字符的词走 FTS5 trigram 索引，
+    /// 更短的词（如单个汉字）退化为对索引表的 LIKE 扫描。结果按时间倒序，与 `list_history_metas`
+    /// 共用游标与过滤条件；每条命中附带用 `<mark>…</mark>` 标出关键字的摘要。
+    pub fn search_history(
+        &self,
+        account_uid: &str,
+        query: &str,
+        limit: usize,
+        cursor: Option<&HistoryCursor>,
+        filter: &HistoryFilter,
+    ) -> anyhow::Result<SearchPage> {
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.to_string()).collect();
+        if terms.is_empty() {
+            anyhow::bail!("SEARCH_QUERY_EMPTY");
+        }
+
+        let rows = self.query_history(account_uid, limit, cursor, filter, Some(&terms))?;
+        let (rows, next_cursor) = Self::split_page(rows, limit);
+        Ok(SearchPage {
+            hits: rows
+                .into_iter()
+                .map(|(meta, snippet)| SearchHit { meta, snippet: snippet.unwrap_or_default() })
+                .collect(),
+            next_cursor,
+        })
+    }
+
+    /// 多取一条的结果拆成本页 + 下一页游标
+    fn split_page(
+        mut rows: Vec<(ItemMeta, HistoryCursor, Option<String>)>,
+        limit: usize,
+    ) -> (Vec<(ItemMeta, Option<String>)>, Option<HistoryCursor>) {
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(_, key, _)| key.clone())
+        } else {
+            None
+        };
+        (rows.into_iter().map(|(meta, _, snippet)| (meta, snippet)).collect(), next_cursor)
+    }
+
+    /// 历史查询的公共实现：keyset 分页 + 过滤，可选附加全文检索条件。
+    /// 返回 limit + 1 条，用于判断是否还有下一页。
+    fn query_history(
+        &self,
+        account_uid: &str,
+        limit: usize,
+        cursor: Option<&HistoryCursor>,
+        filter: &HistoryFilter,
+        search_terms: Option<&[String]>,
+    ) -> anyhow::Result<Vec<(ItemMeta, HistoryCursor, Option<String>)>> {
+        use rusqlite::types::Value;
+
+        if limit == 0 {
+            anyhow::bail!("HISTORY_LIMIT_ZERO");
+        }
+
+        let mut params: Vec<Value> = vec![
+            Value::Text(account_uid.to_string()),
+            cursor.map(|c| c.sort_ts_ms).into(),
+            cursor.map(|c| c.history_id).into(),
+            filter.source_device_id.clone().into(),
+            filter.since_ts_ms.into(),
+            filter.until_ts_ms.into(),
+            filter.cached.map(|b| b as i64).into(),
+            Value::Integer(limit as i64 + 1),
+            filter.pinned.map(|b| b as i64).into(),
+            Value::Integer(crate::util::now_ms()),
+        ];
+
+        // 置顶优先模式下排序键前面多一列 pinned，游标比较也要带上
+        let (order_by, cursor_cond) = if filter.pinned_first {
+            params.push(cursor.map(|c| c.pinned as i64).into());
+            (
+                "h.pinned DESC, h.sort_ts_ms DESC, h.history_id DESC",
+                "(?2 IS NULL OR h.pinned < ?11 OR (h.pinned = ?11 AND \
+                 (h.sort_ts_ms < ?2 OR (h.sort_ts_ms = ?2 AND h.history_id < ?3))))",
+            )
+        } else {
+            (
+                "h.sort_ts_ms DESC, h.history_id DESC",
+                "(?2 IS NULL OR h.sort_ts_ms < ?2 OR (h.sort_ts_ms = ?2 AND h.history_id < ?3))",
+            )
+        };
+
+        // kind 来自枚举映射的固定字符串，可以直接拼进 SQL
+        let mut extra_where = String::new();
+        if !filter.kinds.is_empty() {
+            let kinds: Vec<String> = filter
+                .kinds
+                .iter()
+                .map(|k| format!("'{}'", Self::kind_to_str(k)))
+                .collect();
+            extra_where.push_str(&format!("AND i.kind IN ({})\n", kinds.join(",")));
+        }
+
+        let mut fts_join = "";
+        let mut snippet_cols = "NULL, NULL, NULL, NULL";
+        let mut short_terms: Vec<String> = Vec::new();
+        if let Some(terms) = search_terms {
+            fts_join = "JOIN items_fts ON items_fts.item_id = i.item_id";
+
+            // trigram 分词要求至少 3 个字符，更短的词用 LIKE
+            let (long, short): (Vec<&String>, Vec<&String>) =
+                terms.iter().partition(|t| t.chars().count() >= 3);
+
+            if !long.is_empty() {
+                let phrases: Vec<String> = long
+                    .iter()
+                    .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+                    .collect();
+                params.push(Value::Text(phrases.join(" ")));
+                extra_where.push_str(&format!("AND items_fts MATCH ?{}\n", params.len()));
+                snippet_cols = "snippet(items_fts, -1, '<mark>', '</mark>', '…', 24), NULL, NULL, NULL";
+            } else {
+                snippet_cols = "NULL, items_fts.preview, items_fts.file_names, items_fts.body";
+            }
+
+            for t in short {
+                let escaped = t.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+                params.push(Value::Text(format!("%{}%", escaped)));
+                let n = params.len();
+                extra_where.push_str(&format!(
+                    "AND (items_fts.preview LIKE ?{n} ESCAPE '\\' OR items_fts.body LIKE ?{n} ESCAPE '\\' \
+                     OR items_fts.file_names LIKE ?{n} ESCAPE '\\')\n"
+                ));
+                short_terms.push(t.clone());
+            }
+        }
+
+        let sql = format!(
             r#"
             SELECT
                 i.item_id, i.kind, i.owner_device_id, i.created_ts_ms,
                 i.size_bytes, i.mime, i.sha256_hex,
                 i.preview_json, i.files_json, i.expires_ts_ms,
-                cc.total_bytes
+                cc.total_bytes, i.text_fallback_json, i.representations_json, h.pinned,
+                h.sort_ts_ms, h.history_id,
+                {snippet_cols}
             FROM history h
             JOIN items i ON h.item_id = i.item_id
             JOIN content_cache cc ON i.sha256_hex = cc.sha256_hex
+            {fts_join}
             WHERE h.account_uid=?1 AND h.is_deleted=0
-            ORDER BY h.sort_ts_ms DESC, h.history_id DESC
-            LIMIT ?2
-            "#,
-        )?;
+              AND {cursor_cond}
+              AND (?4 IS NULL OR i.owner_device_id = ?4)
+              AND (?5 IS NULL OR h.sort_ts_ms >= ?5)
+              AND (?6 IS NULL OR h.sort_ts_ms < ?6)
+              AND (?7 IS NULL OR cc.present = ?7)
+              AND (?9 IS NULL OR h.pinned = ?9)
+              AND (i.expires_ts_ms IS NULL OR i.expires_ts_ms > ?10 OR h.pinned = 1)
+              {extra_where}
+            ORDER BY {order_by}
+            LIMIT ?8
+            "#
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
 
-        let rows = stmt.query_map(params![account_uid, limit as i64], |r| {
-            let kind_s: String = r.get(1)?;
-            let kind = match kind_s.as_str() {
-                "text" => ItemKind::Text,
-                "image" => ItemKind::Image,
-                "file_list" => ItemKind::FileList,
-                _ => ItemKind::Text,
-            };
-
-            let preview_json: String = r.get(7)?;
-            let preview: crate::model::ItemPreview =
-                serde_json::from_str(&preview_json).unwrap_or_default();
-
-            let files_json: Option<String> = r.get(8)?;
-            let files: Vec<FileMeta> = files_json
-                .as_deref()
-                .and_then(|s| serde_json::from_str::<Vec<FileMeta>>(s).ok())
-                .unwrap_or_default();
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), |r| {
+            let meta = Self::row_to_item_meta(r)?;
+            let key = HistoryCursor { sort_ts_ms: r.get(14)?, history_id: r.get(15)?, pinned: meta.pinned };
 
-            let total_bytes: i64 = r.get(10)?;
-
-            Ok(ItemMeta {
-                ty: "ItemMeta".to_string(),
-                item_id: r.get(0)?,
-                kind,
-                source_device_id: r.get(2)?,
-                source_device_name: None,
-                created_ts_ms: r.get(3)?,
-                size_bytes: r.get(4)?,
-                preview,
-                content: crate::model::ItemContent {
-                    mime: r.get(5)?,
-                    sha256: r.get(6)?,
-                    total_bytes,
-                },
-                files,
-                expires_ts_ms: r.get(9)?,
-            })
+            let mut snippet: Option<String> = r.get(16)?;
+            if snippet.is_none() && search_terms.is_some() {
+                // 只有短词时 FTS 不参与匹配，自己截取摘要
+                let texts = [
+                    r.get::<_, Option<String>>(17)?.unwrap_or_default(),
+                    r.get::<_, Option<String>>(18)?.unwrap_or_default(),
+                    r.get::<_, Option<String>>(19)?.unwrap_or_default(),
+                ];
+                snippet = Some(make_snippet(&texts, &short_terms));
+            }
+            Ok((meta, key, snippet))
         })?;
 
         let mut out = Vec::new();
@@ -446,47 +819,179 @@ pub fn insert_meta_and_history(
         Ok(out)
     }
 
-    /// History GC：只保留最新 keep_latest 条，其余软删除
+    /// 按 item_id 直接查询单条元数据（走主键索引，不受历史条数限制）。
+    ///
+    /// - `include_deleted = false`：只返回该账号历史中仍可见（未被软删除）的条目；
+    /// - `include_deleted = true`：只要 items 表中存在即返回，包括已被 History GC 软删除的条目。
+    ///
+    /// 用户删除（墓碑）以及已过期（未置顶）的条目在两种模式下都不返回。
+    ///
+    /// `total_bytes` 优先取 content_cache 中的值，缺失时回退到 `size_bytes`。
+    pub fn get_item_meta(&self, account_uid: &str, item_id: &str, include_deleted: bool) -> anyhow::Result<Option<ItemMeta>> {
+        let meta = self.conn.query_row(
+            r#"
+            SELECT
+                i.item_id, i.kind, i.owner_device_id, i.created_ts_ms,
+                i.size_bytes, i.mime, i.sha256_hex,
+                i.preview_json, i.files_json, i.expires_ts_ms,
+                COALESCE(cc.total_bytes, i.size_bytes), i.text_fallback_json, i.representations_json,
+                COALESCE((SELECT h.pinned FROM history h WHERE h.item_id = i.item_id AND h.account_uid = ?2), 0)
+            FROM items i
+            LEFT JOIN content_cache cc ON i.sha256_hex = cc.sha256_hex
+            WHERE i.item_id = ?1 AND i.deleted_ts_ms IS NULL
+              AND (i.expires_ts_ms IS NULL OR i.expires_ts_ms > ?4 OR EXISTS(
+                    SELECT 1 FROM history hp
+                    WHERE hp.item_id = i.item_id AND hp.account_uid = ?2 AND hp.pinned = 1 AND hp.is_deleted = 0))
+              AND (?3 OR EXISTS(
+                    SELECT 1 FROM history h
+                    WHERE h.item_id = i.item_id AND h.account_uid = ?2 AND h.is_deleted = 0))
+            "#,
+            params![item_id, account_uid, include_deleted, crate::util::now_ms()],
+            Self::row_to_item_meta,
+        ).optional()?;
+        Ok(meta)
+    }
+
+    /// 写入（覆盖）item 的正文索引。正文来自已缓存的文本内容，超过 SEARCH_BODY_MAX_BYTES 的部分不索引。
+    pub fn index_item_body(&mut self, item_id: &str, body: &str) -> anyhow::Result<()> {
+        let mut end = body.len().min(SEARCH_BODY_MAX_BYTES);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.conn.execute(
+            "UPDATE items_fts SET body = ?2 WHERE item_id = ?1",
+            params![item_id, &body[..end]],
+        )?;
+        Ok(())
+    }
+
+    /// 等待补建正文索引的 item 及其可检索的表示（有纯文本降级时用降级内容）
+    pub fn list_search_backfill(&self) -> anyhow::Result<Vec<(String, ItemContent)>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT b.item_id, i.mime, i.sha256_hex, i.size_bytes, i.text_fallback_json
+               FROM search_backfill b JOIN items i ON i.item_id = b.item_id"#
+        )?;
+        let rows = stmt.query_map([], |r| {
+            let primary = ItemContent { mime: r.get(1)?, sha256: r.get(2)?, total_bytes: r.get(3)? };
+            let fallback: Option<String> = r.get(4)?;
+            let content = fallback
+                .and_then(|s| serde_json::from_str::<ItemContent>(&s).ok())
+                .unwrap_or(primary);
+            Ok((r.get::<_, String>(0)?, content))
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// 队列处理完毕后清空；当时还没缓存的正文之后随内容到达时照常索引
+    pub fn clear_search_backfill(&mut self) -> anyhow::Result<()> {
+        self.conn.execute("DELETE FROM search_backfill", [])?;
+        Ok(())
+    }
+
+    /// 把 items（+ content_cache.total_bytes）查询行还原为 ItemMeta。
+    /// 前 14 列顺序必须与 query_history / get_item_meta 的 SELECT 保持一致。
+    fn row_to_item_meta(r: &rusqlite::Row<'_>) -> rusqlite::Result<ItemMeta> {
+        let kind_s: String = r.get(1)?;
+        let kind = Self::kind_from_str(&kind_s);
+
+        let preview_json: String = r.get(7)?;
+        let preview: crate::model::ItemPreview =
+            serde_json::from_str(&preview_json).unwrap_or_default();
+
+        let files_json: Option<String> = r.get(8)?;
+        let files: Vec<FileMeta> = files_json
+            .as_deref()
+            .and_then(|s| serde_json::from_str::<Vec<FileMeta>>(s).ok())
+            .unwrap_or_default();
+
+        let total_bytes: i64 = r.get(10)?;
+
+        let fallback_json: Option<String> = r.get(11)?;
+        let text_fallback = fallback_json
+            .as_deref()
+            .and_then(|s| serde_json::from_str::<ItemContent>(s).ok());
+
+        let representations_json: Option<String> = r.get(12)?;
+        let representations: Vec<ItemContent> = representations_json
+            .as_deref()
+            .and_then(|s| serde_json::from_str::<Vec<ItemContent>>(s).ok())
+            .unwrap_or_default();
+
+        Ok(ItemMeta {
+            ty: "ItemMeta".to_string(),
+            item_id: r.get(0)?,
+            kind,
+            source_device_id: r.get(2)?,
+            source_device_name: None,
+            created_ts_ms: r.get(3)?,
+            size_bytes: r.get(4)?,
+            preview,
+            content: crate::model::ItemContent {
+                mime: r.get(5)?,
+                sha256: r.get(6)?,
+                total_bytes,
+            },
+            files,
+            expires_ts_ms: r.get(9)?,
+            text_fallback,
+            representations,
+            pinned: r.get(13)?,
+        })
+    }
+
+    /// History GC：未置顶的条目只保留最新 keep_latest 条，其余软删除；置顶条目不受影响
     pub fn soft_delete_history_keep_latest(&mut self, account_uid: &str, keep_latest: i64) -> anyhow::Result<i64> {
         if keep_latest < 0 {
             return Ok(0);
         }
-        let before = self.conn.changes();
-        self.conn.execute(
+        let n = self.conn.execute(
             r#"
             UPDATE history
             SET is_deleted=1
             WHERE history_id IN (
                 SELECT history_id
                 FROM history
-                WHERE account_uid=?1 AND is_deleted=0
+                WHERE account_uid=?1 AND is_deleted=0 AND pinned=0
                 ORDER BY sort_ts_ms DESC, history_id DESC
                 LIMIT -1 OFFSET ?2
             )
             "#,
             params![account_uid, keep_latest],
         )?;
-        let after = self.conn.changes();
-        Ok((after - before) as i64)
+        Ok(n as i64)
     }
 
-    /// Cache GC：挑 LRU（present=1）最旧的若干条
+    /// Cache GC：挑 LRU（present=1）最旧的若干条。
+    /// 置顶条目引用的内容（主格式、纯文本降级、额外表示、文件列表中的文件）不参与淘汰。
     pub fn select_lru_present(&self, limit: i64) -> anyhow::Result<Vec<(String, i64)>> {
-        let mut stmt = self.conn.prepare(
+        let sql = format!(
             r#"
+            WITH {}
             SELECT sha256_hex, total_bytes
             FROM content_cache
             WHERE present=1
+              AND sha256_hex NOT IN (SELECT sha FROM pinned_blobs WHERE sha IS NOT NULL)
             ORDER BY last_access_ts_ms ASC, sha256_hex ASC
             LIMIT ?1
             "#,
-        )?;
+            blob_refs_cte("pinned", PINNED_ITEMS_SQL)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
         let rows = stmt.query_map(params![limit], |r| Ok((r.get(0)?, r.get(1)?)))?;
         let mut out = vec![];
         for it in rows { out.push(it?); }
         Ok(out)
     }
 
+    /// 设置历史条目的置顶标记。条目不在该账号的可见历史中时返回 false。
+    pub fn set_item_pinned(&mut self, account_uid: &str, item_id: &str, pinned: bool) -> anyhow::Result<bool> {
+        let n = self.conn.execute(
+            "UPDATE history SET pinned=?3 WHERE account_uid=?1 AND item_id=?2 AND is_deleted=0",
+            params![account_uid, item_id, pinned],
+        )?;
+        Ok(n > 0)
+    }
+
     pub fn sum_present_bytes(&self) -> anyhow::Result<i64> {
         let s: i64 = self.conn.query_row(
             "SELECT COALESCE(SUM(total_bytes),0) FROM content_cache WHERE present=1",
@@ -496,6 +1001,113 @@ pub fn insert_meta_and_history(
         Ok(s)
     }
 
+    /// 已缓存（present=1）的内容条数与字节数
+    pub fn present_stats(&self) -> anyhow::Result<(i64, i64)> {
+        let v = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(total_bytes),0) FROM content_cache WHERE present=1",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        Ok(v)
+    }
+
+    /// 被置顶条目引用且已缓存的字节数
+    pub fn pinned_present_bytes(&self) -> anyhow::Result<i64> {
+        let sql = format!(
+            r#"WITH {}
+               SELECT COALESCE(SUM(total_bytes),0) FROM content_cache
+               WHERE present=1 AND sha256_hex IN (SELECT sha FROM pinned_blobs)"#,
+            blob_refs_cte("pinned", PINNED_ITEMS_SQL)
+        );
+        let s: i64 = self.conn.query_row(&sql, [], |r| r.get(0))?;
+        Ok(s)
+    }
+
+    /// 引用计数回收：删除不再被任何存活条目引用的 content_cache 行
+    /// （历史被 History GC 裁剪、条目变为墓碑等）。
+    /// 返回被删除行的 sha256，调用方负责从 CAS 删除对应 blob。
+    pub fn reclaim_unreferenced(&mut self) -> anyhow::Result<Vec<String>> {
+        let tx = self.conn.transaction()?;
+        let sql = format!(
+            r#"WITH {}
+               SELECT sha256_hex FROM content_cache
+               WHERE sha256_hex NOT IN (SELECT sha FROM live_blobs WHERE sha IS NOT NULL)"#,
+            blob_refs_cte("live", LIVE_ITEMS_SQL)
+        );
+        let shas: Vec<String> = {
+            let mut stmt = tx.prepare(&sql)?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        for sha in &shas {
+            tx.execute("DELETE FROM content_cache WHERE sha256_hex = ?1", params![sha])?;
+        }
+        tx.commit()?;
+        Ok(shas)
+    }
+
+    /// 找出引用某个 blob 的全部条目（墓碑除外）
+    pub fn blob_refs(&self, sha256_hex: &str) -> anyhow::Result<Vec<BlobRef>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT item_id, owner_device_id, sha256_hex, text_fallback_json, representations_json, files_json
+               FROM items i
+               WHERE i.deleted_ts_ms IS NULL AND (
+                 i.sha256_hex = ?1
+                 OR json_extract(i.text_fallback_json, '$.sha256') = ?1
+                 OR EXISTS(SELECT 1 FROM json_each(i.representations_json) r WHERE json_extract(r.value, '$.sha256') = ?1)
+                 OR EXISTS(SELECT 1 FROM json_each(i.files_json) f WHERE json_extract(f.value, '$.sha256') = ?1))"#,
+        )?;
+        let rows = stmt.query_map(params![sha256_hex], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, Option<String>>(3)?,
+                r.get::<_, Option<String>>(4)?,
+                r.get::<_, Option<String>>(5)?,
+            ))
+        })?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            let (item_id, owner, sha, fallback_json, representations_json, files_json) = row?;
+            let r = |file_id: Option<String>, mime: Option<String>| BlobRef {
+                item_id: item_id.clone(),
+                owner_device_id: owner.clone(),
+                file_id,
+                mime,
+            };
+            if sha == sha256_hex {
+                out.push(r(None, None));
+            }
+            let mut contents: Vec<ItemContent> = representations_json
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default();
+            contents.extend(fallback_json.and_then(|s| serde_json::from_str(&s).ok()));
+            for c in contents.into_iter().filter(|c| c.sha256 == sha256_hex) {
+                out.push(r(None, Some(c.mime)));
+            }
+            let files: Vec<FileMeta> = files_json
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default();
+            for f in files.into_iter().filter(|f| f.sha256.as_deref() == Some(sha256_hex)) {
+                out.push(r(Some(f.file_id), None));
+            }
+        }
+        Ok(out)
+    }
+
+    /// 存活条目引用的全部 blob（用于清扫 blobs/ 中的孤儿文件）
+    pub fn live_blob_set(&self) -> anyhow::Result<HashSet<String>> {
+        let sql = format!(
+            "WITH {} SELECT sha FROM live_blobs WHERE sha IS NOT NULL",
+            blob_refs_cte("live", LIVE_ITEMS_SQL)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
     /// 获取已保存的设备指纹
     pub fn get_peer_fingerprint(&self, account_uid: &str, device_id: &str) -> anyhow::Result<Option<String>> {
         let res: Option<String> = self.conn.query_row(
@@ -621,21 +1233,22 @@ pub fn insert_meta_and_history(
 
         // 1. content_cache: 远端来的默认 present=0 (Lazy Fetch)
         // 如果本地已存在(可能曾经下载过)，IGNORE 会保留原有状态
-        tx.execute(
-            r#"INSERT OR IGNORE INTO content_cache
-               (sha256_hex, total_bytes, present, last_access_ts_ms, created_ts_ms)
-               VALUES (?1, ?2, 0, ?3, ?4)"#,
-            params![meta.content.sha256, meta.content.total_bytes, now_ms, meta.created_ts_ms],
-        )?;
+        Self::insert_cache_rows(&tx, meta, now_ms)?;
 
         // 2. items: 插入元数据
         let preview_json = serde_json::to_string(&meta.preview)?;
         let files_json = serde_json::to_string(&meta.files)?;
+        let fallback_json = meta.text_fallback.as_ref().map(serde_json::to_string).transpose()?;
+        let representations_json = if meta.representations.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&meta.representations)?)
+        };
 
-        tx.execute(
+        let item_changes = tx.execute(
             r#"INSERT OR IGNORE INTO items
-               (item_id, kind, owner_device_id, created_ts_ms, size_bytes, mime, sha256_hex, preview_json, files_json, expires_ts_ms)
-               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"#,
+               (item_id, kind, owner_device_id, created_ts_ms, size_bytes, mime, sha256_hex, preview_json, files_json, expires_ts_ms, text_fallback_json, representations_json)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"#,
             params![
                 meta.item_id,
                 Self::kind_to_str(&meta.kind),
@@ -646,10 +1259,17 @@ pub fn insert_meta_and_history(
                 meta.content.sha256,
                 preview_json,
                 files_json,
-                meta.expires_ts_ms
+                meta.expires_ts_ms,
+                fallback_json,
+                representations_json
             ],
         )?;
 
+        // 只为新 item 建索引（重放的 meta 已经索引过）
+        if item_changes > 0 {
+            Self::insert_search_row(&tx, meta)?;
+        }
+
         // 3. history: 插入历史
         // 使用 INSERT OR IGNORE。如果 (account_uid, item_id) 已存在，则不执行插入，changes() 为 0
         let changes = tx.execute(
@@ -700,6 +1320,81 @@ pub fn insert_meta_and_history(
         }
     }
 
+    /// 按 MIME 获取 item 的某个表示（主格式 / 额外格式 / 纯文本降级），用于 Session 响应 ContentGet
+    pub fn get_item_representation(&self, item_id: &str, mime: &str) -> anyhow::Result<Option<ItemContent>> {
+        let row = self.conn.query_row(
+            r#"SELECT i.mime, i.sha256_hex, COALESCE(cc.total_bytes, i.size_bytes),
+                      i.text_fallback_json, i.representations_json
+               FROM items i
+               LEFT JOIN content_cache cc ON i.sha256_hex = cc.sha256_hex
+               WHERE i.item_id = ?1"#,
+            params![item_id],
+            |r| Ok((
+                ItemContent { mime: r.get(0)?, sha256: r.get(1)?, total_bytes: r.get(2)? },
+                r.get::<_, Option<String>>(3)?,
+                r.get::<_, Option<String>>(4)?,
+            )),
+        ).optional()?;
+
+        let Some((content, fallback_json, representations_json)) = row else {
+            return Ok(None);
+        };
+        let text_fallback = fallback_json.and_then(|s| serde_json::from_str::<ItemContent>(&s).ok());
+        let representations: Vec<ItemContent> = representations_json
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Ok(std::iter::once(content)
+            .chain(representations)
+            .chain(text_fallback)
+            .find(|c| c.mime.eq_ignore_ascii_case(mime)))
+    }
+
+    /// 为 FileList 中尚无 sha256 的文件补上哈希，并登记对应的缓存行。
+    /// 只更新 file_id 与大小都匹配、且原本没有 sha256 的文件；复制时记录了 local_mtime_ms 的，
+    /// 哈希时的修改时间也必须一致（否则哈希的已不是复制时的内容，跳过）。
+    /// 返回是否有文件被更新。
+    pub fn fill_file_hashes(&mut self, item_id: &str, hashed: &[FileMeta], now_ms: i64) -> anyhow::Result<bool> {
+        let tx = self.conn.transaction()?;
+        let row: Option<(Option<String>, i64)> = tx
+            .query_row(
+                "SELECT files_json, created_ts_ms FROM items WHERE item_id = ?1 AND deleted_ts_ms IS NULL",
+                [item_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?;
+        let Some((Some(files_json), created)) = row else {
+            return Ok(false);
+        };
+        let mut files: Vec<FileMeta> = serde_json::from_str(&files_json).unwrap_or_default();
+
+        let mut changed = false;
+        for f in files.iter_mut().filter(|f| f.sha256.is_none()) {
+            let Some(h) = hashed.iter().find(|h| h.file_id == f.file_id && h.size_bytes == f.size_bytes) else {
+                continue;
+            };
+            let Some(sha) = &h.sha256 else { continue };
+            if f.local_mtime_ms.is_some() && h.local_mtime_ms != f.local_mtime_ms {
+                continue;
+            }
+            Self::insert_cache_row_if_absent(&tx, sha, f.size_bytes, now_ms, created)?;
+            f.sha256 = Some(sha.clone());
+            if h.local_mtime_ms.is_some() {
+                f.local_mtime_ms = h.local_mtime_ms;
+            }
+            changed = true;
+        }
+
+        if changed {
+            tx.execute(
+                "UPDATE items SET files_json = ?2 WHERE item_id = ?1",
+                params![item_id, serde_json::to_string(&files)?],
+            )?;
+            tx.commit()?;
+        }
+        Ok(changed)
+    }
+
     /// M3-3: 获取 FileList 中指定 file_id 的详细信息 (sha256, rel_name, size)
     pub fn get_file_meta(&self, item_id: &str, file_id: &str) -> anyhow::Result<Option<crate::model::FileMeta>> {
         let mut stmt = self.conn.prepare("SELECT files_json FROM items WHERE item_id = ?")?;
@@ -717,15 +1412,330 @@ pub fn insert_meta_and_history(
         Ok(None)
     }
 
+    /// 一次拉取对应内容的 sha256：FileList 子文件 / 指定 MIME 的表示 / 主内容。
+    /// 哈希尚未算出（大文件后台补算中）时返回 None
+    pub fn get_content_sha256(&self, item_id: &str, file_id: Option<&str>, mime: Option<&str>) -> anyhow::Result<Option<String>> {
+        let sha = match (file_id, mime) {
+            (Some(fid), _) => self.get_file_meta(item_id, fid)?.and_then(|f| f.sha256),
+            (None, Some(m)) => self.get_item_representation(item_id, m)?.map(|c| c.sha256),
+            (None, None) => self.get_item_sha256(item_id)?,
+        };
+        Ok(sha.filter(|s| !s.is_empty()))
+    }
+
+    /// 用户删除条目：把 item 变成墓碑（清掉预览、文件名和全文索引），软删除所有账号下的历史，
+    /// 并删除不再被其它条目引用的 content_cache 行。
+    ///
+    /// 返回 `None` 表示条目不存在或已删除；否则返回可以从 CAS 中移除的 blob 列表。
+    pub fn delete_item(&mut self, item_id: &str, now_ms: i64) -> anyhow::Result<Option<Vec<String>>> {
+        let tx = self.conn.transaction()?;
+        let orphans = Self::tombstone_item(&tx, item_id, now_ms)?;
+        tx.commit()?;
+        Ok(orphans)
+    }
+
+    /// 过期清理：把已过期且未置顶的条目变成墓碑（与用户删除相同的处理）。
+    /// 返回被清理的 item_id 列表，以及可以从 CAS 中移除的 blob 列表。
+    pub fn purge_expired(&mut self, now_ms: i64) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+        let tx = self.conn.transaction()?;
+        let expired: Vec<String> = {
+            let mut stmt = tx.prepare(
+                r#"SELECT item_id FROM items i
+                   WHERE i.deleted_ts_ms IS NULL AND i.expires_ts_ms IS NOT NULL AND i.expires_ts_ms <= ?1
+                     AND NOT EXISTS(SELECT 1 FROM history h WHERE h.item_id = i.item_id AND h.pinned = 1 AND h.is_deleted = 0)"#,
+            )?;
+            let rows = stmt.query_map(params![now_ms], |r| r.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut orphans = Vec::new();
+        for item_id in &expired {
+            if let Some(o) = Self::tombstone_item(&tx, item_id, now_ms)? {
+                orphans.extend(o);
+            }
+        }
+        tx.commit()?;
+        Ok((expired, orphans))
+    }
+
+    /// delete_item / purge_expired 的公共实现
+    fn tombstone_item(tx: &Transaction<'_>, item_id: &str, now_ms: i64) -> anyhow::Result<Option<Vec<String>>> {
+        let row = tx.query_row(
+            r#"SELECT sha256_hex, text_fallback_json, representations_json, files_json
+               FROM items WHERE item_id = ?1 AND deleted_ts_ms IS NULL"#,
+            params![item_id],
+            |r| Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, Option<String>>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<String>>(3)?,
+            )),
+        ).optional()?;
+        let Some((sha, fallback_json, representations_json, files_json)) = row else {
+            return Ok(None);
+        };
+
+        let mut blobs = vec![sha];
+        if let Some(c) = fallback_json.and_then(|s| serde_json::from_str::<ItemContent>(&s).ok()) {
+            blobs.push(c.sha256);
+        }
+        let representations: Vec<ItemContent> = representations_json
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        blobs.extend(representations.into_iter().map(|c| c.sha256));
+        let files: Vec<FileMeta> = files_json
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        blobs.extend(files.into_iter().filter_map(|f| f.sha256));
+        blobs.sort();
+        blobs.dedup();
+
+        tx.execute(
+            r#"UPDATE items
+               SET deleted_ts_ms = ?2, preview_json = '{}', files_json = '[]',
+                   text_fallback_json = NULL, representations_json = NULL
+               WHERE item_id = ?1"#,
+            params![item_id, now_ms],
+        )?;
+        tx.execute("UPDATE history SET is_deleted = 1, pinned = 0 WHERE item_id = ?1", params![item_id])?;
+        tx.execute("DELETE FROM items_fts WHERE item_id = ?1", params![item_id])?;
+
+        let mut orphans = Vec::new();
+        for sha in blobs {
+            let referenced: bool = tx.query_row(
+                r#"SELECT EXISTS(
+                     SELECT 1 FROM items i
+                     WHERE i.deleted_ts_ms IS NULL
+                       AND EXISTS(SELECT 1 FROM history h WHERE h.item_id = i.item_id AND h.is_deleted = 0)
+                       AND (
+                       i.sha256_hex = ?1
+                       OR json_extract(i.text_fallback_json, '$.sha256') = ?1
+                       OR EXISTS(SELECT 1 FROM json_each(i.representations_json) r WHERE json_extract(r.value, '$.sha256') = ?1)
+                       OR EXISTS(SELECT 1 FROM json_each(i.files_json) f WHERE json_extract(f.value, '$.sha256') = ?1)))"#,
+                params![sha],
+                |r| r.get(0),
+            )?;
+            if !referenced {
+                tx.execute("DELETE FROM content_cache WHERE sha256_hex = ?1", params![sha])?;
+                orphans.push(sha);
+            }
+        }
+
+        Ok(Some(orphans))
+    }
+
+    /// 条目是否已过期（置顶条目视为不过期）
+    pub fn is_item_expired(&self, item_id: &str, now_ms: i64) -> anyhow::Result<bool> {
+        let expired: bool = self.conn.query_row(
+            r#"SELECT EXISTS(
+                 SELECT 1 FROM items i
+                 WHERE i.item_id = ?1 AND i.expires_ts_ms IS NOT NULL AND i.expires_ts_ms <= ?2
+                   AND NOT EXISTS(SELECT 1 FROM history h WHERE h.item_id = i.item_id AND h.pinned = 1 AND h.is_deleted = 0))"#,
+            params![item_id, now_ms],
+            |r| r.get(0),
+        )?;
+        Ok(expired)
+    }
+
+    /// 条目是否已被用户删除（墓碑）
+    pub fn is_item_deleted(&self, item_id: &str) -> anyhow::Result<bool> {
+        let deleted: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM items WHERE item_id = ?1 AND deleted_ts_ms IS NOT NULL)",
+            params![item_id],
+            |r| r.get(0),
+        )?;
+        Ok(deleted)
+    }
+
+    /// 添加手动配置的对端地址；已存在时返回 false
+    pub fn add_static_peer(&mut self, account_uid: &str, addr: &str, now_ms: i64) -> anyhow::Result<bool> {
+        let n = self.conn.execute(
+            "INSERT OR IGNORE INTO static_peers (account_uid, addr, device_id, added_at_ms) VALUES (?1, ?2, NULL, ?3)",
+            params![account_uid, addr, now_ms],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// 删除手动配置的对端地址；不存在时返回 false
+    pub fn remove_static_peer(&mut self, account_uid: &str, addr: &str) -> anyhow::Result<bool> {
+        let n = self.conn.execute(
+            "DELETE FROM static_peers WHERE account_uid=?1 AND addr=?2",
+            params![account_uid, addr],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// 记下握手得知的对端设备 ID
+    pub fn set_static_peer_device(&mut self, account_uid: &str, addr: &str, device_id: &str) -> anyhow::Result<()> {
+        self.conn.execute(
+            "UPDATE static_peers SET device_id=?3 WHERE account_uid=?1 AND addr=?2",
+            params![account_uid, addr, device_id],
+        )?;
+        Ok(())
+    }
+
+    /// 列出手动配置的对端地址（按添加时间）
+    pub fn list_static_peers(&self, account_uid: &str) -> anyhow::Result<Vec<StaticPeer>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT addr, device_id, added_at_ms FROM static_peers WHERE account_uid=?1 ORDER BY added_at_ms, addr"
+        )?;
+        let rows = stmt.query_map([account_uid], |r| {
+            Ok(StaticPeer {
+                addr: r.get(0)?,
+                device_id: r.get(1)?,
+                added_at_ms: r.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// 记下 mDNS 宣告的对端信息；没带设备名时保留已有的，上次成功的地址不变
+    pub fn upsert_peer(&mut self, account_uid: &str, peer: &PeerRecord) -> anyhow::Result<()> {
+        self.conn.execute(
+            r#"INSERT INTO peers (account_uid, device_id, device_name, addrs_json, capabilities_json, last_seen_ms, last_good_addr)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+               ON CONFLICT(account_uid, device_id) DO UPDATE SET
+                 device_name = COALESCE(excluded.device_name, device_name),
+                 addrs_json = excluded.addrs_json,
+                 capabilities_json = excluded.capabilities_json,
+                 last_seen_ms = MAX(last_seen_ms, excluded.last_seen_ms),
+                 last_good_addr = COALESCE(excluded.last_good_addr, last_good_addr)"#,
+            params![
+                account_uid,
+                peer.device_id,
+                peer.device_name,
+                serde_json::to_string(&peer.addrs)?,
+                serde_json::to_string(&peer.capabilities)?,
+                peer.last_seen_ms,
+                peer.last_good_addr,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 记下连接成功的地址；还没有记录的设备（静态对端）新建一条
+    pub fn set_peer_last_good_addr(&mut self, account_uid: &str, device_id: &str, addr: &str, now_ms: i64) -> anyhow::Result<()> {
+        self.conn.execute(
+            r#"INSERT INTO peers (account_uid, device_id, last_seen_ms, last_good_addr) VALUES (?1, ?2, ?3, ?4)
+               ON CONFLICT(account_uid, device_id) DO UPDATE SET
+                 last_seen_ms = MAX(last_seen_ms, excluded.last_seen_ms),
+                 last_good_addr = excluded.last_good_addr"#,
+            params![account_uid, device_id, now_ms, addr],
+        )?;
+        Ok(())
+    }
+
+    /// 更新最后见到的时间（只会往后推）
+    pub fn touch_peer(&mut self, account_uid: &str, device_id: &str, seen_ms: i64) -> anyhow::Result<()> {
+        self.conn.execute(
+            "UPDATE peers SET last_seen_ms = MAX(last_seen_ms, ?3) WHERE account_uid=?1 AND device_id=?2",
+            params![account_uid, device_id, seen_ms],
+        )?;
+        Ok(())
+    }
+
+    /// 列出地址簿（最近见到的在前）
+    pub fn list_peers(&self, account_uid: &str) -> anyhow::Result<Vec<PeerRecord>> {
+        let mut stmt = self.conn.prepare(
+            r#"SELECT device_id, device_name, addrs_json, capabilities_json, last_seen_ms, last_good_addr
+               FROM peers WHERE account_uid=?1 ORDER BY last_seen_ms DESC, device_id"#
+        )?;
+        let rows = stmt.query_map([account_uid], |r| {
+            let addrs: String = r.get(2)?;
+            let capabilities: String = r.get(3)?;
+            Ok(PeerRecord {
+                device_id: r.get(0)?,
+                device_name: r.get(1)?,
+                addrs: serde_json::from_str(&addrs).unwrap_or_default(),
+                capabilities: serde_json::from_str(&capabilities).unwrap_or_default(),
+                last_seen_ms: r.get(4)?,
+                last_good_addr: r.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
     /// 清空核心数据库的所有表
     pub fn clear_core_db(&mut self) -> anyhow::Result<()> {
         let tx = self.conn.transaction()?;
         tx.execute("DELETE FROM history", [])?;
         tx.execute("DELETE FROM items", [])?;
+        tx.execute("DELETE FROM items_fts", [])?;
         tx.execute("DELETE FROM content_cache", [])?;
         tx.execute("DELETE FROM trusted_peers", [])?;
         tx.execute("DELETE FROM peer_rules", [])?;
+        tx.execute("DELETE FROM static_peers", [])?;
+        tx.execute("DELETE FROM peers", [])?;
+        tx.execute("DELETE FROM search_backfill", [])?;
         tx.commit()?;
         Ok(())
     }
 }
+
+/// 按字节偏移查找 term（ASCII 忽略大小写，与 SQLite LIKE 的语义一致）
+fn find_ignore_ascii_case(text: &str, term: &str, from: usize) -> Option<usize> {
+    if term.is_empty() {
+        return None;
+    }
+    (from..=text.len().saturating_sub(term.len())).find(|&i| {
+        text.is_char_boundary(i)
+            && text.is_char_boundary(i + term.len())
+            && text[i..i + term.len()].eq_ignore_ascii_case(term)
+    })
+}
+
+/// 从第一段包含关键字的文本中截取摘要，并用 <mark></mark> 标出所有关键字
+fn make_snippet(texts: &[String], terms: &[String]) -> String {
+    const BEFORE_CHARS: usize = 16;
+    const WINDOW_CHARS: usize = 64;
+
+    let Some((text, first)) = texts
+        .iter()
+        .find_map(|t| terms.iter().filter_map(|term| find_ignore_ascii_case(t, term, 0)).min().map(|pos| (t, pos)))
+    else {
+        return String::new();
+    };
+
+    // 以字符为单位取窗口，避免截断多字节字符
+    let start = text[..first]
+        .char_indices()
+        .rev()
+        .nth(BEFORE_CHARS.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let end = text[start..]
+        .char_indices()
+        .nth(WINDOW_CHARS)
+        .map(|(i, _)| start + i)
+        .unwrap_or(text.len());
+    let window = &text[start..end];
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut pos = 0;
+    while pos < window.len() {
+        let hit = terms
+            .iter()
+            .filter_map(|term| find_ignore_ascii_case(window, term, pos).map(|p| (p, term.len())))
+            .min();
+        match hit {
+            Some((p, len)) => {
+                out.push_str(&window[pos..p]);
+                out.push_str("<mark>");
+                out.push_str(&window[p..p + len]);
+                out.push_str("</mark>");
+                pos = p + len;
+            }
+            None => {
+                out.push_str(&window[pos..]);
+                break;
+            }
+        }
+    }
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
@@ -0,0 +1,134 @@
+//! 旧版（引入 PRAGMA user_version 之前）数据库夹具。
+//! 按当时的表结构直接建库并写入少量样本数据，user_version 保持为 0，
+//! 用来验证迁移能把老用户的数据库升级到最新版本且不丢数据。
+
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+pub const LEGACY_ACCOUNT_UID: &str = "acct-uid-1";
+pub const LEGACY_TEXT_ITEM_ID: &str = "legacy-text-1";
+pub const LEGACY_FILES_ITEM_ID: &str = "legacy-files-1";
+
+/// 旧版 core.db：没有 text_fallback_json / representations_json 列，也没有 items_fts
+pub fn create_legacy_core_db(data_dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let conn = Connection::open(data_dir.join("core.db"))?;
+    conn.execute_batch(
+        r#"
+        CREATE TABLE items (
+          item_id TEXT PRIMARY KEY,
+          kind TEXT NOT NULL,
+          owner_device_id TEXT NOT NULL,
+          created_ts_ms INTEGER NOT NULL,
+          size_bytes INTEGER NOT NULL,
+          mime TEXT NOT NULL,
+          sha256_hex TEXT NOT NULL,
+          preview_json TEXT,
+          files_json TEXT,
+          expires_ts_ms INTEGER
+        );
+        CREATE INDEX idx_items_created ON items(created_ts_ms);
+        CREATE INDEX idx_items_sha256 ON items(sha256_hex);
+
+        CREATE TABLE history (
+          history_id INTEGER PRIMARY KEY AUTOINCREMENT,
+          account_uid TEXT NOT NULL,
+          item_id TEXT NOT NULL,
+          sort_ts_ms INTEGER NOT NULL,
+          source_device_id TEXT,
+          is_deleted INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX idx_history_account_sort ON history(account_uid, sort_ts_ms DESC);
+        CREATE INDEX idx_history_item ON history(item_id);
+
+        CREATE TABLE content_cache (
+          sha256_hex TEXT PRIMARY KEY,
+          total_bytes INTEGER NOT NULL,
+          present INTEGER NOT NULL,
+          last_access_ts_ms INTEGER NOT NULL,
+          created_ts_ms INTEGER NOT NULL
+        );
+        CREATE INDEX idx_cache_lru ON content_cache(present, last_access_ts_ms);
+
+        CREATE TABLE trusted_peers (
+            account_uid TEXT NOT NULL,
+            device_id TEXT NOT NULL,
+            fingerprint_sha256 TEXT NOT NULL,
+            updated_at_ms INTEGER NOT NULL,
+            PRIMARY KEY (account_uid, device_id)
+        );
+        "#,
+    )?;
+
+    let sha_text = "a".repeat(64);
+    let sha_files = "b".repeat(64);
+    conn.execute(
+        "INSERT INTO items VALUES (?1, 'text', 'dev-old', 1000, 11, 'text/plain', ?2, ?3, '[]', NULL)",
+        params![LEGACY_TEXT_ITEM_ID, sha_text, r#"{"text":"legacy hello"}"#],
+    )?;
+    conn.execute(
+        "INSERT INTO items VALUES (?1, 'file_list', 'dev-old', 2000, 5, 'application/x-file-list', ?2, ?3, ?4, NULL)",
+        params![
+            LEGACY_FILES_ITEM_ID,
+            sha_files,
+            r#"{"file_count":1}"#,
+            r#"[{"file_id":"f1","rel_name":"旧版报告.txt","size_bytes":5,"sha256":null}]"#
+        ],
+    )?;
+    for (item_id, ts) in [(LEGACY_TEXT_ITEM_ID, 1000_i64), (LEGACY_FILES_ITEM_ID, 2000_i64)] {
+        conn.execute(
+            "INSERT INTO history(account_uid, item_id, sort_ts_ms, source_device_id, is_deleted) VALUES (?1, ?2, ?3, 'dev-old', 0)",
+            params![LEGACY_ACCOUNT_UID, item_id, ts],
+        )?;
+    }
+    for (sha, total) in [(&sha_text, 11_i64), (&sha_files, 5_i64)] {
+        conn.execute(
+            "INSERT INTO content_cache VALUES (?1, ?2, 0, 1000, 1000)",
+            params![sha, total],
+        )?;
+    }
+    Ok(())
+}
+
+/// 旧版 logs.db（含一条日志）
+pub fn create_legacy_logs_db(data_dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let conn = Connection::open(data_dir.join("logs.db"))?;
+    conn.execute_batch(
+        r#"
+        CREATE TABLE logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ts_utc INTEGER NOT NULL,
+            level INTEGER NOT NULL,
+            component TEXT NOT NULL,
+            category TEXT NOT NULL,
+            message TEXT NOT NULL,
+            exception TEXT,
+            props_json TEXT
+        );
+        CREATE INDEX idx_logs_ts ON logs(ts_utc);
+        INSERT INTO logs(ts_utc, level, component, category, message) VALUES (1000, 2, 'Core', 'Legacy', 'legacy log');
+        "#,
+    )?;
+    Ok(())
+}
+
+/// 旧版 stats.db（含一条缓存统计）
+pub fn create_legacy_stats_db(data_dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let conn = Connection::open(data_dir.join("stats.db"))?;
+    conn.execute_batch(
+        r#"
+        CREATE TABLE stats (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ts_ms INTEGER NOT NULL,
+            bucket_sec INTEGER NOT NULL,
+            stat_type TEXT NOT NULL,
+            data_json TEXT NOT NULL
+        );
+        INSERT INTO stats(ts_ms, bucket_sec, stat_type, data_json) VALUES (1000, 60, 'cache', '{}');
+        "#,
+    )?;
+    Ok(())
+}
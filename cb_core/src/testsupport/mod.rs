@@ -2,3 +2,4 @@ pub mod dirs;
 pub mod events;
 pub mod core;
 pub mod fake_transport;
+pub mod legacy_db;
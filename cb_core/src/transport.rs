@@ -3,15 +3,15 @@
 pub mod cert;
 
 use std::fmt::Debug;
-use std::net::{SocketAddr, Ipv4Addr}; // Ipv6Addr 在 v1 暂不强制
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
-use quinn::{Endpoint, ServerConfig, TransportConfig, VarInt};
+use quinn::{Endpoint, EndpointConfig, ServerConfig, TransportConfig, VarInt};
 use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
-pub use quinn::{Connection, RecvStream, SendStream};
+pub use quinn::{Connection, Incoming, RecvStream, SendStream, ReadError};
 
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
@@ -19,6 +19,9 @@ use rustls::{DigitallySignedStruct, SignatureScheme};
 use rustls::crypto::CryptoProvider;
 use rustls::crypto::{verify_tls12_signature, verify_tls13_signature};
 use rustls::{ClientConfig as TlsClientConfig, ServerConfig as TlsServerConfig};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::policy::NetworkPolicy;
 
 
 
@@ -78,21 +81,42 @@ impl ServerCertVerifier for BlindVerifier {
 }
 
 
+/// 实际绑定的本地地址
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundAddr {
+    pub addr: SocketAddr,
+    /// IPv6 socket 同时收发 IPv4（v4-mapped）
+    pub dual_stack: bool,
+}
+
+impl BoundAddr {
+    /// 能否从这个 socket 连到 remote
+    fn reaches(&self, remote: &SocketAddr) -> bool {
+        match self.addr {
+            SocketAddr::V4(_) => remote.is_ipv4(),
+            SocketAddr::V6(_) => remote.is_ipv6() || self.dual_stack,
+        }
+    }
+}
+
 pub struct Transport {
-    endpoint: Endpoint,
+    // 每个绑定地址一个 endpoint，端口相同
+    endpoints: Vec<(Endpoint, BoundAddr)>,
     pub local_cert_der: Vec<u8>,
+    /// 双栈绑定失败、退回只监听 IPv4 时的原因（由 NetManager 记录日志）
+    dual_stack_fallback: Option<String>,
 }
 
 impl Transport {
     /// 创建 Transport 实例
     ///
     /// # 参数
-    /// - `listen_port`: 监听端口（0 表示自动分配）
+    /// - `network`: 监听端口（0 表示自动分配）、绑定地址与双栈配置
     /// - `data_dir`: 数据目录，用于持久化证书和私钥
     /// - `device_id`: 设备 ID，用于密钥派生
     /// - `account_uid`: 账号 UID，用于密钥派生
     pub fn new(
-        listen_port: u16,
+        network: &NetworkPolicy,
         data_dir: impl AsRef<Path>,
         device_id: &str,
         account_uid: &str,
@@ -144,31 +168,87 @@ impl Transport {
         let client_config = quinn::ClientConfig::new(Arc::new(quic_client));
 
 
-        // 4. Bind
-        let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), listen_port);
-        let mut endpoint = Endpoint::server(server_config, addr)?;
-        endpoint.set_default_client_config(client_config);
+        // 4. Bind：端口为 0 时由第一个地址分配，其余地址沿用同一端口，mDNS 只需宣告一个端口
+        let mut port = network.listen_port;
+        let mut endpoints = Vec::new();
+        let mut dual_stack_fallback = None;
+        for target in bind_targets(network)? {
+            let target = BoundAddr { addr: with_port(target.addr, port), ..target };
+            let socket = match bind_socket(&target) {
+                Ok(socket) => socket,
+                // 没有 IPv6 的系统上退回只监听 IPv4
+                Err(e) if network.bind.is_empty() && target.addr.is_ipv6() && e.kind() != std::io::ErrorKind::AddrInUse => {
+                    dual_stack_fallback = Some(e.to_string());
+                    let fallback = BoundAddr { addr: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port), dual_stack: false };
+                    bind_socket(&fallback).map_err(|e| bind_error(e, &fallback.addr))?
+                }
+                Err(e) => return Err(bind_error(e, &target.addr)),
+            };
+            let local = socket.local_addr()?;
+            port = local.port();
+
+            let mut endpoint = Endpoint::new(
+                EndpointConfig::default(),
+                Some(server_config.clone()),
+                socket,
+                Arc::new(quinn::TokioRuntime),
+            )?;
+            endpoint.set_default_client_config(client_config.clone());
+            endpoints.push((endpoint, BoundAddr { addr: local, dual_stack: target.dual_stack }));
+        }
 
         Ok(Self {
-            endpoint,
+            endpoints,
             local_cert_der,
+            dual_stack_fallback,
         })
     }
 
     pub fn local_port(&self) -> Result<u16> {
-        Ok(self.endpoint.local_addr()?.port())
+        let (endpoint, _) = self.endpoints.first().context("no bound endpoint")?;
+        Ok(endpoint.local_addr()?.port())
+    }
+
+    pub fn bound_addrs(&self) -> Vec<BoundAddr> {
+        self.endpoints.iter().map(|(_, b)| b.clone()).collect()
+    }
+
+    /// 双栈绑定失败的原因；Some 表示只监听了 IPv4
+    pub fn dual_stack_fallback(&self) -> Option<&str> {
+        self.dual_stack_fallback.as_deref()
+    }
+
+    /// 是否有能连到该地址的 socket（地址族匹配，或双栈 socket）
+    pub fn can_reach(&self, addr: &SocketAddr) -> bool {
+        self.endpoints.iter().any(|(_, b)| b.reaches(addr))
     }
 
     pub async fn connect(&self, addr_str: &str) -> Result<Connection> {
         let addr: SocketAddr = addr_str.parse().context("invalid socket addr")?;
         // "localhost" 只是占位，BlindVerifier 会忽略
-        let connecting = self.endpoint.connect(addr, "localhost")?;
+        // 优先用同地址族的 socket，其次是双栈 socket
+        let (endpoint, _) = self.endpoints.iter()
+            .filter(|(_, b)| b.reaches(&addr))
+            .min_by_key(|(_, b)| b.addr.is_ipv4() != addr.is_ipv4())
+            .with_context(|| format!("no bound socket can reach {}", addr))?;
+        let connecting = endpoint.connect(addr, "localhost")?;
         let conn = connecting.await?;
         Ok(conn)
     }
 
     pub async fn accept(&self) -> Option<Connection> {
-        let incoming = self.endpoint.accept().await?;
+        let incoming = self.accept_incoming().await?;
+        Self::handshake(incoming).await
+    }
+
+    /// 只等待入站连接请求，不做握手，可以放在 select! 里反复取消重建
+    pub async fn accept_incoming(&self) -> Option<Incoming> {
+        let accepts = self.endpoints.iter().map(|(e, _)| Box::pin(e.accept()));
+        futures::future::select_all(accepts).await.0
+    }
+
+    /// 完成入站握手；中途丢弃会直接中止这个连接，调用方应放到独立任务里等待
+    pub async fn handshake(incoming: Incoming) -> Option<Connection> {
         match incoming.await {
             Ok(conn) => Some(conn),
             Err(e) => {
@@ -178,12 +258,78 @@ impl Transport {
         }
     }
 
-    pub fn is_ipv4(&self) -> bool {
-        // 如果获取失败，默认认为是 IPv4（为了兼容性）
-        self.endpoint.local_addr().map(|a| a.is_ipv4()).unwrap_or(true)
+    pub fn shutdown(&self) {
+        for (endpoint, _) in &self.endpoints {
+            endpoint.close(0u32.into(), b"shutdown");
+        }
     }
+}
 
-    pub fn shutdown(&self) {
-        self.endpoint.close(0u32.into(), b"shutdown");
+/// 按配置展开要绑定的地址（端口稍后填入）
+fn bind_targets(network: &NetworkPolicy) -> Result<Vec<BoundAddr>> {
+    if network.bind.is_empty() {
+        let ip: IpAddr = if network.dual_stack { Ipv6Addr::UNSPECIFIED.into() } else { Ipv4Addr::UNSPECIFIED.into() };
+        return Ok(vec![BoundAddr { addr: SocketAddr::new(ip, 0), dual_stack: network.dual_stack }]);
+    }
+
+    let mut interfaces = None;
+    let mut targets: Vec<BoundAddr> = Vec::new();
+    for entry in &network.bind {
+        let entry = entry.trim().trim_start_matches('[').trim_end_matches(']');
+        let found = if let Ok(ip) = entry.parse::<IpAddr>() {
+            vec![BoundAddr {
+                addr: SocketAddr::new(ip, 0),
+                dual_stack: network.dual_stack && ip == IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            }]
+        } else {
+            // 网卡名
+            if interfaces.is_none() {
+                interfaces = Some(if_addrs::get_if_addrs().context("failed to list network interfaces")?);
+            }
+            interfaces.iter().flatten()
+                .filter(|i| i.name == entry && (network.dual_stack || i.ip().is_ipv4()))
+                .map(|i| {
+                    let addr = match i.ip() {
+                        // 链路本地地址必须带上网卡序号
+                        IpAddr::V6(ip) if i.is_link_local() => SocketAddrV6::new(ip, 0, 0, i.index.unwrap_or(0)).into(),
+                        ip => SocketAddr::new(ip, 0),
+                    };
+                    BoundAddr { addr, dual_stack: false }
+                })
+                .collect()
+        };
+        if found.is_empty() {
+            anyhow::bail!("INVALID_BIND_ADDR: {} is neither an IP address nor an interface with usable addresses", entry);
+        }
+        for target in found {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    Ok(targets)
+}
+
+fn with_port(addr: SocketAddr, port: u16) -> SocketAddr {
+    let mut addr = addr;
+    addr.set_port(port);
+    addr
+}
+
+fn bind_socket(target: &BoundAddr) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(target.addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if target.addr.is_ipv6() {
+        // 显式设置：各平台的默认值不同（Windows 默认只收 IPv6）
+        socket.set_only_v6(!target.dual_stack)?;
+    }
+    socket.bind(&target.addr.into())?;
+    Ok(socket.into())
+}
+
+fn bind_error(e: std::io::Error, addr: &SocketAddr) -> anyhow::Error {
+    if e.kind() == std::io::ErrorKind::AddrInUse {
+        anyhow::anyhow!("PORT_IN_USE: {} is already in use by another program", addr)
+    } else {
+        anyhow::anyhow!("BIND_FAILED: cannot listen on {}: {}", addr, e)
     }
 }
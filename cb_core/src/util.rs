@@ -13,6 +13,25 @@ pub fn sha256_hex(bytes: &[u8]) -> String {
     hex::encode(out) // 小写 hex
 }
 
+/// 流式计算文件的 sha256，返回 (hex, 字节数)
+pub fn sha256_file(path: &std::path::Path) -> std::io::Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let len = std::io::copy(&mut file, &mut hasher)?;
+    Ok((hex::encode(hasher.finalize()), len))
+}
+
+/// 文件修改时间（毫秒）；平台不支持时返回 None
+pub fn file_mtime_ms(md: &std::fs::Metadata) -> Option<i64> {
+    let t = md.modified().ok()?;
+    Some(t.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+/// 源文件是否已与记录不一致：大小不同，或记录了修改时间且已变化
+pub fn source_file_changed(md: &std::fs::Metadata, size_bytes: i64, mtime_ms: Option<i64>) -> bool {
+    md.len() as i64 != size_bytes || (mtime_ms.is_some() && file_mtime_ms(md) != mtime_ms)
+}
+
 pub fn truncate_chars(s: &str, max_chars: usize) -> String {
     s.chars().take(max_chars).collect()
 }
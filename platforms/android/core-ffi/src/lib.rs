@@ -7,6 +7,7 @@ use std::sync::Arc;
 use std::panic::{self, AssertUnwindSafe}; // 必须引入这个
 use anyhow::Context;
 use cb_core::api::{Core, CoreEventSink};
+use cb_core::model::{DeleteScope, HistoryCursor, HistoryFilter};
 
 use crate::error::{err_json, ok_json};
 
@@ -76,11 +77,40 @@ struct HistoryQueryDto {
 	#[serde(default = "default_limit")]
 	limit: usize,
 	#[serde(default)]
-	cursor: Option<i64>,
+	cursor: Option<HistoryCursor>,
+	#[serde(default)]
+	filter: HistoryFilter,
 }
 
 fn default_limit() -> usize { 20 }
 
+#[derive(serde::Deserialize)]
+struct SearchQueryDto {
+	query: String,
+	#[serde(default = "default_limit")]
+	limit: usize,
+	#[serde(default)]
+	cursor: Option<HistoryCursor>,
+	#[serde(default)]
+	filter: HistoryFilter,
+}
+
+#[derive(serde::Deserialize)]
+struct DeleteItemDto {
+	item_id: String,
+	#[serde(default)]
+	scope: DeleteScope, // "local"（默认）| "all"
+}
+
+#[derive(serde::Deserialize)]
+struct IngestFileDto {
+	path: String,
+	mime: String,
+	ts_ms: i64,
+	#[serde(default)]
+	share_mode: bridge::ShareMode, // "force" 时跳过软限制确认
+}
+
 fn ret(s: String) -> *const c_char {
 	CString::new(s).unwrap().into_raw()
 }
@@ -157,6 +187,19 @@ pub extern "C" fn cb_ingest_local_copy(h: *mut cb_handle, snapshot_json: *const
     })
 }
 
+#[no_mangle]
+pub extern "C" fn cb_ingest_file(h: *mut cb_handle, req_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let req: IngestFileDto = serde_json::from_str(cstr_to_str(req_json)?)?;
+        let force = matches!(req.share_mode, bridge::ShareMode::Force);
+
+        let hh = unsafe { &mut *h };
+        let meta = hh.core.ingest_from_path(std::path::Path::new(&req.path), &req.mime, req.ts_ms, force)?;
+        Ok(ok_json(serde_json::json!({ "meta": meta })))
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn cb_list_peers(h: *mut cb_handle) -> *const c_char {
 	ffi_safe!({
@@ -177,10 +220,54 @@ pub extern "C" fn cb_get_status(h: *mut cb_handle) -> *const c_char {
     })
 }
 
+#[no_mangle]
+pub extern "C" fn cb_add_static_peer(h: *mut cb_handle, addr_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let json_str = crate::cstr_to_str(addr_json)?;
+        let addr: String = serde_json::from_str(json_str).context("invalid json string")?;
+
+        let added = hh.core.add_static_peer(&addr)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "added": added })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cb_remove_static_peer(h: *mut cb_handle, addr_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let json_str = crate::cstr_to_str(addr_json)?;
+        let addr: String = serde_json::from_str(json_str).context("invalid json string")?;
+
+        let removed = hh.core.remove_static_peer(&addr)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "removed": removed })))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cb_list_static_peers(h: *mut cb_handle) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+        let peers = hh.core.list_static_peers()?;
+        Ok(crate::error::ok_json(serde_json::to_value(peers)?))
+    })
+}
+
 #[derive(serde::Deserialize)]
 struct EnsureContentDto {
 	item_id: String,
 	file_id: Option<String>,
+	// RichText：只拉取纯文本降级内容
+	#[serde(default)]
+	text_fallback: bool,
+	// 多格式 item：按偏好顺序选择要拉取的表示
+	#[serde(default)]
+	preferred_mimes: Vec<String>,
 }
 
 #[no_mangle]
@@ -192,7 +279,11 @@ pub extern "C" fn cb_ensure_content_cached(h: *mut cb_handle, req_json: *const c
         let json_str = crate::cstr_to_str(req_json)?;
         let dto: EnsureContentDto = serde_json::from_str(json_str).context("invalid json")?;
 
-        let transfer_id = hh.core.ensure_content_cached(&dto.item_id, dto.file_id.as_deref())?;
+        let transfer_id = if dto.text_fallback {
+            hh.core.ensure_text_fallback_cached(&dto.item_id)?
+        } else {
+            hh.core.ensure_content_cached_with_mimes(&dto.item_id, dto.file_id.as_deref(), &dto.preferred_mimes)?
+        };
 
         Ok(crate::error::ok_json(serde_json::json!({ "transfer_id": transfer_id })))
     })
@@ -221,20 +312,24 @@ pub extern "C" fn cb_list_history(h: *mut cb_handle, query_json: *const c_char)
         let json_str = crate::cstr_to_str(query_json)?;
         let dto: HistoryQueryDto = serde_json::from_str(json_str).context("invalid query json")?;
 
-        let items = hh.core.list_history(dto.limit, dto.cursor)?;
+        let page = hh.core.list_history_page(dto.limit, dto.cursor, &dto.filter)?;
 
-        let next_cursor = if items.len() >= dto.limit {
-            items.last().map(|i| i.created_ts_ms)
-        } else {
-            None
-        };
+        Ok(crate::error::ok_json(page))
+    })
+}
 
-        let page_data = serde_json::json!({
-            "items": items,
-            "next_cursor": next_cursor
-        });
+#[no_mangle]
+pub extern "C" fn cb_search_history(h: *mut cb_handle, query_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
 
-        Ok(crate::error::ok_json(page_data))
+        let json_str = crate::cstr_to_str(query_json)?;
+        let dto: SearchQueryDto = serde_json::from_str(json_str).context("invalid query json")?;
+
+        let page = hh.core.search_history(&dto.query, &dto.filter, dto.cursor, dto.limit)?;
+
+        Ok(crate::error::ok_json(page))
     })
 }
 
@@ -263,6 +358,96 @@ pub extern "C" fn cb_get_item_meta(h: *mut cb_handle, item_id_json: *const c_cha
     })
 }
 
+/// 解析 item_id 入参：支持 "uuid..." 或 { "item_id": "..." }
+fn parse_item_id(item_id_json: *const c_char) -> anyhow::Result<String> {
+    let json_str = crate::cstr_to_str(item_id_json)?;
+    if let Ok(s) = serde_json::from_str::<String>(json_str) {
+        return Ok(s);
+    }
+    #[derive(serde::Deserialize)]
+    struct IdObj { item_id: String }
+    let obj: IdObj = serde_json::from_str(json_str).context("invalid item_id json")?;
+    Ok(obj.item_id)
+}
+
+/// 删除单条历史；scope=all 时同时通知在线对端
+#[no_mangle]
+pub extern "C" fn cb_delete_item(h: *mut cb_handle, req_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let json_str = crate::cstr_to_str(req_json)?;
+        let dto: DeleteItemDto = serde_json::from_str(json_str).context("invalid delete json")?;
+
+        hh.core.delete_item(&dto.item_id, dto.scope)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "item_id": dto.item_id, "scope": dto.scope })))
+    })
+}
+
+/// 置顶历史条目（不会被 GC 清理）
+#[no_mangle]
+pub extern "C" fn cb_pin_item(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let item_id = parse_item_id(item_id_json)?;
+        hh.core.pin_item(&item_id)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "item_id": item_id, "pinned": true })))
+    })
+}
+
+/// 取消置顶
+#[no_mangle]
+pub extern "C" fn cb_unpin_item(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let item_id = parse_item_id(item_id_json)?;
+        hh.core.unpin_item(&item_id)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "item_id": item_id, "pinned": false })))
+    })
+}
+
+/// 在缓存目录中还原 FileList 的目录树（含空目录）
+#[no_mangle]
+pub extern "C" fn cb_materialize_item_tree(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let item_id = parse_item_id(item_id_json)?;
+        let view = hh.core.materialize_item_tree(&item_id)?;
+        Ok(crate::error::ok_json(serde_json::json!(view)))
+    })
+}
+
+/// 一次拉取整个 FileList，返回父传输 id（可交给 cb_cancel_transfer 整体取消）
+#[no_mangle]
+pub extern "C" fn cb_ensure_item_cached(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+
+        let item_id = parse_item_id(item_id_json)?;
+        let transfer_id = hh.core.ensure_item_cached(&item_id)?;
+        Ok(crate::error::ok_json(serde_json::json!({ "transfer_id": transfer_id })))
+    })
+}
+
+/// 缓存占用与回收报告
+#[no_mangle]
+pub extern "C" fn cb_cache_report(h: *mut cb_handle) -> *const c_char {
+	ffi_safe!({
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let hh = unsafe { &mut *h };
+        let report = hh.core.cache_report()?;
+        Ok(crate::error::ok_json(serde_json::to_value(report)?))
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn cb_get_ffi_version(major: *mut u32, minor: *mut u32) {
 	unsafe {
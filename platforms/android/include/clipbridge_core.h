@@ -25,6 +25,8 @@ CB_API const char* cb_shutdown(cb_handle* h);
 // share_mode 在 snapshot_json 中表达
 CB_API const char* cb_plan_local_ingest(cb_handle* h, const char* snapshot_json);
 CB_API const char* cb_ingest_local_copy(cb_handle* h, const char* snapshot_json);
+// 流式摄入本地文件（大图片 / 文件），req_json: {"path":"...","mime":"...","ts_ms":0,"share_mode":"default"|"force"}
+CB_API const char* cb_ingest_file(cb_handle* h, const char* req_json);
 
 
 // 释放由 core-ffi 返回的字符串
@@ -36,20 +38,69 @@ CB_API const char* cb_list_peers(cb_handle* h);
 // 返回 {"ok":true, "data":{"status":"Running", ...}}
 CB_API const char* cb_get_status(cb_handle* h);
 
+// 静态对端：mDNS 不通的网络里按地址直连，对端身份在握手时得知
+// addr_json: "host:port"（IPv6 写作 "[::1]:port"），地址无效时返回 INVALID_PEER_ADDR
+// 返回 {"ok":true, "data":{"added":true}}，地址已存在时 added=false
+CB_API const char* cb_add_static_peer(cb_handle* h, const char* addr_json);
+
+// 返回 {"ok":true, "data":{"removed":true}}；已建立的会话不受影响，断开后不再重拨
+CB_API const char* cb_remove_static_peer(cb_handle* h, const char* addr_json);
+
+// 返回 {"ok":true, "data":[{"addr":"...", "device_id":"..." | null, "added_at_ms":0}, ...]}
+CB_API const char* cb_list_static_peers(cb_handle* h);
+
 // M3: 确保内容缓存 (Lazy Fetch)
 // req_json: { "item_id": "...", "file_id": "opt", "prefer_peer": "opt" }
 CB_API const char* cb_ensure_content_cached(cb_handle* h, const char* req_json);
 
+// 在缓存目录中还原 FileList 的目录树（含空目录、权限与修改时间），未缓存的文件跳过
+// item_id_json: "uuid-string" 或 { "item_id": "..." }
+// 返回 {"ok":true, "data":{"root_path":"...", "missing_file_ids":["..."]}}
+CB_API const char* cb_materialize_item_tree(cb_handle* h, const char* item_id_json);
+
+// 一次拉取整个 FileList（有限并发，全部到齐才算完成）
+// item_id_json: "uuid-string" 或 { "item_id": "..." }
+// 返回 {"ok":true, "data":{"transfer_id":"..."}}，该 id 可传给 cb_cancel_transfer 整体取消
+// 事件：ITEM_FETCH_PROGRESS {transfer_id, item_id, done_files, total_files, received_bytes, total_bytes, bytes_per_sec}
+//       ITEM_CACHED {transfer_id, item_id, root_path, total_files, total_bytes}
+//       ITEM_FETCH_FAILED {transfer_id, item_id, file_id, code, message}
+CB_API const char* cb_ensure_item_cached(cb_handle* h, const char* item_id_json);
+
 // M3: 取消传输
 // transfer_id_json: "uuid-string"
 CB_API const char* cb_cancel_transfer(cb_handle* h, const char* transfer_id_json);
 
 // 历史查询
+// query_json: { "limit": 20, "cursor": {"sort_ts_ms": 0, "history_id": 0, "pinned": false} | null,
+//               "filter": { "kinds": ["text"], "source_device_id": "opt", "since_ts_ms": 0, "until_ts_ms": 0, "cached": true,
+//                           "pinned": true, "pinned_first": true } }
+// 返回 { "items": [...], "next_cursor": {...} | null }
 CB_API const char* cb_list_history(cb_handle* h, const char* query_json);
 
+// 全文检索历史（预览 / 已缓存文本正文 / 文件名）
+// query_json: { "query": "...", "limit": 20, "cursor": null, "filter": { 同 cb_list_history } }
+// 返回 { "hits": [{ "meta": {...}, "snippet": "...<mark>kw</mark>..." }], "next_cursor": {...} | null }
+CB_API const char* cb_search_history(cb_handle* h, const char* query_json);
+
 // 查询单条元数据
 CB_API const char* cb_get_item_meta(cb_handle* h, const char* item_id_json);
 
+// 删除单条历史（条目变为墓碑，不再被引用的缓存一并删除）
+// req_json: { "item_id": "...", "scope": "local" | "all" }   scope=all 时同时通知在线对端删除
+CB_API const char* cb_delete_item(cb_handle* h, const char* req_json);
+
+// 置顶 / 取消置顶（置顶条目不会被 History GC 清理，内容不会被 Cache GC 淘汰）
+// item_id_json: "uuid-string" 或 { "item_id": "..." }
+CB_API const char* cb_pin_item(cb_handle* h, const char* item_id_json);
+CB_API const char* cb_unpin_item(cb_handle* h, const char* item_id_json);
+
+// 缓存占用与回收报告
+// 返回 { "blob_bytes": 0, "present_count": 0, "present_bytes": 0, "pinned_bytes": 0, "cap_bytes": 0,
+//        "last_gc": { "ts_ms": 0, "trigger": "Startup", "reclaimed": [...] } | null,
+//        "reclaimed_total": [{ "reason": "expired" | "deleted" | "unreferenced" | "lru" | "orphan_blob" | "stale_tmp" | "orphan_view",
+//                              "files": 0, "bytes": 0 }] }
+CB_API const char* cb_cache_report(cb_handle* h);
+
 #ifdef __cplusplus
 }
 #endif
@@ -4,8 +4,8 @@ use base64::engine::general_purpose::STANDARD as B64;
 use base64::Engine;
 use serde::Deserialize;
 use cb_core::api::{AppConfig, Core, CoreConfig, CoreEventSink, GlobalPolicy};
-use cb_core::clipboard::{ClipboardFileEntry, ClipboardSnapshot};
-use cb_core::policy::SizeLimits;
+use cb_core::clipboard::{ClipboardFileEntry, ClipboardRepresentation, ClipboardSnapshot};
+use cb_core::policy::{ExpiryPolicy, NetworkPolicy, ScrubPolicy, SizeLimits, TransferPolicy};
 
 // [新增] 定义 LimitsDto，所有字段均为 Option，以支持局部更新/默认值
 #[derive(Deserialize)]
@@ -17,6 +17,8 @@ struct LimitsDto {
 	#[serde(default)] hard_image_bytes: Option<i64>,
 	#[serde(default)] hard_file_total_bytes: Option<i64>,
 	#[serde(default)] text_auto_prefetch_bytes: Option<i64>,
+	#[serde(default)] soft_rich_text_bytes: Option<i64>,
+	#[serde(default)] hard_rich_text_bytes: Option<i64>,
 }
 
 impl Into<SizeLimits> for LimitsDto {
@@ -30,6 +32,8 @@ impl Into<SizeLimits> for LimitsDto {
 			hard_image_bytes: self.hard_image_bytes.unwrap_or(def.hard_image_bytes),
 			hard_file_total_bytes: self.hard_file_total_bytes.unwrap_or(def.hard_file_total_bytes),
 			text_auto_prefetch_bytes: self.text_auto_prefetch_bytes.unwrap_or(def.text_auto_prefetch_bytes),
+			soft_rich_text_bytes: self.soft_rich_text_bytes.unwrap_or(def.soft_rich_text_bytes),
+			hard_rich_text_bytes: self.hard_rich_text_bytes.unwrap_or(def.hard_rich_text_bytes),
 		}
 	}
 }
@@ -40,6 +44,11 @@ struct AppConfigDto {
 	#[serde(default)] global_policy: Option<String>,
 	#[serde(default)] gc_history_max_items: Option<i64>,
 	#[serde(default)] gc_cas_max_bytes: Option<i64>,
+	#[serde(default)] expiry: Option<ExpiryPolicy>,
+	#[serde(default)] scrub: Option<ScrubPolicy>,
+	#[serde(default)] transfer: Option<TransferPolicy>,
+	#[serde(default)] network: Option<NetworkPolicy>,
+	#[serde(default)] static_peers: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -73,6 +82,7 @@ enum SnapshotKind {
     Text,
     Image,
     FileList,
+    RichText,
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,6 +92,27 @@ struct TextDto {
     utf8: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct RichTextDto {
+    #[serde(default)]
+    html: Option<String>,
+    #[serde(default)]
+    rtf: Option<String>,
+    // 平台剪贴板自带的纯文本表示（可选，缺省时由 core 从富文本中提取）
+    #[serde(default)]
+    plain_text: Option<String>,
+}
+
+// 额外格式：文本类用 utf8，二进制用 bytes_b64（二选一）
+#[derive(Debug, Deserialize)]
+struct RepresentationDto {
+    mime: String,
+    #[serde(default)]
+    utf8: Option<String>,
+    #[serde(default)]
+    bytes_b64: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ImageDto {
     mime: String,
@@ -109,6 +140,11 @@ struct ClipboardSnapshotDto {
     image: Option<ImageDto>,
     #[serde(default)]
     files: Vec<ClipboardFileEntry>,
+    #[serde(default)]
+    rich_text: Option<RichTextDto>,
+    // 同一次复制的其它格式（kind 对应的字段为主格式）
+    #[serde(default)]
+    representations: Vec<RepresentationDto>,
 }
 
 pub fn parse_cfg(json: &str) -> anyhow::Result<cb_core::api::CoreConfig> {
@@ -126,6 +162,11 @@ pub fn parse_cfg(json: &str) -> anyhow::Result<cb_core::api::CoreConfig> {
 			global_policy: policy,
 			gc_history_max_items: app.gc_history_max_items.unwrap_or(50_000),
 			gc_cas_max_bytes: app.gc_cas_max_bytes.unwrap_or(1024 * 1024 * 1024),
+			expiry: app.expiry.unwrap_or_default(),
+			scrub: app.scrub.unwrap_or_default(),
+			transfer: app.transfer.unwrap_or_default(),
+			network: app.network.unwrap_or_default(),
+			static_peers: app.static_peers.unwrap_or_default(),
 		}
 	} else {
 		AppConfig::default()
@@ -170,9 +211,30 @@ pub fn parse_snapshot(json: &str) -> anyhow::Result<(ClipboardSnapshot, ShareMod
             if dto.files.is_empty() { anyhow::bail!("kind=file_list requires non-empty files[]"); }
             ClipboardSnapshot::FileList { files: dto.files, ts_ms }
         }
+        SnapshotKind::RichText => {
+            let r = dto.rich_text.context("kind=rich_text requires .rich_text")?;
+            if r.html.is_none() && r.rtf.is_none() {
+                anyhow::bail!("kind=rich_text requires rich_text.html or rich_text.rtf");
+            }
+            ClipboardSnapshot::RichText { html: r.html, rtf: r.rtf, plain_text: r.plain_text, ts_ms }
+        }
     };
 
-    Ok((snap, dto.share_mode))
+    if dto.representations.is_empty() {
+        return Ok((snap, dto.share_mode));
+    }
+
+    let mut extras = Vec::with_capacity(dto.representations.len());
+    for r in dto.representations {
+        let bytes = match (r.utf8, r.bytes_b64) {
+            (Some(t), _) => t.into_bytes(),
+            (None, Some(b)) => B64.decode(b.as_bytes()).context("invalid representations[].bytes_b64")?,
+            (None, None) => anyhow::bail!("representations[] requires utf8 or bytes_b64"),
+        };
+        extras.push(ClipboardRepresentation { mime: r.mime, bytes });
+    }
+
+    Ok((ClipboardSnapshot::Multi { primary: Box::new(snap), extras }, dto.share_mode))
 }
 
 
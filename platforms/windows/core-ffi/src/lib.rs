@@ -7,6 +7,7 @@ use std::os::raw::{c_char, c_void};
 use std::sync::Arc;
 use anyhow::Context;
 use cb_core::api::{Core, CoreEventSink};
+use cb_core::model::{DeleteScope, HistoryCursor, HistoryFilter};
 
 use crate::error::{err_json, ok_json};
 
@@ -50,11 +51,40 @@ struct HistoryQueryDto {
 	#[serde(default = "default_limit")]
 	limit: usize,
 	#[serde(default)]
-	cursor: Option<i64>, // 分页游标，可选
+	cursor: Option<HistoryCursor>, // 分页游标：上一页返回的 next_cursor，可选
+	#[serde(default)]
+	filter: HistoryFilter,
 }
 
 fn default_limit() -> usize { 20 }
 
+#[derive(serde::Deserialize)]
+struct SearchQueryDto {
+	query: String,
+	#[serde(default = "default_limit")]
+	limit: usize,
+	#[serde(default)]
+	cursor: Option<HistoryCursor>,
+	#[serde(default)]
+	filter: HistoryFilter,
+}
+
+#[derive(serde::Deserialize)]
+struct DeleteItemDto {
+	item_id: String,
+	#[serde(default)]
+	scope: DeleteScope, // "local"（默认）| "all"
+}
+
+#[derive(serde::Deserialize)]
+struct IngestFileDto {
+	path: String,
+	mime: String,
+	ts_ms: i64,
+	#[serde(default)]
+	share_mode: bridge::ShareMode, // "force" 时跳过软限制确认
+}
+
 fn ret(s: String) -> *const c_char {
     CString::new(s).unwrap().into_raw()
 }
@@ -264,6 +294,28 @@ pub extern "C" fn cb_ingest_local_copy(h: *mut cb_handle, snapshot_json: *const
     }
 }
 
+/// 流式摄入本地文件：image/* 按图片入库，其它按单文件 FileList 入库
+///
+/// 请求：{"path": "...", "mime": "...", "ts_ms": 0, "share_mode": "default"|"force"}
+/// 返回格式：{"ok": true, "data": {"meta": {...}}}
+#[no_mangle]
+pub extern "C" fn cb_ingest_file(h: *mut cb_handle, req_json: *const c_char) -> *const c_char {
+    let run = (|| -> anyhow::Result<String> {
+        if h.is_null() { anyhow::bail!("null handle"); }
+        let req: IngestFileDto = serde_json::from_str(cstr_to_str(req_json)?)?;
+        let force = matches!(req.share_mode, bridge::ShareMode::Force);
+
+        let hh = unsafe { &mut *h };
+        let meta = hh.core.ingest_from_path(std::path::Path::new(&req.path), &req.mime, req.ts_ms, force)?;
+        Ok(ok_json(serde_json::json!({ "meta": meta })))
+    })();
+
+    match run {
+        Ok(s) => ret(s),
+        Err(e) => ret(err_json("INGEST_FAILED", &format!("{e:#}"))),
+    }
+}
+
 /// 获取当前在线设备列表
 ///
 /// 返回格式：{"ok": true, "data": [{"device_id": "...", "is_online": true, ...}]}
@@ -306,6 +358,59 @@ pub extern "C" fn cb_get_status(h: *mut cb_handle) -> *const c_char {
     }
 }
 
+/// 添加静态对端，addr_json: "host:port"
+#[no_mangle]
+pub extern "C" fn cb_add_static_peer(h: *mut cb_handle, addr_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let json_str = crate::cstr_to_str(addr_json)?;
+		let addr: String = serde_json::from_str(json_str).context("invalid json string")?;
+
+		let added = hh.core.add_static_peer(&addr)?;
+		Ok(crate::error::ok_json(serde_json::json!({ "added": added })))
+	})();
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("STATIC_PEER_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 删除静态对端，addr_json: "host:port"
+#[no_mangle]
+pub extern "C" fn cb_remove_static_peer(h: *mut cb_handle, addr_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let json_str = crate::cstr_to_str(addr_json)?;
+		let addr: String = serde_json::from_str(json_str).context("invalid json string")?;
+
+		let removed = hh.core.remove_static_peer(&addr)?;
+		Ok(crate::error::ok_json(serde_json::json!({ "removed": removed })))
+	})();
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("STATIC_PEER_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 列出静态对端及握手后得知的 device_id
+#[no_mangle]
+pub extern "C" fn cb_list_static_peers(h: *mut cb_handle) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+		let peers = hh.core.list_static_peers()?;
+		Ok(crate::error::ok_json(serde_json::to_value(peers)?))
+	})();
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("STATIC_PEER_FAILED", &format!("{e:#}"))),
+	}
+}
+
 /// 设置单个设备的共享策略
 ///
 /// 入参格式：{"peer_id": "device_uuid", "share_to_peer": true, "accept_from_peer": false}
@@ -389,6 +494,12 @@ pub extern "C" fn cb_clear_local_cert(h: *mut cb_handle) -> *const c_char {
 struct EnsureContentDto {
 	item_id: String,
 	file_id: Option<String>,
+	// RichText：只拉取纯文本降级内容
+	#[serde(default)]
+	text_fallback: bool,
+	// 多格式 item：按偏好顺序选择要拉取的表示
+	#[serde(default)]
+	preferred_mimes: Vec<String>,
 	// prefer_peer: Option<String>, // 预留，Core API 升级后可传入
 }
 
@@ -402,7 +513,11 @@ pub extern "C" fn cb_ensure_content_cached(h: *mut cb_handle, req_json: *const c
 		let dto: EnsureContentDto = serde_json::from_str(json_str).context("invalid json")?;
 
 		// 调用 Core API
-		let transfer_id = hh.core.ensure_content_cached(&dto.item_id, dto.file_id.as_deref())?;
+		let transfer_id = if dto.text_fallback {
+			hh.core.ensure_text_fallback_cached(&dto.item_id)?
+		} else {
+			hh.core.ensure_content_cached_with_mimes(&dto.item_id, dto.file_id.as_deref(), &dto.preferred_mimes)?
+		};
 
 		Ok(crate::error::ok_json(serde_json::json!({ "transfer_id": transfer_id })))
 	})();
@@ -441,30 +556,34 @@ pub extern "C" fn cb_list_history(h: *mut cb_handle, query_json: *const c_char)
 		let json_str = crate::cstr_to_str(query_json)?;
 		let dto: HistoryQueryDto = serde_json::from_str(json_str).context("invalid query json")?;
 
-		// 2. 调用 Core 获取列表
-		let items = hh.core.list_history(dto.limit, dto.cursor)?;
+		// 2. 调用 Core 分页查询，结构符合 C# HistoryPage 定义：{ items, next_cursor }
+		let page = hh.core.list_history_page(dto.limit, dto.cursor, &dto.filter)?;
 
-		// 3. 【关键修改】构造分页结构
-		// 因为 Core 目前没有直接返回 next_cursor，我们这里简单推算：
-		// 如果返回数量等于 limit，说明可能还有下一页，取最后一条的时间戳做 cursor
-		let next_cursor = if items.len() >= dto.limit {
-			items.last().map(|i| i.created_ts_ms)
-		} else {
-			None
-		};
+		// 3. 包装返回
+		Ok(crate::error::ok_json(page))
+	})();
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("LIST_HISTORY_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 全文检索历史，返回 { hits: [{ meta, snippet }], next_cursor }
+#[no_mangle]
+pub extern "C" fn cb_search_history(h: *mut cb_handle, query_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
 
-		// 构造符合 C# HistoryPage 定义的 JSON 对象
-		let page_data = serde_json::json!({
-            "items": items,
-            "next_cursor": next_cursor
-        });
+		let json_str = crate::cstr_to_str(query_json)?;
+		let dto: SearchQueryDto = serde_json::from_str(json_str).context("invalid query json")?;
 
-		// 4. 包装返回
-		Ok(crate::error::ok_json(page_data))
+		let page = hh.core.search_history(&dto.query, &dto.filter, dto.cursor, dto.limit)?;
+		Ok(crate::error::ok_json(page))
 	})();
 	match run {
 		Ok(s) => crate::ret(s),
-		Err(e) => crate::ret(crate::error::err_json("LIST_HISTORY_FAILED", &format!("{e:#}"))),
+		Err(e) => crate::ret(crate::error::err_json("SEARCH_HISTORY_FAILED", &format!("{e:#}"))),
 	}
 }
 
@@ -505,6 +624,112 @@ pub extern "C" fn cb_get_item_meta(h: *mut cb_handle, item_id_json: *const c_cha
 	}
 }
 
+/// 解析 item_id 入参：支持 "uuid..." 或 { "item_id": "..." }
+fn parse_item_id(item_id_json: *const c_char) -> anyhow::Result<String> {
+	let json_str = crate::cstr_to_str(item_id_json)?;
+	if let Ok(s) = serde_json::from_str::<String>(json_str) {
+		return Ok(s);
+	}
+	#[derive(serde::Deserialize)]
+	struct IdObj { item_id: String }
+	let obj: IdObj = serde_json::from_str(json_str).context("invalid item_id json")?;
+	Ok(obj.item_id)
+}
+
+fn set_item_pinned(h: *mut cb_handle, item_id_json: *const c_char, pinned: bool) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let item_id = parse_item_id(item_id_json)?;
+		if pinned {
+			hh.core.pin_item(&item_id)?;
+		} else {
+			hh.core.unpin_item(&item_id)?;
+		}
+		Ok(crate::error::ok_json(serde_json::json!({ "item_id": item_id, "pinned": pinned })))
+	})();
+
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("PIN_ITEM_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 删除单条历史；scope=all 时同时通知在线对端
+#[no_mangle]
+pub extern "C" fn cb_delete_item(h: *mut cb_handle, req_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let json_str = crate::cstr_to_str(req_json)?;
+		let dto: DeleteItemDto = serde_json::from_str(json_str).context("invalid delete json")?;
+
+		hh.core.delete_item(&dto.item_id, dto.scope)?;
+		Ok(crate::error::ok_json(serde_json::json!({ "item_id": dto.item_id, "scope": dto.scope })))
+	})();
+
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("DELETE_ITEM_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 置顶历史条目（不会被 GC 清理）
+#[no_mangle]
+pub extern "C" fn cb_pin_item(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	set_item_pinned(h, item_id_json, true)
+}
+
+/// 取消置顶
+#[no_mangle]
+pub extern "C" fn cb_unpin_item(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	set_item_pinned(h, item_id_json, false)
+}
+
+/// 在缓存目录中还原 FileList 的目录树（含空目录）
+///
+/// 返回格式：{"ok": true, "data": {"root_path": "...", "missing_file_ids": ["..."]}}
+#[no_mangle]
+pub extern "C" fn cb_materialize_item_tree(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let item_id = parse_item_id(item_id_json)?;
+		let view = hh.core.materialize_item_tree(&item_id)?;
+		Ok(crate::error::ok_json(serde_json::json!(view)))
+	})();
+
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("MATERIALIZE_FAILED", &format!("{e:#}"))),
+	}
+}
+
+/// 一次拉取整个 FileList，返回父传输 id（可交给 cb_cancel_transfer 整体取消）
+///
+/// 进度见 ITEM_FETCH_PROGRESS；完成时发出 ITEM_CACHED（带 root_path），失败时发出 ITEM_FETCH_FAILED
+///
+/// 返回格式：{"ok": true, "data": {"transfer_id": "..."}}
+#[no_mangle]
+pub extern "C" fn cb_ensure_item_cached(h: *mut cb_handle, item_id_json: *const c_char) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let hh = unsafe { &mut *h };
+
+		let item_id = parse_item_id(item_id_json)?;
+		let transfer_id = hh.core.ensure_item_cached(&item_id)?;
+		Ok(crate::error::ok_json(serde_json::json!({ "transfer_id": transfer_id })))
+	})();
+
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("START_FETCH_FAILED", &format!("{e:#}"))),
+	}
+}
+
 /// 写入日志（多语言版本）
 #[no_mangle]
 pub extern "C" fn cb_logs_write(
@@ -965,6 +1190,22 @@ pub extern "C" fn cb_clear_cache(h: *mut cb_handle) -> *const c_char {
 	}
 }
 
+/// 缓存占用与回收报告
+#[no_mangle]
+pub extern "C" fn cb_cache_report(h: *mut cb_handle) -> *const c_char {
+	let run = (|| -> anyhow::Result<String> {
+		if h.is_null() { anyhow::bail!("null handle"); }
+		let handle = unsafe { &mut *h };
+		let report = handle.core.cache_report()?;
+		Ok(crate::error::ok_json(serde_json::to_value(report)?))
+	})();
+
+	match run {
+		Ok(s) => crate::ret(s),
+		Err(e) => crate::ret(crate::error::err_json("QUERY_FAILED", &format!("{e:#}"))),
+	}
+}
+
 /// 查询缓存统计
 #[no_mangle]
 pub extern "C" fn cb_query_cache_stats(h: *mut cb_handle, query_json: *const c_char) -> *const c_char {
@@ -318,7 +318,7 @@ fn ffi_list_history_and_get_item() {
 		let v_list: serde_json::Value = serde_json::from_str(&take_json(out_list)).unwrap();
 
 		assert!(v_list["ok"].as_bool().unwrap(), "list_history failed");
-		let items = v_list["data"].as_array().unwrap();
+		let items = v_list["data"]["items"].as_array().unwrap();
 		assert!(!items.is_empty(), "history should not be empty");
 		assert_eq!(items[0]["item_id"], item_id, "first item should match");
 